@@ -0,0 +1,164 @@
+//! `iron-pony set-best PONY` and `--clear-best`: manage the `best.pony` link that pony
+//! selection prefers over a random pick.
+
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{CommandFactory, Parser};
+use iron_pony_core::{PATH_LIST_SEPARATOR, default_pony_paths, load_pony, user_pony_dir};
+use tracing::{debug, error, info};
+
+use crate::{env_paths, init_tracing, print_stdout, report_error, upstream_compat};
+
+const BEST: &str = "best.pony";
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "iron-pony set-best",
+    about = "Show PONY whenever no pony is requested, by linking best.pony to it"
+)]
+struct SetBestCli {
+    #[arg(value_name = "PONY", help = "Pony name or path to a .pony file")]
+    pony: String,
+
+    #[arg(
+        long = "ponydir",
+        value_delimiter = PATH_LIST_SEPARATOR,
+        help = "Pony search path override"
+    )]
+    pony_paths: Vec<PathBuf>,
+
+    #[arg(
+        short = 'v',
+        long = "verbose",
+        action = clap::ArgAction::Count,
+        help = "Increase log verbosity (-v info, -vv debug); IRON_PONY_LOG overrides"
+    )]
+    verbose: u8,
+}
+
+/// `set-best` as a subcommand of the main command, for the man page and completions.
+pub(crate) fn command() -> clap::Command {
+    SetBestCli::command().name("set-best")
+}
+
+/// Entry point for `iron-pony set-best ...`; `args` starts with `set-best`.
+pub(crate) fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    let cli = SetBestCli::parse_from(args);
+    init_tracing(cli.verbose);
+
+    let pony_paths = if cli.pony_paths.is_empty() {
+        env_paths("PONYSAY_PONY_PATH").unwrap_or_else(default_pony_paths)
+    } else {
+        cli.pony_paths
+    };
+    let pony = match load_pony(&cli.pony, &pony_paths, upstream_compat()) {
+        Ok(pony) => pony,
+        Err(error) => {
+            debug!(%error, "failed to find the new best pony");
+            return report_error(&error);
+        }
+    };
+    let target = fs::canonicalize(&pony.path).unwrap_or(pony.path);
+    let Some(dir) = user_pony_dir() else {
+        error!("no home directory to keep best.pony in");
+        return ExitCode::from(1);
+    };
+    match set_best(&dir, &target) {
+        Ok(link) => print_stdout(
+            &mut io::stdout().lock(),
+            &format!("{} -> {}\n", link.display(), target.display()),
+        ),
+        Err(error) => {
+            error!(dir = %dir.display(), %error, "failed to set best.pony");
+            ExitCode::from(1)
+        }
+    }
+}
+
+/// `--clear-best`: removes the user's `best.pony` link, if any.
+pub(crate) fn clear() -> ExitCode {
+    let Some(dir) = user_pony_dir() else {
+        error!("no home directory to keep best.pony in");
+        return ExitCode::from(1);
+    };
+    match remove_link(&dir.join(BEST)) {
+        Ok(true) => info!(dir = %dir.display(), "removed best.pony"),
+        Ok(false) => debug!(dir = %dir.display(), "no best.pony to remove"),
+        Err(error) => {
+            error!(dir = %dir.display(), %error, "failed to clear best.pony");
+            return ExitCode::from(1);
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+/// Points `dir/best.pony` at `target`, creating `dir` if needed; returns the link.
+fn set_best(dir: &Path, target: &Path) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let link = dir.join(BEST);
+    remove_link(&link)?;
+    symlink(target, &link)?;
+    info!(link = %link.display(), target = %target.display(), "set best.pony");
+    Ok(link)
+}
+
+/// Removes `link` if it is a symlink; `false` when there was nothing to remove. A regular
+/// `best.pony` was put there by hand and is left alone.
+fn remove_link(link: &Path) -> io::Result<bool> {
+    match fs::symlink_metadata(link) {
+        Ok(metadata) if metadata.file_type().is_symlink() => fs::remove_file(link).map(|()| true),
+        Ok(_) => Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} is not a symlink; move it away first", link.display()),
+        )),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(error) => Err(error),
+    }
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+/// Needs Developer Mode or an elevated shell.
+#[cfg(windows)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::windows::fs::symlink_file(target, link)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn symlink(_target: &Path, _link: &Path) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_replaces_and_clears_best_pony() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let alpha = tmp.path().join("alpha.pony");
+        let beta = tmp.path().join("beta.pony");
+        fs::write(&alpha, "alpha").expect("write");
+        fs::write(&beta, "beta").expect("write");
+        let dir = tmp.path().join("user").join("ponies");
+
+        let link = set_best(&dir, &alpha).expect("set alpha");
+        assert_eq!(fs::read_to_string(&link).expect("read"), "alpha");
+        set_best(&dir, &beta).expect("set beta");
+        assert_eq!(fs::read_link(&link).expect("link"), beta);
+
+        assert!(remove_link(&link).expect("clear"));
+        assert!(!remove_link(&link).expect("clear again"));
+
+        fs::write(&link, "hand-made").expect("write");
+        assert!(set_best(&dir, &alpha).is_err());
+        assert_eq!(fs::read_to_string(&link).expect("read"), "hand-made");
+    }
+}
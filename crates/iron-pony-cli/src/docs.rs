@@ -0,0 +1,88 @@
+//! `--print-man-page`, `--write-man-pages DIR` and `--print-completions SHELL`: packaging
+//! files generated from the argument definitions by `clap_mangen` and `clap_complete`, so
+//! they never drift from `--help`. `xtask dist` ships them.
+
+use std::io;
+use std::path::Path;
+
+use clap::Command;
+pub(crate) use clap_complete::Shell;
+
+/// A section 1 man page for `cmd`, listing its subcommands.
+pub(crate) fn man_page(cmd: Command) -> String {
+    let mut out = Vec::new();
+    clap_mangen::Man::new(cmd)
+        .render(&mut out)
+        .expect("writing to a Vec cannot fail");
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Writes `NAME.1` for `cmd` and `NAME-SUBCOMMAND.1` for each of its subcommands into
+/// `dir`, which must exist.
+pub(crate) fn write_man_pages(cmd: Command, dir: &Path) -> io::Result<()> {
+    clap_mangen::generate_to(cmd, dir)
+}
+
+/// A completion script for `cmd` in `shell`.
+pub(crate) fn completions(mut cmd: Command, shell: Shell) -> String {
+    let name = cmd.get_name().to_string();
+    let mut out = Vec::new();
+    clap_complete::generate(shell, &mut cmd, name, &mut out);
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{ArgAction, Parser};
+
+    use super::*;
+
+    #[derive(Debug, Parser)]
+    #[command(name = "demo-pony", version = "1.2.3", about = "Pony -demo")]
+    struct Demo {
+        #[arg(short = 'f', long = "pony", value_name = "PONY", help = "Pony's name")]
+        pony: Option<String>,
+        #[arg(long = "color", value_enum, help = "When to color [auto]")]
+        color: Option<Shell>,
+        #[arg(long = "verbose", action = ArgAction::Count, help = "Louder")]
+        verbose: u8,
+        #[arg(long = "secret", hide = true)]
+        secret: bool,
+        message: Vec<String>,
+    }
+
+    #[test]
+    fn man_page_and_completions_follow_the_arguments() {
+        let cmd = <Demo as clap::CommandFactory>::command()
+            .subcommand(Command::new("set-best").about("Pick the best pony"));
+        let man = man_page(cmd.clone());
+        assert!(man.contains(".TH demo-pony 1"));
+        assert!(man.contains("\\-f\\fR, \\fB\\-\\-pony\\fR"));
+        assert!(man.contains("Pony\\*(Aqs name"));
+        assert!(man.contains("bash"));
+        assert!(man.contains("demo\\-pony\\-set\\-best(1)"));
+        assert!(!man.contains("secret"));
+
+        let bash = completions(cmd.clone(), Shell::Bash);
+        assert!(bash.contains("--color"));
+        assert!(bash.contains("set-best"));
+        assert!(bash.contains("complete -F _demo-pony"));
+
+        let zsh = completions(cmd.clone(), Shell::Zsh);
+        assert!(zsh.starts_with("#compdef demo-pony\n"));
+        assert!(zsh.contains("'*--verbose[Louder]'"));
+
+        let fish = completions(cmd.clone(), Shell::Fish);
+        assert!(fish.contains("complete -c demo-pony"));
+        assert!(fish.contains("-a \"set-best\" -d 'Pick the best pony'"));
+
+        let dir = tempfile::tempdir().expect("tempdir");
+        write_man_pages(cmd.disable_help_subcommand(true), dir.path()).expect("written");
+        let mut pages = std::fs::read_dir(dir.path())
+            .expect("listed")
+            .map(|entry| entry.expect("entry").file_name())
+            .collect::<Vec<_>>();
+        pages.sort();
+        assert_eq!(pages, ["demo-pony-set-best.1", "demo-pony.1"]);
+    }
+}
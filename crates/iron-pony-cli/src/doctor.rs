@@ -0,0 +1,462 @@
+//! `iron-pony doctor`: checks where ponies, balloons and quotes are looked for, the reference
+//! ponysay, the terminal and the settings read from the environment, and says how to fix
+//! what it finds. Most "no ponies found" reports come down to one of these.
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::io;
+use std::path::PathBuf;
+use std::process::{Command, ExitCode};
+
+use clap::{CommandFactory, Parser};
+use iron_pony_core::{
+    ALIASES_FILE, ColorMode, PATH_LIST_SEPARATOR, PonyIndex, alias_file_problems,
+    count_quote_files, default_balloon_paths, default_pony_paths, default_quote_paths,
+    list_balloons,
+};
+use tracing::debug;
+use tracing_subscriber::EnvFilter;
+
+use crate::env::{PonysayEnv, terminal_size};
+use crate::{env_paths, init_tracing, print_stdout};
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "iron-pony doctor",
+    about = "Check pony directories, the terminal and settings, and suggest fixes"
+)]
+struct DoctorCli {
+    #[arg(
+        long = "ponydir",
+        value_delimiter = PATH_LIST_SEPARATOR,
+        help = "Pony search path override"
+    )]
+    pony_paths: Vec<PathBuf>,
+
+    #[arg(
+        long = "balloondir",
+        value_delimiter = PATH_LIST_SEPARATOR,
+        help = "Balloon search path override"
+    )]
+    balloon_paths: Vec<PathBuf>,
+
+    #[arg(
+        long = "quotedir",
+        value_delimiter = PATH_LIST_SEPARATOR,
+        help = "Quote search path override"
+    )]
+    quote_paths: Vec<PathBuf>,
+
+    #[arg(
+        short = 'v',
+        long = "verbose",
+        action = clap::ArgAction::Count,
+        help = "Increase log verbosity (-v info, -vv debug); IRON_PONY_LOG overrides"
+    )]
+    verbose: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Ok,
+    /// Worth knowing, but nothing is broken.
+    Note,
+    Warn,
+    /// iron-pony cannot work until this is fixed.
+    Fail,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Finding {
+    status: Status,
+    what: String,
+    fix: Option<String>,
+}
+
+impl Finding {
+    fn new(status: Status, what: impl Into<String>) -> Self {
+        Self {
+            status,
+            what: what.into(),
+            fix: None,
+        }
+    }
+
+    fn fix(mut self, fix: impl Into<String>) -> Self {
+        self.fix = Some(fix.into());
+        self
+    }
+}
+
+/// Everything the checks look at, gathered up front so they can run against a made-up
+/// environment.
+struct Inputs {
+    pony_paths: Vec<PathBuf>,
+    balloon_paths: Vec<PathBuf>,
+    quote_paths: Vec<PathBuf>,
+    /// The reference program and its `--version` line, or why it could not be run.
+    reference: (String, Result<String, String>),
+    terminal: Option<(usize, usize)>,
+    vars: HashMap<String, String>,
+}
+
+/// `doctor` as a subcommand of the main command, for the man page and completions.
+pub(crate) fn command() -> clap::Command {
+    DoctorCli::command().name("doctor")
+}
+
+/// Entry point for `iron-pony doctor ...`; `args` starts with `doctor`.
+pub(crate) fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    let cli = DoctorCli::parse_from(args);
+    init_tracing(cli.verbose);
+
+    let or_env = |paths: Vec<PathBuf>, var, default: fn() -> Vec<PathBuf>| {
+        if paths.is_empty() {
+            env_paths(var).unwrap_or_else(default)
+        } else {
+            paths
+        }
+    };
+    let vars = std::env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+        .collect::<HashMap<_, _>>();
+    let reference = vars
+        .get("PONYSAY_REF")
+        .cloned()
+        .unwrap_or_else(|| "ponysay".to_string());
+    let inputs = Inputs {
+        pony_paths: or_env(cli.pony_paths, "PONYSAY_PONY_PATH", default_pony_paths),
+        balloon_paths: or_env(
+            cli.balloon_paths,
+            "PONYSAY_BALLOON_PATH",
+            default_balloon_paths,
+        ),
+        quote_paths: if cli.quote_paths.is_empty() {
+            default_quote_paths()
+        } else {
+            cli.quote_paths
+        },
+        reference: (reference.clone(), reference_version(&reference)),
+        terminal: terminal_size(false),
+        vars,
+    };
+
+    let findings = diagnose(&inputs);
+    let status = print_stdout(&mut io::stdout().lock(), &report(&findings));
+    if findings
+        .iter()
+        .any(|finding| finding.status == Status::Fail)
+    {
+        ExitCode::from(1)
+    } else {
+        status
+    }
+}
+
+fn reference_version(program: &str) -> Result<String, String> {
+    let output = Command::new(program)
+        .arg("--version")
+        .output()
+        .map_err(|error| error.to_string())?;
+    if !output.status.success() {
+        return Err(format!(
+            "`{program} --version` failed with {}",
+            output.status
+        ));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout.lines().next().unwrap_or_default().trim().to_string())
+}
+
+fn diagnose(inputs: &Inputs) -> Vec<Finding> {
+    let var = |name: &str| inputs.vars.get(name).cloned();
+    let mut findings = Vec::new();
+
+    let ponies = search_paths(
+        &mut findings,
+        "pony",
+        ("pony", "ponies"),
+        &inputs.pony_paths,
+        |path| PonyIndex::scan(std::slice::from_ref(path)).names().len(),
+    );
+    if ponies == 0 {
+        findings.push(
+            Finding::new(Status::Fail, "no ponies found in any pony directory").fix(
+                "install ponysay's ponies (the `ponysay` package on most systems), or point \
+                 PONYSAY_PONY_PATH or --ponydir at a directory of .pony files",
+            ),
+        );
+    }
+    for root in &inputs.pony_paths {
+        let path = root.join(ALIASES_FILE);
+        match alias_file_problems(root) {
+            Ok(problems) => findings.extend(problems.into_iter().map(|(line, text)| {
+                Finding::new(
+                    Status::Warn,
+                    format!(
+                        "{}:{line}: `{text}` is not `ALIAS -> PONY` and is ignored",
+                        path.display()
+                    ),
+                )
+                .fix("write one `ALIAS -> PONY` per line, or start the line with `#`")
+            })),
+            Err(error) => findings.push(
+                Finding::new(Status::Warn, format!("{}: {error}", path.display()))
+                    .fix("make the aliases file readable, or remove it"),
+            ),
+        }
+    }
+    let balloons = search_paths(
+        &mut findings,
+        "balloon",
+        ("style", "styles"),
+        &inputs.balloon_paths,
+        |path| list_balloons(std::slice::from_ref(path)).len(),
+    );
+    if balloons == 0 {
+        findings.push(
+            Finding::new(
+                Status::Warn,
+                "no balloon styles installed; only the built-in style is available",
+            )
+            .fix(
+                "install ponysay's balloons, or point PONYSAY_BALLOON_PATH or --balloondir at them",
+            ),
+        );
+    }
+    let quotes = search_paths(
+        &mut findings,
+        "quote",
+        ("file", "files"),
+        &inputs.quote_paths,
+        |path| count_quote_files(std::slice::from_ref(path)),
+    );
+    if quotes == 0 {
+        findings.push(
+            Finding::new(
+                Status::Warn,
+                "no quotes installed, so `-q` has nothing to say",
+            )
+            .fix("install ponysay's quotes, or pass --quotedir"),
+        );
+    }
+
+    let (program, version) = &inputs.reference;
+    findings.push(match version {
+        Ok(version) => Finding::new(Status::Ok, format!("reference ponysay: {version}")),
+        Err(reason) => Finding::new(
+            Status::Note,
+            format!("no reference ponysay (`{program}`: {reason}); only parity runs need one"),
+        )
+        .fix("install ponysay, or set PONYSAY_REF to its path, to run `cargo run -p xtask -- parity`"),
+    });
+
+    findings.push(match inputs.terminal {
+        Some((columns, rows)) => Finding::new(Status::Ok, format!("terminal: {columns}x{rows}")),
+        None => Finding::new(
+            Status::Note,
+            "terminal size unknown (stdout is not a terminal); output is not truncated",
+        )
+        .fix("set COLUMNS and LINES, or pass --probe-terminal on serial consoles"),
+    });
+
+    let term = var("TERM");
+    let colorterm = var("COLORTERM");
+    let shown = format!(
+        "TERM={}, COLORTERM={}",
+        term.as_deref().unwrap_or("(unset)"),
+        colorterm.as_deref().unwrap_or("(unset)")
+    );
+    findings.push(
+        match ColorMode::detect(term.as_deref(), colorterm.as_deref()) {
+            ColorMode::Full => Finding::new(Status::Ok, format!("colors: 256 or more ({shown})")),
+            ColorMode::Console => Finding::new(
+                Status::Warn,
+                format!("colors: 16 only ({shown}); --motd quantizes ponies to them"),
+            )
+            .fix("set TERM to a 256-color type such as xterm-256color, or COLORTERM=truecolor, if the terminal supports it"),
+            ColorMode::Off => Finding::new(Status::Warn, format!("colors: none ({shown})"))
+                .fix("set TERM to your terminal's type"),
+        },
+    );
+
+    if cfg!(unix) {
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .into_iter()
+            .find_map(|name| var(name).filter(|value| !value.is_empty()));
+        let utf8 = locale.as_deref().is_some_and(|locale| {
+            let locale = locale.to_ascii_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        });
+        let shown = locale.as_deref().unwrap_or("(unset)");
+        findings.push(if utf8 {
+            Finding::new(Status::Ok, format!("locale: {shown}"))
+        } else {
+            Finding::new(
+                Status::Warn,
+                format!("locale: {shown} is not UTF-8, so pony art may show as garbage"),
+            )
+            .fix("set LANG to a UTF-8 locale such as en_US.UTF-8")
+        });
+    }
+
+    let mut settings = PonysayEnv::problems(var)
+        .into_iter()
+        .map(|(name, problem)| {
+            Finding::new(Status::Warn, format!("{name}: {problem}"))
+                .fix(format!("fix or unset {name}"))
+        })
+        .collect::<Vec<_>>();
+    if let Some(compat) = var("IRON_PONY_COMPAT")
+        && !matches!(compat.as_str(), "ponysay" | "upstream")
+    {
+        settings.push(
+            Finding::new(
+                Status::Warn,
+                format!("IRON_PONY_COMPAT: `{compat}` is not recognized and is ignored"),
+            )
+            .fix("set IRON_PONY_COMPAT=ponysay, or unset it"),
+        );
+    }
+    if let Some(directives) = var("IRON_PONY_LOG")
+        && let Err(error) = EnvFilter::try_new(&directives)
+    {
+        settings.push(
+            Finding::new(Status::Warn, format!("IRON_PONY_LOG: {error}"))
+                .fix("use tracing filter directives such as IRON_PONY_LOG=debug"),
+        );
+    }
+    if settings.is_empty() {
+        settings.push(Finding::new(
+            Status::Ok,
+            "settings: PONYSAY_* and IRON_PONY_* variables are valid",
+        ));
+    }
+    findings.extend(settings);
+
+    debug!(findings = findings.len(), "diagnosed environment");
+    findings
+}
+
+/// Adds a finding per directory in `paths` and returns how many assets they hold in all;
+/// `nouns` names one asset and several.
+fn search_paths(
+    findings: &mut Vec<Finding>,
+    kind: &str,
+    nouns: (&str, &str),
+    paths: &[PathBuf],
+    count: impl Fn(&PathBuf) -> usize,
+) -> usize {
+    let mut total = 0;
+    for path in paths {
+        if !path.is_dir() {
+            findings.push(Finding::new(
+                Status::Note,
+                format!("{kind} directory {}: not present", path.display()),
+            ));
+            continue;
+        }
+        let found = count(path);
+        total += found;
+        let noun = if found == 1 { nouns.0 } else { nouns.1 };
+        findings.push(Finding::new(
+            if found == 0 { Status::Warn } else { Status::Ok },
+            format!("{kind} directory {}: {found} {noun}", path.display()),
+        ));
+    }
+    total
+}
+
+fn report(findings: &[Finding]) -> String {
+    let mut text = String::new();
+    for finding in findings {
+        let tag = match finding.status {
+            Status::Ok => "[ok]",
+            Status::Note => "[--]",
+            Status::Warn => "[warn]",
+            Status::Fail => "[fail]",
+        };
+        text.push_str(&format!("{tag:<6} {}\n", finding.what));
+        if let Some(fix) = &finding.fix {
+            text.push_str(&format!("       fix: {fix}\n"));
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn diagnoses_a_made_up_environment() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let ponies = tmp.path().join("ponies");
+        fs::create_dir_all(&ponies).expect("mkdir");
+        fs::write(ponies.join("alpha.pony"), "$balloon$\nalpha\n").expect("write");
+        let aliased = tmp.path().join("aliased");
+        fs::create_dir_all(&aliased).expect("mkdir");
+        fs::write(aliased.join(ALIASES_FILE), "a -> alpha\nb alpha\n").expect("write");
+        let quotes = tmp.path().join("quotes");
+        fs::create_dir_all(&quotes).expect("mkdir");
+        fs::write(quotes.join("alpha.0"), "Hi.\n").expect("write");
+        fs::write(quotes.join("README"), "not a quote\n").expect("write");
+        let missing = tmp.path().join("missing");
+        let vars = [
+            ("TERM", "xterm-256color"),
+            ("LANG", "C"),
+            ("PONYSAY_WRAP_LIMIT", "wide"),
+            ("IRON_PONY_COMPAT", "strict"),
+        ];
+        let inputs = Inputs {
+            pony_paths: vec![missing.clone(), ponies.clone(), aliased.clone()],
+            balloon_paths: vec![missing.clone()],
+            quote_paths: vec![quotes.clone()],
+            reference: ("ponysay".to_string(), Err("not found".to_string())),
+            terminal: Some((80, 24)),
+            vars: vars
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        };
+
+        let findings = diagnose(&inputs);
+        assert!(
+            !findings
+                .iter()
+                .any(|finding| finding.status == Status::Fail)
+        );
+        let text = report(&findings);
+        for expected in [
+            format!("[--]   pony directory {}: not present\n", missing.display()),
+            format!("[ok]   pony directory {}: 1 pony\n", ponies.display()),
+            "[warn] no balloon styles installed".to_string(),
+            format!("[ok]   quote directory {}: 1 file\n", quotes.display()),
+            format!(
+                "[warn] {}:2: `b alpha` is not `ALIAS -> PONY` and is ignored\n",
+                aliased.join(ALIASES_FILE).display()
+            ),
+            "[ok]   terminal: 80x24\n".to_string(),
+            "[ok]   colors: 256 or more (TERM=xterm-256color, COLORTERM=(unset))\n".to_string(),
+            "[warn] PONYSAY_WRAP_LIMIT: `wide` is not a number\n       fix: fix or unset PONYSAY_WRAP_LIMIT\n".to_string(),
+            "[warn] IRON_PONY_COMPAT: `strict` is not recognized".to_string(),
+        ] {
+            assert!(text.contains(&expected), "{expected:?} missing from:\n{text}");
+        }
+        if cfg!(unix) {
+            assert!(text.contains("[warn] locale: C is not UTF-8"), "{text}");
+        }
+
+        let empty = Inputs {
+            pony_paths: vec![missing],
+            ..inputs
+        };
+        assert!(
+            diagnose(&empty)
+                .iter()
+                .any(|finding| finding.status == Status::Fail)
+        );
+    }
+}
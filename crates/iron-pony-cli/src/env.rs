@@ -0,0 +1,270 @@
+//! Upstream's `PONYSAY_*` environment variables, mapped onto iron-pony's defaults.
+//!
+//! Command-line flags always win over the environment.
+
+use std::io::IsTerminal;
+
+use tracing::debug;
+
+/// Variables upstream reads that have no iron-pony equivalent yet; they are logged and
+/// otherwise ignored.
+const UNSUPPORTED: [&str; 1] = ["PONYSAY_KMS_PALETTE"];
+
+/// Rows upstream leaves for the shell prompt when truncating to the terminal height.
+const DEFAULT_SHELL_LINES: usize = 2;
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct PonysayEnv {
+    /// `PONYSAY_WRAP_LIMIT`: balloon wrap width.
+    pub(crate) wrap_limit: Option<usize>,
+    /// `PONYSAY_WRAP_EXCEED`: columns a word may run past the wrap width before it is broken.
+    pub(crate) wrap_exceed: Option<usize>,
+    /// `PONYSAY_WRAP_HYPHEN`: appended where a word is broken across lines.
+    pub(crate) wrap_hyphen: Option<String>,
+    /// `PONYSAY_FULL_WIDTH`; `Some(false)` clips lines to the terminal width.
+    pub(crate) full_width: Option<bool>,
+    /// `PONYSAY_TRUNCATE_HEIGHT`: cap output to the terminal height.
+    pub(crate) truncate_height: bool,
+    /// `PONYSAY_SHELL_LINES`: rows kept free below the output when truncating height.
+    pub(crate) shell_lines: usize,
+    /// `PONYSAY_UCS_ME`: list ponies by their Unicode names.
+    pub(crate) ucs_me: bool,
+}
+
+impl PonysayEnv {
+    pub(crate) fn from_env() -> Self {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        for name in UNSUPPORTED {
+            if var(name).is_some() {
+                debug!(name, "ignoring unsupported ponysay environment variable");
+            }
+        }
+        let number = |name: &str| {
+            let value = var(name)?;
+            let parsed = value.trim().parse().ok();
+            if parsed.is_none() {
+                debug!(
+                    name,
+                    value, "ignoring non-numeric ponysay environment variable"
+                );
+            }
+            parsed
+        };
+        let flag = |name: &str| var(name).and_then(|value| parse_flag(&value));
+
+        Self {
+            wrap_limit: number("PONYSAY_WRAP_LIMIT").filter(|width| *width > 0),
+            wrap_exceed: number("PONYSAY_WRAP_EXCEED"),
+            wrap_hyphen: var("PONYSAY_WRAP_HYPHEN"),
+            full_width: flag("PONYSAY_FULL_WIDTH"),
+            truncate_height: flag("PONYSAY_TRUNCATE_HEIGHT").unwrap_or(false),
+            shell_lines: number("PONYSAY_SHELL_LINES").unwrap_or(DEFAULT_SHELL_LINES),
+            // Upstream also accepts `harder`, which additionally translates names on input;
+            // iron-pony always accepts Unicode names.
+            ucs_me: var("PONYSAY_UCS_ME")
+                .is_some_and(|value| value == "harder" || parse_flag(&value) == Some(true)),
+        }
+    }
+
+    /// Variables set to something [`Self::from_env`] cannot use or does not support, with
+    /// why; `iron-pony doctor` reports them.
+    pub(crate) fn problems(var: impl Fn(&str) -> Option<String>) -> Vec<(&'static str, String)> {
+        let mut problems = Vec::new();
+        for name in [
+            "PONYSAY_WRAP_LIMIT",
+            "PONYSAY_WRAP_EXCEED",
+            "PONYSAY_SHELL_LINES",
+        ] {
+            if let Some(value) = var(name)
+                && value.trim().parse::<usize>().is_err()
+            {
+                problems.push((name, format!("`{value}` is not a number")));
+            }
+        }
+        for name in [
+            "PONYSAY_FULL_WIDTH",
+            "PONYSAY_TRUNCATE_HEIGHT",
+            "PONYSAY_UCS_ME",
+        ] {
+            if let Some(value) = var(name)
+                && parse_flag(&value).is_none()
+                && !(name == "PONYSAY_UCS_ME" && value == "harder")
+            {
+                problems.push((name, format!("`{value}` is neither yes nor no")));
+            }
+        }
+        for name in UNSUPPORTED {
+            if var(name).is_some() {
+                problems.push((name, "is not supported and is ignored".to_string()));
+            }
+        }
+        problems
+    }
+
+    /// Output height cap implied by `PONYSAY_TRUNCATE_HEIGHT`. `terminal` gives the
+    /// terminal size and is only asked when the variable is set.
+    pub(crate) fn max_height(
+        &self,
+        terminal: impl FnOnce() -> Option<(usize, usize)>,
+    ) -> Option<usize> {
+        if !self.truncate_height {
+            return None;
+        }
+        let (_, rows) = terminal()?;
+        Some(rows.saturating_sub(self.shell_lines).max(1))
+    }
+
+    /// Output width cap implied by `PONYSAY_FULL_WIDTH=no`. `terminal` gives the terminal
+    /// size and is only asked when the variable says to clip.
+    pub(crate) fn max_width(
+        &self,
+        terminal: impl FnOnce() -> Option<(usize, usize)>,
+    ) -> Option<usize> {
+        if self.full_width != Some(false) {
+            return None;
+        }
+        let (columns, _) = terminal()?;
+        Some(columns)
+    }
+}
+
+fn parse_flag(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "yes" | "y" | "true" | "1" | "on" => Some(true),
+        "no" | "n" | "false" | "0" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// `(columns, rows)` of the terminal on stdout, from `COLUMNS`/`LINES` or the tty itself.
+/// With `probe`, a tty that cannot report its size is asked with a cursor position report.
+pub(crate) fn terminal_size(probe: bool) -> Option<(usize, usize)> {
+    let from_env = |name| {
+        std::env::var(name)
+            .ok()
+            .and_then(|value| value.parse::<usize>().ok())
+    };
+    if let (Some(columns), Some(rows)) = (from_env("COLUMNS"), from_env("LINES")) {
+        return Some((columns, rows));
+    }
+    if !std::io::stdout().is_terminal() {
+        return None;
+    }
+    tty_size().or_else(|| probe.then(crate::probe::probe_size).flatten())
+}
+
+#[cfg(unix)]
+fn tty_size() -> Option<(usize, usize)> {
+    // SAFETY: TIOCGWINSZ only writes a `winsize` into the struct we pass.
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let status = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    (status == 0 && size.ws_col > 0 && size.ws_row > 0)
+        .then(|| (usize::from(size.ws_col), usize::from(size.ws_row)))
+}
+
+#[cfg(windows)]
+fn tty_size() -> Option<(usize, usize)> {
+    use windows_sys::Win32::System::Console::{
+        CONSOLE_SCREEN_BUFFER_INFO, GetConsoleScreenBufferInfo, GetStdHandle, STD_OUTPUT_HANDLE,
+    };
+
+    // SAFETY: GetConsoleScreenBufferInfo only writes into the struct we pass, and fails
+    // cleanly on a handle that is not a console.
+    let mut info: CONSOLE_SCREEN_BUFFER_INFO = unsafe { std::mem::zeroed() };
+    if unsafe { GetConsoleScreenBufferInfo(GetStdHandle(STD_OUTPUT_HANDLE), &mut info) } == 0 {
+        return None;
+    }
+    let window = info.srWindow;
+    let columns = usize::try_from(window.Right - window.Left + 1).ok()?;
+    let rows = usize::try_from(window.Bottom - window.Top + 1).ok()?;
+    (columns > 0 && rows > 0).then_some((columns, rows))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn tty_size() -> Option<(usize, usize)> {
+    None
+}
+
+/// Turns on escape sequence handling in the Windows console, which conhost leaves off by
+/// default; elsewhere terminals always interpret them.
+#[cfg(windows)]
+pub(crate) fn enable_virtual_terminal() {
+    use windows_sys::Win32::System::Console::{
+        ENABLE_VIRTUAL_TERMINAL_PROCESSING, GetConsoleMode, GetStdHandle, STD_ERROR_HANDLE,
+        STD_OUTPUT_HANDLE, SetConsoleMode,
+    };
+
+    for stream in [STD_OUTPUT_HANDLE, STD_ERROR_HANDLE] {
+        // SAFETY: the console mode calls fail cleanly on handles that are not consoles.
+        unsafe {
+            let handle = GetStdHandle(stream);
+            let mut mode = 0;
+            if GetConsoleMode(handle, &mut mode) == 0 {
+                continue;
+            }
+            if SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING) == 0 {
+                debug!(
+                    stream,
+                    "console does not support virtual terminal processing"
+                );
+            }
+        }
+    }
+}
+
+#[cfg(not(windows))]
+pub(crate) fn enable_virtual_terminal() {}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn env(vars: &[(&str, &str)]) -> PonysayEnv {
+        let vars = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect::<HashMap<_, _>>();
+        PonysayEnv::from_vars(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn maps_ponysay_variables() {
+        assert_eq!(
+            env(&[]),
+            PonysayEnv {
+                shell_lines: DEFAULT_SHELL_LINES,
+                ..PonysayEnv::default()
+            }
+        );
+
+        let vars = env(&[
+            ("PONYSAY_WRAP_LIMIT", "60"),
+            ("PONYSAY_FULL_WIDTH", "no"),
+            ("PONYSAY_TRUNCATE_HEIGHT", "yes"),
+            ("PONYSAY_SHELL_LINES", "4"),
+            ("PONYSAY_UCS_ME", "harder"),
+            ("PONYSAY_WRAP_EXCEED", "3"),
+            ("PONYSAY_WRAP_HYPHEN", "~"),
+        ]);
+        assert_eq!(vars.wrap_limit, Some(60));
+        assert_eq!(vars.wrap_exceed, Some(3));
+        assert_eq!(vars.wrap_hyphen.as_deref(), Some("~"));
+        assert!(vars.ucs_me);
+        assert_eq!(vars.max_height(|| Some((80, 24))), Some(20));
+        assert_eq!(vars.max_width(|| Some((80, 24))), Some(80));
+        assert_eq!(vars.max_height(|| None), None);
+
+        let vars = env(&[
+            ("PONYSAY_WRAP_LIMIT", "wide"),
+            ("PONYSAY_FULL_WIDTH", "yes"),
+        ]);
+        assert_eq!(vars.wrap_limit, None);
+        assert_eq!(vars.max_width(|| unreachable!("size looked up")), None);
+        assert_eq!(vars.max_height(|| unreachable!("size looked up")), None);
+    }
+}
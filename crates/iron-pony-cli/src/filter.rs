@@ -0,0 +1,184 @@
+//! `--filter`: decorates piped input as it arrives instead of reading it all first.
+
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+
+use clap::ValueEnum;
+use iron_pony_core::{PonyAsset, PonyError, RenderConfig, Renderer};
+use tracing::debug;
+
+use crate::output::OutputWriter;
+
+/// Input lines kept in the `stream` balloon; a taller balloon could scroll past the top
+/// of the terminal, where it can no longer be redrawn.
+const STREAM_WINDOW: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum FilterMode {
+    /// One balloon per blank-line-separated paragraph.
+    Paragraph,
+    /// A single balloon showing the latest lines, redrawn as each line arrives.
+    Stream,
+}
+
+#[derive(Debug)]
+pub(crate) enum FilterError {
+    Render(PonyError),
+    Io(io::Error),
+}
+
+impl From<io::Error> for FilterError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// Reads `input` to the end, writing balloons to `writer` as `mode` dictates. `redraw`
+/// enables in-place updates in `stream` mode; without it only the final balloon is shown.
+pub(crate) fn run<W: Write>(
+    mode: FilterMode,
+    input: impl BufRead,
+    renderer: &Renderer,
+    config: &mut RenderConfig,
+    pony: &PonyAsset,
+    writer: &mut OutputWriter<W>,
+    redraw: bool,
+) -> Result<(), FilterError> {
+    let render = |config: &RenderConfig, writer: &mut OutputWriter<W>| {
+        let text = renderer
+            .render_output(config, pony)
+            .map_err(FilterError::Render)?
+            .text();
+        writer.write_text(&text)?;
+        Ok::<_, FilterError>(text.split('\n').count())
+    };
+
+    let mut lines = Lines(input);
+    match mode {
+        FilterMode::Paragraph => {
+            let mut paragraph = Vec::new();
+            let mut rendered = 0;
+            while let Some(line) = lines.next_line()? {
+                if !line.trim().is_empty() {
+                    paragraph.push(line);
+                    continue;
+                }
+                if paragraph.is_empty() {
+                    continue;
+                }
+                config.message = paragraph.join("\n");
+                paragraph.clear();
+                render(config, writer)?;
+                rendered += 1;
+            }
+            if !paragraph.is_empty() {
+                config.message = paragraph.join("\n");
+                render(config, writer)?;
+                rendered += 1;
+            }
+            debug!(paragraphs = rendered, "filter input ended");
+        }
+        FilterMode::Stream => {
+            let mut window = VecDeque::with_capacity(STREAM_WINDOW);
+            let mut drawn = 0;
+            while let Some(line) = lines.next_line()? {
+                if window.len() == STREAM_WINDOW {
+                    window.pop_front();
+                }
+                window.push_back(line);
+                // A window of blank lines has nothing to say; the last frame stays up.
+                let blank = window.iter().all(|line| line.trim().is_empty());
+                if redraw && !blank {
+                    config.message = Vec::from(window.clone()).join("\n");
+                    writer.erase_lines(drawn)?;
+                    drawn = render(config, writer)?;
+                }
+            }
+            if !redraw && window.iter().any(|line| !line.trim().is_empty()) {
+                config.message = Vec::from(window).join("\n");
+                render(config, writer)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Lines of possibly non-UTF-8 input (build logs, binary junk), decoded lossily.
+struct Lines<R>(R);
+
+impl<R: BufRead> Lines<R> {
+    fn next_line(&mut self) -> io::Result<Option<String>> {
+        let mut buffer = Vec::new();
+        if self.0.read_until(b'\n', &mut buffer)? == 0 {
+            return Ok(None);
+        }
+        let line = String::from_utf8_lossy(&buffer);
+        Ok(Some(line.trim_end_matches(['\n', '\r']).to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use iron_pony_core::{BalloonMode, BalloonStyle, strip_ansi};
+
+    use super::*;
+    use crate::output::Pace;
+
+    fn filtered(mode: FilterMode, input: &str, redraw: bool) -> String {
+        let mut config = RenderConfig {
+            balloon_style: Some(
+                BalloonStyle::from_chars("+-+|+-+|", BalloonMode::Say).expect("style"),
+            ),
+            leading_reset: false,
+            ..RenderConfig::default()
+        };
+        let pony = PonyAsset {
+            path: PathBuf::from("test.pony"),
+            metadata: Default::default(),
+            body: "$balloon$\npony\n".to_string(),
+        };
+        let renderer = Renderer::new(&config);
+        let mut out = Vec::new();
+        let mut writer = OutputWriter::new(&mut out, Pace::Instant);
+        run(
+            mode,
+            input.as_bytes(),
+            &renderer,
+            &mut config,
+            &pony,
+            &mut writer,
+            redraw,
+        )
+        .expect("filter");
+        strip_ansi(&String::from_utf8(out).expect("utf-8"))
+    }
+
+    #[test]
+    fn renders_paragraphs_and_streams() {
+        assert_eq!(
+            filtered(FilterMode::Paragraph, "one\ntwo\n\n\nthree", false),
+            "+-----+\n| one |\n| two |\n+-----+\npony\n\
+             +-------+\n| three |\n+-------+\npony\n"
+        );
+
+        let streamed = filtered(FilterMode::Stream, "a\nb\n", true);
+        assert_eq!(streamed.matches("pony").count(), 2);
+        let final_frame = "+---+\n| a |\n| b |\n+---+\npony\n";
+        assert!(streamed.ends_with(final_frame), "{streamed:?}");
+        assert_eq!(filtered(FilterMode::Stream, "a\nb\n", false), final_frame);
+        assert_eq!(filtered(FilterMode::Paragraph, "", false), "");
+    }
+
+    #[test]
+    fn stream_keeps_blank_lines_without_drawing_empty_balloons() {
+        let streamed = filtered(FilterMode::Stream, "a\n\nb\n", true);
+        assert_eq!(streamed.matches("pony").count(), 3);
+        assert!(streamed.ends_with("+---+\n| a |\n|   |\n| b |\n+---+\npony\n"));
+
+        let streamed = filtered(FilterMode::Stream, "\n \na\n", true);
+        assert_eq!(streamed.matches("pony").count(), 1);
+        assert_eq!(filtered(FilterMode::Stream, "\n\n", false), "");
+    }
+}
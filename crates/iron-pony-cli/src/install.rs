@@ -0,0 +1,281 @@
+//! `iron-pony install-pony PATH...` and `remove-pony NAME...`: manage custom ponies in the
+//! user's pony directory without knowing where it lives.
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{CommandFactory, Parser};
+use iron_pony_core::{LintReport, PonyError, lint_pony_dir, user_pony_dir};
+use tracing::{debug, error, info};
+
+use crate::{init_tracing, print_stdout, report_error};
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "iron-pony install-pony",
+    about = "Lint pony files and copy them into your pony directory"
+)]
+struct InstallCli {
+    #[arg(
+        value_name = "PATH",
+        required = true,
+        help = "Pony files, or directories of them"
+    )]
+    paths: Vec<PathBuf>,
+
+    #[arg(
+        long = "force",
+        help = "Install despite lint errors and replace installed ponies of the same name"
+    )]
+    force: bool,
+
+    #[arg(
+        short = 'v',
+        long = "verbose",
+        action = clap::ArgAction::Count,
+        help = "Increase log verbosity (-v info, -vv debug); IRON_PONY_LOG overrides"
+    )]
+    verbose: u8,
+}
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "iron-pony remove-pony",
+    about = "Delete ponies installed with install-pony"
+)]
+struct RemoveCli {
+    #[arg(value_name = "NAME", required = true)]
+    names: Vec<String>,
+
+    #[arg(
+        short = 'v',
+        long = "verbose",
+        action = clap::ArgAction::Count,
+        help = "Increase log verbosity (-v info, -vv debug); IRON_PONY_LOG overrides"
+    )]
+    verbose: u8,
+}
+
+/// `install-pony` and `remove-pony` as subcommands of the main command, for the man page
+/// and completions.
+pub(crate) fn commands() -> [clap::Command; 2] {
+    [
+        InstallCli::command().name("install-pony"),
+        RemoveCli::command().name("remove-pony"),
+    ]
+}
+
+/// Entry point for `iron-pony install-pony ...`; `args` starts with `install-pony`.
+pub(crate) fn install_main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    let cli = InstallCli::parse_from(args);
+    init_tracing(cli.verbose);
+    let Some(dir) = user_pony_dir() else {
+        error!("no home directory to install ponies into");
+        return ExitCode::from(1);
+    };
+
+    let mut reports = Vec::new();
+    for path in &cli.paths {
+        match lint_pony_dir(path) {
+            Ok(found) if found.is_empty() => {
+                error!(path = %path.display(), "no .pony files found");
+                return ExitCode::from(1);
+            }
+            Ok(found) => reports.extend(found),
+            Err(error) => {
+                error!(path = %path.display(), %error, "failed to read ponies");
+                return ExitCode::from(1);
+            }
+        }
+    }
+    for report in &reports {
+        for issue in &report.issues {
+            eprintln!(
+                "{}:{}: {} [{}] {}",
+                report.path.display(),
+                issue.line,
+                issue.kind.severity(),
+                issue.kind.code(),
+                issue.message
+            );
+        }
+    }
+    if !cli.force && reports.iter().any(LintReport::has_errors) {
+        error!("lint errors found; fix them or pass --force");
+        return ExitCode::from(1);
+    }
+
+    match install(&dir, &reports, cli.force) {
+        Ok(installed) => print_stdout(&mut io::stdout().lock(), &installed),
+        Err(error) => {
+            error!(dir = %dir.display(), %error, "failed to install ponies");
+            ExitCode::from(1)
+        }
+    }
+}
+
+/// Entry point for `iron-pony remove-pony ...`; `args` starts with `remove-pony`.
+pub(crate) fn remove_main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    let cli = RemoveCli::parse_from(args);
+    init_tracing(cli.verbose);
+    let Some(dir) = user_pony_dir() else {
+        error!("no home directory to remove ponies from");
+        return ExitCode::from(1);
+    };
+
+    for name in &cli.names {
+        match remove(&dir, name) {
+            Ok(()) => {}
+            Err(RemoveError::NotInstalled) => {
+                return report_error(&PonyError::PonyNotFound { name: name.clone() });
+            }
+            Err(RemoveError::Io(error)) => {
+                error!(pony = %name, %error, "failed to remove pony");
+                return ExitCode::from(1);
+            }
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+/// Copies every linted pony into `dir`, returning one `NAME -> PATH` line per pony. Nothing
+/// is copied when two of them share a file name, or when one would replace an installed
+/// pony and `force` is off.
+fn install(dir: &Path, reports: &[LintReport], force: bool) -> io::Result<String> {
+    let mut sources = BTreeMap::new();
+    for report in reports {
+        let Some(file_name) = report.path.file_name() else {
+            continue;
+        };
+        if let Some(earlier) = sources.insert(file_name, &report.path)
+            && earlier != &report.path
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!(
+                    "{} and {} would both be installed as {}",
+                    earlier.display(),
+                    report.path.display(),
+                    file_name.to_string_lossy()
+                ),
+            ));
+        }
+    }
+
+    fs::create_dir_all(dir)?;
+    let mut copies = Vec::new();
+    for (file_name, source) in sources {
+        let installed = dir.join(file_name);
+        if !force && fs::symlink_metadata(&installed).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!(
+                    "{} is already installed; pass --force to replace it",
+                    installed.display()
+                ),
+            ));
+        }
+        copies.push((source, installed));
+    }
+
+    let mut summary = String::new();
+    for (source, installed) in copies {
+        fs::copy(source, &installed)?;
+        let name = installed
+            .file_stem()
+            .map(|stem| stem.to_string_lossy())
+            .unwrap_or_default();
+        info!(pony = %name, path = %installed.display(), "installed pony");
+        summary.push_str(&format!("{name} -> {}\n", installed.display()));
+    }
+    Ok(summary)
+}
+
+#[derive(Debug)]
+enum RemoveError {
+    NotInstalled,
+    Io(io::Error),
+}
+
+/// Deletes `dir/NAME.pony`. Names are never paths, so only the user's own ponies can go.
+fn remove(dir: &Path, name: &str) -> Result<(), RemoveError> {
+    if name.is_empty() || name.contains(std::path::is_separator) || name.starts_with('.') {
+        return Err(RemoveError::NotInstalled);
+    }
+    let path = dir.join(format!("{name}.pony"));
+    match fs::remove_file(&path) {
+        Ok(()) => {
+            info!(path = %path.display(), "removed pony");
+            Ok(())
+        }
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            debug!(path = %path.display(), "pony not installed");
+            Err(RemoveError::NotInstalled)
+        }
+        Err(error) => Err(RemoveError::Io(error)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn installs_and_removes_user_ponies() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let source = tmp.path().join("source");
+        fs::create_dir_all(&source).expect("mkdir");
+        fs::write(source.join("alpha.pony"), "$balloon$\nalpha\n").expect("write");
+        fs::write(source.join("broken.pony"), "$$$\nNAME: broken\n").expect("write");
+        let reports = lint_pony_dir(&source).expect("lint");
+        assert_eq!(reports.len(), 2);
+        assert!(reports.iter().any(LintReport::has_errors));
+
+        let dir = tmp.path().join("user").join("ponies");
+        let summary = install(&dir, &reports, false).expect("install");
+        assert_eq!(
+            summary,
+            format!(
+                "alpha -> {}\nbroken -> {}\n",
+                dir.join("alpha.pony").display(),
+                dir.join("broken.pony").display()
+            )
+        );
+        let again = install(&dir, &reports, false).expect_err("already installed");
+        assert_eq!(again.kind(), io::ErrorKind::AlreadyExists);
+        install(&dir, &reports, true).expect("forced");
+
+        let other = tmp.path().join("other");
+        fs::create_dir_all(&other).expect("mkdir");
+        fs::write(other.join("alpha.pony"), "$balloon$\nother\n").expect("write");
+        let mut clashing = lint_pony_dir(&other).expect("lint");
+        clashing.extend(reports.iter().cloned());
+        let clash = install(&dir, &clashing, true).expect_err("same file name twice");
+        assert_eq!(clash.kind(), io::ErrorKind::AlreadyExists);
+        assert!(
+            clash
+                .to_string()
+                .contains("both be installed as alpha.pony")
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("alpha.pony")).expect("kept"),
+            "$balloon$\nalpha\n"
+        );
+
+        remove(&dir, "alpha").expect("remove");
+        assert!(!dir.join("alpha.pony").exists());
+        assert!(matches!(
+            remove(&dir, "alpha"),
+            Err(RemoveError::NotInstalled)
+        ));
+        assert!(matches!(
+            remove(&dir, "../source/alpha"),
+            Err(RemoveError::NotInstalled)
+        ));
+        assert!(source.join("alpha.pony").exists());
+    }
+}
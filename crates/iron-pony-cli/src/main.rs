@@ -1,12 +1,43 @@
-use std::io::{self, IsTerminal, Read, Write};
+mod best;
+mod docs;
+mod doctor;
+mod env;
+mod filter;
+mod install;
+mod output;
+#[cfg(feature = "pick")]
+mod pick;
+mod probe;
+#[cfg(feature = "serve")]
+mod serve;
+
+use std::cell::LazyCell;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::io::{self, IsTerminal};
 use std::path::PathBuf;
 use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
 
-use clap::Parser;
+use clap::{CommandFactory, Parser, ValueEnum};
 use iron_pony_core::{
-    FortuneConfig, Mode, RenderConfig, default_balloon_paths, default_pony_paths, list_ponies,
-    pick_fortune, render, select_pony,
+    AssetPack, BalloonMode, BalloonPlacement, BalloonStyle, BuiltinTransform, ColorMode,
+    CompatLevel, FortuneConfig, MessageSource, Mode, PATH_LIST_SEPARATOR, Platform, PonyAsset,
+    PonyError, PonyIndex, PonyParseError, PonySummary, RenderConfig, RenderOutput, Renderer,
+    SearchMatch, SourceOptions, UcsMap, WrapOptions, default_balloon_paths,
+    default_index_cache_path, default_pony_paths, default_quote_paths, default_ucsmap_paths,
+    list_balloons, list_quoted_ponies, load_pony, load_style, pony_file_problems, sample_ponies,
+    search_ponies, select_pony_indexed, strip_ansi,
 };
+#[cfg(feature = "figlet")]
+use iron_pony_core::{BUILTIN_FIGFONT, default_figlet_paths, load_figfont};
+use serde_json::json;
+
+use crate::docs::Shell;
+use crate::env::{PonysayEnv, enable_virtual_terminal, terminal_size};
+use crate::filter::{FilterError, FilterMode};
+use crate::output::{OutputWriter, Pace};
 use tracing::{debug, error, info, warn};
 use tracing_subscriber::EnvFilter;
 
@@ -23,32 +54,332 @@ struct Cli {
     #[arg(short = 'b', long = "balloon", help = "Balloon style name")]
     balloon: Option<String>,
 
+    #[arg(
+        long = "builtin-balloon",
+        conflicts_with = "balloon",
+        help = "Use the built-in balloon style instead of installed defaults"
+    )]
+    builtin_balloon: bool,
+
+    #[arg(
+        long = "balloon-chars",
+        value_name = "GLYPHS",
+        conflicts_with_all = ["balloon", "builtin_balloon"],
+        help = "One-off balloon from 8 glyphs clockwise from the top-left, plus an optional link glyph (e.g. '╭─╮│╯─╰│')"
+    )]
+    balloon_chars: Option<String>,
+
+    #[arg(
+        long = "balloon-file",
+        value_name = "PATH",
+        conflicts_with_all = ["balloon", "builtin_balloon", "balloon_chars"],
+        help = "One-off balloon from a TOML description (see `xtask convert-balloon`)"
+    )]
+    balloon_file: Option<PathBuf>,
+
+    #[arg(
+        long = "corrected-balloons",
+        help = "Size balloon borders from their actual pieces instead of upstream's width arithmetic"
+    )]
+    corrected_balloons: bool,
+
+    #[arg(
+        long = "balloon-preview",
+        value_name = "STYLE",
+        num_args = 0..=1,
+        default_missing_value = "",
+        conflicts_with_all = ["message", "info", "info_json", "list", "quotes_list", "search"],
+        help = "Show a sample balloon in every installed style (or just STYLE) instead of rendering"
+    )]
+    balloon_preview: Option<String>,
+
     #[arg(long = "think", help = "Render using think mode")]
     think: bool,
 
-    #[arg(long = "verbose", help = "Enable tracing logs")]
-    verbose: bool,
+    #[arg(
+        long = "output",
+        value_enum,
+        default_value_t = OutputFormat::Text,
+        help = "Output format"
+    )]
+    output: OutputFormat,
+
+    #[arg(
+        long = "plain",
+        help = "Omit the leading terminal reset that upstream prints before the pony"
+    )]
+    plain: bool,
+
+    #[arg(
+        short = 'v',
+        long = "verbose",
+        action = clap::ArgAction::Count,
+        help = "Log progress to stderr (-v info, -vv debug); IRON_PONY_LOG overrides"
+    )]
+    verbose: u8,
+
+    #[arg(
+        long = "motd",
+        help = "Login-safe output: console-detected colors, capped height, trailing reset, no logs"
+    )]
+    motd: bool,
+
+    #[arg(
+        long = "slow",
+        value_name = "RATE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "40",
+        help = "Type the output out at RATE characters per second (default: 40)"
+    )]
+    slow: Option<f64>,
+
+    #[arg(
+        long = "slow-lines",
+        requires = "slow",
+        help = "Make --slow print RATE whole lines per second instead"
+    )]
+    slow_lines: bool,
+
+    #[arg(
+        long = "filter",
+        value_enum,
+        value_name = "MODE",
+        conflicts_with_all = ["message", "message_file", "fortune", "quote", "rounds"],
+        help = "Decorate stdin as it arrives: a balloon per paragraph, or one balloon redrawn per line"
+    )]
+    filter: Option<FilterMode>,
 
-    #[arg(long = "wrap", default_value_t = 40, help = "Balloon wrap width")]
-    wrap: usize,
+    #[arg(
+        long = "loop",
+        value_name = "ROUNDS",
+        conflicts_with_all = ["info", "list", "quotes_list", "search"],
+        help = "Render ROUNDS times (0 = forever), drawing a new random pony, quote or fortune each round"
+    )]
+    rounds: Option<u64>,
+
+    #[arg(
+        long = "each",
+        requires = "message",
+        conflicts_with_all = ["message_file", "fortune", "quote", "filter", "rounds"],
+        help = "Render every MESSAGE argument on its own, each with its own pony"
+    )]
+    each: bool,
+
+    #[arg(
+        long = "stack",
+        requires = "each",
+        help = "With --each, stack the balloons above a single pony instead"
+    )]
+    stack: bool,
+
+    #[arg(
+        long = "dialogue",
+        value_name = "PONY",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "",
+        requires = "message",
+        conflicts_with_all = ["message_file", "fortune", "quote", "filter", "rounds", "each", "output"],
+        help = "Play MESSAGE arguments as a conversation between the pony and PONY (random if omitted), facing each other"
+    )]
+    dialogue: Option<String>,
+
+    #[arg(
+        long = "gallery",
+        value_name = "N",
+        conflicts_with_all = ["pony", "message", "message_file", "fortune", "quote", "filter", "rounds", "each", "dialogue", "output"],
+        help = "Show N random ponies (use --seed to repeat a draw) in a grid as wide as the terminal, each captioned with its name"
+    )]
+    gallery: Option<usize>,
+
+    #[arg(
+        long = "loop-delay",
+        value_name = "SECONDS",
+        default_value_t = 5.0,
+        requires = "rounds",
+        help = "Pause between --loop rounds"
+    )]
+    loop_delay: f64,
+
+    #[arg(
+        long = "max-height",
+        value_name = "LINES",
+        help = "Drop output lines past this many (--motd defaults to 40)"
+    )]
+    max_height: Option<usize>,
+
+    #[arg(
+        long = "probe-terminal",
+        help = "Fit balloons and lines to the terminal, asking it for its size when the tty cannot report it (serial consoles)"
+    )]
+    probe_terminal: bool,
+
+    #[arg(
+        long = "wrap",
+        value_name = "COLUMNS",
+        help = "Balloon wrap width [default: PONYSAY_WRAP_LIMIT or 40]"
+    )]
+    wrap: Option<usize>,
+
+    #[arg(
+        long = "flip",
+        help = "Mirror the pony left to right, keeping the balloon text readable"
+    )]
+    flip: bool,
+
+    #[arg(
+        long = "show-name",
+        help = "Caption the pony with its name (NAME metadata or file name) under the art"
+    )]
+    show_name: bool,
+
+    #[arg(long = "center", help = "Center the balloon over the pony art")]
+    center: bool,
+
+    #[arg(
+        long = "balloon-column",
+        value_name = "COLUMN",
+        conflicts_with = "center",
+        help = "Start the balloon at a fixed column instead of the template anchor"
+    )]
+    balloon_column: Option<usize>,
 
     #[arg(
         long = "ponydir",
-        value_delimiter = ':',
+        value_delimiter = PATH_LIST_SEPARATOR,
         help = "Pony search path override"
     )]
     pony_paths: Vec<PathBuf>,
 
     #[arg(
         long = "balloondir",
-        value_delimiter = ':',
+        value_delimiter = PATH_LIST_SEPARATOR,
         help = "Balloon search path override"
     )]
     balloon_paths: Vec<PathBuf>,
 
+    #[arg(
+        long = "ponypack",
+        help = "Load ponies and balloons from a .tar or .zip asset pack"
+    )]
+    pony_pack: Option<PathBuf>,
+
+    #[arg(
+        long = "ucsmapdir",
+        value_delimiter = PATH_LIST_SEPARATOR,
+        help = "UCS name map search path override"
+    )]
+    ucsmap_paths: Vec<PathBuf>,
+
+    #[arg(
+        short = 'u',
+        long = "ucs",
+        help = "Show Unicode pony names in listings"
+    )]
+    ucs: bool,
+
+    #[arg(
+        long = "no-ucs",
+        overrides_with = "ucs",
+        help = "Show ASCII pony file names in listings (alias: +u)"
+    )]
+    no_ucs: bool,
+
+    #[arg(
+        short = 'i',
+        long = "info",
+        help = "Print pony metadata instead of rendering"
+    )]
+    info: bool,
+
+    #[arg(
+        long = "info-json",
+        conflicts_with_all = ["info", "list", "quotes_list", "search"],
+        help = "Print pony metadata, art size and balloon preferences as JSON instead of rendering"
+    )]
+    info_json: bool,
+
+    #[arg(
+        long = "all",
+        requires = "info_json",
+        conflicts_with = "pony",
+        help = "With --info-json, describe every installed pony"
+    )]
+    all: bool,
+
+    #[arg(long = "no-cache", help = "Bypass the cached pony directory index")]
+    no_cache: bool,
+
+    #[cfg(feature = "pick")]
+    #[arg(
+        long = "pick",
+        conflicts_with_all = ["pony", "quote"],
+        help = "Choose the pony in an interactive fuzzy picker with a live preview"
+    )]
+    pick: bool,
+
     #[arg(long = "list", help = "List available ponies")]
     list: bool,
 
+    #[arg(
+        long = "clear-best",
+        help = "Remove the best.pony link set by `iron-pony set-best`"
+    )]
+    clear_best: bool,
+
+    #[arg(
+        long = "print-man-page",
+        hide = true,
+        help = "Print the man page (for packagers)"
+    )]
+    print_man_page: bool,
+
+    #[arg(
+        long = "write-man-pages",
+        value_name = "DIR",
+        hide = true,
+        help = "Write the man page and one per subcommand into DIR (for packagers)"
+    )]
+    write_man_pages: Option<PathBuf>,
+
+    #[arg(
+        long = "print-completions",
+        value_name = "SHELL",
+        hide = true,
+        help = "Print a shell completion script (for packagers)"
+    )]
+    print_completions: Option<Shell>,
+
+    #[arg(
+        short = 'q',
+        long = "quote",
+        num_args = 0..,
+        value_name = "PONY",
+        help = "Use a random quote from one of the given ponies (any pony with quotes if none)"
+    )]
+    quote: Option<Vec<String>>,
+
+    #[arg(
+        long = "quotes-list",
+        help = "List ponies that have quotes, with their quote counts"
+    )]
+    quotes_list: bool,
+
+    #[arg(
+        long = "search",
+        value_name = "QUERY",
+        help = "List ponies whose name, NAME/OTHER NAMES/GROUP tags or quotes contain QUERY"
+    )]
+    search: Option<String>,
+
+    #[arg(
+        long = "quotedir",
+        value_delimiter = PATH_LIST_SEPARATOR,
+        help = "Quote search path override"
+    )]
+    quote_paths: Vec<PathBuf>,
+
     #[arg(long = "fortune", help = "Use internal fortune selection")]
     fortune: bool,
 
@@ -64,25 +395,178 @@ struct Cli {
     )]
     fortune_equal: bool,
 
+    #[arg(
+        long = "fortune-fallback",
+        requires = "fortune",
+        help = "Run the system `fortune` when no internal fortune database is found, as upstream does"
+    )]
+    fortune_fallback: bool,
+
     #[arg(
         long = "fortune-path",
-        value_delimiter = ':',
+        value_delimiter = PATH_LIST_SEPARATOR,
         help = "Fortune database search path override"
     )]
     fortune_paths: Vec<PathBuf>,
 
-    #[arg(long = "seed", help = "Deterministic seed for random selection")]
+    #[cfg(feature = "figlet")]
+    #[arg(
+        long = "figlet",
+        value_name = "FONT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = BUILTIN_FIGFONT,
+        help = "Render the message as a FIGlet banner (font name or .flf path; default: compact)"
+    )]
+    figlet: Option<String>,
+
+    #[arg(
+        long = "transform",
+        value_enum,
+        value_name = "NAME",
+        value_delimiter = ',',
+        help = "Rewrite the message before wrapping; comma-separated, applied in order"
+    )]
+    transforms: Vec<TransformName>,
+
+    #[cfg(feature = "bidi")]
+    #[arg(
+        long = "bidi",
+        help = "Reorder right-to-left (Hebrew, Arabic) lines for terminals without bidi support"
+    )]
+    bidi: bool,
+
+    #[arg(
+        long = "message-file",
+        value_name = "PATH",
+        conflicts_with_all = ["message", "fortune", "quote"],
+        help = "Read the message verbatim from a file ('-' for stdin)"
+    )]
+    message_file: Option<PathBuf>,
+
+    #[arg(
+        long = "seed",
+        help = "Deterministic seed for random selection (picks as Python's random.seed would)"
+    )]
     seed: Option<u64>,
 
     #[arg(value_name = "MESSAGE", trailing_var_arg = true)]
     message: Vec<String>,
 }
 
+impl Cli {
+    /// Whether `--pick` chooses the pony; always false without the `pick` feature.
+    fn picks_pony(&self) -> bool {
+        #[cfg(feature = "pick")]
+        let picks = self.pick;
+        #[cfg(not(feature = "pick"))]
+        let picks = false;
+        picks
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// ANSI text exactly as ponysay would print it.
+    Text,
+    /// Versioned JSON document for scripts and bots.
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum TransformName {
+    /// UPPERCASE.
+    Upper,
+    /// lowercase.
+    Lower,
+    /// Small capitals (ꜱᴍᴀʟʟ ᴄᴀᴘꜱ).
+    Smallcaps,
+    /// Drop stacked combining marks ("zalgo" text), keeping ordinary accents.
+    StripZalgo,
+}
+
+impl From<TransformName> for BuiltinTransform {
+    fn from(name: TransformName) -> Self {
+        match name {
+            TransformName::Upper => Self::Upper,
+            TransformName::Lower => Self::Lower,
+            TransformName::Smallcaps => Self::SmallCaps,
+            TransformName::StripZalgo => Self::StripZalgo,
+        }
+    }
+}
+
+/// Balloon wrap width when neither `--wrap` nor `PONYSAY_WRAP_LIMIT` is set.
+const DEFAULT_WRAP: usize = 40;
+
+/// Text inside the `--balloon-preview` balloons.
+const PREVIEW_SAMPLE: &str = "Hello, pony!";
+
+/// Gallery width when the terminal size is unknown.
+const DEFAULT_GALLERY_WIDTH: usize = 80;
+
+/// Height cap for `--motd` when `--max-height` is not given; keeps a pony from pushing
+/// the rest of the message of the day off a 50-row terminal.
+const MOTD_MAX_HEIGHT: usize = 40;
+
+/// Exit status when the `--pick` picker is dismissed, as with fzf and skim.
+#[cfg(feature = "pick")]
+const PICK_CANCELLED: u8 = 130;
+
+/// Exit status when stdout is closed early (`iron-pony | head`), matching the 128 + SIGPIPE
+/// that shells report for filters killed by the signal.
+const BROKEN_PIPE: u8 = 141;
+
+/// Bumped whenever the `--output json` document changes incompatibly.
+const JSON_SCHEMA_VERSION: u32 = 1;
+
 fn main() -> ExitCode {
-    let cli = Cli::parse();
-    init_tracing(cli.verbose);
+    // Clap subcommands would swallow messages starting with a word like `serve`, so they
+    // are dispatched by hand; `iron-pony -- serve` still says "serve".
+    let args = std::env::args_os().skip(1);
+    match std::env::args_os()
+        .nth(1)
+        .as_ref()
+        .and_then(|arg| arg.to_str())
+    {
+        #[cfg(feature = "serve")]
+        Some("serve") => return serve::main(args),
+        Some("set-best") => return best::main(args),
+        Some("install-pony") => return install::install_main(args),
+        Some("remove-pony") => return install::remove_main(args),
+        Some("doctor") => return doctor::main(args),
+        _ => {}
+    }
+
+    enable_virtual_terminal();
+    let cli = Cli::parse_from(normalize_plus_flags(std::env::args_os()));
+    // Log lines would end up in the message of the day.
+    if !cli.motd {
+        init_tracing(cli.verbose);
+    }
     debug!(?cli, "parsed CLI options");
 
+    if cli.clear_best {
+        return best::clear();
+    }
+    if let Some(dir) = &cli.write_man_pages {
+        return match docs::write_man_pages(documented_command(), dir) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(source) => report_error(&PonyError::Io {
+                path: dir.clone(),
+                source,
+            }),
+        };
+    }
+    if cli.print_man_page || cli.print_completions.is_some() {
+        let command = documented_command();
+        let text = match cli.print_completions {
+            Some(shell) => docs::completions(command, shell),
+            None => docs::man_page(command),
+        };
+        return print_stdout(&mut io::stdout().lock(), &text);
+    }
+
     let pony_paths = if cli.pony_paths.is_empty() {
         env_paths("PONYSAY_PONY_PATH").unwrap_or_else(default_pony_paths)
     } else {
@@ -95,121 +579,1130 @@ fn main() -> ExitCode {
         cli.balloon_paths.clone()
     };
 
+    let ucsmap = if cli.ucsmap_paths.is_empty() {
+        UcsMap::load(&default_ucsmap_paths())
+    } else {
+        UcsMap::load(&cli.ucsmap_paths)
+    };
+    let ponysay_env = PonysayEnv::from_env();
+    let use_ucs = (cli.ucs || ponysay_env.ucs_me) && !cli.no_ucs;
+
+    let cache_path = if cli.no_cache {
+        None
+    } else {
+        default_index_cache_path()
+    };
+    let mut index = PonyIndex::load(&pony_paths, cache_path.as_deref());
+
+    let pack = match cli.pony_pack.as_deref().map(AssetPack::open).transpose() {
+        Ok(pack) => pack,
+        Err(error) => {
+            debug!(%error, "failed to open asset pack");
+            return report_error(&error);
+        }
+    };
+    if let Some(pack) = &pack {
+        index.add_pack(pack);
+    }
+
+    if let Some(name) = &cli.balloon_preview {
+        let mode = if cli.think {
+            BalloonMode::Think
+        } else {
+            BalloonMode::Say
+        };
+        let compat = if cli.corrected_balloons {
+            CompatLevel::Corrected
+        } else {
+            CompatLevel::Upstream
+        };
+        let name = (!name.is_empty()).then_some(name.as_str());
+        return match balloon_previews(name, &balloon_paths, pack.as_ref(), mode, compat) {
+            Ok(text) => print_stdout(&mut io::stdout().lock(), &text),
+            Err(error) => report_error(&error),
+        };
+    }
+
     if cli.list {
-        let names = list_ponies(&pony_paths);
+        let ucsmap = use_ucs.then_some(&ucsmap);
+        let bold = io::stdout().is_terminal() && color_mode(&cli) != ColorMode::Off;
+        let text = match cli.output {
+            OutputFormat::Text => list_text(&index, ucsmap, bold),
+            OutputFormat::Json => format!("{}\n", list_document(&index, ucsmap)),
+        };
+        return print_stdout(&mut io::stdout().lock(), &text);
+    }
+
+    let quote_paths = if cli.quote_paths.is_empty() {
+        default_quote_paths()
+    } else {
+        cli.quote_paths.clone()
+    };
+
+    if cli.quotes_list {
+        let mut text = String::new();
+        for (name, count) in list_quoted_ponies(&quote_paths, &index) {
+            let name = if use_ucs { ucsmap.to_ucs(&name) } else { &name };
+            text.push_str(&format!("{name}\t{count}\n"));
+        }
+        return print_stdout(&mut io::stdout().lock(), &text);
+    }
+
+    if let Some(query) = &cli.search {
+        let matches = search_ponies(&index, &quote_paths, query);
+        let ucsmap = use_ucs.then_some(&ucsmap);
+        let text = match cli.output {
+            OutputFormat::Text => search_text(&matches, ucsmap),
+            OutputFormat::Json => format!("{}\n", search_document(query, &matches, ucsmap)),
+        };
+        let status = print_stdout(&mut io::stdout().lock(), &text);
+        // Like grep, finding nothing is a failure scripts can test for.
+        return if matches.is_empty() && status == ExitCode::SUCCESS {
+            ExitCode::from(1)
+        } else {
+            status
+        };
+    }
+
+    if cli.info {
+        return print_info(
+            cli.pony.as_deref(),
+            &pony_paths,
+            &index,
+            pack.as_ref(),
+            &ucsmap,
+            cli.seed,
+        );
+    }
+
+    if cli.info_json {
+        let requested = cli.pony.as_deref().map(|name| ucsmap.to_ascii(name));
+        let names = if cli.all {
+            index.names()
+        } else {
+            match select_pony_indexed(requested, &pony_paths, &index, cli.seed) {
+                Ok(name) => vec![name],
+                Err(error) => return report_error(&error),
+            }
+        };
+        let mut assets = Vec::with_capacity(names.len());
         for name in names {
-            println!("{name}");
+            match pack
+                .as_ref()
+                .and_then(|pack| pack.load_pony(&name, upstream_compat()))
+                .unwrap_or_else(|| load_pony(&name, &pony_paths, upstream_compat()))
+            {
+                Ok(asset) => assets.push(asset),
+                // One unreadable pony should not spoil a dump of all of them.
+                Err(error) if cli.all => warn!(pony = %name, %error, "skipping pony"),
+                Err(error) => return report_error(&error),
+            }
         }
-        return ExitCode::SUCCESS;
+        let text = info_document(&assets, use_ucs.then_some(&ucsmap));
+        return print_stdout(&mut io::stdout().lock(), &format!("{text}\n"));
     }
 
-    let message = match resolve_message(&cli) {
-        Ok(message) => message,
+    let rounds = Rounds {
+        cli: &cli,
+        index: &index,
+        pony_paths: &pony_paths,
+        quote_paths: &quote_paths,
+        ucsmap: &ucsmap,
+    };
+    let (pony, message) = match rounds.select(0, None) {
+        Ok(selected) => selected,
         Err(error) => {
-            error!(%error, "failed to resolve message input");
-            eprintln!("iron-pony: {error}");
-            return ExitCode::from(1);
+            debug!(%error, "failed to select pony and message");
+            return report_error(&error);
         }
     };
 
-    let pony = match select_pony(cli.pony.as_deref(), &pony_paths, cli.seed) {
-        Ok(pony) => pony,
+    let mode = if cli.think { Mode::Think } else { Mode::Say };
+    let balloon_style = match custom_balloon(&cli, mode) {
+        Ok(style) => style,
         Err(error) => {
-            error!(%error, "failed to resolve pony");
-            eprintln!("iron-pony: {error}");
-            return ExitCode::from(1);
+            debug!(%error, "failed to build custom balloon");
+            return report_error(&error);
         }
     };
 
-    let config = RenderConfig {
+    #[cfg(feature = "figlet")]
+    let figlet = match cli
+        .figlet
+        .as_deref()
+        .map(|name| {
+            let font_paths = env_paths("FIGLET_FONTDIR").unwrap_or_else(default_figlet_paths);
+            load_figfont(name, &font_paths)
+        })
+        .transpose()
+    {
+        Ok(font) => font,
+        Err(error) => {
+            debug!(%error, "failed to load FIGlet font");
+            return report_error(&error);
+        }
+    };
+
+    let (stack, message) = if cli.stack {
+        let (last, stack) = cli.message.split_last().expect("--each requires messages");
+        (stack.to_vec(), last.clone())
+    } else {
+        (Vec::new(), message)
+    };
+
+    // Only looked up when something needs it. `--probe-terminal` fits the output to the
+    // terminal: balloons wrap no wider than it and lines are clipped to it.
+    let terminal = LazyCell::new(|| terminal_size(cli.probe_terminal));
+    let fit_columns = cli
+        .probe_terminal
+        .then(|| terminal.map(|(columns, _)| columns))
+        .flatten();
+    let mut config = RenderConfig {
         message,
+        stack,
         pony,
-        pony_paths,
-        balloon: cli.balloon,
+        pony_paths: pony_paths.clone(),
+        balloon: cli.balloon.clone(),
         balloon_paths,
-        mode: if cli.think { Mode::Think } else { Mode::Say },
-        wrap_width: cli.wrap.max(1),
+        mode,
+        wrap_width: cli
+            .wrap
+            .or(ponysay_env.wrap_limit)
+            .unwrap_or_else(|| {
+                fit_columns.map_or(DEFAULT_WRAP, |columns| columns.min(DEFAULT_WRAP))
+            })
+            .max(1),
+        wrap_options: WrapOptions {
+            exceed: ponysay_env.wrap_exceed.unwrap_or_default(),
+            hyphen: ponysay_env.wrap_hyphen.clone().unwrap_or_default(),
+        },
+        balloon_placement: match (cli.center, cli.balloon_column) {
+            (true, _) => BalloonPlacement::Center,
+            (false, Some(column)) => BalloonPlacement::Column(column),
+            (false, None) => BalloonPlacement::Anchor,
+        },
+        builtin_balloon: cli.builtin_balloon,
+        balloon_style,
+        compat: if cli.corrected_balloons {
+            CompatLevel::Corrected
+        } else {
+            CompatLevel::Upstream
+        },
+        #[cfg(feature = "figlet")]
+        figlet,
+        leading_reset: !cli.plain,
+        trailing_reset: cli.motd,
+        color: color_mode(&cli),
+        max_height: cli
+            .max_height
+            .or(cli.motd.then_some(MOTD_MAX_HEIGHT))
+            .or_else(|| ponysay_env.max_height(|| *terminal)),
+        max_width: ponysay_env.max_width(|| *terminal).or(fit_columns),
+        trim_trailing_whitespace: !upstream_compat(),
+        transforms: cli
+            .transforms
+            .iter()
+            .map(|&name| Arc::new(BuiltinTransform::from(name)) as _)
+            .collect(),
+        #[cfg(feature = "bidi")]
+        bidi: cli.bidi,
+        exact_names: upstream_compat(),
+        aliases: BTreeMap::new(),
+        flip: cli.flip,
+        show_name: cli.show_name,
+        pack,
     };
 
-    match render(&config) {
-        Ok(output) => {
-            info!("render completed");
-            let mut stdout = io::stdout().lock();
-            if let Err(error) = writeln!(stdout, "{output}") {
-                error!(%error, "failed to write output");
-                return ExitCode::from(1);
+    #[cfg(feature = "pick")]
+    if cli.pick {
+        match pick::run(&index, &config) {
+            Ok(Some(pony)) => config.pony = pony,
+            Ok(None) => return ExitCode::from(PICK_CANCELLED),
+            Err(source) => {
+                return report_error(&PonyError::Io {
+                    path: PathBuf::from("<terminal>"),
+                    source,
+                });
             }
-            ExitCode::SUCCESS
         }
-        Err(error) => {
-            error!(%error, "render failed");
-            eprintln!("iron-pony: {error}");
-            ExitCode::from(1)
+    }
+
+    let pace = match (cli.slow, cli.slow_lines) {
+        (None, _) => Pace::Instant,
+        (Some(rate), false) => Pace::Chars(rate),
+        (Some(rate), true) => Pace::Lines(rate),
+    };
+    let mut writer = OutputWriter::new(io::stdout().lock(), pace);
+    let renderer = Renderer::new(&config).with_index(index.clone());
+    if let Some(count) = cli.gallery {
+        let width = config
+            .max_width
+            .or_else(|| terminal.map(|(columns, _)| columns))
+            .unwrap_or(DEFAULT_GALLERY_WIDTH);
+        let names = sample_ponies(&index, count, cli.seed);
+        let text = names
+            .into_iter()
+            .map(|pony| {
+                renderer.resolve_pony(
+                    &RenderConfig {
+                        pony,
+                        ..config.clone()
+                    },
+                    None,
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .and_then(|ponies| renderer.render_gallery(&config, &ponies, width));
+        return match text {
+            Ok(text) => match writer.write_text(&text) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(error) => write_failed(&error),
+            },
+            Err(error) => {
+                debug!(%error, "gallery failed");
+                report_error(&error)
+            }
+        };
+    }
+    if let Some(other) = &cli.dialogue {
+        let requested = Some(other.as_str())
+            .filter(|name| !name.is_empty())
+            .map(|name| ucsmap.to_ascii(name));
+        // A different seed, so a seeded pair of random ponies is not the same pony twice.
+        let seed = cli.seed.map(|seed| seed.wrapping_add(1));
+        let text = select_pony_indexed(requested, &pony_paths, &index, seed).and_then(|pony| {
+            let right = renderer.resolve_pony(
+                &RenderConfig {
+                    pony,
+                    ..config.clone()
+                },
+                None,
+            )?;
+            let left = renderer.resolve_pony(&config, None)?;
+            renderer.render_dialogue(&config, &left, &right, &cli.message)
+        });
+        return match text {
+            Ok(text) => match writer.write_text(&text) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(error) => write_failed(&error),
+            },
+            Err(error) => {
+                debug!(%error, "dialogue failed");
+                report_error(&error)
+            }
+        };
+    }
+    if let Some(mode) = cli.filter {
+        let pony = match renderer.resolve_pony(&config, None) {
+            Ok(pony) => pony,
+            Err(error) => return report_error(&error),
+        };
+        let redraw = io::stdout().is_terminal();
+        return match filter::run(
+            mode,
+            io::stdin().lock(),
+            &renderer,
+            &mut config,
+            &pony,
+            &mut writer,
+            redraw,
+        ) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(FilterError::Render(error)) => {
+                debug!(%error, "render failed");
+                report_error(&error)
+            }
+            Err(FilterError::Io(error)) if error.kind() == io::ErrorKind::BrokenPipe => {
+                write_failed(&error)
+            }
+            Err(FilterError::Io(error)) => {
+                error!(%error, "failed to filter input");
+                ExitCode::from(1)
+            }
+        };
+    }
+
+    let delay = Duration::try_from_secs_f64(cli.loop_delay).unwrap_or(Duration::ZERO);
+    let mut round = 0;
+    loop {
+        // `config.pony` is already resolved, so this only loads it.
+        let output = match renderer
+            .resolve_pony(&config, None)
+            .and_then(|pony| renderer.render_output(&config, &pony))
+        {
+            Ok(output) => output,
+            Err(error) => {
+                debug!(%error, "render failed");
+                return report_error(&error);
+            }
+        };
+        info!(round, "render completed");
+        let text = match cli.output {
+            OutputFormat::Text => output.text(),
+            OutputFormat::Json => json_document(&output),
+        };
+        if let Err(error) = writer.write_text(&text) {
+            return write_failed(&error);
+        }
+
+        round += 1;
+        let more = match cli.rounds {
+            Some(0) => true,
+            Some(total) => round < total,
+            None => cli.each && !cli.stack && round < cli.message.len() as u64,
+        };
+        if !more {
+            return ExitCode::SUCCESS;
+        }
+        if cli.rounds.is_some() {
+            writer.pause(delay);
+        }
+        let previous = std::mem::take(&mut config.message);
+        match rounds.select(round, Some(previous)) {
+            Ok((pony, message)) => {
+                if !pony.is_empty() {
+                    config.pony = pony;
+                }
+                config.message = message;
+            }
+            Err(error) => {
+                debug!(%error, "failed to select pony and message");
+                return report_error(&error);
+            }
         }
     }
 }
 
-fn resolve_message(cli: &Cli) -> Result<String, String> {
-    if cli.fortune {
-        info!("using internal fortune mode");
-        let mut fortune_config = FortuneConfig::default();
-        fortune_config.include_offensive = cli.fortune_all;
-        fortune_config.equal_files = cli.fortune_equal;
-        fortune_config.seed = cli.seed;
-        if !cli.fortune_paths.is_empty() {
-            fortune_config.search_paths = cli.fortune_paths.clone();
-        } else if let Some(paths) = env_paths("FORTUNE_PATH") {
-            fortune_config.search_paths = paths;
+/// Draws the pony and message for each render: a quote and its pony with `-q`,
+/// otherwise the message input and the requested pony (or a random one).
+struct Rounds<'a> {
+    cli: &'a Cli,
+    index: &'a PonyIndex,
+    pony_paths: &'a [PathBuf],
+    quote_paths: &'a [PathBuf],
+    ucsmap: &'a UcsMap,
+}
+
+impl Rounds<'_> {
+    /// Selection for `--loop` round (or `--each` message) `round`, seeded with `--seed` plus
+    /// the round number. Later rounds keep the `previous` message unless it came from
+    /// fortunes, since arguments and stdin cannot change. The pony is empty while `--pick`
+    /// has yet to run.
+    fn select(&self, round: u64, previous: Option<String>) -> Result<(String, String), PonyError> {
+        let cli = self.cli;
+        let seed = cli.seed.map(|seed| seed.wrapping_add(round));
+        let message = match self.message_source(seed) {
+            Some(source) => {
+                let sourced =
+                    source.resolve(&self.options(seed), io::stdin(), io::stdin().is_terminal())?;
+                if let Some(pony) = sourced.pony {
+                    return Ok((pony, sourced.message));
+                }
+                sourced.message
+            }
+            None => match previous {
+                _ if cli.each => usize::try_from(round)
+                    .ok()
+                    .and_then(|round| cli.message.get(round))
+                    .cloned()
+                    .unwrap_or_default(),
+                Some(message) => message,
+                // Read incrementally once rendering starts, or not needed at all.
+                _ if cli.filter.is_some() || cli.gallery.is_some() => String::new(),
+                _ => {
+                    let source = match &cli.message_file {
+                        Some(path) => MessageSource::File(path.clone()),
+                        None => MessageSource::Args(cli.message.clone()),
+                    };
+                    source
+                        .resolve(&self.options(seed), io::stdin(), io::stdin().is_terminal())?
+                        .message
+                }
+            },
+        };
+        if cli.picks_pony() {
+            // Picked once the render config exists, so the picker can preview with it.
+            return Ok((String::new(), message));
+        }
+        let requested = cli.pony.as_deref().map(|name| self.ucsmap.to_ascii(name));
+        let pony = select_pony_indexed(requested, self.pony_paths, self.index, seed)?;
+        Ok((pony, message))
+    }
+
+    fn options(&self, seed: Option<u64>) -> SourceOptions<'_> {
+        SourceOptions {
+            quote_paths: self.quote_paths,
+            index: self.index,
+            seed,
         }
-        return pick_fortune(&fortune_config).map_err(|error| error.to_string());
     }
 
-    if !cli.message.is_empty() {
-        return Ok(cli.message.join(" "));
+    /// `-q` and `--fortune` as a [`MessageSource`]: with both, a quote when one of the
+    /// ponies has any and a fortune otherwise.
+    fn message_source(&self, seed: Option<u64>) -> Option<MessageSource> {
+        let cli = self.cli;
+        let quoted = cli.quote.as_ref().map(|requested| {
+            requested
+                .iter()
+                .map(|name| self.ucsmap.to_ascii(name).to_string())
+                .collect::<Vec<_>>()
+        });
+        match (quoted, cli.fortune) {
+            (Some(ponies), false) => Some(MessageSource::Quote(ponies)),
+            (Some(ponies), true) => Some(MessageSource::QuoteOrFortune(
+                ponies,
+                fortune_config(cli, seed),
+            )),
+            (None, true) => Some(MessageSource::Fortune(fortune_config(cli, seed))),
+            (None, false) => None,
+        }
     }
+}
 
-    let mut stdin = io::stdin();
-    if !stdin.is_terminal() {
-        let mut data = String::new();
-        stdin
-            .read_to_string(&mut data)
-            .map_err(|error| format!("failed reading stdin: {error}"))?;
-        let trimmed = data.trim().to_string();
-        if !trimmed.is_empty() {
-            return Ok(trimmed);
+/// `--list` output: like upstream, a "ponies located in" header per directory, in bold
+/// when `bold` is set.
+fn list_text(index: &PonyIndex, ucsmap: Option<&UcsMap>, bold: bool) -> String {
+    let mut out = String::new();
+    for (root, ponies) in summaries_by_root(index) {
+        let header = format!("ponies located in {}", root.display());
+        if bold {
+            out.push_str(&format!("\u{1b}[1m{header}\u{1b}[21m\n"));
+        } else {
+            out.push_str(&header);
+            out.push('\n');
+        }
+        let mut names = ponies
+            .iter()
+            .map(|pony| ucsmap.map_or(pony.name.as_str(), |map| map.to_ucs(&pony.name)))
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        for name in names {
+            out.push_str(name);
+            out.push('\n');
         }
     }
+    out
+}
 
-    warn!("no message source resolved");
-    Err("no message provided".to_string())
+/// `--list --output json`: every pony with the file it was found in, grouped by root.
+fn list_document(index: &PonyIndex, ucsmap: Option<&UcsMap>) -> String {
+    let roots = summaries_by_root(index)
+        .into_iter()
+        .map(|(root, ponies)| {
+            let ponies = ponies
+                .into_iter()
+                .map(|pony| {
+                    let mut entry = json!({
+                        "name": pony.name,
+                        "path": pony.path.display().to_string(),
+                        "class": pony.class.as_str(),
+                    });
+                    if let Some(map) = ucsmap {
+                        entry["ucs_name"] = json!(map.to_ucs(&pony.name));
+                    }
+                    entry
+                })
+                .collect::<Vec<_>>();
+            json!({ "root": root.display().to_string(), "ponies": ponies })
+        })
+        .collect::<Vec<_>>();
+    let document = json!({
+        "version": JSON_SCHEMA_VERSION,
+        "roots": roots,
+    });
+    serde_json::to_string_pretty(&document).unwrap_or_else(|_| document.to_string())
 }
 
-fn env_paths(var: &str) -> Option<Vec<PathBuf>> {
-    let value = std::env::var(var).ok()?;
-    let mut paths = Vec::new();
-    for part in value.split(':') {
-        if part.is_empty() {
-            continue;
+/// One `PONY<TAB>FIELD<TAB>TEXT` line per match; quotes are folded onto one line.
+fn search_text(matches: &[SearchMatch], ucsmap: Option<&UcsMap>) -> String {
+    let mut out = String::new();
+    for found in matches {
+        let pony = ucsmap.map_or(found.pony.as_str(), |map| map.to_ucs(&found.pony));
+        let text = found.text.split_whitespace().collect::<Vec<_>>().join(" ");
+        out.push_str(&format!("{pony}\t{}\t{text}\n", found.field.as_str()));
+    }
+    out
+}
+
+fn search_document(query: &str, matches: &[SearchMatch], ucsmap: Option<&UcsMap>) -> String {
+    let matches = matches
+        .iter()
+        .map(|found| {
+            let mut entry = json!({
+                "pony": found.pony,
+                "field": found.field.as_str(),
+                "text": found.text,
+            });
+            if let Some(map) = ucsmap {
+                entry["ucs_name"] = json!(map.to_ucs(&found.pony));
+            }
+            entry
+        })
+        .collect::<Vec<_>>();
+    let document = json!({
+        "version": JSON_SCHEMA_VERSION,
+        "query": query,
+        "matches": matches,
+    });
+    serde_json::to_string_pretty(&document).unwrap_or_else(|_| document.to_string())
+}
+
+/// The index's ponies grouped by search root, in search order.
+fn summaries_by_root(index: &PonyIndex) -> Vec<(PathBuf, Vec<PonySummary>)> {
+    let mut groups: Vec<(PathBuf, Vec<PonySummary>)> = Vec::new();
+    for pony in index.summaries() {
+        match groups.last_mut() {
+            Some((root, ponies)) if *root == pony.root => ponies.push(pony),
+            _ => groups.push((pony.root.clone(), vec![pony])),
         }
-        paths.push(PathBuf::from(part));
     }
-    if paths.is_empty() { None } else { Some(paths) }
+    groups
+}
+
+fn json_document(output: &RenderOutput) -> String {
+    let metadata = &output.pony.metadata;
+    let document = json!({
+        "version": JSON_SCHEMA_VERSION,
+        "pony": output.pony.display_name(),
+        "pony_path": output.pony.path.display().to_string(),
+        "balloon": output.balloon,
+        "lines": output.lines.iter().map(|line| strip_ansi(line)).collect::<Vec<_>>(),
+        "ansi": output.lines,
+        "metadata": {
+            "tags": metadata.tags,
+            "comments": metadata.comments,
+        },
+    });
+    serde_json::to_string_pretty(&document).unwrap_or_else(|_| document.to_string())
+}
+
+/// `--info-json`: each pony's metadata, art size and preferred balloons.
+fn info_document(assets: &[PonyAsset], ucsmap: Option<&UcsMap>) -> String {
+    let ponies = assets
+        .iter()
+        .map(|asset| {
+            let name = asset
+                .path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let (width, height) = asset.dimensions();
+            let mut entry = json!({
+                "name": name,
+                "display_name": asset.display_name(),
+                "path": asset.path.display().to_string(),
+                "width": width,
+                "height": height,
+                "balloons": asset.metadata.balloon_preference(),
+                "tags": asset.metadata.tags,
+                "comments": asset.metadata.comments,
+                "problems": parse_problems(asset)
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>(),
+            });
+            if let Some(map) = ucsmap {
+                entry["ucs_name"] = json!(map.to_ucs(&name));
+            }
+            entry
+        })
+        .collect::<Vec<_>>();
+    let document = json!({
+        "version": JSON_SCHEMA_VERSION,
+        "ponies": ponies,
+    });
+    serde_json::to_string_pretty(&document).unwrap_or_else(|_| document.to_string())
+}
+
+fn print_info(
+    requested: Option<&str>,
+    pony_paths: &[PathBuf],
+    index: &PonyIndex,
+    pack: Option<&AssetPack>,
+    ucsmap: &UcsMap,
+    seed: Option<u64>,
+) -> ExitCode {
+    let requested = requested.map(|name| ucsmap.to_ascii(name));
+    let asset = match select_pony_indexed(requested, pony_paths, index, seed).and_then(|name| {
+        pack.and_then(|pack| pack.load_pony(&name, upstream_compat()))
+            .unwrap_or_else(|| load_pony(&name, pony_paths, upstream_compat()))
+    }) {
+        Ok(asset) => asset,
+        Err(error) => {
+            debug!(%error, "failed to load pony info");
+            return report_error(&error);
+        }
+    };
+
+    let metadata = &asset.metadata;
+    let mut lines = Vec::new();
+    lines.push(format!("NAME: {}", asset.display_name()));
+    // The name the pony is installed under, when it was asked for by an alias or in
+    // another case.
+    let canonical = asset.path.file_stem().and_then(|stem| stem.to_str());
+    if let (Some(requested), Some(canonical)) = (requested, canonical)
+        && requested != canonical
+        && !requested.contains(std::path::is_separator)
+        && !requested.ends_with(".pony")
+    {
+        lines.push(format!("PONY: {canonical}"));
+    }
+    lines.push(format!("FILE: {}", asset.path.display()));
+    let other_names = metadata.other_names();
+    if !other_names.is_empty() {
+        lines.push(format!("OTHER NAMES: {}", other_names.join(", ")));
+    }
+    let groups = metadata.groups();
+    if !groups.is_empty() {
+        lines.push(format!("GROUP: {}", groups.join(", ")));
+    }
+    let balloons = metadata.balloon_preference();
+    if !balloons.is_empty() {
+        lines.push(format!("BALLOON: {}", balloons.join(", ")));
+    }
+    if let Some(source) = metadata.source() {
+        lines.push(format!("SOURCE: {source}"));
+    }
+    if let Some(license) = metadata.license() {
+        lines.push(format!("LICENSE: {license}"));
+    }
+    for comment in &metadata.comments {
+        lines.push(comment.clone());
+    }
+    let status = print_stdout(&mut io::stdout().lock(), &(lines.join("\n") + "\n"));
+    // `-i` is how pony files get checked, so what the strict syntax rejects is pointed
+    // out; the pony still renders, so it is not an error.
+    for problem in parse_problems(&asset) {
+        eprintln!("iron-pony: warning: {}: {problem}", asset.path.display());
+    }
+    status
+}
+
+/// What the strict pony syntax rejects in `asset`'s file; nothing for a pony that is
+/// not a file on disk.
+fn parse_problems(asset: &PonyAsset) -> Vec<PonyParseError> {
+    if !asset.path.is_file() {
+        return Vec::new();
+    }
+    pony_file_problems(&asset.path).unwrap_or_else(|error| {
+        debug!(%error, "failed to check pony syntax");
+        Vec::new()
+    })
+}
+
+/// Writes `text` to `out` in one go, for the listing modes.
+fn print_stdout(out: &mut impl io::Write, text: &str) -> ExitCode {
+    match out.write_all(text.as_bytes()).and_then(|()| out.flush()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => write_failed(&error),
+    }
+}
+
+/// A reader that quit early is not worth an error message; anything else is.
+fn write_failed(error: &io::Error) -> ExitCode {
+    if error.kind() == io::ErrorKind::BrokenPipe {
+        debug!("stdout closed early");
+        return ExitCode::from(BROKEN_PIPE);
+    }
+    error!(%error, "failed to write output");
+    ExitCode::from(1)
+}
+
+fn fortune_config(cli: &Cli, seed: Option<u64>) -> FortuneConfig {
+    let mut fortune_config = FortuneConfig {
+        include_offensive: cli.fortune_all,
+        equal_files: cli.fortune_equal,
+        seed,
+        fortune6: upstream_compat(),
+        fallback_command: cli.fortune_fallback.then(|| {
+            let mut command = vec!["fortune".to_string()];
+            command.extend(cli.fortune_all.then(|| "-a".to_string()));
+            command.extend(cli.fortune_equal.then(|| "-e".to_string()));
+            command
+        }),
+        ..FortuneConfig::default()
+    };
+    if !cli.fortune_paths.is_empty() {
+        fortune_config.search_paths = cli.fortune_paths.clone();
+    } else if let Some(paths) = env_paths("FORTUNE_PATH") {
+        fortune_config.search_paths = paths;
+    }
+    fortune_config
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ErrorStyle {
+    /// `iron-pony: <error>` on stderr, exit status 1.
+    Native,
+    /// ponysay's own wording and exit statuses, for error-path parity cases.
+    Upstream,
+}
+
+impl ErrorStyle {
+    fn from_env() -> Self {
+        if upstream_compat() {
+            Self::Upstream
+        } else {
+            Self::Native
+        }
+    }
+}
+
+/// How much color output may use: detected from the terminal for `--motd`, full otherwise.
+fn color_mode(cli: &Cli) -> ColorMode {
+    if cli.motd {
+        ColorMode::detect(
+            std::env::var("TERM").ok().as_deref(),
+            std::env::var("COLORTERM").ok().as_deref(),
+        )
+    } else {
+        ColorMode::Full
+    }
+}
+
+/// `IRON_PONY_COMPAT=ponysay`: match upstream byte for byte where iron-pony otherwise
+/// cleans up after it (error wording, trailing padding, case-insensitive names).
+fn upstream_compat() -> bool {
+    matches!(
+        std::env::var("IRON_PONY_COMPAT").as_deref(),
+        Ok("ponysay" | "upstream")
+    )
+}
+
+/// Formats a fatal error for stderr and picks the exit status.
+fn format_error(error: &PonyError, style: ErrorStyle) -> (String, u8) {
+    if style == ErrorStyle::Native {
+        return (format!("iron-pony: {error}"), 1);
+    }
+
+    match error {
+        PonyError::PonyNotFound { name } => {
+            (format!("I have never heard of anypony named {name}"), 1)
+        }
+        PonyError::PonyFileNotFound { path } => (
+            format!("I have never heard of anypony named {}", path.display()),
+            1,
+        ),
+        PonyError::BalloonNotFound { name } => {
+            (format!("That balloon style {name} does not exist"), 1)
+        }
+        PonyError::NoMessage => ("No message was specified".to_string(), 2),
+        other => (format!("ponysay: {other}"), 1),
+    }
+}
+
+fn report_error(error: &PonyError) -> ExitCode {
+    let (message, code) = format_error(error, ErrorStyle::from_env());
+    eprintln!("{message}");
+    ExitCode::from(code)
+}
+
+/// The style from `--balloon-chars` or `--balloon-file`, if either was given.
+fn custom_balloon(cli: &Cli, mode: Mode) -> Result<Option<BalloonStyle>, PonyError> {
+    let mode = match mode {
+        Mode::Say => BalloonMode::Say,
+        Mode::Think => BalloonMode::Think,
+    };
+    if let Some(spec) = &cli.balloon_chars {
+        return BalloonStyle::from_chars(spec, mode).map(Some);
+    }
+    let Some(path) = &cli.balloon_file else {
+        return Ok(None);
+    };
+    let raw = std::fs::read_to_string(path).map_err(|source| PonyError::Io {
+        path: path.clone(),
+        source,
+    })?;
+    BalloonStyle::from_toml(&raw).map(Some)
+}
+
+/// The style `name` from `pack` when it has one, otherwise from the balloon directories.
+fn load_named_style(
+    name: &str,
+    balloon_paths: &[PathBuf],
+    pack: Option<&AssetPack>,
+    mode: BalloonMode,
+    exact: bool,
+) -> Result<Option<BalloonStyle>, iron_pony_core::BalloonParseError> {
+    match pack.and_then(|pack| pack.load_style(name, mode, exact)) {
+        Some(packed) => packed.map(Some),
+        None => load_style(Some(name), balloon_paths, mode, exact),
+    }
 }
 
-fn init_tracing(verbose: bool) {
-    if !verbose {
-        return;
+/// `--balloon-preview`: the style `name` drawn around [`PREVIEW_SAMPLE`], or with no name
+/// the built-in style and every installed or packed one, each under its name.
+fn balloon_previews(
+    name: Option<&str>,
+    balloon_paths: &[PathBuf],
+    pack: Option<&AssetPack>,
+    mode: BalloonMode,
+    compat: CompatLevel,
+) -> Result<String, PonyError> {
+    let exact = upstream_compat();
+    let mut styles = Vec::new();
+    match name {
+        Some(name) => {
+            let style = load_named_style(name, balloon_paths, pack, mode, exact)
+                .map_err(|source| PonyError::BalloonParse {
+                    name: name.to_string(),
+                    source,
+                })?
+                .ok_or_else(|| PonyError::BalloonNotFound {
+                    name: name.to_string(),
+                })?;
+            styles.push((name.to_string(), style));
+        }
+        None => {
+            styles.push((
+                "default (built-in)".to_string(),
+                BalloonStyle::default_for_mode(mode),
+            ));
+            let mut names = list_balloons(balloon_paths);
+            if let Some(pack) = pack {
+                names.extend(pack.balloon_names());
+                names.sort();
+                names.dedup();
+            }
+            for name in names {
+                match load_named_style(&name, balloon_paths, pack, mode, exact) {
+                    Ok(Some(style)) => styles.push((name, style)),
+                    Ok(None) => {}
+                    Err(error) => warn!(balloon = %name, %error, "skipping broken balloon style"),
+                }
+            }
+        }
     }
+    let mut text = String::new();
+    for (index, (name, style)) in styles.into_iter().enumerate() {
+        if index > 0 {
+            text.push('\n');
+        }
+        text.push_str(&name);
+        text.push('\n');
+        for row in style.preview(PREVIEW_SAMPLE, compat) {
+            text.push_str(&row);
+            text.push('\n');
+        }
+    }
+    Ok(text)
+}
+
+/// The options with the hand-dispatched subcommands attached, for the man page and
+/// completions.
+fn documented_command() -> clap::Command {
+    let command = Cli::command()
+        .disable_help_subcommand(true)
+        .subcommand(best::command())
+        .subcommands(install::commands())
+        .subcommand(doctor::command());
+    #[cfg(feature = "serve")]
+    let command = command.subcommand(serve::command());
+    command
+}
 
-    let filter = EnvFilter::new(
-        "info,iron_pony_core=debug,iron_pony_cli=debug,iron_pony_parity=debug,xtask=debug",
-    );
+/// Rewrites upstream's `+x` negation flags into their long `--no-*` forms.
+fn normalize_plus_flags(args: impl Iterator<Item = OsString>) -> Vec<OsString> {
+    args.map(|arg| match arg.to_str() {
+        Some("+u") => OsString::from("--no-ucs"),
+        _ => arg,
+    })
+    .collect()
+}
+
+fn env_paths(var: &str) -> Option<Vec<PathBuf>> {
+    let value = std::env::var(var).ok()?;
+    let paths = Platform::current().split_path_list(&value);
+    if paths.is_empty() { None } else { Some(paths) }
+}
+
+/// Installs the stderr subscriber. Errors are already reported by [`report_error`], so
+/// the default `error` level stays silent on ordinary failures and matches ponysay's
+/// empty stderr.
+fn init_tracing(verbosity: u8) {
+    let filter = std::env::var("IRON_PONY_LOG")
+        .ok()
+        .and_then(|directives| EnvFilter::try_new(directives).ok())
+        .unwrap_or_else(|| {
+            EnvFilter::new(match verbosity {
+                0 => "error",
+                1 => "warn,iron_pony=info,iron_pony_core=info",
+                _ => "info,iron_pony=debug,iron_pony_core=debug",
+            })
+        });
 
     tracing_subscriber::fmt()
         .with_env_filter(filter)
         .with_target(true)
         .with_thread_ids(false)
-        .with_ansi(true)
+        .with_ansi(io::stderr().is_terminal())
         .with_writer(std::io::stderr)
         .init();
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+
+    use iron_pony_core::PonyMetadata;
+
+    use super::*;
+
+    #[test]
+    fn closed_stdout_exits_quietly() {
+        struct Closed(io::ErrorKind);
+        impl io::Write for Closed {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(self.0.into())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        assert_eq!(print_stdout(&mut Vec::new(), "ok\n"), ExitCode::SUCCESS);
+        assert_eq!(
+            print_stdout(&mut Closed(io::ErrorKind::BrokenPipe), "lost\n"),
+            ExitCode::from(BROKEN_PIPE)
+        );
+        assert_eq!(
+            print_stdout(&mut Closed(io::ErrorKind::StorageFull), "lost\n"),
+            ExitCode::from(1)
+        );
+    }
+
+    #[test]
+    fn short_v_counts_verbosity_and_capital_v_prints_version() {
+        let cli = Cli::try_parse_from(["iron-pony", "-vv", "hi"]).expect("parse -vv");
+        assert_eq!(cli.verbose, 2);
+        let version = Cli::try_parse_from(["iron-pony", "-V"]).expect_err("version exits");
+        assert_eq!(version.kind(), clap::error::ErrorKind::DisplayVersion);
+    }
+
+    #[test]
+    fn json_output_snapshot() {
+        let output = RenderOutput {
+            pony: PonyAsset {
+                path: PathBuf::from("/ponies/twilight.pony"),
+                metadata: PonyMetadata {
+                    tags: BTreeMap::from([(
+                        "NAME".to_string(),
+                        vec!["Twilight Sparkle".to_string()],
+                    )]),
+                    comments: vec!["drawn by hand".to_string()],
+                },
+                body: String::new(),
+            },
+            balloon: "default".to_string(),
+            lines: vec![
+                "\u{1b}[0m< \u{1b}[35mhi\u{1b}[0m >".to_string(),
+                "  \\".to_string(),
+            ],
+        };
+
+        let expected = r#"{
+  "ansi": [
+    "\u001b[0m< \u001b[35mhi\u001b[0m >",
+    "  \\"
+  ],
+  "balloon": "default",
+  "lines": [
+    "< hi >",
+    "  \\"
+  ],
+  "metadata": {
+    "comments": [
+      "drawn by hand"
+    ],
+    "tags": {
+      "NAME": [
+        "Twilight Sparkle"
+      ]
+    }
+  },
+  "pony": "Twilight Sparkle",
+  "pony_path": "/ponies/twilight.pony",
+  "version": 1
+}"#;
+        assert_eq!(json_document(&output), expected);
+    }
+
+    #[test]
+    fn info_document_describes_art_and_preferences() {
+        let pony = PonyAsset {
+            path: PathBuf::from("/ponies/twilight.pony"),
+            metadata: PonyMetadata {
+                tags: BTreeMap::from([("BALLOON".to_string(), vec!["round, ascii".to_string()])]),
+                comments: Vec::new(),
+            },
+            body: "$balloon8$\n  $\\$\n \u{1b}[35m(o$$o)\u{1b}[0m\n".to_string(),
+        };
+        let document: serde_json::Value =
+            serde_json::from_str(&info_document(&[pony], None)).expect("json");
+        let entry = &document["ponies"][0];
+        assert_eq!(entry["name"], "twilight");
+        assert_eq!(entry["display_name"], "twilight");
+        assert_eq!((&entry["width"], &entry["height"]), (&json!(6), &json!(2)));
+        assert_eq!(entry["balloons"], json!(["round", "ascii"]));
+        assert_eq!(entry["tags"]["BALLOON"], json!(["round, ascii"]));
+        assert!(entry.get("ucs_name").is_none());
+        assert_eq!(entry["problems"], json!([]));
+    }
+
+    #[test]
+    fn upstream_error_style_uses_ponysay_wording() {
+        let missing = PonyError::PonyNotFound {
+            name: "nopony".to_string(),
+        };
+        assert_eq!(
+            format_error(&missing, ErrorStyle::Native),
+            ("iron-pony: pony 'nopony' was not found".to_string(), 1)
+        );
+        assert_eq!(
+            format_error(&missing, ErrorStyle::Upstream),
+            ("I have never heard of anypony named nopony".to_string(), 1)
+        );
+        assert_eq!(
+            format_error(&PonyError::NoMessage, ErrorStyle::Upstream).1,
+            2
+        );
+    }
+
+    #[test]
+    fn list_groups_by_directory() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let ponies = tmp.path().join("ponies");
+        std::fs::create_dir_all(&ponies).expect("pony dir");
+        std::fs::write(ponies.join("twilight.pony"), "").expect("write twilight");
+        std::fs::write(ponies.join("applejack.pony"), "").expect("write applejack");
+        let index = PonyIndex::scan(&[ponies.clone(), tmp.path().join("extra")]);
+        let ucsmap = UcsMap::parse("Twilight Sparkle → twilight\n");
+
+        assert_eq!(
+            list_text(&index, Some(&ucsmap), true),
+            format!(
+                "\u{1b}[1mponies located in {}\u{1b}[21m\nTwilight Sparkle\napplejack\n",
+                ponies.display()
+            )
+        );
+        assert_eq!(
+            list_text(&index, Some(&ucsmap), false),
+            format!(
+                "ponies located in {}\nTwilight Sparkle\napplejack\n",
+                ponies.display()
+            )
+        );
+
+        let document: serde_json::Value =
+            serde_json::from_str(&list_document(&index, Some(&ucsmap))).expect("json");
+        let entry = &document["roots"][0]["ponies"][1];
+        assert_eq!(entry["name"], "twilight");
+        assert_eq!(entry["ucs_name"], "Twilight Sparkle");
+        assert_eq!(
+            entry["path"],
+            ponies.join("twilight.pony").display().to_string()
+        );
+        assert_eq!(document["roots"].as_array().map(Vec::len), Some(1));
+    }
+}
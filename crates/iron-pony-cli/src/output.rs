@@ -0,0 +1,125 @@
+//! Writes rendered output to stdout, optionally paced for `--slow`.
+
+use std::io::{self, Write};
+use std::time::Duration;
+
+use iron_pony_core::ansi_chunks;
+
+/// How `--slow` paces output.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Pace {
+    /// Everything at once.
+    Instant,
+    /// This many characters per second. Whitespace is written without a delay so
+    /// padding in the art does not stall the animation.
+    Chars(f64),
+    /// This many lines per second.
+    Lines(f64),
+}
+
+pub(crate) struct OutputWriter<W: Write> {
+    out: W,
+    pace: Pace,
+    sleep: fn(Duration),
+}
+
+impl<W: Write> OutputWriter<W> {
+    pub(crate) fn new(out: W, pace: Pace) -> Self {
+        Self {
+            out,
+            pace,
+            sleep: std::thread::sleep,
+        }
+    }
+
+    /// Writes `text` and a trailing newline. Escape sequences are never split, so an
+    /// interrupted animation leaves the terminal in a sane state.
+    pub(crate) fn write_text(&mut self, text: &str) -> io::Result<()> {
+        match self.pace {
+            Pace::Instant => writeln!(self.out, "{text}")?,
+            Pace::Chars(rate) => {
+                let delay = per_item(rate);
+                for chunk in ansi_chunks(text).into_iter().chain(["\n"]) {
+                    self.out.write_all(chunk.as_bytes())?;
+                    if !chunk.ends_with(char::is_whitespace) {
+                        self.out.flush()?;
+                        (self.sleep)(delay);
+                    }
+                }
+            }
+            Pace::Lines(rate) => {
+                let delay = per_item(rate);
+                for line in text.split('\n') {
+                    writeln!(self.out, "{line}")?;
+                    self.out.flush()?;
+                    (self.sleep)(delay);
+                }
+            }
+        }
+        self.out.flush()
+    }
+
+    /// Erases the last `lines` rows written by moving the cursor up and clearing below.
+    pub(crate) fn erase_lines(&mut self, lines: usize) -> io::Result<()> {
+        if lines == 0 {
+            return Ok(());
+        }
+        write!(self.out, "\u{1b}[{lines}A\u{1b}[J")
+    }
+
+    /// Waits `delay` between `--loop` rounds.
+    pub(crate) fn pause(&self, delay: Duration) {
+        (self.sleep)(delay);
+    }
+}
+
+fn per_item(rate: f64) -> Duration {
+    Duration::try_from_secs_f64(rate.recip()).unwrap_or(Duration::ZERO)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    thread_local! {
+        static SLEPT: RefCell<Vec<Duration>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn record(delay: Duration) {
+        SLEPT.with(|slept| slept.borrow_mut().push(delay));
+    }
+
+    fn paced(text: &str, pace: Pace) -> (String, Vec<Duration>) {
+        SLEPT.with(|slept| slept.borrow_mut().clear());
+        let mut writer = OutputWriter {
+            out: Vec::new(),
+            pace,
+            sleep: record,
+        };
+        writer.write_text(text).expect("write");
+        let out = String::from_utf8(writer.out).expect("utf-8");
+        (out, SLEPT.with(|slept| slept.take()))
+    }
+
+    #[test]
+    fn paces_characters_and_lines() {
+        let text = "\u{1b}[31mhi there\u{1b}[0m\n  x";
+        let (out, slept) = paced(text, Pace::Instant);
+        assert_eq!(out, format!("{text}\n"));
+        assert!(slept.is_empty());
+
+        let (out, slept) = paced(text, Pace::Chars(4.0));
+        assert_eq!(out, format!("{text}\n"));
+        // One delay per letter; spaces and the reset before the newline are free.
+        assert_eq!(slept, vec![Duration::from_millis(250); 8]);
+
+        let (out, slept) = paced(text, Pace::Lines(2.0));
+        assert_eq!(out, format!("{text}\n"));
+        assert_eq!(slept, vec![Duration::from_millis(500); 2]);
+
+        let (_, slept) = paced("ab", Pace::Chars(0.0));
+        assert_eq!(slept, vec![Duration::ZERO; 2]);
+    }
+}
@@ -0,0 +1,277 @@
+//! `--pick`: fuzzy pony picker with a live preview of the current message.
+//!
+//! The picker draws on stderr so the chosen pony can still be piped from stdout.
+
+use std::collections::HashMap;
+use std::io::{self, Stderr};
+use std::time::Duration;
+
+use ansi_to_tui::IntoText;
+use iron_pony_core::{PonyAsset, PonyError, PonyIndex, RenderConfig, load_pony, render_output};
+use ratatui::Frame;
+use ratatui::Terminal;
+use ratatui::backend::CrosstermBackend;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::crossterm::execute;
+use ratatui::crossterm::terminal::{
+    EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
+};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Text};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use tracing::debug;
+
+const HELP: &str = "type to filter  up/down select  enter pick  esc cancel";
+
+struct Picker<'a> {
+    config: &'a RenderConfig,
+    names: Vec<String>,
+    query: String,
+    /// Indices into `names` matching `query`, best match first.
+    matches: Vec<usize>,
+    list: ListState,
+    previews: HashMap<usize, Text<'static>>,
+}
+
+/// Runs the picker over the index's ponies; `None` when the user cancels.
+pub(crate) fn run(index: &PonyIndex, config: &RenderConfig) -> io::Result<Option<String>> {
+    let mut picker = Picker::new(index.names(), config);
+
+    let picked = {
+        let _screen = Screen::enter()?;
+        let mut terminal = Terminal::new(CrosstermBackend::new(io::stderr()))?;
+        picker.event_loop(&mut terminal)?
+    };
+    debug!(
+        pony = picked.as_deref().unwrap_or("<cancelled>"),
+        "pony picker closed"
+    );
+    Ok(picked)
+}
+
+/// Raw mode on the alternate screen for as long as it lives. Dropping it gives the
+/// terminal back, also when an error returns early or a panic unwinds.
+struct Screen;
+
+impl Screen {
+    fn enter() -> io::Result<Self> {
+        enable_raw_mode()?;
+        let screen = Self;
+        execute!(io::stderr(), EnterAlternateScreen)?;
+        Ok(screen)
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+        let _ = execute!(io::stderr(), LeaveAlternateScreen);
+    }
+}
+
+impl<'a> Picker<'a> {
+    fn new(names: Vec<String>, config: &'a RenderConfig) -> Self {
+        let mut picker = Self {
+            config,
+            names,
+            query: String::new(),
+            matches: Vec::new(),
+            list: ListState::default(),
+            previews: HashMap::new(),
+        };
+        picker.refilter();
+        picker
+    }
+
+    fn selected(&self) -> Option<usize> {
+        self.list
+            .selected()
+            .and_then(|row| self.matches.get(row))
+            .copied()
+    }
+
+    fn refilter(&mut self) {
+        let mut scored = self
+            .names
+            .iter()
+            .enumerate()
+            .filter_map(|(index, name)| fuzzy_score(&self.query, name).map(|score| (score, index)))
+            .collect::<Vec<_>>();
+        scored.sort_unstable();
+        self.matches = scored.into_iter().map(|(_, index)| index).collect();
+        self.list.select((!self.matches.is_empty()).then_some(0));
+    }
+
+    fn select(&mut self, delta: isize) {
+        if self.matches.is_empty() {
+            return;
+        }
+        let current = self.list.selected().unwrap_or(0);
+        let next = current
+            .saturating_add_signed(delta)
+            .min(self.matches.len() - 1);
+        self.list.select(Some(next));
+    }
+
+    fn event_loop(
+        &mut self,
+        terminal: &mut Terminal<CrosstermBackend<Stderr>>,
+    ) -> io::Result<Option<String>> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if !event::poll(Duration::from_millis(250))? {
+                continue;
+            }
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+            match key.code {
+                KeyCode::Esc => return Ok(None),
+                KeyCode::Char('c') if ctrl => return Ok(None),
+                KeyCode::Enter => {
+                    return Ok(self.selected().map(|index| self.names[index].clone()));
+                }
+                KeyCode::Down => self.select(1),
+                KeyCode::Char('n') if ctrl => self.select(1),
+                KeyCode::Up => self.select(-1),
+                KeyCode::Char('p') if ctrl => self.select(-1),
+                KeyCode::PageDown => self.select(10),
+                KeyCode::PageUp => self.select(-10),
+                KeyCode::Backspace => {
+                    self.query.pop();
+                    self.refilter();
+                }
+                KeyCode::Char(c) if !ctrl => {
+                    self.query.push(c);
+                    self.refilter();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// The current message rendered with pony `index`, converted for the preview pane.
+    fn preview(&mut self, index: usize) -> &Text<'static> {
+        let config = self.config;
+        let name = &self.names[index];
+        self.previews.entry(index).or_insert_with(|| {
+            let rendered = load_asset(name, config)
+                .and_then(|pony| render_output(config, &pony))
+                .map(|output| output.text());
+            match rendered {
+                Ok(text) => text
+                    .into_text()
+                    .unwrap_or_else(|_| Text::raw(iron_pony_core::strip_ansi(&text))),
+                Err(error) => Text::raw(error.to_string()),
+            }
+        })
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [search, main, footer] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [left, right] =
+            Layout::horizontal([Constraint::Percentage(30), Constraint::Min(0)]).areas(main);
+
+        frame.render_widget(
+            Paragraph::new(format!("> {}", self.query)).block(Block::bordered().title(format!(
+                " ponies {}/{} ",
+                self.matches.len(),
+                self.names.len()
+            ))),
+            search,
+        );
+
+        let items = self
+            .matches
+            .iter()
+            .map(|&index| ListItem::new(self.names[index].as_str()))
+            .collect::<Vec<_>>();
+        let list = List::new(items)
+            .block(Block::bordered())
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, left, &mut self.list);
+
+        let (title, preview) = match self.selected() {
+            Some(index) => (
+                format!(" {} ", self.names[index]),
+                self.preview(index).clone(),
+            ),
+            None => (" no match ".to_string(), Text::default()),
+        };
+        frame.render_widget(
+            Paragraph::new(preview).block(Block::bordered().title(title)),
+            right,
+        );
+        frame.render_widget(Line::from(HELP), footer);
+    }
+}
+
+fn load_asset(name: &str, config: &RenderConfig) -> Result<PonyAsset, PonyError> {
+    config
+        .pack
+        .as_ref()
+        .and_then(|pack| pack.load_pony(name, config.exact_names))
+        .unwrap_or_else(|| load_pony(name, &config.pony_paths, config.exact_names))
+}
+
+/// Ranks `candidate` against `query` as a case-insensitive subsequence; lower is better,
+/// `None` when it does not match. Gaps between matched characters cost the most, then a
+/// late first match, so `tw` prefers `twilight` over `applejack_twin`.
+fn fuzzy_score(query: &str, candidate: &str) -> Option<(usize, usize, usize)> {
+    let mut chars = candidate.char_indices();
+    let (mut first, mut last, mut gaps) = (None, 0, 0);
+    for wanted in query.chars() {
+        let (position, _) = chars
+            .by_ref()
+            .find(|(_, c)| c.to_lowercase().eq(wanted.to_lowercase()))?;
+        match first {
+            None => first = Some(position),
+            Some(_) => gaps += position - last - 1,
+        }
+        last = position;
+    }
+    Some((gaps, first.unwrap_or(0), candidate.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_and_ranks_fuzzy_matches() {
+        let config = RenderConfig::default();
+        let names = ["applejack_twin", "rarity", "twilight", "trixie_wizard"]
+            .map(String::from)
+            .to_vec();
+        let mut picker = Picker::new(names, &config);
+        assert_eq!(picker.matches.len(), 4);
+
+        picker.query = "TW".to_string();
+        picker.refilter();
+        let matched = picker
+            .matches
+            .iter()
+            .map(|&index| picker.names[index].as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(matched, ["twilight", "applejack_twin", "trixie_wizard"]);
+        assert_eq!(picker.selected(), Some(2));
+
+        picker.select(5);
+        assert_eq!(picker.selected(), Some(3));
+
+        picker.query = "zzz".to_string();
+        picker.refilter();
+        assert_eq!(picker.selected(), None);
+    }
+}
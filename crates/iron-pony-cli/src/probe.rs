@@ -0,0 +1,115 @@
+//! `--probe-terminal`: asks the terminal for its size with a cursor position report, for
+//! serial consoles and other ttys where `TIOCGWINSZ` reports nothing useful.
+
+use std::time::Duration;
+
+use tracing::debug;
+
+/// How long to wait for the terminal to answer; terminals that do not support the report
+/// never answer at all.
+const PROBE_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// Save the cursor, move it as far down and right as the terminal allows, ask where it
+/// ended up, and restore it.
+#[cfg_attr(not(unix), allow(dead_code))]
+const QUERY: &[u8] = b"\x1b7\x1b[9999;9999H\x1b[6n\x1b8";
+
+/// `(columns, rows)` of the controlling terminal, or `None` when it does not answer.
+pub(crate) fn probe_size() -> Option<(usize, usize)> {
+    let size = query_tty(PROBE_TIMEOUT)
+        .as_deref()
+        .and_then(parse_cursor_report);
+    debug!(?size, "probed terminal size");
+    size
+}
+
+/// Parses a `CSI row ; column R` report into `(columns, rows)`. Keystrokes typed before
+/// the report arrived are skipped.
+fn parse_cursor_report(response: &[u8]) -> Option<(usize, usize)> {
+    let start = response.windows(2).rposition(|pair| pair == b"\x1b[")? + 2;
+    let report = std::str::from_utf8(&response[start..]).ok()?;
+    let (row, column) = report.strip_suffix('R')?.split_once(';')?;
+    let (row, column) = (row.parse().ok()?, column.parse().ok()?);
+    (row > 0 && column > 0).then_some((column, row))
+}
+
+#[cfg(unix)]
+fn query_tty(timeout: Duration) -> Option<Vec<u8>> {
+    use std::fs::OpenOptions;
+    use std::os::fd::AsRawFd;
+
+    let mut tty = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .ok()?;
+    let fd = tty.as_raw_fd();
+    // SAFETY: tcgetattr only writes a `termios` into the struct we pass.
+    let mut original: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(fd, &mut original) } != 0 {
+        return None;
+    }
+    // Without canonical mode the report is readable before a newline; without echo it
+    // does not show up on screen.
+    let mut raw = original;
+    raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+    if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &raw) } != 0 {
+        return None;
+    }
+    let response = read_report(&mut tty, timeout);
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &original) };
+    response
+}
+
+#[cfg(unix)]
+fn read_report(tty: &mut std::fs::File, timeout: Duration) -> Option<Vec<u8>> {
+    use std::io::{Read, Write};
+    use std::os::fd::AsRawFd;
+    use std::time::Instant;
+
+    tty.write_all(QUERY).ok()?;
+    tty.flush().ok()?;
+    let deadline = Instant::now() + timeout;
+    let mut response = Vec::new();
+    while !response.ends_with(b"R") {
+        let remaining = deadline.checked_duration_since(Instant::now())?;
+        let mut pollfd = libc::pollfd {
+            fd: tty.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let millis = i32::try_from(remaining.as_millis()).unwrap_or(i32::MAX);
+        // SAFETY: one valid `pollfd` is passed with a count of one.
+        if unsafe { libc::poll(&mut pollfd, 1, millis) } <= 0 {
+            return None;
+        }
+        let mut buffer = [0; 32];
+        match tty.read(&mut buffer).ok()? {
+            0 => return None,
+            read => response.extend_from_slice(&buffer[..read]),
+        }
+    }
+    Some(response)
+}
+
+#[cfg(not(unix))]
+fn query_tty(_timeout: Duration) -> Option<Vec<u8>> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cursor_position_reports() {
+        assert_eq!(parse_cursor_report(b"\x1b[24;80R"), Some((80, 24)));
+        assert_eq!(
+            parse_cursor_report(b"ab\x1b[A\x1b[50;132R"),
+            Some((132, 50))
+        );
+        assert_eq!(parse_cursor_report(b"\x1b[0;80R"), None);
+        assert_eq!(parse_cursor_report(b"\x1b[24;80"), None);
+        assert_eq!(parse_cursor_report(b"24;80R"), None);
+    }
+}
@@ -0,0 +1,474 @@
+//! `iron-pony serve`: renders ponies over HTTP for chat bots and dashboards.
+//!
+//! A deliberately small HTTP/1.1 server: one `GET` per connection, answered and closed.
+//! The pony index is built once at startup, so requests never walk the pony directories.
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex, mpsc};
+use std::time::{Duration, Instant};
+
+use clap::{CommandFactory, Parser};
+use iron_pony_core::{
+    AssetPack, Mode, PATH_LIST_SEPARATOR, PonyError, PonyIndex, RenderConfig, Renderer,
+    ansi_to_html, default_balloon_paths, default_index_cache_path, default_pony_paths, strip_ansi,
+};
+use tracing::{debug, error, info, warn};
+
+use crate::{env_paths, init_tracing};
+
+/// Requests larger than this are rejected; a `GET` line and headers fit easily.
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+/// Limit on any single read of a request.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+/// Limit on reading a whole request, so a client trickling bytes cannot hold a worker.
+const REQUEST_DEADLINE: Duration = Duration::from_secs(10);
+/// Accepted connections that may wait for a free worker before accepting pauses.
+const QUEUED_PER_WORKER: usize = 4;
+
+#[derive(Debug, Parser)]
+#[command(name = "iron-pony serve", about = "Serve rendered ponies over HTTP")]
+pub(crate) struct ServeCli {
+    #[arg(
+        long = "addr",
+        default_value = "127.0.0.1:4141",
+        help = "Address to listen on"
+    )]
+    addr: SocketAddr,
+
+    #[arg(
+        long = "ponydir",
+        value_delimiter = PATH_LIST_SEPARATOR,
+        help = "Pony search path override"
+    )]
+    pony_paths: Vec<PathBuf>,
+
+    #[arg(
+        long = "balloondir",
+        value_delimiter = PATH_LIST_SEPARATOR,
+        help = "Balloon search path override"
+    )]
+    balloon_paths: Vec<PathBuf>,
+
+    #[arg(
+        long = "ponypack",
+        help = "Load ponies and balloons from a .tar or .zip asset pack"
+    )]
+    pony_pack: Option<PathBuf>,
+
+    #[arg(
+        long = "workers",
+        default_value_t = 8,
+        value_parser = clap::value_parser!(u16).range(1..),
+        help = "Connections handled at once"
+    )]
+    workers: u16,
+
+    #[arg(
+        short = 'v',
+        long = "verbose",
+        action = clap::ArgAction::Count,
+        help = "Log requests to stderr (-v info, -vv debug); IRON_PONY_LOG overrides"
+    )]
+    verbose: u8,
+}
+
+/// Output flavors of `GET /say`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Plain,
+    Ansi,
+    Html,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn text(status: u16, body: impl Into<String>) -> Self {
+        let mut body = body.into();
+        body.push('\n');
+        Self {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body,
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            _ => "Internal Server Error",
+        }
+    }
+}
+
+struct Server {
+    /// Config every request starts from; requests fill in message, pony and style.
+    base: RenderConfig,
+    renderer: Renderer,
+}
+
+/// `serve` as a subcommand of the main command, for the man page and completions.
+pub(crate) fn command() -> clap::Command {
+    ServeCli::command().name("serve")
+}
+
+/// Entry point for `iron-pony serve ...`; `args` starts with `serve`.
+pub(crate) fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    let cli = ServeCli::parse_from(args);
+    init_tracing(cli.verbose);
+
+    let pack = match cli.pony_pack.as_deref().map(AssetPack::open).transpose() {
+        Ok(pack) => pack,
+        Err(error) => {
+            debug!(%error, "failed to open asset pack");
+            return crate::report_error(&error);
+        }
+    };
+    let pony_paths = if cli.pony_paths.is_empty() {
+        env_paths("PONYSAY_PONY_PATH").unwrap_or_else(default_pony_paths)
+    } else {
+        cli.pony_paths
+    };
+    let balloon_paths = if cli.balloon_paths.is_empty() {
+        env_paths("PONYSAY_BALLOON_PATH").unwrap_or_else(default_balloon_paths)
+    } else {
+        cli.balloon_paths
+    };
+    let mut index = PonyIndex::load(&pony_paths, default_index_cache_path().as_deref());
+    if let Some(pack) = &pack {
+        index.add_pack(pack);
+    }
+    let base = RenderConfig {
+        pony_paths,
+        balloon_paths,
+        pack,
+        ..RenderConfig::default()
+    };
+    let server = Arc::new(Server {
+        renderer: Renderer::new(&base).with_index(index),
+        base,
+    });
+
+    let listener = match TcpListener::bind(cli.addr) {
+        Ok(listener) => listener,
+        Err(error) => {
+            error!(addr = %cli.addr, %error, "failed to bind");
+            return ExitCode::from(1);
+        }
+    };
+    eprintln!("iron-pony: serving on http://{}", cli.addr);
+    let workers = usize::from(cli.workers);
+    let (queue, connections) = mpsc::sync_channel::<TcpStream>(workers * QUEUED_PER_WORKER);
+    let connections = Arc::new(Mutex::new(connections));
+    for _ in 0..workers {
+        let server = Arc::clone(&server);
+        let connections = Arc::clone(&connections);
+        std::thread::spawn(move || {
+            loop {
+                // The guard is dropped before handling, so other workers can take the next one.
+                let next = connections
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .recv();
+                match next {
+                    Ok(stream) => server.handle(stream),
+                    Err(_) => break,
+                }
+            }
+        });
+    }
+    for stream in listener.incoming() {
+        match stream {
+            // Blocks while every worker is busy and the queue is full, leaving further
+            // clients in the listen backlog.
+            Ok(stream) => {
+                if queue.send(stream).is_err() {
+                    break;
+                }
+            }
+            Err(error) => warn!(%error, "failed to accept connection"),
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+impl Server {
+    fn handle(&self, mut stream: TcpStream) {
+        let peer = stream.peer_addr().ok();
+        let response = match read_request_line(&mut stream, REQUEST_DEADLINE) {
+            Ok(line) => {
+                let response = self.respond(&line);
+                info!(?peer, request = %line, status = response.status, "served request");
+                response
+            }
+            Err(message) => Response::text(400, message),
+        };
+        let head = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            response.status,
+            response.reason(),
+            response.content_type,
+            response.body.len()
+        );
+        if let Err(error) = stream
+            .write_all(head.as_bytes())
+            .and_then(|()| stream.write_all(response.body.as_bytes()))
+        {
+            debug!(?peer, %error, "failed to write response");
+        }
+    }
+
+    /// Answers one request line, e.g. `GET /say?message=hi HTTP/1.1`.
+    fn respond(&self, request_line: &str) -> Response {
+        let mut parts = request_line.split(' ');
+        let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+            return Response::text(400, "malformed request line");
+        };
+        if method != "GET" {
+            return Response::text(405, format!("{method} is not supported; use GET"));
+        }
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        match path {
+            "/say" => match parse_query(query) {
+                Some(params) => self.say(&params),
+                None => Response::text(400, "malformed query string"),
+            },
+            "/ponies" => Response::text(200, self.renderer.index().names().join("\n")),
+            _ => Response::text(404, format!("no such endpoint {path}; try /say or /ponies")),
+        }
+    }
+
+    fn say(&self, params: &HashMap<String, String>) -> Response {
+        let param = |name: &str| params.get(name).map(String::as_str);
+        let format = match param("format").unwrap_or("ansi") {
+            "plain" => Format::Plain,
+            "ansi" => Format::Ansi,
+            "html" => Format::Html,
+            other => return Response::text(400, format!("unknown format '{other}'")),
+        };
+        let wrap_width = match param("wrap").map(str::parse::<usize>) {
+            None => self.base.wrap_width,
+            Some(Ok(width)) => width.max(1),
+            Some(Err(_)) => return Response::text(400, "wrap must be a number"),
+        };
+        // Names only: anything path-like would let clients read arbitrary files.
+        if let Some(pony) = param("pony")
+            && self
+                .renderer
+                .index()
+                .find(pony, self.base.exact_names)
+                .is_none()
+        {
+            return error_response(&PonyError::PonyNotFound {
+                name: pony.to_string(),
+            });
+        }
+        if let Some(balloon) = param("balloon")
+            && (balloon.contains(['/', '\\']) || balloon.starts_with('.'))
+        {
+            return Response::text(400, format!("invalid balloon name '{balloon}'"));
+        }
+
+        let config = RenderConfig {
+            message: param("message").unwrap_or_default().to_string(),
+            pony: param("pony").unwrap_or_default().to_string(),
+            balloon: param("balloon").map(str::to_string),
+            mode: match param("think") {
+                Some("" | "1" | "true" | "yes") => Mode::Think,
+                _ => Mode::Say,
+            },
+            wrap_width,
+            ..self.base.clone()
+        };
+        let rendered = self
+            .renderer
+            .resolve_pony(&config, None)
+            .and_then(|pony| self.renderer.render_output(&config, &pony));
+        match rendered {
+            Ok(output) => {
+                let text = output.text();
+                match format {
+                    Format::Plain => Response::text(200, strip_ansi(&text)),
+                    Format::Ansi => Response::text(200, text),
+                    Format::Html => Response {
+                        status: 200,
+                        content_type: "text/html; charset=utf-8",
+                        body: ansi_to_html(&text),
+                    },
+                }
+            }
+            Err(error) => error_response(&error),
+        }
+    }
+}
+
+fn error_response(error: &PonyError) -> Response {
+    let status = match error {
+        PonyError::NoMessage => 400,
+        PonyError::PonyNotFound { .. }
+        | PonyError::PonyFileNotFound { .. }
+        | PonyError::BalloonNotFound { .. } => 404,
+        _ => 500,
+    };
+    Response::text(status, error.to_string())
+}
+
+/// Reads up to the end of the request headers and returns the request line, giving up once
+/// `deadline` has passed however the bytes trickle in.
+fn read_request_line(stream: &mut TcpStream, deadline: Duration) -> Result<String, &'static str> {
+    let deadline = Instant::now() + deadline;
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err("request timed out");
+        }
+        stream
+            .set_read_timeout(Some(remaining.min(READ_TIMEOUT)))
+            .map_err(|_| "failed to configure connection")?;
+        let read = stream.read(&mut buffer).map_err(|_| {
+            if Instant::now() >= deadline {
+                "request timed out"
+            } else {
+                "failed reading request"
+            }
+        })?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buffer[..read]);
+        if request.len() > MAX_REQUEST_BYTES {
+            return Err("request too large");
+        }
+    }
+    let line = request.split(|&byte| byte == b'\n').next().unwrap_or(&[]);
+    let line = std::str::from_utf8(line).map_err(|_| "request line is not UTF-8")?;
+    Ok(line.trim_end_matches('\r').to_string())
+}
+
+/// Decodes an `application/x-www-form-urlencoded` query; `None` on bad escapes or
+/// invalid UTF-8. Later duplicates win.
+fn parse_query(query: &str) -> Option<HashMap<String, String>> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            Some((decode_component(name)?, decode_component(value)?))
+        })
+        .collect()
+}
+
+fn decode_component(raw: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(raw.len());
+    let mut input = raw.bytes();
+    while let Some(byte) = input.next() {
+        bytes.push(match byte {
+            b'+' => b' ',
+            b'%' => {
+                let hex = [input.next()?, input.next()?];
+                u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?
+            }
+            _ => byte,
+        });
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn answers_say_requests() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        fs::write(tmp.path().join("red.pony"), "$balloon$\n\u{1b}[31mred\n").expect("write");
+        let pony_paths = vec![tmp.path().to_path_buf()];
+        let base = RenderConfig {
+            pony_paths,
+            balloon_paths: Vec::new(),
+            ..RenderConfig::default()
+        };
+        let server = Server {
+            renderer: Renderer::new(&base),
+            base,
+        };
+
+        let plain = server.respond("GET /say?message=hi+%26+bye&format=plain HTTP/1.1");
+        assert_eq!(plain.status, 200, "{}", plain.body);
+        assert_eq!(plain.body, " __________\n< hi & bye >\n ----------\nred\n");
+
+        let ansi = server.respond("GET /say?message=hi&pony=red HTTP/1.1");
+        assert!(ansi.body.starts_with("\u{1b}[0m"), "{:?}", ansi.body);
+        let folded = server.respond("GET /say?message=hi&pony=RED&format=plain HTTP/1.1");
+        assert_eq!(folded.status, 200, "{}", folded.body);
+        let html = server.respond("GET /say?message=hi&format=html HTTP/1.1");
+        assert_eq!(html.content_type, "text/html; charset=utf-8");
+        assert!(
+            html.body
+                .contains("<span style=\"color:#aa0000\">red</span>")
+        );
+
+        assert_eq!(server.respond("GET /ponies HTTP/1.1").body, "red\n");
+        assert_eq!(server.respond("GET /say HTTP/1.1").status, 400);
+        assert_eq!(
+            server
+                .respond("GET /say?message=hi&format=pdf HTTP/1.1")
+                .status,
+            400
+        );
+        assert_eq!(server.respond("GET /say?message=%zz HTTP/1.1").status, 400);
+        assert_eq!(
+            server
+                .respond("GET /say?message=hi&pony=%2Fetc%2Fpasswd HTTP/1.1")
+                .status,
+            404
+        );
+        assert_eq!(
+            server
+                .respond("GET /say?message=hi&balloon=..%2Fx HTTP/1.1")
+                .status,
+            400
+        );
+        assert_eq!(server.respond("POST /say HTTP/1.1").status, 405);
+        assert_eq!(server.respond("GET / HTTP/1.1").status, 404);
+    }
+
+    #[test]
+    fn slow_requests_hit_the_overall_deadline() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let mut client = TcpStream::connect(listener.local_addr().expect("addr")).expect("connect");
+        let (mut stream, _) = listener.accept().expect("accept");
+        let trickle = std::thread::spawn(move || {
+            for byte in b"GET / HTTP/1.1\r\n".iter().cycle().take(40) {
+                if client.write_all(&[*byte]).is_err() {
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(20));
+            }
+        });
+
+        let started = Instant::now();
+        let result = read_request_line(&mut stream, Duration::from_millis(200));
+        assert_eq!(result, Err("request timed out"));
+        assert!(started.elapsed() < Duration::from_secs(2));
+        drop(stream);
+        trickle.join().expect("client");
+    }
+}
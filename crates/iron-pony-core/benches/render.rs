@@ -0,0 +1,81 @@
+//! Rendering hot-path benchmarks.
+//!
+//! Set `IRON_PONY_BENCH_PONY` to a real pony file (e.g. one fetched by `xtask assets`) to
+//! benchmark against it; otherwise a synthetic 3000-line colored pony is used.
+
+use std::hint::black_box;
+use std::path::PathBuf;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use iron_pony_core::{
+    Mode, RenderConfig, load_pony, render_from_sources, resolve_and_render, visible_width,
+    wrap_message,
+};
+
+const MESSAGE: &str = "The quick brown fox jumps over the lazy dog while Twilight \
+    Sparkle catalogues every book in the Golden Oak Library, twice.";
+
+fn synthetic_pony(lines: usize) -> String {
+    let mut pony = String::from("$$$\nNAME: Bench\nWIDTH: 80\n$$$\n$balloon$\n");
+    for line in 0..lines {
+        let color = 16 + (line % 216);
+        pony.push_str(&format!(
+            "  $\\$  \u{1b}[38;5;{color}m▄▄▀▀▄▄\u{1b}[48;5;{color}m  ░░  \u{1b}[0m  ponies {line}\n"
+        ));
+    }
+    pony
+}
+
+fn bench_pony() -> (String, String) {
+    if let Some(path) = std::env::var_os("IRON_PONY_BENCH_PONY") {
+        let path = PathBuf::from(path);
+        let raw = std::fs::read_to_string(&path).expect("read IRON_PONY_BENCH_PONY");
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| "bench".to_string());
+        return (name, raw);
+    }
+    ("bench".to_string(), synthetic_pony(3000))
+}
+
+fn benches(c: &mut Criterion) {
+    let (name, source) = bench_pony();
+    let line = source.lines().nth(6).unwrap_or_default().to_string();
+    let long_message = MESSAGE.repeat(20);
+
+    c.bench_function("wrap_message", |b| {
+        b.iter(|| wrap_message(black_box(&long_message), black_box(40)))
+    });
+    c.bench_function("visible_width", |b| {
+        b.iter(|| visible_width(black_box(&line)))
+    });
+    c.bench_function("render_from_sources", |b| {
+        b.iter(|| {
+            render_from_sources(black_box(MESSAGE), black_box(&source), None, Mode::Say, 40)
+                .expect("render")
+        })
+    });
+
+    let tmp = tempfile::tempdir().expect("tempdir");
+    std::fs::write(tmp.path().join(format!("{name}.pony")), &source).expect("write pony");
+    let pony_paths = vec![tmp.path().to_path_buf()];
+
+    c.bench_function("load_pony", |b| {
+        b.iter(|| load_pony(black_box(&name), &pony_paths, false).expect("load"))
+    });
+
+    let config = RenderConfig {
+        message: MESSAGE.to_string(),
+        pony: name.clone(),
+        pony_paths: pony_paths.clone(),
+        balloon_paths: Vec::new(),
+        ..RenderConfig::default()
+    };
+    c.bench_function("render", |b| {
+        b.iter(|| resolve_and_render(black_box(&config)).expect("render"))
+    });
+}
+
+criterion_group!(render_benches, benches);
+criterion_main!(render_benches);
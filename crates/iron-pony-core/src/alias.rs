@@ -0,0 +1,106 @@
+//! Pony aliases: short names like `tw` for `twilight`, kept in an `aliases` file in any
+//! pony directory.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use tracing::{debug, warn};
+
+/// File name of the alias list in a pony directory.
+pub const ALIASES_FILE: &str = "aliases";
+
+/// Aliases from the `aliases` file of every root, one `ALIAS -> PONY` per line with `#`
+/// starting a comment. When roots disagree, the earlier root wins, as for ponies.
+pub fn load_aliases(roots: &[PathBuf]) -> BTreeMap<String, String> {
+    let mut aliases = BTreeMap::new();
+    for root in roots {
+        let path = root.join(ALIASES_FILE);
+        let raw = match fs::read_to_string(&path) {
+            Ok(raw) => raw,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+            Err(error) => {
+                warn!(path = %path.display(), %error, "failed reading pony aliases");
+                continue;
+            }
+        };
+        let (parsed, malformed) = parse_aliases(&raw);
+        for (line, text) in malformed {
+            warn!(path = %path.display(), line, text, "ignoring malformed pony alias");
+        }
+        for (alias, pony) in parsed {
+            aliases.entry(alias).or_insert(pony);
+        }
+    }
+    debug!(aliases = aliases.len(), "loaded pony aliases");
+    aliases
+}
+
+/// Lines of `root`'s `aliases` file that [`load_aliases`] skips. A missing file has none; an unreadable one is the error.
+pub fn alias_file_problems(root: &Path) -> io::Result<Vec<MalformedAlias>> {
+    match fs::read_to_string(root.join(ALIASES_FILE)) {
+        Ok(raw) => Ok(parse_aliases(&raw).1),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(error) => Err(error),
+    }
+}
+
+/// A line that is not `ALIAS -> PONY`: its 1-based number and text.
+pub type MalformedAlias = (usize, String);
+
+/// The aliases in `raw`, and the lines that are not `ALIAS -> PONY`.
+fn parse_aliases(raw: &str) -> (Vec<(String, String)>, Vec<MalformedAlias>) {
+    let mut out = Vec::new();
+    let mut malformed = Vec::new();
+    for (number, line) in raw.lines().enumerate() {
+        let line = line
+            .split_once('#')
+            .map_or(line, |(line, _comment)| line)
+            .trim();
+        if line.is_empty() {
+            continue;
+        }
+        match line.split_once("->") {
+            Some((alias, pony)) if !alias.trim().is_empty() && !pony.trim().is_empty() => {
+                out.push((alias.trim().to_string(), pony.trim().to_string()));
+            }
+            _ => malformed.push((number + 1, line.to_string())),
+        }
+    }
+    (out, malformed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn earlier_roots_win_and_bad_lines_are_skipped() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let (first, second) = (tmp.path().join("first"), tmp.path().join("second"));
+        fs::create_dir_all(&first).expect("mkdir");
+        fs::create_dir_all(&second).expect("mkdir");
+        fs::write(
+            first.join(ALIASES_FILE),
+            "# mine\ntw -> twilight\nnonsense\nrd->rainbow # dash\n",
+        )
+        .expect("write");
+        fs::write(second.join(ALIASES_FILE), "tw -> trixie\naj -> applejack\n").expect("write");
+
+        assert_eq!(
+            alias_file_problems(&first).expect("readable"),
+            [(3, "nonsense".to_string())]
+        );
+        assert!(alias_file_problems(&second).expect("readable").is_empty());
+        let aliases = load_aliases(&[first, second, tmp.path().join("missing")]);
+        assert_eq!(
+            aliases.into_iter().collect::<Vec<_>>(),
+            [
+                ("aj".to_string(), "applejack".to_string()),
+                ("rd".to_string(), "rainbow".to_string()),
+                ("tw".to_string(), "twilight".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,535 @@
+use unicode_width::UnicodeWidthChar;
+
+const ESC: char = '\u{1b}';
+const BEL: char = '\u{7}';
+/// 8-bit String Terminator.
+const ST: char = '\u{9c}';
+
+/// Closes an OSC 8 hyperlink.
+pub(crate) const HYPERLINK_CLOSE: &str = "\u{1b}]8;;\u{1b}\\";
+
+/// Escape sequence families, following ECMA-48.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SequenceKind {
+    /// `ESC [` ... final byte in `@`..=`~`.
+    Csi,
+    /// `ESC ]` ... terminated by BEL or ST.
+    Osc,
+    /// `ESC P` ... ST.
+    Dcs,
+    /// `ESC X` ... ST.
+    Sos,
+    /// `ESC ^` ... ST.
+    Pm,
+    /// `ESC _` ... ST.
+    Apc,
+    /// Any other escape: intermediates in ` `..=`/` followed by one final byte.
+    Escape,
+}
+
+impl SequenceKind {
+    /// Kinds whose payload runs until a string terminator.
+    fn is_string(self) -> bool {
+        matches!(
+            self,
+            Self::Osc | Self::Dcs | Self::Sos | Self::Pm | Self::Apc
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Sequence<'a> {
+    pub(crate) kind: SequenceKind,
+    /// Full source text, introducer and terminator included.
+    pub(crate) raw: &'a str,
+    /// False when the input ended before the sequence did.
+    pub(crate) terminated: bool,
+}
+
+impl<'a> Sequence<'a> {
+    /// Parameter bytes of a CSI sequence ending in `m` (SGR).
+    pub(crate) fn sgr_params(&self) -> Option<&'a str> {
+        if self.kind != SequenceKind::Csi || !self.terminated {
+            return None;
+        }
+        let body = self
+            .raw
+            .strip_prefix("\u{1b}[")
+            .or_else(|| self.raw.strip_prefix('\u{9b}'))?;
+        body.strip_suffix('m')
+    }
+
+    /// Target URI of an OSC 8 hyperlink sequence; empty when it closes a link.
+    pub(crate) fn hyperlink_target(&self) -> Option<&'a str> {
+        if self.kind != SequenceKind::Osc || !self.terminated {
+            return None;
+        }
+        let body = self
+            .raw
+            .strip_prefix("\u{1b}]")
+            .or_else(|| self.raw.strip_prefix('\u{9d}'))?;
+        let body = body
+            .strip_suffix("\u{1b}\\")
+            .or_else(|| body.strip_suffix([BEL, ST]))?;
+        let (_params, uri) = body.strip_prefix("8;")?.split_once(';')?;
+        Some(uri)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Token<'a> {
+    Text(&'a str),
+    Sequence(Sequence<'a>),
+}
+
+/// Splits `input` into plain text runs and complete escape sequences.
+pub(crate) fn tokens(input: &str) -> Tokens<'_> {
+    Tokens { rest: input }
+}
+
+pub(crate) struct Tokens<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = self.rest;
+        let first = rest.chars().next()?;
+
+        let Some(kind) = introducer(rest) else {
+            let end = rest
+                .char_indices()
+                .skip(1)
+                .find(|(index, _)| introducer(&rest[*index..]).is_some())
+                .map_or(rest.len(), |(index, _)| index);
+            self.rest = &rest[end..];
+            return Some(Token::Text(&rest[..end]));
+        };
+
+        let header = if first == ESC && kind != SequenceKind::Escape {
+            2
+        } else if first == ESC {
+            1
+        } else {
+            first.len_utf8()
+        };
+        let (len, terminated) = sequence_body_len(kind, &rest[header..]);
+        let end = header + len;
+        self.rest = &rest[end..];
+        Some(Token::Sequence(Sequence {
+            kind,
+            raw: &rest[..end],
+            terminated,
+        }))
+    }
+}
+
+fn introducer(input: &str) -> Option<SequenceKind> {
+    let mut chars = input.chars();
+    match chars.next()? {
+        ESC => Some(match chars.next() {
+            Some('[') => SequenceKind::Csi,
+            Some(']') => SequenceKind::Osc,
+            Some('P') => SequenceKind::Dcs,
+            Some('X') => SequenceKind::Sos,
+            Some('^') => SequenceKind::Pm,
+            Some('_') => SequenceKind::Apc,
+            _ => SequenceKind::Escape,
+        }),
+        '\u{9b}' => Some(SequenceKind::Csi),
+        '\u{9d}' => Some(SequenceKind::Osc),
+        '\u{90}' => Some(SequenceKind::Dcs),
+        '\u{98}' => Some(SequenceKind::Sos),
+        '\u{9e}' => Some(SequenceKind::Pm),
+        '\u{9f}' => Some(SequenceKind::Apc),
+        _ => None,
+    }
+}
+
+/// Length of the sequence after its introducer, and whether it was terminated.
+fn sequence_body_len(kind: SequenceKind, body: &str) -> (usize, bool) {
+    if kind.is_string() {
+        let mut previous_esc = false;
+        for (index, ch) in body.char_indices() {
+            let terminator = ch == ST || (previous_esc && ch == '\\');
+            if terminator || (kind == SequenceKind::Osc && ch == BEL) {
+                return (index + ch.len_utf8(), true);
+            }
+            previous_esc = ch == ESC;
+        }
+        return (body.len(), false);
+    }
+
+    let finals = match kind {
+        SequenceKind::Csi => '@'..='~',
+        _ => '0'..='~',
+    };
+    for (index, ch) in body.char_indices() {
+        if finals.contains(&ch) {
+            return (index + 1, true);
+        }
+        let allowed = match kind {
+            SequenceKind::Csi => ('0'..='?').contains(&ch) || (' '..='/').contains(&ch),
+            _ => (' '..='/').contains(&ch),
+        };
+        if !allowed {
+            // Malformed: end the sequence before the offending character.
+            return (index, false);
+        }
+    }
+    (body.len(), false)
+}
+
+/// Display width of `input` in terminal columns, ignoring escape sequences.
+pub fn visible_width(input: &str) -> usize {
+    tokens(input)
+        .map(|token| match token {
+            Token::Text(text) => text
+                .chars()
+                .map(|ch| UnicodeWidthChar::width(ch).unwrap_or(0))
+                .sum(),
+            Token::Sequence(_) => 0,
+        })
+        .sum()
+}
+
+/// `input` with every escape sequence removed, leaving only the text that is displayed.
+pub fn strip_ansi(input: &str) -> String {
+    tokens(input)
+        .filter_map(|token| match token {
+            Token::Text(text) => Some(text),
+            Token::Sequence(_) => None,
+        })
+        .collect()
+}
+
+/// `input` with each run of text replaced by `map(run)`; escape sequences are copied
+/// through unchanged.
+pub(crate) fn map_text(input: &str, mut map: impl FnMut(&str) -> String) -> String {
+    let mut out = String::with_capacity(input.len());
+    for token in tokens(input) {
+        match token {
+            Token::Text(text) => out.push_str(&map(text)),
+            Token::Sequence(sequence) => out.push_str(sequence.raw),
+        }
+    }
+    out
+}
+
+/// Clips `input` to `width` display columns. Escape sequences past the cut are kept, so
+/// a trailing color reset or hyperlink close still takes effect.
+pub fn truncate_width(input: &str, width: usize) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut used = 0;
+    let mut cut = false;
+    for token in tokens(input) {
+        match token {
+            Token::Sequence(sequence) => out.push_str(sequence.raw),
+            Token::Text(_) if cut => {}
+            Token::Text(text) => {
+                for ch in text.chars() {
+                    let ch_width = UnicodeWidthChar::width(ch).unwrap_or(0);
+                    if used + ch_width > width {
+                        cut = true;
+                        break;
+                    }
+                    used += ch_width;
+                    out.push(ch);
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Splits `input` into pieces that each end in one character of text, with the escape
+/// sequences before it kept in front; sequences after the last character form a final
+/// piece. Writing the pieces one at a time never splits a sequence, and concatenating
+/// them reproduces `input`.
+pub fn ansi_chunks(input: &str) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let (mut start, mut offset) = (0, 0);
+    for token in tokens(input) {
+        match token {
+            Token::Sequence(sequence) => offset += sequence.raw.len(),
+            Token::Text(text) => {
+                for ch in text.chars() {
+                    offset += ch.len_utf8();
+                    chunks.push(&input[start..offset]);
+                    start = offset;
+                }
+            }
+        }
+    }
+    if start < input.len() {
+        chunks.push(&input[start..]);
+    }
+    chunks
+}
+
+/// Removes the whitespace at the end of `input` that a terminal would show as blank.
+/// Escape sequences are kept, so a trailing color reset still applies; spaces under a
+/// background color or reverse video are art, not padding, and are kept too.
+pub fn trim_trailing_whitespace(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    // Blank text and the sequences interleaved with it since the last visible character.
+    let mut pending = String::new();
+    let mut pending_sequences = String::new();
+    let mut painted = false;
+    for token in tokens(input) {
+        match token {
+            Token::Sequence(sequence) => {
+                if let Some(params) = sequence.sgr_params() {
+                    painted = paints_background(params, painted);
+                }
+                pending.push_str(sequence.raw);
+                pending_sequences.push_str(sequence.raw);
+            }
+            Token::Text(text) => {
+                for ch in text.chars() {
+                    pending.push(ch);
+                    if painted || !ch.is_whitespace() {
+                        out.push_str(&pending);
+                        pending.clear();
+                        pending_sequences.clear();
+                    }
+                }
+            }
+        }
+    }
+    out.push_str(&pending_sequences);
+    out
+}
+
+/// Whether blank cells are still drawn in color after the SGR `params`, given whether
+/// they were before.
+fn paints_background(params: &str, mut painted: bool) -> bool {
+    let mut values = params.split(';');
+    while let Some(value) = values.next() {
+        match value {
+            "" | "0" | "27" | "49" => painted = false,
+            "7" | "48" => painted = true,
+            _ if value
+                .parse::<u8>()
+                .is_ok_and(|code| (40..=47).contains(&code) || (100..=107).contains(&code)) =>
+            {
+                painted = true
+            }
+            _ => {}
+        }
+        // Extended colors carry their own parameters, which must not be read as codes.
+        if matches!(value, "38" | "48" | "58") {
+            match values.next() {
+                Some("5") => {
+                    values.next();
+                }
+                Some("2") => {
+                    values.nth(2);
+                }
+                _ => {}
+            }
+        }
+    }
+    painted
+}
+
+/// Makes every line self-contained with respect to OSC 8 hyperlinks: a link still open at
+/// the end of a line is closed there and reopened at the start of the next one, so
+/// balloon borders and padding never become part of a link.
+pub(crate) fn balance_hyperlinks(lines: &mut [String]) {
+    let mut open: Option<String> = None;
+    for line in lines.iter_mut() {
+        let carried = open.clone();
+        for token in tokens(line) {
+            if let Token::Sequence(sequence) = token
+                && let Some(target) = sequence.hyperlink_target()
+            {
+                open = (!target.is_empty()).then(|| sequence.raw.to_string());
+            }
+        }
+
+        if carried.is_none() && open.is_none() {
+            continue;
+        }
+        let mut balanced = carried.unwrap_or_default();
+        balanced.push_str(line);
+        if open.is_some() {
+            balanced.push_str(HYPERLINK_CLOSE);
+        }
+        *line = balanced;
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    /// Text interleaved with SGR, hyperlink and unterminated sequences.
+    pub(crate) fn styled_text() -> impl Strategy<Value = String> {
+        prop::collection::vec(
+            prop_oneof![
+                4 => "[a-zA-Z]{1,12}",
+                3 => Just(" ".to_string()),
+                1 => Just("\u{e9}t\u{e9}".to_string()),
+                1 => Just("日本".to_string()),
+                1 => Just("\u{1b}[1;31m".to_string()),
+                1 => Just("\u{1b}[0m".to_string()),
+                1 => Just("\u{1b}]8;;https://pony.example\u{1b}\\".to_string()),
+                1 => Just(HYPERLINK_CLOSE.to_string()),
+            ],
+            0..40,
+        )
+        .prop_map(|parts| parts.concat())
+    }
+
+    fn kinds(input: &str) -> Vec<(Option<SequenceKind>, &str, bool)> {
+        tokens(input)
+            .map(|token| match token {
+                Token::Text(text) => (None, text, true),
+                Token::Sequence(sequence) => {
+                    (Some(sequence.kind), sequence.raw, sequence.terminated)
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn classifies_sequences() {
+        let input =
+            "a\u{1b}[1;31mb\u{1b}]0;title\u{1b}\\c\u{1b}Pq#0\u{1b}\\\u{1b}(Bd\u{1b}_x\u{9c}";
+        assert_eq!(
+            kinds(input),
+            vec![
+                (None, "a", true),
+                (Some(SequenceKind::Csi), "\u{1b}[1;31m", true),
+                (None, "b", true),
+                (Some(SequenceKind::Osc), "\u{1b}]0;title\u{1b}\\", true),
+                (None, "c", true),
+                (Some(SequenceKind::Dcs), "\u{1b}Pq#0\u{1b}\\", true),
+                (Some(SequenceKind::Escape), "\u{1b}(B", true),
+                (None, "d", true),
+                (Some(SequenceKind::Apc), "\u{1b}_x\u{9c}", true),
+            ]
+        );
+    }
+
+    #[test]
+    fn unterminated_sequences_swallow_the_rest() {
+        assert_eq!(
+            kinds("x\u{1b}]8;;http://a b"),
+            vec![
+                (None, "x", true),
+                (Some(SequenceKind::Osc), "\u{1b}]8;;http://a b", false),
+            ]
+        );
+        assert_eq!(visible_width("\u{1b}[31"), 0);
+    }
+
+    #[test]
+    fn widths_ignore_string_sequences() {
+        assert_eq!(
+            visible_width("\u{1b}]8;;http://x\u{1b}\\link\u{1b}]8;;\u{1b}\\"),
+            4
+        );
+        assert_eq!(
+            visible_width("\u{1b}]8;;http://x\u{7}日本\u{1b}]8;;\u{7}"),
+            4
+        );
+        assert_eq!(visible_width("\u{1b}P1;2|x\\y\u{1b}\\ok"), 2);
+    }
+
+    #[test]
+    fn hyperlinks_are_reopened_on_each_line() {
+        let open = "\u{1b}]8;;https://pony.example\u{1b}\\";
+        let mut lines = vec![
+            format!("see {open}the"),
+            "docs".to_string(),
+            format!("now{HYPERLINK_CLOSE} done"),
+            "plain".to_string(),
+        ];
+        balance_hyperlinks(&mut lines);
+        assert_eq!(
+            lines,
+            vec![
+                format!("see {open}the{HYPERLINK_CLOSE}"),
+                format!("{open}docs{HYPERLINK_CLOSE}"),
+                format!("{open}now{HYPERLINK_CLOSE} done"),
+                "plain".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn strip_ansi_keeps_only_text() {
+        assert_eq!(
+            strip_ansi("\u{1b}[1mbold\u{1b}[0m \u{1b}]8;;x\u{7}link\u{1b}]8;;\u{7}"),
+            "bold link"
+        );
+        assert_eq!(strip_ansi("cut \u{1b}[3"), "cut ");
+    }
+
+    #[test]
+    fn truncation_counts_columns_and_keeps_escapes() {
+        assert_eq!(
+            truncate_width("\u{1b}[31m日本語\u{1b}[0m!", 5),
+            "\u{1b}[31m日本\u{1b}[0m"
+        );
+        assert_eq!(truncate_width("short", 10), "short");
+    }
+
+    #[test]
+    fn chunks_keep_sequences_with_the_next_character() {
+        assert_eq!(
+            ansi_chunks("\u{1b}[31mhi\u{1b}[0m\n\u{1b}[0m"),
+            ["\u{1b}[31mh", "i", "\u{1b}[0m\n", "\u{1b}[0m"]
+        );
+        assert!(ansi_chunks("").is_empty());
+    }
+
+    #[test]
+    fn trims_blank_cells_but_keeps_escapes_and_painted_spaces() {
+        assert_eq!(
+            trim_trailing_whitespace("\u{1b}[31mhi  \u{1b}[0m  "),
+            "\u{1b}[31mhi\u{1b}[0m"
+        );
+        assert_eq!(
+            trim_trailing_whitespace("hi\u{1b}[48;5;1m  \u{1b}[49m "),
+            "hi\u{1b}[48;5;1m  \u{1b}[49m"
+        );
+        assert_eq!(
+            trim_trailing_whitespace("hi\u{1b}[38;5;41m  \u{1b}[0m"),
+            "hi\u{1b}[38;5;41m\u{1b}[0m"
+        );
+        assert_eq!(trim_trailing_whitespace("   "), "");
+    }
+
+    proptest! {
+        #[test]
+        fn chunks_reassemble_input(input in styled_text()) {
+            let chunks = ansi_chunks(&input);
+            prop_assert_eq!(chunks.concat(), input.clone());
+            prop_assert_eq!(chunks.len(), strip_ansi(&input).chars().count() + usize::from(
+                chunks.last().is_some_and(|chunk| strip_ansi(chunk).is_empty())
+            ));
+        }
+
+        #[test]
+        fn stripping_preserves_width(input in styled_text()) {
+            let stripped = strip_ansi(&input);
+            prop_assert_eq!(visible_width(&stripped), visible_width(&input));
+            prop_assert!(!stripped.contains(ESC), "escape left in {:?}", stripped);
+            prop_assert_eq!(strip_ansi(&stripped), stripped);
+        }
+
+        #[test]
+        fn truncation_fits_width(input in styled_text(), width in 0usize..20) {
+            let truncated = truncate_width(&input, width);
+            prop_assert!(visible_width(&truncated) <= width);
+            prop_assert!(strip_ansi(&input).starts_with(&strip_ansi(&truncated)));
+        }
+    }
+}
@@ -1,17 +1,106 @@
 use std::collections::HashMap;
+use std::fmt::Write as _;
+#[cfg(feature = "fs")]
 use std::path::{Path, PathBuf};
 
-use tracing::{debug, trace, warn};
+use tracing::trace;
+#[cfg(feature = "fs")]
+use tracing::{debug, warn};
 use unicode_width::UnicodeWidthChar;
+
+#[cfg(feature = "fs")]
 use walkdir::WalkDir;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+use crate::PonyError;
+use crate::ansi::{self, Token, visible_width};
+#[cfg(feature = "fs")]
+use crate::encoding;
+
+const RESET: &str = "\u{1b}[0m";
+
+/// An installed balloon file that exists but cannot be used.
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{}{}: {reason}", path.display(), line.map(|line| format!(":{line}")).unwrap_or_default())]
+pub struct BalloonParseError {
+    pub path: PathBuf,
+    /// 1-based line the problem is on; `None` when the file could not be read at all.
+    pub line: Option<usize>,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum BalloonMode {
     Say,
     Think,
 }
 
-#[derive(Debug, Clone)]
+/// Horizontal alignment of the text lines inside a balloon.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Justify {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+/// How [`BalloonStyle`] sizes a balloon around its text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum CompatLevel {
+    /// Upstream's arithmetic, byte for byte: borders take twice the width of the widest
+    /// right-hand piece, and only as many top and bottom rows are drawn as `n` and `s`
+    /// have, however tall the corners are.
+    #[default]
+    Upstream,
+    /// Borders take the widest left-hand piece plus the widest right-hand piece, and every
+    /// row of a multi-row corner is drawn.
+    Corrected,
+}
+
+/// What [`render_balloon`] draws, apart from the style. Build it from
+/// [`BalloonRequest::new`] with struct update syntax, so options added later do not break
+/// callers.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BalloonRequest<'a> {
+    /// Text to show; every `\n` starts a new line.
+    pub message: &'a str,
+    /// Word-wrap the message to this many text columns with [`wrap_message_with`]. `None`
+    /// keeps its lines as they are, as for a FIGlet banner.
+    pub wrap: Option<usize>,
+    /// How words too long for `wrap` are handled.
+    pub wrap_options: WrapOptions,
+    pub justify: Justify,
+    /// Grow the balloon to at least this many columns, borders included.
+    pub min_width: usize,
+    /// Grow the balloon to at least this many rows, borders included.
+    pub min_height: usize,
+    /// Reorder right-to-left lines into visual order after wrapping.
+    #[cfg(feature = "bidi")]
+    pub bidi: bool,
+    /// Balloon geometry: upstream's sizing, or borders sized from their actual pieces.
+    pub compat: CompatLevel,
+}
+
+/// How [`wrap_message_with`] treats words wider than the wrap width.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WrapOptions {
+    /// A word at most this many columns wider than the wrap width is kept whole on a line
+    /// of its own instead of being broken.
+    pub exceed: usize,
+    /// Appended to every piece of a broken word but the last, e.g. `-`. Empty by default.
+    pub hyphen: String,
+}
+
+impl<'a> BalloonRequest<'a> {
+    pub fn new(message: &'a str) -> Self {
+        Self {
+            message,
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct BalloonStyle {
     pub link: String,
     pub link_mirror: String,
@@ -134,7 +223,43 @@ impl BalloonStyle {
         }
     }
 
-    fn default_for_mode(mode: BalloonMode) -> Self {
+    /// Columns the borders take around the text. [`Self::min_width`] is upstream's figure,
+    /// which counts the widest right-hand piece twice; [`CompatLevel::Corrected`] adds the
+    /// widest left-hand piece to the widest right-hand one instead.
+    pub(crate) fn border_width(&self, compat: CompatLevel) -> usize {
+        let widest = |pieces: [&[String]; 6]| {
+            pieces
+                .into_iter()
+                .flatten()
+                .map(|piece| visible_width(piece))
+                .max()
+                .unwrap_or(0)
+        };
+        match compat {
+            CompatLevel::Upstream => self.min_width,
+            CompatLevel::Corrected => {
+                widest([
+                    &self.nw,
+                    std::slice::from_ref(&self.nww),
+                    std::slice::from_ref(&self.w),
+                    std::slice::from_ref(&self.sww),
+                    &self.sw,
+                    std::slice::from_ref(&self.ww),
+                ]) + widest([
+                    &self.ne,
+                    std::slice::from_ref(&self.nee),
+                    std::slice::from_ref(&self.e),
+                    std::slice::from_ref(&self.see),
+                    &self.se,
+                    std::slice::from_ref(&self.ee),
+                ])
+            }
+        }
+    }
+
+    /// The built-in style for `mode` (named `default`), used by `--builtin-balloon` and
+    /// when no style is installed.
+    pub fn default_for_mode(mode: BalloonMode) -> Self {
         match mode {
             BalloonMode::Think => Self::new(
                 "o".to_string(),
@@ -185,9 +310,193 @@ impl BalloonStyle {
         }
     }
 
-    fn render(&self, minw: usize, minh: usize, lines: &[String]) -> Vec<String> {
+    /// Keys of the upstream balloon file format, in the order [`Self::to_balloon_file`]
+    /// writes them. `\\`, `/` and `X` hold `link`, `link_mirror` and `link_cross`.
+    pub const KEYS: [&'static str; 21] = [
+        "\\", "/", "X", "ww", "ee", "nw", "nnw", "n", "nne", "ne", "nee", "e", "see", "se", "sse",
+        "s", "ssw", "sw", "sww", "w", "nww",
+    ];
+
+    /// Parses an upstream balloon file. Unknown keys and lines without a `:` are ignored.
+    pub fn parse(raw: &str) -> Self {
+        parse_style(raw)
+    }
+
+    /// Builds a one-off style from the glyphs for `nw`, `n`, `ne`, `e`, `se`, `s`, `sw` and
+    /// `w`, clockwise from the top-left corner (e.g. `╭─╮│╯─╰│`). Sides are padded with a
+    /// space like the built-in styles. An optional ninth glyph replaces the mode's link
+    /// characters between the balloon and the pony.
+    pub fn from_chars(spec: &str, mode: BalloonMode) -> Result<Self, PonyError> {
+        if spec.chars().any(char::is_control) {
+            return Err(PonyError::InvalidBalloon(
+                "balloon glyphs must not contain control characters".to_string(),
+            ));
+        }
+        let glyphs = spec.chars().map(String::from).collect::<Vec<_>>();
+        let [nw, n, ne, e, se, s, sw, w, link @ ..] = glyphs.as_slice() else {
+            return Err(PonyError::InvalidBalloon(format!(
+                "expected 8 or 9 balloon glyphs, got {}",
+                glyphs.len()
+            )));
+        };
+        let default = Self::default_for_mode(mode);
+        let (link, link_mirror, link_cross) = match link {
+            [] => (default.link, default.link_mirror, default.link_cross),
+            [link] => (link.clone(), link.clone(), link.clone()),
+            _ => {
+                return Err(PonyError::InvalidBalloon(format!(
+                    "expected 8 or 9 balloon glyphs, got {}",
+                    glyphs.len()
+                )));
+            }
+        };
+        let (left, right) = (format!("{w} "), format!(" {e}"));
+        Ok(Self::new(
+            link,
+            link_mirror,
+            link_cross,
+            left.clone(),
+            right.clone(),
+            vec![nw.clone()],
+            Vec::new(),
+            vec![n.clone()],
+            Vec::new(),
+            vec![ne.clone()],
+            right.clone(),
+            right.clone(),
+            right,
+            vec![se.clone()],
+            Vec::new(),
+            vec![s.clone()],
+            Vec::new(),
+            vec![sw.clone()],
+            left.clone(),
+            left.clone(),
+            left,
+        ))
+    }
+
+    /// Builds a style from a TOML table keyed by the upstream balloon keys ([`Self::KEYS`]).
+    /// `link`, `link_mirror` and `link_cross` may stand in for `\`, `/` and `X`; multi-row
+    /// pieces take an array of strings.
+    #[cfg(feature = "toml")]
+    pub fn from_toml(raw: &str) -> Result<Self, PonyError> {
+        let table = toml::from_str::<toml::Table>(raw)
+            .map_err(|error| PonyError::InvalidBalloon(error.message().to_string()))?;
+        let mut file = String::new();
+        for (name, value) in &table {
+            let key = match name.as_str() {
+                "link" => "\\",
+                "link_mirror" => "/",
+                "link_cross" => "X",
+                key if Self::KEYS.contains(&key) => key,
+                _ => {
+                    return Err(PonyError::InvalidBalloon(format!(
+                        "unknown balloon key `{name}` (expected one of {} or link, link_mirror, link_cross)",
+                        Self::KEYS.join(" ")
+                    )));
+                }
+            };
+            let values = match value {
+                toml::Value::String(value) => vec![value.as_str()],
+                toml::Value::Array(items) => items
+                    .iter()
+                    .map(toml::Value::as_str)
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| {
+                        PonyError::InvalidBalloon(format!("balloon key `{name}` must hold strings"))
+                    })?,
+                _ => {
+                    return Err(PonyError::InvalidBalloon(format!(
+                        "balloon key `{name}` must be a string or an array of strings"
+                    )));
+                }
+            };
+            if values.iter().any(|value| value.contains(['\n', '\r'])) {
+                return Err(PonyError::InvalidBalloon(format!(
+                    "balloon key `{name}` contains a line break; use an array for multiple rows"
+                )));
+            }
+            for (index, value) in values.iter().enumerate() {
+                let prefix = if index == 0 { key } else { "" };
+                let _ = writeln!(file, "{prefix}:{value}");
+            }
+        }
+        Ok(parse_style(&file))
+    }
+
+    /// Serializes the style back to the upstream balloon file format.
+    ///
+    /// Multi-line pieces continue on `:`-prefixed lines and empty single pieces are
+    /// omitted. [`Self::parse`] of the output yields an equal style unless a piece
+    /// contains a line break, which the format cannot express.
+    pub fn to_balloon_file(&self) -> String {
+        let mut out = String::new();
+        for (key, values) in Self::KEYS.iter().zip(self.pieces()) {
+            for (index, value) in values.iter().enumerate() {
+                let prefix = if index == 0 { key } else { "" };
+                let _ = writeln!(out, "{prefix}:{value}");
+            }
+        }
+        out
+    }
+
+    /// A small balloon around `sample_text` with a link below it, enough to see what the
+    /// style looks like without a pony. Rows start and end with a color reset.
+    pub fn preview(&self, sample_text: &str, compat: CompatLevel) -> Vec<String> {
+        let request = BalloonRequest {
+            compat,
+            ..BalloonRequest::new(sample_text)
+        };
+        let mut rows = render_balloon(&request, self);
+        let indent = visible_width(&self.ww).max(1);
+        rows.push(format!("{}{}", " ".repeat(indent), self.link));
+        rows
+    }
+
+    /// Every piece as a slice, in [`Self::KEYS`] order.
+    fn pieces(&self) -> [&[String]; 21] {
+        fn one(value: &String) -> &[String] {
+            if value.is_empty() {
+                &[]
+            } else {
+                std::slice::from_ref(value)
+            }
+        }
+        [
+            one(&self.link),
+            one(&self.link_mirror),
+            one(&self.link_cross),
+            one(&self.ww),
+            one(&self.ee),
+            &self.nw,
+            &self.nnw,
+            &self.n,
+            &self.nne,
+            &self.ne,
+            one(&self.nee),
+            one(&self.e),
+            one(&self.see),
+            &self.se,
+            &self.sse,
+            &self.s,
+            &self.ssw,
+            &self.sw,
+            one(&self.sww),
+            one(&self.w),
+            one(&self.nww),
+        ]
+    }
+
+    fn render(
+        &self,
+        compat: CompatLevel,
+        minw: usize,
+        minh: usize,
+        lines: &[String],
+    ) -> Vec<String> {
         let mut h = self.min_height + lines.len();
-        let mut w = self.min_width
+        let mut w = self.border_width(compat)
             + lines
                 .iter()
                 .max_by_key(|line| visible_width(line))
@@ -201,6 +510,17 @@ impl BalloonStyle {
             h = minh;
         }
 
+        // Grow vertically to the requested height by adding blank body rows.
+        let body_rows = h.saturating_sub(self.min_height).max(lines.len());
+        let mut padded;
+        let lines = if body_rows > lines.len() {
+            padded = lines.to_vec();
+            padded.resize(body_rows, String::new());
+            padded.as_slice()
+        } else {
+            lines
+        };
+
         let mut ws = HashMap::<usize, &str>::new();
         let mut es = HashMap::<usize, &str>::new();
 
@@ -218,14 +538,34 @@ impl BalloonStyle {
             es.insert(0, self.ee.as_str());
         }
 
+        // Rows of the top and bottom borders, what fills a row the middle piece lacks, and
+        // what stands in for the rows a shorter corner lacks.
+        let (top, bottom, fill, sides) = match compat {
+            CompatLevel::Upstream => (self.n.len(), self.s.len(), "", ("", "")),
+            CompatLevel::Corrected => (
+                [&self.nw, &self.nnw, &self.n, &self.nne, &self.ne]
+                    .map(Vec::len)
+                    .into_iter()
+                    .max()
+                    .unwrap_or(0),
+                [&self.sw, &self.ssw, &self.s, &self.sse, &self.se]
+                    .map(Vec::len)
+                    .into_iter()
+                    .max()
+                    .unwrap_or(0),
+                " ",
+                (self.w.as_str(), self.e.as_str()),
+            ),
+        };
+
         let mut rendered = Vec::new();
 
-        for j in 0..self.n.len() {
-            let nw = self.nw.get(j).map_or("", String::as_str);
-            let ne = self.ne.get(j).map_or("", String::as_str);
+        for j in 0..top {
+            let nw = self.nw.get(j).map_or(sides.0, String::as_str);
+            let ne = self.ne.get(j).map_or(sides.1, String::as_str);
             let nnw = self.nnw.get(j).map_or("", String::as_str);
             let nne = self.nne.get(j).map_or("", String::as_str);
-            let n = self.n.get(j).map_or("", String::as_str);
+            let n = self.n.get(j).map_or(fill, String::as_str);
 
             let outer = visible_width(nw) + visible_width(ne);
             let inner = visible_width(nnw) + visible_width(nne);
@@ -240,26 +580,28 @@ impl BalloonStyle {
                     ne
                 ));
             } else {
-                rendered.push(format!("{}{}{}", nw, n.repeat(w - outer), ne));
+                rendered.push(format!("{}{}{}", nw, n.repeat(w.saturating_sub(outer)), ne));
             }
         }
 
         for (index, line) in lines.iter().enumerate() {
             let left = ws.get(&index).copied().unwrap_or(self.w.as_str());
             let right = es.get(&index).copied().unwrap_or(self.e.as_str());
+            // Pad against the pieces actually used on this row so the right border
+            // lines up even when `ww`/`nww`/`sww` differ in width from `w`.
             let pad = w
                 .saturating_sub(visible_width(line))
-                .saturating_sub(visible_width(self.w.as_str()))
-                .saturating_sub(visible_width(self.e.as_str()));
+                .saturating_sub(visible_width(left))
+                .saturating_sub(visible_width(right));
             rendered.push(format!("{}{}{}", left, line, " ".repeat(pad) + right));
         }
 
-        for j in 0..self.s.len() {
-            let sw = self.sw.get(j).map_or("", String::as_str);
-            let se = self.se.get(j).map_or("", String::as_str);
+        for j in 0..bottom {
+            let sw = self.sw.get(j).map_or(sides.0, String::as_str);
+            let se = self.se.get(j).map_or(sides.1, String::as_str);
             let ssw = self.ssw.get(j).map_or("", String::as_str);
             let sse = self.sse.get(j).map_or("", String::as_str);
-            let s = self.s.get(j).map_or("", String::as_str);
+            let s = self.s.get(j).map_or(fill, String::as_str);
 
             let outer = visible_width(sw) + visible_width(se);
             let inner = visible_width(ssw) + visible_width(sse);
@@ -274,7 +616,7 @@ impl BalloonStyle {
                     se
                 ));
             } else {
-                rendered.push(format!("{}{}{}", sw, s.repeat(w - outer), se));
+                rendered.push(format!("{}{}{}", sw, s.repeat(w.saturating_sub(outer)), se));
             }
         }
 
@@ -288,16 +630,67 @@ impl BalloonStyle {
     }
 }
 
+/// Installed style upstream uses when no `-b` is given.
+#[cfg(feature = "fs")]
+pub(crate) const DEFAULT_STYLE_NAME: &str = "unicode";
+
+/// Loads the style `name`, or the default style when `None`. Unless `exact`, a file whose
+/// name only matches when case is ignored is used when no file matches exactly.
+///
+/// `Ok(None)` means no file for `name` exists. Files that exist but cannot be parsed are
+/// passed over for the next candidate; the first one's error is returned only when no
+/// candidate loads.
+#[cfg(feature = "fs")]
 pub fn load_style(
     name: Option<&str>,
     roots: &[PathBuf],
     mode: BalloonMode,
-) -> Option<BalloonStyle> {
+    exact: bool,
+) -> Result<Option<BalloonStyle>, BalloonParseError> {
     let Some(name) = name else {
-        return Some(BalloonStyle::default_for_mode(mode));
+        return Ok(Some(load_default_style(roots, mode, exact).1));
     };
 
-    for candidate in style_candidates(name, roots, mode) {
+    let own = load_first(style_candidates(name, roots, mode, exact));
+    if let Ok(Some(style)) = own {
+        return Ok(Some(style));
+    }
+    match load_first(other_mode_candidates(name, roots, mode)) {
+        Ok(Some(style)) => {
+            debug!(
+                name,
+                ?mode,
+                "balloon style only exists for the other mode; falling back"
+            );
+            Ok(Some(style))
+        }
+        other => own.and(other),
+    }
+}
+
+/// The installed `unicode` style for `mode` when present, otherwise the built-in style
+/// (named `default`).
+#[cfg(feature = "fs")]
+pub fn load_default_style(
+    roots: &[PathBuf],
+    mode: BalloonMode,
+    exact: bool,
+) -> (String, BalloonStyle) {
+    match load_first(style_candidates(DEFAULT_STYLE_NAME, roots, mode, exact)) {
+        Ok(Some(style)) => (DEFAULT_STYLE_NAME.to_string(), style),
+        _ => {
+            trace!(?mode, "no installed default balloon; using built-in style");
+            ("default".to_string(), BalloonStyle::default_for_mode(mode))
+        }
+    }
+}
+
+/// The first candidate that exists and parses. When some exist but none parse, the first
+/// failure is the error.
+#[cfg(feature = "fs")]
+fn load_first(candidates: Vec<PathBuf>) -> Result<Option<BalloonStyle>, BalloonParseError> {
+    let mut failure = None;
+    for candidate in candidates {
         if !candidate.is_file() {
             continue;
         }
@@ -305,63 +698,220 @@ pub fn load_style(
         match parse_style_file(&candidate) {
             Ok(style) => {
                 debug!(path = %candidate.display(), "loaded balloon style");
-                return Some(style);
+                return Ok(Some(style));
             }
             Err(error) => {
-                warn!(path = %candidate.display(), %error, "failed parsing balloon style");
+                warn!(%error, "failed parsing balloon style; trying the next candidate");
+                failure.get_or_insert(error);
             }
         }
     }
 
+    failure.map_or(Ok(None), Err)
+}
+
+/// Resolves the first loadable style from a pony's `BALLOON` preference list.
+///
+/// Entries carrying an explicit `.say`/`.think` suffix are only considered in the
+/// matching mode; bare names go through the regular candidate lookup.
+#[cfg(feature = "fs")]
+pub fn load_preferred_style(
+    preferences: &[&str],
+    roots: &[PathBuf],
+    mode: BalloonMode,
+    exact: bool,
+) -> Option<(String, BalloonStyle)> {
+    let (own, other) = match mode {
+        BalloonMode::Say => (".say", ".think"),
+        BalloonMode::Think => (".think", ".say"),
+    };
+
+    for preference in preferences {
+        if preference.ends_with(other) {
+            trace!(preference, "skipping balloon preference for other mode");
+            continue;
+        }
+
+        let name = preference.strip_suffix(own).unwrap_or(preference);
+        if let Ok(Some(style)) = load_first(style_candidates(name, roots, mode, exact)) {
+            debug!(preference, "using pony balloon preference");
+            return Some((name.to_string(), style));
+        }
+    }
+
     None
 }
 
-pub fn render_balloon(message: &str, width: usize, style: &BalloonStyle) -> Vec<String> {
-    let wrap_target = width.saturating_sub(style.min_width).max(1);
-    let wrapped = wrap_message(message, wrap_target)
+/// Columns left for text in a balloon of `width` columns drawn with `style`.
+pub(crate) fn text_width(style: &BalloonStyle, width: usize, compat: CompatLevel) -> usize {
+    width.saturating_sub(style.border_width(compat)).max(1)
+}
+
+/// Draws `request` as a balloon in `style`, one string per row, each starting and ending
+/// with a color reset.
+pub fn render_balloon(request: &BalloonRequest<'_>, style: &BalloonStyle) -> Vec<String> {
+    let lines = match request.wrap {
+        Some(width) => wrap_message_with(request.message, width.max(1), &request.wrap_options),
+        None => request.message.split('\n').map(str::to_string).collect(),
+    };
+    #[cfg(feature = "bidi")]
+    let lines = if request.bidi {
+        lines
+            .iter()
+            .map(|line| crate::bidi::reorder_bidi(line))
+            .collect()
+    } else {
+        lines
+    };
+
+    // Justify against the text area the balloon will actually have.
+    let area = lines
+        .iter()
+        .map(|line| visible_width(line))
+        .max()
+        .unwrap_or(0)
+        .max(
+            request
+                .min_width
+                .saturating_sub(style.border_width(request.compat)),
+        );
+    let wrapped = lines
         .into_iter()
-        .map(|line| format!("{line}\u{1b}[0m"))
+        .map(|line| {
+            let slack = area - visible_width(&line);
+            let indent = match request.justify {
+                Justify::Left => 0,
+                Justify::Center => slack / 2,
+                Justify::Right => slack,
+            };
+            format!("{}{line}{RESET}", " ".repeat(indent))
+        })
         .collect::<Vec<_>>();
-    let rendered = style.render(0, 0, &wrapped);
 
-    rendered
+    style
+        .render(
+            request.compat,
+            request.min_width,
+            request.min_height,
+            &wrapped,
+        )
         .into_iter()
-        .map(|line| format!("\u{1b}[0m{}\u{1b}[0m", line))
+        .map(|row| {
+            let mut line = String::with_capacity(row.len() + 2 * RESET.len());
+            line.push_str(RESET);
+            line.push_str(&row);
+            line.push_str(RESET);
+            line
+        })
         .collect()
 }
 
-fn style_candidates(name: &str, roots: &[PathBuf], mode: BalloonMode) -> Vec<PathBuf> {
+/// Candidate files for `-b name` in `mode`, in precedence order: an explicit path, then
+/// per root `name.<mode>`, `name`, and `name.balloon`. Unless `exact`, the same file
+/// names ignoring case follow, again per root.
+#[cfg(feature = "fs")]
+fn style_candidates(name: &str, roots: &[PathBuf], mode: BalloonMode, exact: bool) -> Vec<PathBuf> {
     let mut out = Vec::new();
     let name_path = PathBuf::from(name);
 
-    if name_path.is_absolute() || name.contains('/') {
+    if name_path.is_absolute() || name.contains(std::path::is_separator) {
         out.push(name_path.clone());
     }
 
-    let suffix = match mode {
-        BalloonMode::Say => "say",
-        BalloonMode::Think => "think",
-    };
-
+    let suffix = mode_suffix(mode);
+    let file_names = [
+        format!("{name}.{suffix}"),
+        name.to_string(),
+        format!("{name}.balloon"),
+    ];
     for root in roots {
-        out.push(root.join(name));
-        out.push(root.join(format!("{name}.{suffix}")));
-        out.push(root.join(format!("{name}.balloon")));
+        out.extend(file_names.iter().map(|file_name| root.join(file_name)));
+    }
+    if !exact {
+        for root in roots {
+            out.extend(crate::scan::case_insensitive_matches(root, &file_names));
+        }
     }
 
     out
 }
 
-fn parse_style_file(path: &Path) -> Result<BalloonStyle, std::io::Error> {
-    let raw = std::fs::read_to_string(path)?;
+/// Last-resort candidates: the same style written for the other mode, so `-b round`
+/// still renders when only `round.think` is installed.
+#[cfg(feature = "fs")]
+fn other_mode_candidates(name: &str, roots: &[PathBuf], mode: BalloonMode) -> Vec<PathBuf> {
+    let other = match mode {
+        BalloonMode::Say => mode_suffix(BalloonMode::Think),
+        BalloonMode::Think => mode_suffix(BalloonMode::Say),
+    };
+    roots
+        .iter()
+        .map(|root| root.join(format!("{name}.{other}")))
+        .collect()
+}
 
-    let keys = [
-        "\\", "/", "X", "ww", "ee", "nw", "nnw", "n", "nne", "ne", "nee", "e", "see", "se", "sse",
-        "s", "ssw", "sw", "sww", "w", "nww",
-    ];
+#[cfg(feature = "fs")]
+pub(crate) fn mode_suffix(mode: BalloonMode) -> &'static str {
+    match mode {
+        BalloonMode::Say => "say",
+        BalloonMode::Think => "think",
+    }
+}
 
+#[cfg(feature = "fs")]
+fn parse_style_file(path: &Path) -> Result<BalloonStyle, BalloonParseError> {
+    let error = |line, reason| BalloonParseError {
+        path: path.to_path_buf(),
+        line,
+        reason,
+    };
+    let raw = encoding::read_text(path).map_err(|source| error(None, source.to_string()))?;
+    parse_style_source(path, &raw)
+}
+
+/// Parses the already-decoded contents of the balloon file at `path`, rejecting lines
+/// upstream would.
+#[cfg(feature = "fs")]
+pub(crate) fn parse_style_source(
+    path: &Path,
+    raw: &str,
+) -> Result<BalloonStyle, BalloonParseError> {
+    if let Some((line, reason)) = malformed_line(raw) {
+        return Err(BalloonParseError {
+            path: path.to_path_buf(),
+            line: Some(line),
+            reason: reason.to_string(),
+        });
+    }
+    Ok(parse_style(raw))
+}
+
+/// The first line upstream's balloon parser would reject, with why. [`parse_style`] itself
+/// skips such lines, which is right for one-off styles but hides broken installed files.
+#[cfg(feature = "fs")]
+fn malformed_line(raw: &str) -> Option<(usize, &'static str)> {
+    let mut seen_key = false;
+    for (index, line) in raw.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with(':') {
+            if !seen_key {
+                return Some((index + 1, "continuation line before any key"));
+            }
+        } else if line.contains(':') {
+            seen_key = true;
+        } else {
+            return Some((index + 1, "expected `key:value`"));
+        }
+    }
+    None
+}
+
+pub(crate) fn parse_style(raw: &str) -> BalloonStyle {
     let mut map = HashMap::<String, Vec<String>>::new();
-    for key in keys {
+    for key in BalloonStyle::KEYS {
         map.insert(key.to_string(), Vec::new());
     }
 
@@ -374,10 +924,10 @@ fn parse_style_file(path: &Path) -> Result<BalloonStyle, std::io::Error> {
         }
 
         if let Some(rest) = line.strip_prefix(':') {
-            if let Some(last_key) = &last {
-                if let Some(values) = map.get_mut(last_key) {
-                    values.push(rest.to_string());
-                }
+            if let Some(last_key) = &last
+                && let Some(values) = map.get_mut(last_key)
+            {
+                values.push(rest.to_string());
             }
             continue;
         }
@@ -403,7 +953,7 @@ fn parse_style_file(path: &Path) -> Result<BalloonStyle, std::io::Error> {
         map.get(key).cloned().unwrap_or_default()
     }
 
-    Ok(BalloonStyle::new(
+    BalloonStyle::new(
         one(&map, "\\"),
         one(&map, "/"),
         one(&map, "X"),
@@ -425,11 +975,21 @@ fn parse_style_file(path: &Path) -> Result<BalloonStyle, std::io::Error> {
         one(&map, "sww"),
         one(&map, "w"),
         one(&map, "nww"),
-    ))
+    )
 }
 
-fn wrap_message(message: &str, width: usize) -> Vec<String> {
+/// Word-wraps `message` to `width` columns, preserving blank lines.
+///
+/// Escape sequences never split a word, and OSC 8 hyperlinks spanning a line break are
+/// closed and reopened so each output line stands alone.
+pub fn wrap_message(message: &str, width: usize) -> Vec<String> {
+    wrap_message_with(message, width, &WrapOptions::default())
+}
+
+/// [`wrap_message`], with over-long words kept or broken as `options` says.
+pub fn wrap_message_with(message: &str, width: usize, options: &WrapOptions) -> Vec<String> {
     let mut out = Vec::new();
+    let fits_alone = |word_width: usize| word_width <= width.saturating_add(options.exceed);
 
     for line in message.lines() {
         if line.trim().is_empty() {
@@ -437,29 +997,32 @@ fn wrap_message(message: &str, width: usize) -> Vec<String> {
             continue;
         }
 
-        let mut current = String::new();
-        for word in line.split_whitespace() {
+        let mut current = String::with_capacity(line.len().min(width * 4));
+        let mut current_width = 0;
+        for word in words(line) {
             let word_width = visible_width(word);
             if current.is_empty() {
-                if word_width <= width {
+                if fits_alone(word_width) {
                     current.push_str(word);
+                    current_width = word_width;
                 } else {
-                    out.extend(hard_wrap(word, width));
+                    out.extend(hard_wrap(word, width, &options.hyphen));
                 }
                 continue;
             }
 
-            let projected = visible_width(&current) + 1 + word_width;
-            if projected <= width {
+            if current_width + 1 + word_width <= width {
                 current.push(' ');
                 current.push_str(word);
+                current_width += 1 + word_width;
             } else {
-                out.push(current);
-                if word_width <= width {
-                    current = word.to_string();
+                out.push(std::mem::take(&mut current));
+                current_width = 0;
+                if fits_alone(word_width) {
+                    current.push_str(word);
+                    current_width = word_width;
                 } else {
-                    out.extend(hard_wrap(word, width));
-                    current = String::new();
+                    out.extend(hard_wrap(word, width, &options.hyphen));
                 }
             }
         }
@@ -473,88 +1036,81 @@ fn wrap_message(message: &str, width: usize) -> Vec<String> {
         out.push(String::new());
     }
 
+    ansi::balance_hyperlinks(&mut out);
     out
 }
 
-fn hard_wrap(word: &str, width: usize) -> Vec<String> {
-    let mut out = Vec::new();
-    let mut current = String::new();
-    let mut current_width = 0;
-
-    for ch in word.chars() {
-        let w = UnicodeWidthChar::width(ch).unwrap_or(0);
-        if current_width + w > width && !current.is_empty() {
-            out.push(current);
-            current = String::new();
-            current_width = 0;
+/// Whitespace-separated words of `line`; whitespace inside escape sequences does not split.
+fn words(line: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut start = None;
+    let mut offset = 0;
+
+    for token in ansi::tokens(line) {
+        match token {
+            Token::Sequence(sequence) => {
+                start.get_or_insert(offset);
+                offset += sequence.raw.len();
+            }
+            Token::Text(text) => {
+                for (index, ch) in text.char_indices() {
+                    let at = offset + index;
+                    if ch.is_whitespace() {
+                        if let Some(begin) = start.take() {
+                            words.push(&line[begin..at]);
+                        }
+                    } else {
+                        start.get_or_insert(at);
+                    }
+                }
+                offset += text.len();
+            }
         }
-        current.push(ch);
-        current_width += w;
     }
 
-    if !current.is_empty() {
-        out.push(current);
+    if let Some(begin) = start {
+        words.push(&line[begin..]);
     }
-
-    out
+    words
 }
 
-fn visible_width(input: &str) -> usize {
-    let mut width = 0;
-    let chars = input.chars().collect::<Vec<_>>();
-    let mut i = 0;
-
-    while i < chars.len() {
-        let ch = chars[i];
-        if ch == '\u{1b}' {
-            i += consume_escape(&chars[i..]);
-            continue;
-        }
-
-        i += 1;
-        width += UnicodeWidthChar::width(ch).unwrap_or(0);
-    }
-
-    width
-}
-
-fn consume_escape(chars: &[char]) -> usize {
-    if chars.is_empty() || chars[0] != '\u{1b}' {
-        return 0;
-    }
-    if chars.len() == 1 {
-        return 1;
-    }
+/// Splits an over-long word into `width`-column pieces, keeping escape sequences intact.
+/// Every piece but the last ends with `hyphen`, which counts toward its width.
+fn hard_wrap(word: &str, width: usize, hyphen: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut current = String::new();
+    let mut current_width = 0;
+    let piece_width = width.saturating_sub(visible_width(hyphen)).max(1);
 
-    let mut i = 1;
-    let c = chars[i];
-    i += 1;
+    for token in ansi::tokens(word) {
+        let text = match token {
+            Token::Sequence(sequence) => {
+                current.push_str(sequence.raw);
+                continue;
+            }
+            Token::Text(text) => text,
+        };
 
-    if c == ']' {
-        while i < chars.len() {
-            let ch = chars[i];
-            i += 1;
-            if ch == '\\' || ch == '\u{7}' {
-                break;
+        for ch in text.chars() {
+            let w = UnicodeWidthChar::width(ch).unwrap_or(0);
+            if current_width + w > piece_width && current_width > 0 {
+                current.push_str(hyphen);
+                out.push(std::mem::take(&mut current));
+                current_width = 0;
             }
+            current.push(ch);
+            current_width += w;
         }
-        return i;
     }
 
-    if c == '[' {
-        while i < chars.len() {
-            let ch = chars[i];
-            i += 1;
-            if ch == '~' || ch.is_ascii_alphabetic() {
-                break;
-            }
-        }
-        return i;
+    if !current.is_empty() {
+        out.push(current);
     }
 
-    i
+    out
 }
 
+#[cfg(feature = "fs")]
 pub fn list_balloon_names(root: &Path) -> Vec<String> {
     let mut names = Vec::new();
 
@@ -587,12 +1143,237 @@ pub fn list_balloon_names(root: &Path) -> Vec<String> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn previews_draw_the_sample_and_the_link() {
+        let style = BalloonStyle::from_chars("╭─╮│╯─╰│", BalloonMode::Think).expect("style");
+        let rows = style
+            .preview("Hi!", CompatLevel::Upstream)
+            .iter()
+            .map(|row| ansi::strip_ansi(row))
+            .collect::<Vec<_>>();
+        assert_eq!(rows, ["╭─────╮", "│ Hi! │", "╰─────╯", "  o"]);
+    }
+
     #[test]
     fn wrap_splits_words() {
         let lines = wrap_message("a bb ccc dddd", 6);
         assert_eq!(lines, vec!["a bb", "ccc", "dddd"]);
     }
 
+    #[test]
+    fn wrap_hard_splits_long_words_and_keeps_blank_lines() {
+        let lines = wrap_message("ab abcdefgh c\n\nz", 4);
+        assert_eq!(lines, vec!["ab", "abcd", "efgh", "c", "", "z"]);
+    }
+
+    #[test]
+    fn wrap_options_keep_slightly_long_words_and_hyphenate_breaks() {
+        let options = WrapOptions {
+            exceed: 2,
+            hyphen: "-".to_string(),
+        };
+        assert_eq!(
+            wrap_message_with("ab abcdef abcdefghij", 4, &options),
+            vec!["ab", "abcdef", "abc-", "def-", "ghi-", "j"]
+        );
+    }
+
+    #[test]
+    fn visible_width_skips_escapes() {
+        assert_eq!(visible_width("\u{1b}[38;5;196mred\u{1b}[0m"), 3);
+        assert_eq!(
+            visible_width("\u{1b}]8;;http://x\u{7}link\u{1b}]8;;\u{7}"),
+            4
+        );
+        assert_eq!(visible_width("日本\u{1b}"), 4);
+    }
+
+    #[test]
+    fn wrap_keeps_sequences_whole_and_hyperlinks_per_line() {
+        let open = "\u{1b}]8;;https://x.example/a b\u{1b}\\";
+        let close = ansi::HYPERLINK_CLOSE;
+        let message = format!("go {open}read the docs{close} now");
+        assert_eq!(
+            wrap_message(&message, 9),
+            vec![
+                format!("go {open}read{close}"),
+                format!("{open}the docs{close}"),
+                "now".to_string(),
+            ]
+        );
+
+        let pieces = hard_wrap("\u{1b}[31mabcdef\u{1b}[0m", 4, "");
+        assert_eq!(pieces, vec!["\u{1b}[31mabcd", "ef\u{1b}[0m"]);
+    }
+
+    fn row_widths(style: &BalloonStyle, lines: &[&str]) -> Vec<usize> {
+        let lines = lines
+            .iter()
+            .map(|line| line.to_string())
+            .collect::<Vec<_>>();
+        style
+            .render(CompatLevel::Upstream, 0, 0, &lines)
+            .iter()
+            .map(|row| visible_width(row))
+            .collect()
+    }
+
+    #[test]
+    fn body_rows_pad_against_their_own_side_pieces() {
+        let style = parse_style(
+            "nw:.\nn:-\nne:.\nww:<<\nee:>>\nnww:/ \nnee: \\\nw:|\ne:|\nsww:\\ \nsee: /\n\
+             sw:'\ns:-\nse:'\n",
+        );
+        assert_eq!(style.nww, "/ ");
+        assert_eq!(style.w, "|");
+
+        for lines in [&["hi"][..], &["one", "two", "three"][..]] {
+            let widths = row_widths(&style, lines);
+            assert!(
+                widths.iter().all(|width| *width == widths[0]),
+                "ragged rows for {lines:?}: {widths:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn corrected_geometry_sizes_borders_from_their_pieces() {
+        let style = parse_style(
+            "nw:+\n:|\nn:-\nne:+\nww:[[ \nee: ]\nnww:[[ \nnee: ]\nw:[[ \ne: ]\n\
+             sww:[[ \nsee: ]\nsw:+\ns:-\nse:+\n",
+        );
+        let hi = ["hi".to_string()];
+        assert_eq!(style.border_width(CompatLevel::Upstream), 4);
+        assert_eq!(
+            style.render(CompatLevel::Upstream, 0, 0, &hi),
+            ["+----+", "[[ hi ]", "+----+"]
+        );
+
+        assert_eq!(style.border_width(CompatLevel::Corrected), 5);
+        assert_eq!(
+            style.render(CompatLevel::Corrected, 0, 0, &hi),
+            ["+-----+", "|     ]", "[[ hi ]", "+-----+"]
+        );
+        let request = BalloonRequest {
+            compat: CompatLevel::Corrected,
+            ..BalloonRequest::new("hi")
+        };
+        assert_eq!(render_balloon(&request, &style).len(), 4);
+    }
+
+    #[test]
+    fn default_styles_render_rectangular_balloons() {
+        for mode in [BalloonMode::Say, BalloonMode::Think] {
+            let widths = row_widths(&BalloonStyle::default_for_mode(mode), &["a", "bb", "ccc"]);
+            assert!(widths.iter().all(|width| *width == widths[0]), "{widths:?}");
+        }
+    }
+
+    #[test]
+    fn honors_minimum_width_and_height() {
+        let style = BalloonStyle::default_for_mode(BalloonMode::Say);
+        let rows = style.render(CompatLevel::Upstream, 12, 5, &["hi".to_string()]);
+        assert_eq!(rows.len(), 5);
+        assert!(
+            rows.iter().all(|row| visible_width(row) == 12),
+            "{:?}",
+            rows
+        );
+        assert!(rows[1].starts_with("/ hi"));
+        assert!(rows[2].starts_with("| "));
+        assert!(rows[3].starts_with("\\ "));
+
+        let rows = style.render(CompatLevel::Upstream, 0, 0, &["hi".to_string()]);
+        assert_eq!(rows.len(), 3);
+        assert!(rows[1].starts_with("< hi"));
+    }
+
+    #[test]
+    fn requests_wrap_and_justify_lines() {
+        let style = BalloonStyle::default_for_mode(BalloonMode::Say);
+        let rows = |request: &BalloonRequest<'_>| {
+            render_balloon(request, &style)
+                .iter()
+                .map(|row| ansi::strip_ansi(row))
+                .collect::<Vec<_>>()
+        };
+        let request = BalloonRequest {
+            wrap: Some(5),
+            justify: Justify::Right,
+            ..BalloonRequest::new("a bb ccc")
+        };
+        assert_eq!(rows(&request)[1..3], ["/ a bb \\", "\\  ccc /"]);
+
+        let centered = BalloonRequest {
+            justify: Justify::Center,
+            min_width: 10,
+            ..BalloonRequest::new("hi")
+        };
+        assert_eq!(rows(&centered)[1], "<   hi   >");
+    }
+
+    #[test]
+    fn balloon_files_round_trip() {
+        for mode in [BalloonMode::Say, BalloonMode::Think] {
+            let style = BalloonStyle::default_for_mode(mode);
+            assert_eq!(BalloonStyle::parse(&style.to_balloon_file()), style);
+        }
+
+        let raw = "\\:\\\nww:<\nee:>\nn:\u{1b}[1m-\n:\n:=\nsee:\n";
+        let style = BalloonStyle::parse(raw);
+        assert_eq!(style.n, vec!["\u{1b}[1m-", "", "="]);
+        let written = style.to_balloon_file();
+        assert_eq!(written, "\\:\\\nww:<\nee:>\nn:\u{1b}[1m-\n:\n:=\n");
+        assert_eq!(BalloonStyle::parse(&written), style);
+    }
+
+    #[test]
+    fn glyph_specs_build_rectangular_styles() {
+        let style = BalloonStyle::from_chars("╭─╮│╯─╰│", BalloonMode::Say).expect("style");
+        assert_eq!(style.link, "\\");
+        let rows = style.render(
+            CompatLevel::Upstream,
+            0,
+            0,
+            &["hi".to_string(), "there".to_string()],
+        );
+        assert_eq!(
+            rows,
+            vec!["╭───────╮", "│ hi    │", "│ there │", "╰───────╯"]
+        );
+
+        let style = BalloonStyle::from_chars("........*", BalloonMode::Think).expect("link");
+        assert_eq!((style.link.as_str(), style.link_cross.as_str()), ("*", "*"));
+        assert!(BalloonStyle::from_chars("+-+", BalloonMode::Say).is_err());
+        assert!(BalloonStyle::from_chars("+-+|+-+|\u{1b}", BalloonMode::Say).is_err());
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_descriptions_build_styles() {
+        let style = BalloonStyle::from_toml(
+            r#"
+link = "\\"
+ww = "< "
+ee = " >"
+n = ["_", "="]
+"#,
+        )
+        .expect("style");
+        assert_eq!(style.link, "\\");
+        assert_eq!(style.n, vec!["_", "="]);
+        assert_eq!(style.to_balloon_file(), "\\:\\\nww:< \nee: >\nn:_\n:=\n");
+
+        let error = BalloonStyle::from_toml("top = \"-\"").expect_err("unknown key");
+        assert!(
+            error.to_string().contains("unknown balloon key `top`"),
+            "{error}"
+        );
+        assert!(BalloonStyle::from_toml("w = \"|\\n|\"").is_err());
+        assert!(BalloonStyle::from_toml("n = [1]").is_err());
+    }
+
+    #[cfg(feature = "fs")]
     #[test]
     fn parse_ascii_style() {
         let raw = "\\:\\\n/:/\nX:X\n\nn:_\n: \n";
@@ -602,4 +1383,134 @@ mod tests {
         assert_eq!(style.link, "\\");
         assert_eq!(style.link_mirror, "/");
     }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn balloon_lookup_falls_back_to_other_mode() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        std::fs::write(tmp.path().join("round.think"), "ww:(\nee:)\n").expect("write");
+        std::fs::write(tmp.path().join("both.say"), "ww:<\nee:>\n").expect("write");
+        std::fs::write(tmp.path().join("both.think"), "ww:(\nee:)\n").expect("write");
+        let roots = vec![tmp.path().to_path_buf()];
+
+        let style = load_style(Some("round"), &roots, BalloonMode::Say, true)
+            .expect("parses")
+            .expect("fallback");
+        assert_eq!(style.ww, "(");
+        let style = load_style(Some("both"), &roots, BalloonMode::Say, true)
+            .expect("parses")
+            .expect("own mode");
+        assert_eq!(style.ww, "<");
+        assert!(
+            load_style(Some("missing"), &roots, BalloonMode::Think, true)
+                .expect("parses")
+                .is_none()
+        );
+        assert!(
+            load_preferred_style(&["round"], &roots, BalloonMode::Say, true).is_none(),
+            "pony preferences never cross modes"
+        );
+
+        let style = load_style(Some("BOTH"), &roots, BalloonMode::Think, false)
+            .expect("parses")
+            .expect("any case");
+        assert_eq!(style.ww, "(");
+        assert!(
+            load_style(Some("BOTH"), &roots, BalloonMode::Think, true)
+                .expect("parses")
+                .is_none()
+        );
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn default_style_prefers_installed_unicode() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let roots = vec![tmp.path().to_path_buf()];
+        let (name, style) = load_default_style(&roots, BalloonMode::Think, true);
+        assert_eq!(name, "default");
+        assert_eq!(style.ww, "( ");
+
+        std::fs::write(
+            tmp.path().join("unicode.think"),
+            "ww:\u{2768}\nee:\u{2769}\n",
+        )
+        .expect("write");
+        let (name, style) = load_default_style(&roots, BalloonMode::Think, true);
+        assert_eq!(name, "unicode");
+        assert_eq!(style.ww, "\u{2768}");
+        assert_eq!(
+            load_style(None, &roots, BalloonMode::Say, true)
+                .expect("parses")
+                .expect("say")
+                .ww,
+            "< "
+        );
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn broken_balloon_files_are_skipped_and_reported() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let (first, second) = (tmp.path().join("first"), tmp.path().join("second"));
+        std::fs::create_dir_all(&first).expect("mkdir");
+        std::fs::create_dir_all(&second).expect("mkdir");
+        std::fs::write(first.join("stars.say"), "ww:*\nee *\n").expect("write");
+        std::fs::write(first.join("lone.say"), ":dangling\n").expect("write");
+        std::fs::write(second.join("stars.say"), "ww:*\nee:*\n").expect("write");
+
+        let both = vec![first.clone(), second];
+        let style = load_style(Some("stars"), &both, BalloonMode::Say, true)
+            .expect("second root parses")
+            .expect("found");
+        assert_eq!(style.ee, "*");
+
+        let error = load_style(Some("stars"), &both[..1], BalloonMode::Say, true)
+            .expect_err("only the broken file");
+        assert_eq!(error.path, first.join("stars.say"));
+        assert_eq!(error.line, Some(2));
+        assert!(
+            error
+                .to_string()
+                .ends_with("stars.say:2: expected `key:value`")
+        );
+        let error = load_style(Some("lone"), &both, BalloonMode::Think, true).expect_err("broken");
+        assert_eq!(error.reason, "continuation line before any key");
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn wrapped_lines_fit_unless_a_single_glyph_is_wider(
+            message in crate::ansi::tests::styled_text(),
+            width in 1usize..40,
+        ) {
+            for line in wrap_message(&message, width) {
+                let glyphs = crate::strip_ansi(&line).chars().count();
+                proptest::prop_assert!(
+                    visible_width(&line) <= width || glyphs == 1,
+                    "{:?} is wider than {}",
+                    line,
+                    width
+                );
+            }
+        }
+
+        #[test]
+        fn hard_wrap_round_trips(word in "[a-z\u{e9}日本]{1,30}", width in 1usize..12) {
+            proptest::prop_assert_eq!(hard_wrap(&word, width, "").concat(), word);
+        }
+
+        #[test]
+        fn balloons_are_rectangular(
+            message in crate::ansi::tests::styled_text(),
+            width in 1usize..30,
+            think in proptest::bool::ANY,
+        ) {
+            let mode = if think { BalloonMode::Think } else { BalloonMode::Say };
+            let lines = wrap_message(&message, width);
+            let rows = BalloonStyle::default_for_mode(mode).render(CompatLevel::Upstream, 0, 0, &lines);
+            let first = visible_width(&rows[0]);
+            proptest::prop_assert!(rows.iter().all(|row| visible_width(row) == first), "{:?}", rows);
+        }
+    }
 }
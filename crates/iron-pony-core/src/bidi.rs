@@ -0,0 +1,33 @@
+//! Visual reordering of right-to-left text, for terminals that print characters in
+//! logical order.
+
+use unicode_bidi::ParagraphBidiInfo;
+
+/// Reorders one wrapped balloon line from logical to visual order with the Unicode
+/// Bidirectional Algorithm, taking the direction from its first strong character.
+///
+/// Lines without right-to-left text come back unchanged, as do lines carrying escape
+/// sequences: reordering would scatter their codes across the text.
+pub fn reorder_bidi(line: &str) -> String {
+    if line.contains('\u{1b}') {
+        return line.to_string();
+    }
+    let info = ParagraphBidiInfo::new(line, None);
+    if info.is_pure_ltr {
+        return line.to_string();
+    }
+    info.reorder_line(0..line.len()).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reorders_only_plain_rtl_lines() {
+        assert_eq!(reorder_bidi("שלום"), "םולש");
+        assert_eq!(reorder_bidi("hi שלום 42"), "hi 42 םולש");
+        assert_eq!(reorder_bidi("plain text"), "plain text");
+        assert_eq!(reorder_bidi("\u{1b}[31mשלום"), "\u{1b}[31mשלום");
+    }
+}
@@ -0,0 +1,221 @@
+use std::borrow::Cow;
+
+use tracing::trace;
+
+use crate::ansi::{self, Token};
+
+/// Default Linux console (VGA) palette, indexed by console color number.
+pub const CONSOLE_PALETTE: [(u8, u8, u8); 16] = [
+    (0x00, 0x00, 0x00),
+    (0xaa, 0x00, 0x00),
+    (0x00, 0xaa, 0x00),
+    (0xaa, 0x55, 0x00),
+    (0x00, 0x00, 0xaa),
+    (0xaa, 0x00, 0xaa),
+    (0x00, 0xaa, 0xaa),
+    (0xaa, 0xaa, 0xaa),
+    (0x55, 0x55, 0x55),
+    (0xff, 0x55, 0x55),
+    (0x55, 0xff, 0x55),
+    (0xff, 0xff, 0x55),
+    (0x55, 0x55, 0xff),
+    (0xff, 0x55, 0xff),
+    (0x55, 0xff, 0xff),
+    (0xff, 0xff, 0xff),
+];
+
+/// Nearest console palette index for an RGB color (squared euclidean distance).
+pub fn nearest_console_color(rgb: (u8, u8, u8)) -> u8 {
+    let distance = |(r, g, b): (u8, u8, u8)| {
+        let dr = i32::from(r) - i32::from(rgb.0);
+        let dg = i32::from(g) - i32::from(rgb.1);
+        let db = i32::from(b) - i32::from(rgb.2);
+        dr * dr + dg * dg + db * db
+    };
+
+    CONSOLE_PALETTE
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, color)| distance(**color))
+        .map(|(index, _)| index as u8)
+        .unwrap_or(7)
+}
+
+/// RGB value of an xterm 256-color palette index.
+pub fn xterm_to_rgb(index: u8) -> (u8, u8, u8) {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    match index {
+        0..=15 => CONSOLE_PALETTE[usize::from(index)],
+        16..=231 => {
+            let cube = index - 16;
+            (
+                LEVELS[usize::from(cube / 36)],
+                LEVELS[usize::from((cube / 6) % 6)],
+                LEVELS[usize::from(cube % 6)],
+            )
+        }
+        _ => {
+            let gray = 8 + (index - 232) * 10;
+            (gray, gray, gray)
+        }
+    }
+}
+
+/// How much color rendered output may use.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorMode {
+    /// Escapes pass through unchanged.
+    #[default]
+    Full,
+    /// Extended colors are quantized to the 16-color Linux console palette.
+    Console,
+    /// Every escape sequence is stripped.
+    Off,
+}
+
+impl ColorMode {
+    /// Picks a mode from `TERM` and `COLORTERM`.
+    ///
+    /// A missing `TERM` (login scripts, `update-motd`) and anything not known to handle
+    /// 256 colors get [`ColorMode::Console`], which every color terminal can show; only
+    /// `TERM=dumb` loses color entirely.
+    pub fn detect(term: Option<&str>, colorterm: Option<&str>) -> Self {
+        let term = term.unwrap_or_default();
+        if term == "dumb" {
+            Self::Off
+        } else if term == "linux" || term.starts_with("vt") || term.is_empty() {
+            Self::Console
+        } else if matches!(colorterm, Some("truecolor" | "24bit")) || term.contains("256color") {
+            Self::Full
+        } else {
+            Self::Console
+        }
+    }
+
+    /// Rewrites `text` to fit this mode.
+    pub fn apply(self, text: &str) -> Cow<'_, str> {
+        match self {
+            Self::Full => Cow::Borrowed(text),
+            Self::Console => Cow::Owned(quantize_to_console(text)),
+            Self::Off => Cow::Owned(ansi::strip_ansi(text)),
+        }
+    }
+}
+
+/// Rewrites 256-color and truecolor SGR parameters into the 16-color console palette.
+///
+/// Non-SGR escapes and plain text pass through untouched, so pony templates keep
+/// their `$balloon$` anchors and metadata.
+pub fn quantize_to_console(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    for token in ansi::tokens(input) {
+        match token {
+            Token::Text(text) => out.push_str(text),
+            Token::Sequence(sequence) => match sequence.sgr_params() {
+                Some(params) => {
+                    out.push_str("\u{1b}[");
+                    out.push_str(&quantize_sgr(params));
+                    out.push('m');
+                }
+                None => out.push_str(sequence.raw),
+            },
+        }
+    }
+    out
+}
+
+fn quantize_sgr(params: &str) -> String {
+    let values = params.split(';').collect::<Vec<_>>();
+    let mut out = Vec::with_capacity(values.len());
+    let mut index = 0;
+
+    while index < values.len() {
+        let value = values[index];
+        let background = match value {
+            "38" => false,
+            "48" => true,
+            _ => {
+                out.push(value.to_string());
+                index += 1;
+                continue;
+            }
+        };
+
+        let parsed = |offset: usize| {
+            values
+                .get(index + offset)
+                .and_then(|value| value.parse::<u8>().ok())
+        };
+        let (rgb, consumed) = match values.get(index + 1).copied() {
+            Some("5") => (parsed(2).map(xterm_to_rgb), 3),
+            Some("2") => match (parsed(2), parsed(3), parsed(4)) {
+                (Some(r), Some(g), Some(b)) => (Some((r, g, b)), 5),
+                _ => (None, 5),
+            },
+            _ => (None, 1),
+        };
+
+        if let Some(rgb) = rgb {
+            out.push(console_sgr(nearest_console_color(rgb), background));
+        } else {
+            trace!(params, "dropping malformed extended color parameter");
+        }
+        index += consumed;
+    }
+
+    out.join(";")
+}
+
+fn console_sgr(color: u8, background: bool) -> String {
+    let base = color % 8;
+    match (background, color >= 8) {
+        // The console has no bright backgrounds; fold them onto the normal range.
+        (true, _) => format!("{}", 40 + base),
+        (false, false) => format!("{}", 30 + base),
+        (false, true) => format!("{}", 90 + base),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_mode_detection_and_application() {
+        assert_eq!(ColorMode::detect(None, None), ColorMode::Console);
+        assert_eq!(ColorMode::detect(Some("linux"), None), ColorMode::Console);
+        assert_eq!(
+            ColorMode::detect(Some("xterm"), Some("truecolor")),
+            ColorMode::Full
+        );
+        assert_eq!(
+            ColorMode::detect(Some("screen-256color"), None),
+            ColorMode::Full
+        );
+        assert_eq!(ColorMode::detect(Some("xterm"), None), ColorMode::Console);
+        assert_eq!(
+            ColorMode::detect(Some("dumb"), Some("truecolor")),
+            ColorMode::Off
+        );
+
+        let text = "\u{1b}[38;5;196mred\u{1b}[0m";
+        assert_eq!(ColorMode::Full.apply(text), text);
+        assert_eq!(ColorMode::Console.apply(text), "\u{1b}[31mred\u{1b}[0m");
+        assert_eq!(ColorMode::Off.apply(text), "red");
+    }
+
+    #[test]
+    fn quantizes_extended_colors() {
+        let input = "\u{1b}[38;5;196mred\u{1b}[48;2;0;0;170;1mblue\u{1b}[0m\u{1b}[2Kx";
+        assert_eq!(
+            quantize_to_console(input),
+            "\u{1b}[31mred\u{1b}[44;1mblue\u{1b}[0m\u{1b}[2Kx"
+        );
+    }
+
+    #[test]
+    fn maps_xterm_grayscale() {
+        assert_eq!(xterm_to_rgb(232), (8, 8, 8));
+        assert_eq!(nearest_console_color(xterm_to_rgb(255)), 15);
+    }
+}
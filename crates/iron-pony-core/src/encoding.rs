@@ -0,0 +1,131 @@
+use std::borrow::Cow;
+use std::io;
+use std::path::Path;
+
+use tracing::debug;
+
+/// Windows-1252 characters for bytes `0x80..=0x9f`; `None` where the code page has a hole.
+const CP1252_HIGH: [Option<char>; 32] = [
+    Some('\u{20ac}'),
+    None,
+    Some('\u{201a}'),
+    Some('\u{192}'),
+    Some('\u{201e}'),
+    Some('\u{2026}'),
+    Some('\u{2020}'),
+    Some('\u{2021}'),
+    Some('\u{2c6}'),
+    Some('\u{2030}'),
+    Some('\u{160}'),
+    Some('\u{2039}'),
+    Some('\u{152}'),
+    None,
+    Some('\u{17d}'),
+    None,
+    None,
+    Some('\u{2018}'),
+    Some('\u{2019}'),
+    Some('\u{201c}'),
+    Some('\u{201d}'),
+    Some('\u{2022}'),
+    Some('\u{2013}'),
+    Some('\u{2014}'),
+    Some('\u{2dc}'),
+    Some('\u{2122}'),
+    Some('\u{161}'),
+    Some('\u{203a}'),
+    Some('\u{153}'),
+    None,
+    Some('\u{17e}'),
+    Some('\u{178}'),
+];
+
+/// Decodes pony or balloon source bytes.
+///
+/// UTF-8 is used when valid. Anything else is treated as Windows-1252, the superset of
+/// ISO-8859-1 legacy files were saved in: bytes `0xa0` and up map to the code point of the
+/// same value, and `0x80..=0x9f` to the code page's punctuation rather than C1 controls,
+/// which terminals would act on. Input containing NUL bytes, a byte Windows-1252 leaves
+/// undefined, or a UTF-16 byte order mark is binary or in an unsupported encoding and is
+/// rejected.
+pub(crate) fn decode(raw: &[u8]) -> io::Result<Cow<'_, str>> {
+    if let Ok(text) = std::str::from_utf8(raw) {
+        return Ok(Cow::Borrowed(text));
+    }
+
+    if raw.starts_with(&[0xff, 0xfe]) || raw.starts_with(&[0xfe, 0xff]) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "UTF-16 input is not supported",
+        ));
+    }
+    if let Some(offset) = raw.iter().position(|&byte| byte == 0) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("undecodable input: NUL byte at offset {offset}"),
+        ));
+    }
+
+    debug!(
+        bytes = raw.len(),
+        "input is not UTF-8; decoding as windows-1252"
+    );
+    raw.iter()
+        .enumerate()
+        .map(|(offset, &byte)| match byte {
+            0x80..=0x9f => CP1252_HIGH[usize::from(byte - 0x80)].ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("undecodable input: byte {byte:#04x} at offset {offset}"),
+                )
+            }),
+            _ => Ok(char::from(byte)),
+        })
+        .collect::<io::Result<String>>()
+        .map(Cow::Owned)
+}
+
+/// Reads `path` as bytes and decodes it with [`decode`].
+pub(crate) fn read_text(path: &Path) -> io::Result<String> {
+    let raw = std::fs::read(path)?;
+    decode(&raw).map(Cow::into_owned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ansi::visible_width;
+
+    #[test]
+    fn utf8_is_borrowed() {
+        let decoded = decode("caf\u{e9} 日本".as_bytes()).expect("decode");
+        assert!(matches!(decoded, Cow::Borrowed("caf\u{e9} 日本")));
+    }
+
+    #[test]
+    fn latin1_is_transcoded_with_correct_widths() {
+        let decoded = decode(b"caf\xe9 \xab\x1b[31mX\x1b[0m\xbb").expect("decode");
+        assert_eq!(decoded, "caf\u{e9} \u{ab}\u{1b}[31mX\u{1b}[0m\u{bb}");
+        assert_eq!(visible_width(&decoded), 8);
+    }
+
+    #[test]
+    fn c1_bytes_decode_as_windows_1252() {
+        let decoded = decode(b"\x93caf\xe9\x94 \x9b31m").expect("decode");
+        assert_eq!(decoded, "\u{201c}caf\u{e9}\u{201d} \u{203a}31m");
+        assert_eq!(visible_width(&decoded), 11);
+        assert_eq!(
+            decode(b"caf\xe9\x81").expect_err("hole").to_string(),
+            "undecodable input: byte 0x81 at offset 4"
+        );
+    }
+
+    #[test]
+    fn rejects_binary_and_utf16() {
+        assert_eq!(
+            decode(b"\xffpony\x00").expect_err("nul").kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert!(decode(b"\xff\xfep\x00").is_err());
+    }
+}
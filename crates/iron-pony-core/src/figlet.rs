@@ -0,0 +1,523 @@
+use std::collections::HashMap;
+#[cfg(feature = "fs")]
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "fs")]
+use tracing::debug;
+use tracing::trace;
+
+use crate::PonyError;
+use crate::ansi::strip_ansi;
+#[cfg(feature = "fs")]
+use crate::encoding;
+
+/// Name of the font compiled into the binary.
+pub const BUILTIN_FIGFONT: &str = "compact";
+
+const BUILTIN_SOURCE: &str = include_str!("../fonts/compact.flf");
+
+/// Code points every FIGfont defines after the printable ASCII range.
+const DEUTSCH: [u32; 7] = [196, 214, 220, 228, 246, 252, 223];
+
+/// Horizontal smushing rule bits (FIGfont 2 spec, "Layout").
+const SMUSH_EQUAL: u32 = 1;
+const SMUSH_LOWLINE: u32 = 2;
+const SMUSH_HIERARCHY: u32 = 4;
+const SMUSH_PAIR: u32 = 8;
+const SMUSH_BIGX: u32 = 16;
+const SMUSH_HARDBLANK: u32 = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layout {
+    FullWidth,
+    Kerning,
+    /// Controlled smushing with the given rule bits; `0` means universal smushing.
+    Smushing(u32),
+}
+
+/// A parsed FIGlet font (`.flf`), rendering text as multi-row banners.
+///
+/// Only left-to-right horizontal layout is implemented; vertical layout and
+/// right-to-left print direction are ignored.
+#[derive(Debug, Clone)]
+pub struct FigFont {
+    hardblank: char,
+    height: usize,
+    layout: Layout,
+    glyphs: HashMap<char, Vec<Vec<char>>>,
+}
+
+impl FigFont {
+    /// The embedded [`BUILTIN_FIGFONT`], a small 3-row font.
+    pub fn builtin() -> Self {
+        Self::parse(BUILTIN_SOURCE).expect("embedded FIGfont parses")
+    }
+
+    /// Parses FIGfont source. Missing characters are tolerated and skipped at render time.
+    pub fn parse(source: &str) -> Result<Self, PonyError> {
+        let invalid = |message: &str| PonyError::InvalidFigFont(message.to_string());
+        let mut lines = source.lines().map(|line| line.trim_end_matches('\r'));
+        let header = lines.next().ok_or_else(|| invalid("empty font"))?;
+        let signature = header
+            .strip_prefix("flf2a")
+            .ok_or_else(|| invalid("missing flf2a signature"))?;
+        let mut fields = signature.chars();
+        let hardblank = fields.next().ok_or_else(|| invalid("missing hardblank"))?;
+        let numbers = fields
+            .as_str()
+            .split_whitespace()
+            .map(|field| {
+                field
+                    .parse::<i64>()
+                    .map_err(|_| invalid("malformed header"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let [
+            height,
+            _baseline,
+            _max_length,
+            old_layout,
+            comment_lines,
+            rest @ ..,
+        ] = numbers.as_slice()
+        else {
+            return Err(invalid("header needs at least five numbers"));
+        };
+        let height = usize::try_from(*height)
+            .ok()
+            .filter(|height| *height > 0)
+            .ok_or_else(|| invalid("height must be positive"))?;
+        let layout = layout(*old_layout, rest.get(1).copied());
+
+        let mut lines = lines.skip(usize::try_from(*comment_lines).unwrap_or(0));
+        let mut glyphs = HashMap::new();
+        let read_glyph = |lines: &mut dyn Iterator<Item = &str>| -> Option<Vec<Vec<char>>> {
+            let rows = (0..height)
+                .map(|_| lines.next().map(strip_endmark))
+                .collect::<Option<Vec<_>>>()?;
+            let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+            Some(
+                rows.into_iter()
+                    .map(|mut row| {
+                        row.resize(width, ' ');
+                        row
+                    })
+                    .collect(),
+            )
+        };
+
+        let required = (32..=126).chain(DEUTSCH);
+        for code in required {
+            let Some(glyph) = read_glyph(&mut lines) else {
+                break;
+            };
+            if let Some(ch) = char::from_u32(code) {
+                glyphs.insert(ch, glyph);
+            }
+        }
+        while let Some(tag) = lines.next() {
+            let code = tag.split_whitespace().next().and_then(parse_code);
+            let Some(glyph) = read_glyph(&mut lines) else {
+                break;
+            };
+            if let Some(ch) = code.and_then(char::from_u32) {
+                glyphs.insert(ch, glyph);
+            }
+        }
+        if !glyphs.contains_key(&' ') {
+            return Err(invalid("font defines no characters"));
+        }
+
+        trace!(height, ?layout, glyphs = glyphs.len(), "parsed FIGfont");
+        Ok(Self {
+            hardblank,
+            height,
+            layout,
+            glyphs,
+        })
+    }
+
+    /// Rows per line of banner text.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Renders `text` as banner rows no wider than `width` columns where possible.
+    ///
+    /// Words wrap like a balloon message; a word wider than `width` breaks between
+    /// characters. Input lines stay separate banner lines, and an empty input line becomes
+    /// a single blank row. Escape sequences are dropped and characters the font lacks are
+    /// skipped.
+    pub fn render(&self, text: &str, width: usize) -> Vec<String> {
+        let text = strip_ansi(text);
+        let mut out = Vec::new();
+        for line in text.lines() {
+            if line.trim().is_empty() {
+                out.push(String::new());
+                continue;
+            }
+            let mut banner = Banner::new(self);
+            for word in line.split_whitespace() {
+                if !banner.is_empty() {
+                    let mut attempt = banner.clone();
+                    attempt.push(' ');
+                    attempt.push_str(word);
+                    if attempt.width() <= width {
+                        banner = attempt;
+                        continue;
+                    }
+                    out.extend(banner.rows());
+                    banner = Banner::new(self);
+                }
+                // The word starts a line; break it between characters if it is too wide.
+                for ch in word.chars() {
+                    let mut attempt = banner.clone();
+                    attempt.push(ch);
+                    if attempt.width() > width && !banner.is_empty() {
+                        out.extend(banner.rows());
+                        banner = Banner::new(self);
+                        banner.push(ch);
+                    } else {
+                        banner = attempt;
+                    }
+                }
+            }
+            out.extend(banner.rows());
+        }
+        out
+    }
+
+    /// Number of columns to overlap when appending `glyph` after `rows` (figlet's
+    /// `smushamt`).
+    fn overlap(&self, rows: &[Vec<char>], glyph: &[Vec<char>], previous_width: usize) -> usize {
+        if self.layout == Layout::FullWidth {
+            return 0;
+        }
+        let glyph_width = glyph.first().map_or(0, Vec::len);
+        let line_width = rows.first().map_or(0, Vec::len);
+        let mut amount = glyph_width;
+        for (row, glyph_row) in rows.iter().zip(glyph) {
+            let mut line_edge = line_width;
+            while line_edge > 0 && row.get(line_edge).is_none_or(|&ch| ch == ' ') {
+                line_edge -= 1;
+            }
+            let line_char = row.get(line_edge).copied();
+            let mut glyph_edge = 0;
+            while glyph_edge + 1 < glyph_width && glyph_row[glyph_edge] == ' ' {
+                glyph_edge += 1;
+            }
+            let glyph_char = glyph_row.get(glyph_edge).copied();
+
+            // Gap between the line's last ink and the glyph's first, plus one column when
+            // those two characters may overlap.
+            let mut row_amount = (glyph_edge + line_width) as isize - 1 - line_edge as isize;
+            match (line_char, glyph_char) {
+                (None | Some(' '), _) => row_amount += 1,
+                (Some(left), Some(right))
+                    if self
+                        .smush(left, right, previous_width, glyph_width)
+                        .is_some() =>
+                {
+                    row_amount += 1;
+                }
+                _ => {}
+            }
+            let row_amount = usize::try_from(row_amount).unwrap_or(0);
+            amount = amount.min(row_amount);
+        }
+        amount.min(line_width)
+    }
+
+    /// The character two overlapping characters merge into, if the layout allows it.
+    fn smush(
+        &self,
+        left: char,
+        right: char,
+        left_width: usize,
+        right_width: usize,
+    ) -> Option<char> {
+        if left == ' ' {
+            return Some(right);
+        }
+        if right == ' ' {
+            return Some(left);
+        }
+        let Layout::Smushing(rules) = self.layout else {
+            return None;
+        };
+        // Single-column characters never smush, so `i`, `l` and friends stay legible.
+        if left_width < 2 || right_width < 2 {
+            return None;
+        }
+        if rules == 0 {
+            return Some(if left == self.hardblank {
+                right
+            } else if right == self.hardblank {
+                left
+            } else {
+                right
+            });
+        }
+        if left == self.hardblank || right == self.hardblank {
+            return (rules & SMUSH_HARDBLANK != 0 && left == right).then_some(left);
+        }
+        if rules & SMUSH_EQUAL != 0 && left == right {
+            return Some(left);
+        }
+        const BORDERS: &str = "|/\\[]{}()<>";
+        if rules & SMUSH_LOWLINE != 0 {
+            if left == '_' && BORDERS.contains(right) {
+                return Some(right);
+            }
+            if right == '_' && BORDERS.contains(left) {
+                return Some(left);
+            }
+        }
+        if rules & SMUSH_HIERARCHY != 0 {
+            const CLASSES: [&str; 6] = ["|", "/\\", "[]", "{}", "()", "<>"];
+            let class = |ch| CLASSES.iter().position(|class| class.contains(ch));
+            if let (Some(left_class), Some(right_class)) = (class(left), class(right))
+                && left_class != right_class
+            {
+                return Some(if left_class > right_class {
+                    left
+                } else {
+                    right
+                });
+            }
+        }
+        if rules & SMUSH_PAIR != 0
+            && matches!(
+                (left, right),
+                ('[', ']') | (']', '[') | ('{', '}') | ('}', '{') | ('(', ')') | (')', '(')
+            )
+        {
+            return Some('|');
+        }
+        if rules & SMUSH_BIGX != 0 {
+            match (left, right) {
+                ('/', '\\') => return Some('|'),
+                ('\\', '/') => return Some('Y'),
+                ('>', '<') => return Some('X'),
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+/// One line of banner text under construction.
+#[derive(Clone)]
+struct Banner<'a> {
+    font: &'a FigFont,
+    rows: Vec<Vec<char>>,
+    previous_width: usize,
+}
+
+impl<'a> Banner<'a> {
+    fn new(font: &'a FigFont) -> Self {
+        Self {
+            font,
+            rows: vec![Vec::new(); font.height],
+            previous_width: 0,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.width() == 0
+    }
+
+    fn width(&self) -> usize {
+        self.rows.first().map_or(0, Vec::len)
+    }
+
+    fn push_str(&mut self, text: &str) {
+        for ch in text.chars() {
+            self.push(ch);
+        }
+    }
+
+    fn push(&mut self, ch: char) {
+        let Some(glyph) = self.font.glyphs.get(&ch) else {
+            return;
+        };
+        let glyph_width = glyph.first().map_or(0, Vec::len);
+        let amount = self.font.overlap(&self.rows, glyph, self.previous_width);
+        for (row, glyph_row) in self.rows.iter_mut().zip(glyph) {
+            let start = row.len() - amount;
+            for (offset, &right) in glyph_row.iter().take(amount).enumerate() {
+                let left = row[start + offset];
+                row[start + offset] = self
+                    .font
+                    .smush(left, right, self.previous_width, glyph_width)
+                    .unwrap_or(right);
+            }
+            row.extend_from_slice(&glyph_row[amount..]);
+        }
+        self.previous_width = glyph_width;
+    }
+
+    fn rows(&self) -> Vec<String> {
+        self.rows
+            .iter()
+            .map(|row| {
+                let row = row
+                    .iter()
+                    .map(|&ch| if ch == self.font.hardblank { ' ' } else { ch })
+                    .collect::<String>();
+                row.trim_end().to_string()
+            })
+            .collect()
+    }
+}
+
+fn layout(old_layout: i64, full_layout: Option<i64>) -> Layout {
+    match full_layout {
+        Some(full) => {
+            let full = u32::try_from(full).unwrap_or(0);
+            if full & 128 != 0 {
+                Layout::Smushing(full & 63)
+            } else if full & 64 != 0 {
+                Layout::Kerning
+            } else {
+                Layout::FullWidth
+            }
+        }
+        None => match old_layout {
+            ..0 => Layout::FullWidth,
+            0 => Layout::Kerning,
+            rules => Layout::Smushing(u32::try_from(rules).unwrap_or(0) & 63),
+        },
+    }
+}
+
+/// Drops trailing whitespace and then every trailing copy of the endmark character.
+fn strip_endmark(line: &str) -> Vec<char> {
+    let line = line.trim_end();
+    let endmark = line.chars().last();
+    line.trim_end_matches(|ch| Some(ch) == endmark)
+        .chars()
+        .collect()
+}
+
+/// Parses a code tag: decimal, `0x` hexadecimal or `0`-prefixed octal. Negative codes
+/// (figlet's translation table entries) are ignored.
+fn parse_code(tag: &str) -> Option<u32> {
+    if let Some(hex) = tag.strip_prefix("0x").or_else(|| tag.strip_prefix("0X")) {
+        u32::from_str_radix(hex, 16).ok()
+    } else if tag.len() > 1
+        && let Some(octal) = tag.strip_prefix('0')
+    {
+        u32::from_str_radix(octal, 8).ok()
+    } else {
+        tag.parse().ok()
+    }
+}
+
+/// Directories searched for `--figlet NAME` fonts.
+#[cfg(feature = "fs")]
+pub fn default_figlet_paths() -> Vec<PathBuf> {
+    crate::platform::share_subdirs("figlet", &["", "fonts"])
+}
+
+/// Loads a font by name or path: [`BUILTIN_FIGFONT`], an existing `.flf` file, or
+/// `<name>.flf` in the first of `font_paths` that has it.
+#[cfg(feature = "fs")]
+pub fn load_figfont(name: &str, font_paths: &[PathBuf]) -> Result<FigFont, PonyError> {
+    if name == BUILTIN_FIGFONT {
+        return Ok(FigFont::builtin());
+    }
+    let direct = Path::new(name);
+    let path = if name.contains(std::path::is_separator) || direct.is_file() {
+        Some(direct.to_path_buf())
+    } else {
+        let file = format!("{}.flf", name.trim_end_matches(".flf"));
+        font_paths
+            .iter()
+            .map(|root| root.join(&file))
+            .find(|path| path.is_file())
+    };
+    let path = path.ok_or_else(|| PonyError::FigFontNotFound {
+        name: name.to_string(),
+    })?;
+    let source = encoding::read_text(&path).map_err(|source| PonyError::Io {
+        path: path.clone(),
+        source,
+    })?;
+    debug!(path = %path.display(), "loaded FIGfont");
+    FigFont::parse(&source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two-row font with `|` and `_` glyphs under the given layout numbers.
+    fn font(layout: &str) -> FigFont {
+        let mut source = format!("flf2a$ 2 2 4 {layout} 0\n");
+        for code in (32..=126).chain(DEUTSCH) {
+            let glyph = match char::from_u32(code).expect("char") {
+                ' ' => ["$@", "$@@"],
+                '|' => ["|  @", "|  @@"],
+                '_' => ["   @", "__ @@"],
+                '/' => [" /@", "/ @@"],
+                '\\' => ["\\ @", " \\@@"],
+                _ => ["@", "@@"],
+            };
+            source.push_str(&glyph.join("\n"));
+            source.push('\n');
+        }
+        source.push_str("0x263A smiley\n:)@\n  @@\n");
+        FigFont::parse(&source).expect("font")
+    }
+
+    #[test]
+    fn builtin_font_renders_and_wraps_to_width() {
+        let font = FigFont::builtin();
+        assert_eq!(font.height(), 3);
+        assert_eq!(font.render("Hi", 40), vec!["", "|_| |", "| | |"]);
+        assert_eq!(
+            font.render("\u{1b}[1mHi\u{1b}[0m hi\n\nok", 40),
+            vec![
+                "",
+                "|_| |   |_| |",
+                "| | |   | | |",
+                "",
+                " _",
+                "/ \\ |_/",
+                "\\_/ | \\",
+            ]
+        );
+
+        let rows = font.render("hi hi", 8);
+        assert_eq!(rows.len(), 6, "{rows:?}");
+        let rows = font.render("HIHIHI", 8);
+        assert!(rows.iter().all(|row| row.chars().count() <= 8), "{rows:?}");
+        assert_eq!(rows.len(), 9, "{rows:?}");
+    }
+
+    #[test]
+    fn layouts_follow_the_header() {
+        assert_eq!(font("-1").render("||", 40), vec!["|  |", "|  |"]);
+        assert_eq!(font("0").render("||", 40), vec!["||", "||"]);
+        // Controlled smushing with the lowline rule merges `_` into the border.
+        assert_eq!(font("2").render("_|", 40), vec![" |", "_|"]);
+        // Big X: `/` then `\` becomes `|`.
+        assert_eq!(font("16").render("/\\", 40), vec![" |", "/ \\"]);
+        assert_eq!(font("0 0 0").render("||", 40), vec!["|  |", "|  |"]);
+        assert_eq!(font("-1").render("\u{263a}", 40), vec![":)", ""]);
+    }
+
+    #[test]
+    fn rejects_malformed_fonts() {
+        assert!(matches!(
+            FigFont::parse("tlf2a$ 1 1 1 0 0"),
+            Err(PonyError::InvalidFigFont(_))
+        ));
+        assert!(FigFont::parse("flf2a$ 0 1 1 0 0").is_err());
+        assert!(FigFont::parse("flf2a$ 1 1 1 0 0\n").is_err());
+        assert_eq!(parse_code("0x41"), Some(65));
+        assert_eq!(parse_code("0101"), Some(65));
+        assert_eq!(parse_code("-5"), None);
+    }
+}
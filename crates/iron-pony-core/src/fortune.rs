@@ -1,5 +1,6 @@
 use std::collections::BTreeSet;
 use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 
 use rand::rngs::StdRng;
 use rand::{RngExt, SeedableRng};
@@ -13,6 +14,15 @@ pub struct FortuneConfig {
     pub seed: Option<u64>,
     pub sources: Vec<PathBuf>,
     pub search_paths: Vec<PathBuf>,
+    /// Read databases through their strfile(8) `.dat` index, as `fortune(6)` does: files
+    /// without one are skipped, each file weighs as many cookies as its index lists, and
+    /// cookies are cut at the recorded offsets in the recorded order, so doubled `%` lines
+    /// come out as strfile counted them. Comment lines and rot13 are handled when the
+    /// index flags them.
+    pub fortune6: bool,
+    /// Program and arguments run when no internal database has a fortune, as upstream runs
+    /// `fortune`; its stdout becomes the fortune. `None` makes that an error instead.
+    pub fallback_command: Option<Vec<String>>,
 }
 
 impl Default for FortuneConfig {
@@ -22,11 +32,12 @@ impl Default for FortuneConfig {
             equal_files: false,
             seed: None,
             sources: Vec::new(),
-            search_paths: vec![
-                PathBuf::from("testdata/fortunes"),
-                PathBuf::from("/usr/share/games/fortunes"),
-                PathBuf::from("/usr/share/fortune"),
-            ],
+            search_paths: std::iter::once(PathBuf::from("testdata/fortunes"))
+                .chain(crate::platform::share_subdirs("games", &["fortunes"]))
+                .chain(crate::platform::share_subdirs("fortune", &[""]))
+                .collect(),
+            fortune6: false,
+            fallback_command: None,
         }
     }
 }
@@ -43,8 +54,25 @@ pub enum FortuneError {
         #[source]
         source: std::io::Error,
     },
+    #[error("{path} is not a strfile index")]
+    InvalidIndex { path: PathBuf },
+    #[error("{internal}, and the fallback `{command}` failed: {reason}")]
+    Fallback {
+        command: String,
+        #[source]
+        internal: Box<FortuneError>,
+        reason: String,
+    },
 }
 
+/// `str_flags` bits of a strfile(8) header.
+const STR_ROTATED: u32 = 0x4;
+const STR_COMMENTS: u32 = 0x8;
+
+/// Size of the strfile(8) header: five big-endian words and the delimiter, padded to four
+/// bytes. The offset table follows.
+const STRFILE_HEADER: usize = 24;
+
 #[derive(Debug, Clone)]
 struct Db {
     path: PathBuf,
@@ -52,6 +80,45 @@ struct Db {
 }
 
 pub fn pick_fortune(config: &FortuneConfig) -> Result<String, FortuneError> {
+    match (pick_internal(config), &config.fallback_command) {
+        (Err(error @ (FortuneError::NoSources | FortuneError::NoFortunes)), Some(command)) => {
+            info!(%error, ?command, "no internal fortunes; running fallback command");
+            run_fallback(command).map_err(|reason| FortuneError::Fallback {
+                command: command.join(" "),
+                internal: Box::new(error),
+                reason,
+            })
+        }
+        (result, _) => result,
+    }
+}
+
+/// Runs `command` and returns its stdout without the final newline, or why it failed.
+fn run_fallback(command: &[String]) -> Result<String, String> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| "the command is empty".to_string())?;
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|error| error.to_string())?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(match stderr.trim() {
+            "" => output.status.to_string(),
+            stderr => format!("{}: {stderr}", output.status),
+        });
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let fortune = stdout.strip_suffix('\n').unwrap_or(&stdout);
+    if fortune.trim().is_empty() {
+        return Err("it printed nothing".to_string());
+    }
+    Ok(fortune.to_string())
+}
+
+fn pick_internal(config: &FortuneConfig) -> Result<String, FortuneError> {
     info!(
         equal_files = config.equal_files,
         include_offensive = config.include_offensive,
@@ -61,7 +128,14 @@ pub fn pick_fortune(config: &FortuneConfig) -> Result<String, FortuneError> {
     let sources = resolve_sources(config)?;
     let mut dbs = Vec::new();
     for source in sources {
-        let db = load_db(&source)?;
+        let db = if config.fortune6 {
+            match load_indexed_db(&source)? {
+                Some(db) => db,
+                None => continue,
+            }
+        } else {
+            load_db(&source)?
+        };
         if !db.fortunes.is_empty() {
             dbs.push(db);
         }
@@ -97,8 +171,12 @@ fn resolve_sources(config: &FortuneConfig) -> Result<Vec<PathBuf>, FortuneError>
     let mut found = BTreeSet::new();
 
     if config.sources.is_empty() {
-        for root in &config.search_paths {
-            collect(root, config.include_offensive, &mut found);
+        for files in crate::scan::scan_roots(&config.search_paths, |root| {
+            let mut files = BTreeSet::new();
+            collect(root, config.include_offensive, &mut files);
+            files
+        }) {
+            found.extend(files);
         }
     } else {
         for source in &config.sources {
@@ -176,6 +254,75 @@ fn load_db(path: &Path) -> Result<Db, FortuneError> {
     })
 }
 
+/// Reads `path` through `path.dat`; `None` when there is no index, which `fortune(6)` takes
+/// to mean the file is not a fortune database.
+fn load_indexed_db(path: &Path) -> Result<Option<Db>, FortuneError> {
+    let mut index_path = path.as_os_str().to_owned();
+    index_path.push(".dat");
+    let index_path = PathBuf::from(index_path);
+    let Ok(index) = std::fs::read(&index_path) else {
+        debug!(path = %path.display(), "no strfile index; skipping as fortune(6) does");
+        return Ok(None);
+    };
+    let invalid = || FortuneError::InvalidIndex {
+        path: index_path.clone(),
+    };
+    let word = |at: usize| {
+        index
+            .get(at..at + 4)
+            .and_then(|bytes| <[u8; 4]>::try_from(bytes).ok())
+            .map(u32::from_be_bytes)
+    };
+    let count = word(4).ok_or_else(invalid)? as usize;
+    let flags = word(16).ok_or_else(invalid)?;
+    let delim = *index.get(20).ok_or_else(invalid)?;
+    let offsets = (0..count)
+        .map(|entry| word(STRFILE_HEADER + entry * 4).map(|offset| offset as usize))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(invalid)?;
+
+    let raw = std::fs::read(path).map_err(|source| FortuneError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let fortunes = offsets
+        .into_iter()
+        .map(|offset| indexed_cookie(raw.get(offset..).unwrap_or_default(), delim, flags))
+        .collect::<Vec<_>>();
+    trace!(path = %path.display(), fortunes = fortunes.len(), flags, "loaded indexed fortune database");
+
+    Ok(Some(Db {
+        path: path.to_path_buf(),
+        fortunes,
+    }))
+}
+
+/// The cookie at the start of `raw`, read line by line up to a line holding only `delim`,
+/// as `fortune(6)` prints it (minus the final newline).
+fn indexed_cookie(raw: &[u8], delim: u8, flags: u32) -> String {
+    let mut cookie = Vec::new();
+    for line in raw.split_inclusive(|&byte| byte == b'\n') {
+        if line == [delim, b'\n'] {
+            break;
+        }
+        if flags & STR_COMMENTS != 0 && line.starts_with(&[delim, delim]) {
+            continue;
+        }
+        cookie.extend_from_slice(line);
+    }
+    if flags & STR_ROTATED != 0 {
+        for byte in &mut cookie {
+            *byte = match *byte {
+                b'a'..=b'z' => b'a' + (*byte - b'a' + 13) % 26,
+                b'A'..=b'Z' => b'A' + (*byte - b'A' + 13) % 26,
+                other => other,
+            };
+        }
+    }
+    let text = String::from_utf8_lossy(&cookie);
+    text.strip_suffix('\n').unwrap_or(&text).to_string()
+}
+
 fn split_fortunes(text: &str) -> Vec<String> {
     let mut out = Vec::new();
     let mut current = String::new();
@@ -224,4 +371,84 @@ mod tests {
         let parsed = split_fortunes("one\n%\ntwo\n%\nthree\n");
         assert_eq!(parsed, vec!["one", "two", "three"]);
     }
+
+    /// A strfile(8) index for `offsets`.
+    fn strfile_index(offsets: &[u32], flags: u32) -> Vec<u8> {
+        let count = offsets.len() as u32;
+        let mut out = Vec::new();
+        for word in [2, count, 0, 0, flags] {
+            out.extend_from_slice(&u32::to_be_bytes(word));
+        }
+        out.extend_from_slice(b"%\0\0\0");
+        for offset in offsets {
+            out.extend_from_slice(&offset.to_be_bytes());
+        }
+        out
+    }
+
+    #[test]
+    fn fortune6_mode_reads_through_strfile_indexes() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let root = dir.path();
+        std::fs::write(root.join("plain"), "never picked\n").expect("plain");
+        std::fs::write(root.join("cookies"), "first\n%\n%\nsecond\n  indented\n%\n")
+            .expect("cookies");
+        std::fs::write(root.join("cookies.dat"), strfile_index(&[10, 0, 8], 0)).expect("index");
+        std::fs::write(root.join("secret"), "%%note\nUryyb\n%\n").expect("secret");
+        std::fs::write(
+            root.join("secret.dat"),
+            strfile_index(&[0], STR_ROTATED | STR_COMMENTS),
+        )
+        .expect("index");
+
+        let db = load_indexed_db(&root.join("cookies"))
+            .expect("load")
+            .expect("indexed");
+        assert_eq!(db.fortunes, ["second\n  indented", "first", ""]);
+        let db = load_indexed_db(&root.join("secret"))
+            .expect("load")
+            .expect("indexed");
+        assert_eq!(db.fortunes, ["Hello"]);
+        assert!(
+            load_indexed_db(&root.join("plain"))
+                .expect("load")
+                .is_none()
+        );
+
+        let config = FortuneConfig {
+            sources: vec![root.join("plain"), root.join("secret")],
+            fortune6: true,
+            seed: Some(3),
+            ..FortuneConfig::default()
+        };
+        assert_eq!(pick_fortune(&config).expect("fortune"), "Hello");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn falls_back_to_a_fortune_command_when_no_database_has_one() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let command = |script: &str| Some(vec!["sh".into(), "-c".into(), script.into()]);
+        let config = FortuneConfig {
+            search_paths: vec![dir.path().to_path_buf()],
+            fallback_command: command("printf 'From the system\\n'"),
+            ..FortuneConfig::default()
+        };
+        assert_eq!(pick_fortune(&config).expect("fallback"), "From the system");
+
+        let config = FortuneConfig {
+            fallback_command: command("echo gone >&2; exit 3"),
+            ..config
+        };
+        let error = pick_fortune(&config).expect_err("both fail");
+        assert_eq!(
+            error.to_string(),
+            "no fortune sources found, and the fallback `sh -c echo gone >&2; exit 3` failed: \
+             exit status: 3: gone"
+        );
+        assert_eq!(
+            std::error::Error::source(&error).map(ToString::to_string),
+            Some("no fortune sources found".to_string())
+        );
+    }
 }
@@ -0,0 +1,209 @@
+//! HTML export of rendered output, for web pages, dashboards and chat bots.
+
+use crate::ansi::{self, Token};
+use crate::color::xterm_to_rgb;
+
+/// Text attributes set by SGR sequences that have an HTML equivalent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct SgrState {
+    fg: Option<(u8, u8, u8)>,
+    bg: Option<(u8, u8, u8)>,
+    bold: bool,
+    italic: bool,
+    underline: bool,
+}
+
+impl SgrState {
+    fn apply(&mut self, params: &str) {
+        let values = params
+            .split(';')
+            .map(|value| value.parse::<u16>().unwrap_or(0))
+            .collect::<Vec<_>>();
+        let mut index = 0;
+        while index < values.len() {
+            let value = values[index];
+            index += 1;
+            match value {
+                0 => *self = Self::default(),
+                1 => self.bold = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                22 => self.bold = false,
+                23 => self.italic = false,
+                24 => self.underline = false,
+                30..=37 => self.fg = Some(xterm_to_rgb((value - 30) as u8)),
+                90..=97 => self.fg = Some(xterm_to_rgb((value - 90 + 8) as u8)),
+                39 => self.fg = None,
+                40..=47 => self.bg = Some(xterm_to_rgb((value - 40) as u8)),
+                100..=107 => self.bg = Some(xterm_to_rgb((value - 100 + 8) as u8)),
+                49 => self.bg = None,
+                38 | 48 => {
+                    let byte = |offset: usize| {
+                        values
+                            .get(index + offset)
+                            .and_then(|value| u8::try_from(*value).ok())
+                    };
+                    let (rgb, consumed) = match values.get(index) {
+                        Some(5) => (byte(1).map(xterm_to_rgb), 2),
+                        Some(2) => match (byte(1), byte(2), byte(3)) {
+                            (Some(r), Some(g), Some(b)) => (Some((r, g, b)), 4),
+                            _ => (None, 4),
+                        },
+                        _ => (None, 0),
+                    };
+                    index += consumed;
+                    if value == 38 {
+                        self.fg = rgb;
+                    } else {
+                        self.bg = rgb;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn css(&self) -> String {
+        let mut css = Vec::new();
+        if let Some((r, g, b)) = self.fg {
+            css.push(format!("color:#{r:02x}{g:02x}{b:02x}"));
+        }
+        if let Some((r, g, b)) = self.bg {
+            css.push(format!("background-color:#{r:02x}{g:02x}{b:02x}"));
+        }
+        if self.bold {
+            css.push("font-weight:bold".to_string());
+        }
+        if self.italic {
+            css.push("font-style:italic".to_string());
+        }
+        if self.underline {
+            css.push("text-decoration:underline".to_string());
+        }
+        css.join(";")
+    }
+}
+
+/// Converts rendered output into a `<pre class="iron-pony">` fragment.
+///
+/// Text is HTML-escaped, SGR colors and attributes become inline-styled `<span>`s, and
+/// OSC 8 hyperlinks to `http`, `https` and `mailto` targets become `<a>` elements. Other
+/// escape sequences are dropped.
+pub fn ansi_to_html(input: &str) -> String {
+    let mut out = String::with_capacity(input.len() * 2);
+    out.push_str("<pre class=\"iron-pony\">");
+    let mut state = SgrState::default();
+    let mut open_span: Option<SgrState> = None;
+    let mut in_link = false;
+
+    for token in ansi::tokens(input) {
+        match token {
+            Token::Text(text) => {
+                if open_span != Some(state) {
+                    if open_span.take().is_some() {
+                        out.push_str("</span>");
+                    }
+                    if state != SgrState::default() {
+                        out.push_str(&format!("<span style=\"{}\">", state.css()));
+                        open_span = Some(state);
+                    }
+                }
+                push_escaped(&mut out, text);
+            }
+            Token::Sequence(sequence) => {
+                if let Some(params) = sequence.sgr_params() {
+                    state.apply(params);
+                    continue;
+                }
+                let Some(target) = sequence.hyperlink_target() else {
+                    continue;
+                };
+                // Links and spans may not overlap; the span is reopened by the next text.
+                if open_span.take().is_some() {
+                    out.push_str("</span>");
+                }
+                if in_link {
+                    out.push_str("</a>");
+                }
+                in_link = is_safe_link(target);
+                if in_link {
+                    out.push_str("<a href=\"");
+                    push_escaped(&mut out, target);
+                    out.push_str("\">");
+                }
+            }
+        }
+    }
+
+    if open_span.is_some() {
+        out.push_str("</span>");
+    }
+    if in_link {
+        out.push_str("</a>");
+    }
+    out.push_str("</pre>");
+    out
+}
+
+/// Only web and mail links become `<a>` elements; anything else (`javascript:`, `data:`,
+/// relative paths) could run script in the page, so its text is shown unlinked.
+fn is_safe_link(target: &str) -> bool {
+    let Some((scheme, _)) = target.split_once(':') else {
+        return false;
+    };
+    ["http", "https", "mailto"]
+        .iter()
+        .any(|safe| scheme.eq_ignore_ascii_case(safe))
+}
+
+fn push_escaped(out: &mut String, text: &str) {
+    for ch in text.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(ch),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_sgr_and_links_to_markup() {
+        assert_eq!(
+            ansi_to_html("\u{1b}[0m<hi> & \u{1b}[1;38;5;196mred\u{1b}[39m bold\u{1b}[0m."),
+            "<pre class=\"iron-pony\">&lt;hi&gt; &amp; \
+             <span style=\"color:#ff0000;font-weight:bold\">red</span>\
+             <span style=\"font-weight:bold\"> bold</span>.</pre>"
+        );
+        assert_eq!(
+            ansi_to_html("\u{1b}[31m\u{1b}]8;;https://x.test/?a&b\u{7}link\u{1b}]8;;\u{7}!"),
+            "<pre class=\"iron-pony\"><a href=\"https://x.test/?a&amp;b\">\
+             <span style=\"color:#aa0000\">link</span></a>\
+             <span style=\"color:#aa0000\">!</span></pre>"
+        );
+        assert_eq!(
+            ansi_to_html("\u{1b}[48;2;1;2;3mx\u{1b}[2J"),
+            "<pre class=\"iron-pony\"><span style=\"background-color:#010203\">x</span></pre>"
+        );
+    }
+
+    #[test]
+    fn only_web_and_mail_links_become_anchors() {
+        assert_eq!(
+            ansi_to_html("\u{1b}]8;;javascript:alert(1)\u{7}click\u{1b}]8;;\u{7}"),
+            "<pre class=\"iron-pony\">click</pre>"
+        );
+        assert_eq!(
+            ansi_to_html(
+                "\u{1b}]8;; JavaScript:x\u{7}a\u{1b}]8;;\u{7}\u{1b}]8;;MAILTO:p@x.test\u{7}b"
+            ),
+            "<pre class=\"iron-pony\">a<a href=\"MAILTO:p@x.test\">b</a></pre>"
+        );
+    }
+}
@@ -0,0 +1,485 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::UNIX_EPOCH;
+
+use tracing::{debug, trace, warn};
+use walkdir::WalkDir;
+
+use crate::platform::{Platform, env_var};
+use crate::{AssetPack, PonyMetadata};
+
+const CACHE_HEADER: &str = "iron-pony-index 1";
+
+/// Name → path index over the configured pony roots.
+///
+/// The index records the mtime of every directory it walked so a cached copy can be
+/// validated cheaply: adding or removing a pony file bumps its directory's mtime.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PonyIndex {
+    roots: Vec<RootIndex>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct RootIndex {
+    root: PathBuf,
+    dirs: Vec<(PathBuf, u128)>,
+    ponies: Vec<(String, PathBuf)>,
+}
+
+/// Kind of search root a pony was found under, judged by the root's directory name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PonyClass {
+    /// `ponies`: the canonical set.
+    Pony,
+    /// `extraponies`: non-canon ponies upstream only lists with `-F`.
+    ExtraPony,
+    /// `ttyponies`: 16-color variants for the Linux console.
+    TtyPony,
+    /// `extrattyponies`.
+    ExtraTtyPony,
+    /// Any other directory, or an asset pack.
+    Other,
+}
+
+impl PonyClass {
+    fn of_root(root: &Path) -> Self {
+        match root.file_name().and_then(|name| name.to_str()) {
+            Some("ponies") => Self::Pony,
+            Some("extraponies") => Self::ExtraPony,
+            Some("ttyponies") => Self::TtyPony,
+            Some("extrattyponies") => Self::ExtraTtyPony,
+            _ => Self::Other,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pony => "ponies",
+            Self::ExtraPony => "extraponies",
+            Self::TtyPony => "ttyponies",
+            Self::ExtraTtyPony => "extrattyponies",
+            Self::Other => "other",
+        }
+    }
+}
+
+/// One indexed pony. Its metadata header is only read when first asked for.
+#[derive(Debug, Clone)]
+pub struct PonySummary {
+    pub name: String,
+    pub path: PathBuf,
+    /// Search root the pony was found under.
+    pub root: PathBuf,
+    pub class: PonyClass,
+    metadata: OnceLock<PonyMetadata>,
+}
+
+impl PonySummary {
+    /// Metadata from the pony file's header; empty when the file cannot be read (as for
+    /// ponies inside an asset pack).
+    pub fn metadata(&self) -> &PonyMetadata {
+        self.metadata
+            .get_or_init(|| crate::pony::read_metadata(&self.path))
+    }
+}
+
+/// Every pony under `pony_paths`; see [`PonyIndex::summaries`].
+pub fn iter_ponies(pony_paths: &[PathBuf]) -> impl Iterator<Item = PonySummary> + use<> {
+    PonyIndex::scan(pony_paths)
+        .summaries()
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+impl PonyIndex {
+    pub fn scan(roots: &[PathBuf]) -> Self {
+        Self {
+            roots: crate::scan::scan_roots(roots, scan_root),
+        }
+    }
+
+    /// Loads the index from `cache_path` when it is still valid for `roots`, otherwise
+    /// rescans and refreshes the cache file. `None` disables caching entirely.
+    pub fn load(roots: &[PathBuf], cache_path: Option<&Path>) -> Self {
+        let Some(cache_path) = cache_path else {
+            return Self::scan(roots);
+        };
+
+        if let Some(cached) = read_cache(cache_path)
+            && cached.is_fresh_for(roots)
+        {
+            debug!(path = %cache_path.display(), "using cached pony index");
+            return cached;
+        }
+
+        let index = Self::scan(roots);
+        if let Err(error) = index.write_cache(cache_path) {
+            warn!(path = %cache_path.display(), %error, "failed writing pony index cache");
+        }
+        index
+    }
+
+    /// Puts an asset pack's ponies ahead of the filesystem roots, since loading consults the
+    /// pack first (never cached).
+    pub fn add_pack(&mut self, pack: &AssetPack) {
+        let ponies = pack
+            .pony_names()
+            .into_iter()
+            .map(|name| {
+                let path = pack.path().join(format!("{name}.pony"));
+                (name, path)
+            })
+            .collect();
+        self.roots.insert(
+            0,
+            RootIndex {
+                root: pack.path().to_path_buf(),
+                dirs: Vec::new(),
+                ponies,
+            },
+        );
+    }
+
+    /// Sorted, de-duplicated pony names across all roots.
+    pub fn names(&self) -> Vec<String> {
+        let mut names = self
+            .roots
+            .iter()
+            .flat_map(|root| root.ponies.iter().map(|(name, _)| name.clone()))
+            .collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Ponies grouped by the root they were found under, in search order. Names are
+    /// sorted and unique within a root; roots without ponies are skipped.
+    pub fn by_root(&self) -> Vec<(&Path, Vec<(&str, &Path)>)> {
+        self.roots
+            .iter()
+            .filter(|root| !root.ponies.is_empty())
+            .map(|root| {
+                let mut ponies = root
+                    .ponies
+                    .iter()
+                    .map(|(name, path)| (name.as_str(), path.as_path()))
+                    .collect::<Vec<_>>();
+                ponies.dedup_by(|next, kept| next.0 == kept.0);
+                (root.root.as_path(), ponies)
+            })
+            .collect()
+    }
+
+    /// Every pony in search order, sorted by name within each root. A name found twice
+    /// under one root is reported once; a name found under several roots is reported for
+    /// each, and the first is the one [`PonyIndex::path`] resolves.
+    pub fn summaries(&self) -> impl Iterator<Item = PonySummary> + '_ {
+        self.by_root().into_iter().flat_map(|(root, ponies)| {
+            let class = PonyClass::of_root(root);
+            ponies.into_iter().map(move |(name, path)| PonySummary {
+                name: name.to_string(),
+                path: path.to_path_buf(),
+                root: root.to_path_buf(),
+                class,
+                metadata: OnceLock::new(),
+            })
+        })
+    }
+
+    /// Path of the first pony named `name`, honoring root order.
+    pub fn path(&self, name: &str) -> Option<&Path> {
+        self.roots
+            .iter()
+            .flat_map(|root| root.ponies.iter())
+            .find(|(candidate, _)| candidate == name)
+            .map(|(_, path)| path.as_path())
+    }
+
+    /// Path of the pony `name` as the loaders find it: an exact name in any root first,
+    /// then unless `exact` one matching when case is ignored.
+    pub fn find(&self, name: &str, exact: bool) -> Option<&Path> {
+        self.path(name).or_else(|| {
+            if exact {
+                return None;
+            }
+            let lowered = name.to_lowercase();
+            self.roots
+                .iter()
+                .flat_map(|root| root.ponies.iter())
+                .find(|(candidate, _)| candidate.to_lowercase() == lowered)
+                .map(|(_, path)| path.as_path())
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roots.iter().all(|root| root.ponies.is_empty())
+    }
+
+    fn is_fresh_for(&self, roots: &[PathBuf]) -> bool {
+        if self.roots.len() != roots.len() {
+            return false;
+        }
+
+        for (cached, root) in self.roots.iter().zip(roots) {
+            if &cached.root != root {
+                return false;
+            }
+            if cached.dirs.is_empty() && root.exists() {
+                return false;
+            }
+            for (dir, mtime) in &cached.dirs {
+                if dir_mtime(dir) != Some(*mtime) {
+                    trace!(path = %dir.display(), "pony index directory changed");
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Writes the cache beside `path` and renames it into place, so a concurrent reader
+    /// never sees a partial file.
+    fn write_cache(&self, path: &Path) -> std::io::Result<()> {
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        std::fs::create_dir_all(parent)?;
+
+        let mut out = String::new();
+        out.push_str(CACHE_HEADER);
+        out.push('\n');
+        for root in &self.roots {
+            out.push_str(&format!("root\t{}\n", root.root.display()));
+            for (dir, mtime) in &root.dirs {
+                out.push_str(&format!("dir\t{mtime}\t{}\n", dir.display()));
+            }
+            for (name, pony) in &root.ponies {
+                out.push_str(&format!("pony\t{name}\t{}\n", pony.display()));
+            }
+        }
+
+        let mut temp = tempfile::NamedTempFile::new_in(parent)?;
+        temp.write_all(out.as_bytes())?;
+        temp.persist(path).map_err(|error| error.error)?;
+        debug!(path = %path.display(), "wrote pony index cache");
+        Ok(())
+    }
+}
+
+pub fn default_index_cache_path() -> Option<PathBuf> {
+    let base = Platform::current().cache_dir(env_var)?;
+    Some(base.join("iron-pony").join("pony-index"))
+}
+
+fn scan_root(root: &Path) -> RootIndex {
+    let mut index = RootIndex {
+        root: root.to_path_buf(),
+        ..RootIndex::default()
+    };
+
+    if !root.exists() {
+        return index;
+    }
+
+    for entry in WalkDir::new(root)
+        .follow_links(false)
+        .max_depth(3)
+        .into_iter()
+        .filter_map(Result::ok)
+    {
+        if entry.file_type().is_dir() {
+            if let Some(mtime) = dir_mtime(entry.path()) {
+                index.dirs.push((entry.path().to_path_buf(), mtime));
+            }
+            continue;
+        }
+
+        if entry.depth() == 0 || !entry.file_type().is_file() {
+            continue;
+        }
+
+        if let Some(stem) = entry.path().file_stem().and_then(|s| s.to_str()) {
+            index
+                .ponies
+                .push((stem.to_string(), entry.path().to_path_buf()));
+        }
+    }
+
+    index.ponies.sort();
+    trace!(root = %root.display(), ponies = index.ponies.len(), "scanned pony root");
+    index
+}
+
+fn dir_mtime(path: &Path) -> Option<u128> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_nanos())
+}
+
+fn read_cache(path: &Path) -> Option<PonyIndex> {
+    let raw = std::fs::read_to_string(path).ok()?;
+    let mut lines = raw.lines();
+    if lines.next()? != CACHE_HEADER {
+        return None;
+    }
+
+    let mut index = PonyIndex::default();
+    for line in lines {
+        let mut fields = line.splitn(3, '\t');
+        match (fields.next()?, fields.next(), fields.next()) {
+            ("root", Some(root), None) => index.roots.push(RootIndex {
+                root: PathBuf::from(root),
+                ..RootIndex::default()
+            }),
+            ("dir", Some(mtime), Some(dir)) => {
+                let mtime = mtime.parse().ok()?;
+                index
+                    .roots
+                    .last_mut()?
+                    .dirs
+                    .push((PathBuf::from(dir), mtime));
+            }
+            ("pony", Some(name), Some(pony)) => index
+                .roots
+                .last_mut()?
+                .ponies
+                .push((name.to_string(), PathBuf::from(pony))),
+            _ => return None,
+        }
+    }
+
+    Some(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn cache_round_trips_and_invalidates() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let pony_dir = tmp.path().join("ponies");
+        fs::create_dir_all(&pony_dir).expect("pony dir");
+        fs::write(pony_dir.join("alpha.pony"), "a").expect("write alpha");
+        let cache = tmp.path().join("cache/pony-index");
+        let roots = vec![pony_dir.clone()];
+
+        let first = PonyIndex::load(&roots, Some(&cache));
+        assert_eq!(first.names(), vec!["alpha"]);
+        assert_eq!(read_cache(&cache), Some(first.clone()));
+        assert!(first.is_fresh_for(&roots));
+
+        fs::create_dir_all(pony_dir.join("extra")).expect("sub dir");
+        fs::write(pony_dir.join("extra/beta.pony"), "b").expect("write beta");
+        // Coarse filesystem timestamps can hide the change; pin a distinct mtime.
+        fs::File::open(&pony_dir)
+            .and_then(|dir| dir.set_modified(UNIX_EPOCH + std::time::Duration::from_secs(1)))
+            .expect("set dir mtime");
+        assert!(!first.is_fresh_for(&roots));
+
+        let second = PonyIndex::load(&roots, Some(&cache));
+        assert_eq!(second.names(), vec!["alpha", "beta"]);
+        assert!(
+            second
+                .path("beta")
+                .expect("beta path")
+                .ends_with("beta.pony")
+        );
+        let cache_dir = fs::read_dir(cache.parent().expect("cache dir")).expect("read cache dir");
+        assert_eq!(cache_dir.count(), 1, "temp files are renamed into place");
+    }
+
+    #[test]
+    fn pack_ponies_shadow_the_search_paths() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let pony_dir = tmp.path().join("ponies");
+        fs::create_dir_all(&pony_dir).expect("pony dir");
+        fs::write(pony_dir.join("alpha.pony"), "disk").expect("write alpha");
+        fs::write(pony_dir.join("beta.pony"), "disk").expect("write beta");
+        let pack_path = tmp.path().join("assets.tar");
+        let mut builder = tar::Builder::new(fs::File::create(&pack_path).expect("create tar"));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "alpha.pony", "pack".as_bytes())
+            .expect("append member");
+        builder.finish().expect("finish tar");
+
+        let mut index = PonyIndex::scan(std::slice::from_ref(&pony_dir));
+        index.add_pack(&AssetPack::open(&pack_path).expect("open pack"));
+        assert_eq!(
+            index.path("alpha"),
+            Some(pack_path.join("alpha.pony").as_path())
+        );
+        assert_eq!(
+            index.path("beta"),
+            Some(pony_dir.join("beta.pony").as_path())
+        );
+        assert_eq!(index.find("BETA", false), index.path("beta"));
+        assert_eq!(index.find("BETA", true), None);
+        assert_eq!(index.by_root()[0].0, pack_path);
+    }
+
+    #[test]
+    fn groups_ponies_by_root() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let first = tmp.path().join("first");
+        let second = tmp.path().join("second");
+        fs::create_dir_all(first.join("nested")).expect("first dir");
+        fs::create_dir_all(&second).expect("second dir");
+        fs::write(first.join("beta.pony"), "b").expect("write beta");
+        fs::write(first.join("alpha.pony"), "a").expect("write alpha");
+        fs::write(first.join("nested/alpha.pony"), "a").expect("write nested alpha");
+        fs::write(second.join("alpha.pony"), "a").expect("write second alpha");
+
+        let roots = vec![first.clone(), tmp.path().join("missing"), second.clone()];
+        let index = PonyIndex::scan(&roots);
+        let groups = index.by_root();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].0, first);
+        assert_eq!(
+            groups[0].1,
+            vec![
+                ("alpha", first.join("alpha.pony").as_path()),
+                ("beta", first.join("beta.pony").as_path())
+            ]
+        );
+        assert_eq!(groups[1].0, second);
+    }
+
+    #[test]
+    fn summaries_classify_roots_and_read_metadata_lazily() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let ponies = tmp.path().join("ponies");
+        let extra = tmp.path().join("extraponies");
+        fs::create_dir_all(&ponies).expect("ponies dir");
+        fs::create_dir_all(&extra).expect("extra dir");
+        fs::write(ponies.join("alpha.pony"), "$$$\nNAME: Alpha\n$$$\na\n").expect("write");
+        fs::write(extra.join("alpha.pony"), "a").expect("write extra alpha");
+        fs::write(extra.join("beta.pony"), "b").expect("write beta");
+
+        let summaries = iter_ponies(&[ponies.clone(), extra.clone()]).collect::<Vec<_>>();
+        let listed = summaries
+            .iter()
+            .map(|summary| (summary.name.as_str(), summary.class))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            listed,
+            [
+                ("alpha", PonyClass::Pony),
+                ("alpha", PonyClass::ExtraPony),
+                ("beta", PonyClass::ExtraPony)
+            ]
+        );
+        assert_eq!(summaries[0].root, ponies);
+
+        fs::write(ponies.join("alpha.pony"), "$$$\nNAME: Renamed\n$$$\na\n").expect("rewrite");
+        assert_eq!(summaries[0].metadata().name(), Some("Renamed"));
+        assert!(summaries[2].metadata().tags.is_empty());
+    }
+}
@@ -0,0 +1,283 @@
+//! Arranging rendered output: mirroring art left-right and putting blocks side by side.
+
+use unicode_width::UnicodeWidthChar;
+
+use crate::ansi::{self, Token, visible_width};
+
+const RESET: &str = "\u{1b}[0m";
+
+/// Stands in for balloon cells while the art around them is mirrored.
+const BALLOON_CELL: char = '\u{e000}';
+
+/// One terminal cell of a line: a character (plus any zero-width marks on it), the SGR
+/// sequences in effect for it, and other escape sequences written just before it.
+struct Cell {
+    style: String,
+    prefix: String,
+    text: String,
+    width: usize,
+}
+
+fn cells(line: &str) -> Vec<Cell> {
+    let mut cells: Vec<Cell> = Vec::new();
+    let mut style = String::new();
+    let mut prefix = String::new();
+    for token in ansi::tokens(line) {
+        match token {
+            Token::Sequence(sequence) => match sequence.sgr_params() {
+                Some(params) => {
+                    if matches!(params.split(';').next(), Some("" | "0")) {
+                        style.clear();
+                    }
+                    if !matches!(params, "" | "0") {
+                        style.push_str(sequence.raw);
+                    }
+                }
+                None => prefix.push_str(sequence.raw),
+            },
+            Token::Text(text) => {
+                for ch in text.chars() {
+                    let width = UnicodeWidthChar::width(ch).unwrap_or(0);
+                    if width == 0
+                        && let Some(last) = cells.last_mut()
+                    {
+                        last.text.push(ch);
+                        continue;
+                    }
+                    cells.push(Cell {
+                        style: style.clone(),
+                        prefix: std::mem::take(&mut prefix),
+                        text: ch.to_string(),
+                        width,
+                    });
+                }
+            }
+        }
+    }
+    cells
+}
+
+/// `line` mirrored left-right within `width` columns: cells in reverse order, each keeping
+/// its colors, with directional characters such as `/`, `(` and `▌` swapped for their
+/// mirror images. Short lines are padded on the left so columns stay aligned.
+pub(crate) fn mirror_line(line: &str, width: usize) -> String {
+    let cells = cells(line);
+    let used = cells.iter().map(|cell| cell.width).sum::<usize>();
+    let mut out = " ".repeat(width.saturating_sub(used));
+    let mut current = "";
+    for cell in cells.iter().rev() {
+        out.push_str(&cell.prefix);
+        if cell.style != current {
+            out.push_str(RESET);
+            out.push_str(&cell.style);
+            current = &cell.style;
+        }
+        out.extend(cell.text.chars().map(mirror_char));
+    }
+    if !current.is_empty() {
+        out.push_str(RESET);
+    }
+    out
+}
+
+/// Mirrors every line of `lines` within the width of the widest one.
+pub(crate) fn mirror_lines<S: AsRef<str>>(lines: &[S]) -> Vec<String> {
+    let width = lines
+        .iter()
+        .map(|line| visible_width(line.as_ref()))
+        .max()
+        .unwrap_or(0);
+    lines
+        .iter()
+        .map(|line| mirror_line(line.as_ref(), width))
+        .collect()
+}
+
+/// Blank rows the size of `balloon` to draw the art around before [`mirror_around_balloon`].
+/// Like balloon rows, they start and end in the default colors.
+pub(crate) fn balloon_placeholder(balloon: &[String]) -> Vec<String> {
+    balloon
+        .iter()
+        .map(|row| {
+            let cells = BALLOON_CELL.to_string().repeat(visible_width(row));
+            format!("{RESET}{cells}{RESET}")
+        })
+        .collect()
+}
+
+/// Mirrors `art`, drawn with a [`balloon_placeholder`], and puts the rows of `balloon` back
+/// where the placeholder ended up: the pony turns around, the message stays readable.
+pub(crate) fn mirror_around_balloon(art: &str, balloon: &[String]) -> String {
+    let mut rows = balloon.iter();
+    let lines = mirror_lines(&art.split('\n').collect::<Vec<_>>())
+        .into_iter()
+        .map(|line| {
+            let (Some(start), Some(end)) = (line.find(BALLOON_CELL), line.rfind(BALLOON_CELL))
+            else {
+                return line;
+            };
+            let row = rows.next().map_or("", String::as_str);
+            format!(
+                "{}{row}{}",
+                &line[..start],
+                &line[end + BALLOON_CELL.len_utf8()..]
+            )
+        })
+        .collect::<Vec<_>>();
+    lines.join("\n")
+}
+
+fn mirror_char(c: char) -> char {
+    const PAIRS: [(char, char); 34] = [
+        ('/', '\\'),
+        ('(', ')'),
+        ('<', '>'),
+        ('[', ']'),
+        ('{', '}'),
+        ('«', '»'),
+        ('‹', '›'),
+        ('┌', '┐'),
+        ('└', '┘'),
+        ('├', '┤'),
+        ('┏', '┓'),
+        ('┗', '┛'),
+        ('┣', '┫'),
+        ('╔', '╗'),
+        ('╚', '╝'),
+        ('╠', '╣'),
+        ('╭', '╮'),
+        ('╰', '╯'),
+        ('╱', '╲'),
+        ('▌', '▐'),
+        ('▖', '▗'),
+        ('▘', '▝'),
+        ('▙', '▟'),
+        ('▛', '▜'),
+        ('▚', '▞'),
+        ('◀', '▶'),
+        ('◢', '◣'),
+        ('◤', '◥'),
+        ('⌐', '¬'),
+        ('◖', '◗'),
+        ('⎛', '⎞'),
+        ('⎝', '⎠'),
+        ('⎡', '⎤'),
+        ('⎣', '⎦'),
+    ];
+    PAIRS
+        .iter()
+        .find_map(|&(left, right)| {
+            if c == left {
+                Some(right)
+            } else if c == right {
+                Some(left)
+            } else {
+                None
+            }
+        })
+        .unwrap_or(c)
+}
+
+/// Puts rendered `blocks` next to each other, `gap` columns apart, standing on a shared
+/// bottom line the way ponies stand side by side. Each block takes the width of its widest
+/// line, measured without escape sequences, and colors a line leaves open are reset before
+/// the next block starts, unless the line already ends with a reset.
+pub fn compose_horizontal(blocks: &[Vec<String>], gap: usize) -> Vec<String> {
+    let height = blocks.iter().map(Vec::len).max().unwrap_or(0);
+    // Each row and the column it has been filled to; padding is only added before text.
+    let mut rows = vec![(String::new(), 0); height];
+    let mut column = 0;
+    for block in blocks {
+        for (row, (out, filled)) in rows.iter_mut().enumerate() {
+            let Some(line) = (row + block.len())
+                .checked_sub(height)
+                .and_then(|line| block.get(line))
+                .filter(|line| !line.is_empty())
+            else {
+                continue;
+            };
+            out.push_str(&" ".repeat(column - *filled));
+            out.push_str(line);
+            if line.contains('\u{1b}') && !line.ends_with(RESET) {
+                out.push_str(RESET);
+            }
+            *filled = column + visible_width(line);
+        }
+        let width = block
+            .iter()
+            .map(|line| visible_width(line))
+            .max()
+            .unwrap_or(0);
+        column += width + gap;
+    }
+    rows.into_iter().map(|(row, _)| row).collect()
+}
+
+/// Stacks rendered `blocks` top to bottom with `gap` blank lines between them. A block that
+/// uses escape sequences ends with a single reset, so its colors stay out of the next one.
+pub fn compose_vertical(blocks: &[Vec<String>], gap: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for (index, block) in blocks.iter().enumerate() {
+        if index > 0 {
+            lines.extend(std::iter::repeat_n(String::new(), gap));
+        }
+        lines.extend(block.iter().cloned());
+        if let Some(last) = lines.last_mut()
+            && block.iter().any(|line| line.contains('\u{1b}'))
+            && !last.ends_with(RESET)
+        {
+            last.push_str(RESET);
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mirrors_lines_around_the_balloon() {
+        assert_eq!(mirror_line("ab/(", 6), "  )\\ba");
+        assert_eq!(
+            mirror_line("\u{1b}[31mx▌\u{1b}[0my", 3),
+            "y\u{1b}[0m\u{1b}[31m▐x\u{1b}[0m"
+        );
+        assert_eq!(mirror_line("e\u{301}日", 3), "日e\u{301}");
+
+        let balloon = vec!["(hi)".to_string()];
+        let art = format!("{}\n    \\\n     o", balloon_placeholder(&balloon)[0]);
+        assert_eq!(
+            mirror_around_balloon(&art, &balloon),
+            "  (hi)\n /    \no     "
+        );
+    }
+
+    #[test]
+    fn composes_blocks_across_and_down() {
+        let left = vec!["ab".to_string(), "abcd".to_string()];
+        let right = vec!["\u{1b}[32mz".to_string()];
+        assert_eq!(
+            compose_horizontal(&[left.clone(), right.clone()], 1),
+            ["ab", "abcd \u{1b}[32mz\u{1b}[0m"]
+        );
+        assert_eq!(
+            compose_horizontal(&[right.clone(), Vec::new(), vec!["q".to_string()]], 2),
+            ["\u{1b}[32mz\u{1b}[0m    q"]
+        );
+        assert_eq!(
+            compose_vertical(&[right, left.clone()], 1),
+            ["\u{1b}[32mz\u{1b}[0m", "", "ab", "abcd"]
+        );
+
+        let reset = vec!["\u{1b}[31my\u{1b}[0m".to_string()];
+        assert_eq!(
+            compose_horizontal(&[reset.clone(), reset.clone()], 1),
+            ["\u{1b}[31my\u{1b}[0m \u{1b}[31my\u{1b}[0m"]
+        );
+        assert_eq!(
+            compose_vertical(&[reset, left], 0),
+            ["\u{1b}[31my\u{1b}[0m", "ab", "abcd"]
+        );
+    }
+}
@@ -1,18 +1,100 @@
+#[cfg(feature = "fs")]
+mod alias;
+mod ansi;
 mod balloon;
+#[cfg(feature = "bidi")]
+mod bidi;
+mod color;
+#[cfg(feature = "fs")]
+mod encoding;
+#[cfg(feature = "figlet")]
+mod figlet;
+#[cfg(feature = "fs")]
 mod fortune;
+mod html;
+#[cfg(feature = "fs")]
+mod index;
+mod layout;
+mod lint;
+#[cfg(feature = "fs")]
+mod pack;
+mod platform;
 mod pony;
+#[cfg(feature = "fs")]
+mod quote;
+#[cfg(feature = "fs")]
+mod random;
+#[cfg(feature = "fs")]
+mod renderer;
+#[cfg(feature = "fs")]
+mod scan;
+#[cfg(feature = "fs")]
+mod search;
+#[cfg(feature = "fs")]
+mod source;
+pub mod text;
+mod transform;
+mod ucsmap;
 
-use std::collections::BTreeSet;
+#[cfg(feature = "fs")]
+use std::collections::{BTreeMap, BTreeSet};
 use std::path::PathBuf;
+use std::sync::Arc;
 
-use rand::rngs::StdRng;
-use rand::{RngExt, SeedableRng};
+#[cfg(feature = "fs")]
+use rand::RngExt;
 use thiserror::Error;
-use tracing::{debug, info, trace};
+use tracing::info;
+#[cfg(feature = "fs")]
+use tracing::{debug, trace};
 
-pub use balloon::{BalloonMode, BalloonStyle};
+#[cfg(feature = "fs")]
+use crate::renderer::StyleKey;
+
+#[cfg(feature = "fs")]
+pub use alias::{ALIASES_FILE, MalformedAlias, alias_file_problems, load_aliases};
+pub use ansi::{ansi_chunks, strip_ansi, trim_trailing_whitespace, truncate_width, visible_width};
+pub use balloon::{
+    BalloonMode, BalloonRequest, BalloonStyle, CompatLevel, Justify, WrapOptions, render_balloon,
+    wrap_message, wrap_message_with,
+};
+#[cfg(feature = "fs")]
+pub use balloon::{BalloonParseError, load_style};
+#[cfg(feature = "bidi")]
+pub use bidi::reorder_bidi;
+pub use color::{
+    CONSOLE_PALETTE, ColorMode, nearest_console_color, quantize_to_console, xterm_to_rgb,
+};
+#[cfg(feature = "figlet")]
+pub use figlet::{BUILTIN_FIGFONT, FigFont};
+#[cfg(all(feature = "figlet", feature = "fs"))]
+pub use figlet::{default_figlet_paths, load_figfont};
+#[cfg(feature = "fs")]
 pub use fortune::FortuneConfig;
-pub use pony::{PonyAsset, PonyMetadata};
+pub use html::ansi_to_html;
+#[cfg(feature = "fs")]
+pub use index::{PonyClass, PonyIndex, PonySummary, default_index_cache_path, iter_ponies};
+pub use layout::{compose_horizontal, compose_vertical};
+#[cfg(feature = "fs")]
+pub use lint::lint_pony_dir;
+pub use lint::{LintIssue, LintKind, LintReport, LintSeverity, lint_pony};
+#[cfg(feature = "fs")]
+pub use pack::AssetPack;
+pub use platform::{PATH_LIST_SEPARATOR, Platform};
+pub use pony::{
+    BalloonPlacement, DollarCommand, DollarCommands, PonyAsset, PonyMetadata, PonyParseError,
+    dollar_commands, parse_pony_strict,
+};
+#[cfg(feature = "fs")]
+pub use pony::{pony_file_problems, read_pony_strict};
+#[cfg(feature = "fs")]
+pub use renderer::Renderer;
+#[cfg(feature = "fs")]
+pub use search::{SearchField, SearchMatch, search_ponies};
+#[cfg(feature = "fs")]
+pub use source::{MessageSource, SourceOptions, SourcedMessage};
+pub use transform::{BuiltinTransform, COMBINING_MARK_LIMIT, MessageTransform};
+pub use ucsmap::{UcsMap, default_ucsmap_paths};
 
 #[derive(Debug, Error)]
 pub enum PonyError {
@@ -20,8 +102,34 @@ pub enum PonyError {
     NoMessage,
     #[error("pony '{name}' was not found")]
     PonyNotFound { name: String },
+    #[error("pony file '{}' does not exist", path.display())]
+    PonyFileNotFound { path: PathBuf },
+    #[error("malformed pony file {}: {source}", path.display())]
+    PonyParse {
+        path: PathBuf,
+        #[source]
+        source: pony::PonyParseError,
+    },
     #[error("balloon style '{name}' was not found")]
     BalloonNotFound { name: String },
+    #[error("invalid balloon definition: {0}")]
+    InvalidBalloon(String),
+    /// An installed style exists under the requested name, but no file for it parses.
+    #[cfg(feature = "fs")]
+    #[error("balloon style '{name}' could not be loaded: {source}")]
+    BalloonParse {
+        name: String,
+        #[source]
+        source: balloon::BalloonParseError,
+    },
+    #[error("FIGlet font '{name}' was not found")]
+    FigFontNotFound { name: String },
+    #[error("invalid FIGlet font: {0}")]
+    InvalidFigFont(String),
+    /// An asset pack that is neither an uncompressed `.tar` nor a `.zip`.
+    #[cfg(feature = "fs")]
+    #[error("unsupported asset pack format for {}: use an uncompressed .tar or a .zip", path.display())]
+    UnsupportedPack { path: PathBuf },
     #[error("io error for {path}: {source}")]
     Io {
         path: PathBuf,
@@ -32,6 +140,13 @@ pub enum PonyError {
     InvalidRegex(#[from] regex::Error),
     #[error("fortune selection failed: {0}")]
     Fortune(String),
+    #[error("pony '{pony}' has no quotes")]
+    NoQuotes { pony: String },
+    #[error("{quote}, and {fortune}")]
+    NoQuoteOrFortune {
+        quote: Box<PonyError>,
+        fortune: Box<PonyError>,
+    },
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -43,66 +158,175 @@ pub enum Mode {
 #[derive(Debug, Clone)]
 pub struct RenderConfig {
     pub message: String,
+    /// Messages drawn in balloons of their own, stacked above the one for `message`, top
+    /// first.
+    pub stack: Vec<String>,
     pub pony: String,
     pub pony_paths: Vec<PathBuf>,
     pub balloon: Option<String>,
     pub balloon_paths: Vec<PathBuf>,
     pub mode: Mode,
     pub wrap_width: usize,
+    /// How words wider than the wrap width are kept or broken.
+    pub wrap_options: WrapOptions,
+    pub balloon_placement: BalloonPlacement,
+    /// Use the built-in balloon style whenever `balloon` is unset, ignoring installed
+    /// defaults and pony preferences.
+    pub builtin_balloon: bool,
+    /// One-off style (e.g. from [`BalloonStyle::from_chars`]) that takes precedence over
+    /// `balloon` and every installed style. Reported as `"custom"`.
+    pub balloon_style: Option<BalloonStyle>,
+    /// Balloon geometry. [`CompatLevel::Upstream`] keeps upstream's output byte for byte,
+    /// oddities included; [`CompatLevel::Corrected`] sizes borders from their actual pieces.
+    pub compat: CompatLevel,
+    /// Render the message as a banner in this font before it enters the balloon.
+    #[cfg(feature = "figlet")]
+    pub figlet: Option<FigFont>,
+    /// Prefix the output with `\e[0m`, as upstream does. Embedders that manage terminal
+    /// state themselves (TUI widgets, HTML export) can turn this off.
+    pub leading_reset: bool,
+    /// End the output with `\e[0m` so no color leaks into whatever is printed next
+    /// (shell prompts, the rest of a MOTD). Ignored with [`ColorMode::Off`].
+    pub trailing_reset: bool,
+    /// How much color the output may use: [`ColorMode::Full`] passes escapes through,
+    /// [`ColorMode::Console`] quantizes to 16 colors and [`ColorMode::Off`] strips them.
+    pub color: ColorMode,
+    /// Keep at most this many output lines, dropping the bottom of the art.
+    pub max_height: Option<usize>,
+    /// Clip output lines to this many columns, keeping their escape sequences.
+    pub max_width: Option<usize>,
+    /// Drop the blank padding that balloons and pony bodies leave at the end of lines
+    /// (see [`trim_trailing_whitespace`]). Upstream keeps it.
+    pub trim_trailing_whitespace: bool,
+    /// Applied to the message, in order, before it is wrapped or set in a FIGlet font.
+    pub transforms: Vec<Arc<dyn MessageTransform>>,
+    /// Reorder right-to-left lines into visual order after wrapping (see [`reorder_bidi`]).
+    /// Leave off for terminals that implement bidi themselves.
+    #[cfg(feature = "bidi")]
+    pub bidi: bool,
+    /// Only load ponies and balloon styles whose file names match `pony`/`balloon` exactly,
+    /// as upstream does. Otherwise a name differing only in case is found when nothing
+    /// matches exactly.
+    #[cfg(feature = "fs")]
+    pub exact_names: bool,
+    /// Pony aliases (`tw` -> `twilight`) applied to `pony` before anything else, ahead of
+    /// the `aliases` files in the pony directories.
+    #[cfg(feature = "fs")]
+    pub aliases: BTreeMap<String, String>,
+    /// Mirror the pony left to right so it faces the other way. Directional characters
+    /// (`/`, `<`, `(`, half blocks, ...) are swapped for their mirror images; the balloon
+    /// text reads as usual.
+    pub flip: bool,
+    /// End the output with the pony's [`PonyAsset::display_name`], centered under the art.
+    pub show_name: bool,
+    /// Asset pack consulted before the pony/balloon search paths.
+    #[cfg(feature = "fs")]
+    pub pack: Option<AssetPack>,
 }
 
 impl Default for RenderConfig {
     fn default() -> Self {
         Self {
             message: String::new(),
+            stack: Vec::new(),
             pony: String::new(),
             pony_paths: default_pony_paths(),
             balloon: None,
             balloon_paths: default_balloon_paths(),
             mode: Mode::Say,
             wrap_width: 40,
+            wrap_options: WrapOptions::default(),
+            balloon_placement: BalloonPlacement::Anchor,
+            builtin_balloon: false,
+            balloon_style: None,
+            compat: CompatLevel::Upstream,
+            #[cfg(feature = "figlet")]
+            figlet: None,
+            leading_reset: true,
+            trailing_reset: false,
+            color: ColorMode::Full,
+            max_height: None,
+            max_width: None,
+            trim_trailing_whitespace: true,
+            transforms: Vec::new(),
+            #[cfg(feature = "bidi")]
+            bidi: false,
+            #[cfg(feature = "fs")]
+            exact_names: false,
+            #[cfg(feature = "fs")]
+            aliases: BTreeMap::new(),
+            flip: false,
+            show_name: false,
+            #[cfg(feature = "fs")]
+            pack: None,
         }
     }
 }
 
+#[cfg(feature = "fs")]
+impl RenderConfig {
+    /// `pony` with [`RenderConfig::aliases`] applied; `None` when it is blank.
+    pub(crate) fn requested_pony(&self) -> Option<&str> {
+        let name = Some(self.pony.as_str()).filter(|name| !name.trim().is_empty())?;
+        Some(self.aliases.get(name).map_or(name, String::as_str))
+    }
+}
+
+/// The share roots' pony directories. On Unix the XDG [`user_pony_dir`] comes first; on
+/// Windows `%APPDATA%` is already one of the share roots.
 pub fn default_pony_paths() -> Vec<PathBuf> {
-    vec![
-        PathBuf::from("/usr/share/ponysay/ponies"),
-        PathBuf::from("/usr/share/ponysay/extraponies"),
-        PathBuf::from("/usr/share/ponysay/ttyponies"),
-        PathBuf::from("/usr/local/share/ponysay/ponies"),
-        PathBuf::from("/usr/local/share/ponysay/extraponies"),
-        PathBuf::from("/usr/local/share/ponysay/ttyponies"),
-    ]
+    let user = user_pony_dir().filter(|_| Platform::current() == Platform::Unix);
+    user.into_iter()
+        .chain(platform::share_subdirs(
+            "ponysay",
+            &["ponies", "extraponies", "ttyponies"],
+        ))
+        .collect()
+}
+
+/// Where `iron-pony set-best` keeps `best.pony`: the user's own pony directory.
+pub fn user_pony_dir() -> Option<PathBuf> {
+    Platform::current()
+        .user_data_dir(platform::env_var)
+        .map(|data| data.join("ponysay").join("ponies"))
 }
 
 pub fn default_balloon_paths() -> Vec<PathBuf> {
-    vec![
-        PathBuf::from("/usr/share/ponysay/balloons"),
-        PathBuf::from("/usr/local/share/ponysay/balloons"),
-    ]
+    platform::share_subdirs("ponysay", &["balloons"])
 }
 
+pub fn default_quote_paths() -> Vec<PathBuf> {
+    platform::share_subdirs("ponysay", &["quotes"])
+}
+
+/// Sorted pony names under `pony_paths`, from the index cached at
+/// [`default_index_cache_path`].
+#[cfg(feature = "fs")]
 pub fn list_ponies(pony_paths: &[PathBuf]) -> Vec<String> {
+    PonyIndex::load(pony_paths, default_index_cache_path().as_deref()).names()
+}
+
+#[cfg(feature = "fs")]
+pub fn list_ponies_ucs(pony_paths: &[PathBuf], ucsmap: &UcsMap) -> Vec<String> {
     let mut names = BTreeSet::new();
-    for path in pony_paths {
-        for name in pony::list_pony_names(path) {
-            names.insert(name);
-        }
+    for name in list_ponies(pony_paths) {
+        names.insert(ucsmap.to_ucs(&name).to_string());
     }
     names.into_iter().collect()
 }
 
+#[cfg(feature = "fs")]
 pub fn list_balloons(balloon_paths: &[PathBuf]) -> Vec<String> {
     let mut names = BTreeSet::new();
-    for path in balloon_paths {
-        for name in balloon::list_balloon_names(path) {
-            names.insert(name);
-        }
+    for found in scan::scan_roots(balloon_paths, balloon::list_balloon_names) {
+        names.extend(found);
     }
     names.into_iter().collect()
 }
 
+/// `requested`, or else `best.pony` or a random pony from the index cached at
+/// [`default_index_cache_path`].
+#[cfg(feature = "fs")]
 pub fn select_pony(
     requested: Option<&str>,
     pony_paths: &[PathBuf],
@@ -112,20 +336,35 @@ pub fn select_pony(
         return Ok(name.to_string());
     }
 
+    let index = PonyIndex::load(pony_paths, default_index_cache_path().as_deref());
+    select_pony_indexed(None, pony_paths, &index, seed)
+}
+
+/// Like [`select_pony`], but draws random candidates from a prebuilt (possibly cached) index.
+#[cfg(feature = "fs")]
+pub fn select_pony_indexed(
+    requested: Option<&str>,
+    pony_paths: &[PathBuf],
+    index: &PonyIndex,
+    seed: Option<u64>,
+) -> Result<String, PonyError> {
+    if let Some(name) = requested {
+        return Ok(name.to_string());
+    }
+
     if let Some(best_path) = find_best_pony(pony_paths) {
         info!(path = %best_path.display(), "auto-selected best.pony");
         return Ok(best_path.to_string_lossy().to_string());
     }
 
-    let names = list_ponies(pony_paths);
+    let names = index.names();
     if names.is_empty() {
         return Err(PonyError::PonyNotFound {
             name: "<auto>".to_string(),
         });
     }
 
-    let mut rng = seeded_rng(seed);
-    let index = rng.random_range(0..names.len());
+    let index = random_index(names.len(), seed);
     let selected = names[index].clone();
     info!(
         pony = %selected,
@@ -135,51 +374,638 @@ pub fn select_pony(
     Ok(selected)
 }
 
+/// Up to `count` different ponies from `index`, in random order; a seeded draw is
+/// reproducible.
+#[cfg(feature = "fs")]
+pub fn sample_ponies(index: &PonyIndex, count: usize, seed: Option<u64>) -> Vec<String> {
+    let mut names = index.names();
+    let mut seeded = seed.map(random::PythonRandom::new);
+    let mut picked = Vec::with_capacity(count.min(names.len()));
+    while picked.len() < count && !names.is_empty() {
+        let index = match &mut seeded {
+            Some(random) => random.randbelow(names.len()),
+            None => rand::rng().random_range(0..names.len()),
+        };
+        picked.push(names.remove(index));
+    }
+    debug!(count = picked.len(), "sampled ponies");
+    picked
+}
+
+/// Loads the pony `name` from `pony_paths`, or from a file when `name` is a path. A file
+/// named exactly `name` or `name.pony` wins; failing that, unless `exact` (see
+/// [`RenderConfig::exact_names`]), one matching when case is ignored.
+#[cfg(feature = "fs")]
+pub fn load_pony(name: &str, pony_paths: &[PathBuf], exact: bool) -> Result<PonyAsset, PonyError> {
+    pony::load_pony(name, pony_paths, exact)
+}
+
+#[cfg(feature = "fs")]
 pub fn pick_fortune(config: &FortuneConfig) -> Result<String, PonyError> {
     fortune::pick_fortune(config).map_err(|error| PonyError::Fortune(error.to_string()))
 }
 
-pub fn render(config: &RenderConfig) -> Result<String, PonyError> {
-    if config.message.trim().is_empty() {
+/// Quotes attributed to `pony` in the upstream `quotes/` directories.
+///
+/// Quote files are named `<pony>[+<pony>...].<n>`; a quote is listed for every pony
+/// linked in its file name.
+#[cfg(feature = "fs")]
+pub fn list_quotes(pony: &str, quote_paths: &[PathBuf]) -> Vec<String> {
+    quote::list_quotes(pony, quote_paths)
+}
+
+#[cfg(feature = "fs")]
+pub fn pick_quote(
+    pony: &str,
+    quote_paths: &[PathBuf],
+    seed: Option<u64>,
+) -> Result<String, PonyError> {
+    let quotes = quote::list_quotes(pony, quote_paths);
+    if quotes.is_empty() {
+        return Err(PonyError::NoQuotes {
+            pony: pony.to_string(),
+        });
+    }
+
+    let index = random_index(quotes.len(), seed);
+    debug!(pony, index, choices = quotes.len(), "selected quote");
+    Ok(quotes[index].clone())
+}
+
+/// How many quote files the quote loader finds in `quote_paths`: those named like quotes,
+/// with a file found in an earlier directory hiding one of the same name in a later one.
+#[cfg(feature = "fs")]
+pub fn count_quote_files(quote_paths: &[PathBuf]) -> usize {
+    quote::quote_files(quote_paths).len()
+}
+
+/// Installed ponies that have at least one quote, with their quote counts.
+#[cfg(feature = "fs")]
+pub fn list_quoted_ponies(quote_paths: &[PathBuf], index: &PonyIndex) -> BTreeMap<String, usize> {
+    quote::quote_counts(quote_paths)
+        .into_iter()
+        .filter(|(pony, _)| index.path(pony).is_some())
+        .collect()
+}
+
+/// Picks a pony for quote mode: one of `requested` that has quotes, or any installed
+/// pony with quotes when `requested` is empty.
+#[cfg(feature = "fs")]
+pub fn select_quoted_pony(
+    requested: &[String],
+    quote_paths: &[PathBuf],
+    index: &PonyIndex,
+    seed: Option<u64>,
+) -> Result<String, PonyError> {
+    let counts = quote::quote_counts(quote_paths);
+    let candidates = if requested.is_empty() {
+        counts
+            .keys()
+            .filter(|pony| index.path(pony).is_some())
+            .cloned()
+            .collect::<Vec<_>>()
+    } else {
+        requested
+            .iter()
+            .filter(|pony| counts.contains_key(pony.as_str()))
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+
+    if candidates.is_empty() {
+        return Err(PonyError::NoQuotes {
+            pony: if requested.is_empty() {
+                "<auto>".to_string()
+            } else {
+                requested.join(", ")
+            },
+        });
+    }
+
+    let selected = candidates[random_index(candidates.len(), seed)].clone();
+    info!(
+        pony = %selected,
+        choices = candidates.len(),
+        "selected pony for quote mode"
+    );
+    Ok(selected)
+}
+
+/// A rendered pony along with what was resolved to produce it.
+#[derive(Debug, Clone)]
+pub struct RenderOutput {
+    pub pony: PonyAsset,
+    /// Balloon style name, or `"default"` for the built-in style.
+    pub balloon: String,
+    /// Output lines; joining with `\n` reproduces [`render`]'s text exactly.
+    pub lines: Vec<String>,
+}
+
+impl RenderOutput {
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+}
+
+/// Selects `config.pony` (a random installed pony when it is empty) and loads it,
+/// consulting `config.pack` before the search paths.
+#[cfg(feature = "fs")]
+pub fn resolve_pony(config: &RenderConfig, seed: Option<u64>) -> Result<PonyAsset, PonyError> {
+    let requested = config.requested_pony();
+    let name = select_pony(requested, &config.pony_paths, seed)?;
+    match config
+        .pack
+        .as_ref()
+        .and_then(|pack| pack.load_pony(&name, config.exact_names))
+    {
+        Some(packed) => packed,
+        None => pony::load_pony(&name, &config.pony_paths, config.exact_names),
+    }
+}
+
+/// [`resolve_pony`] followed by [`render`], for callers that do not need the asset.
+#[cfg(feature = "fs")]
+pub fn resolve_and_render(config: &RenderConfig) -> Result<String, PonyError> {
+    render(config, &resolve_pony(config, None)?)
+}
+
+/// Renders `config.message` with an already resolved `pony`; `config.pony` is ignored.
+#[cfg(feature = "fs")]
+pub fn render(config: &RenderConfig, pony: &PonyAsset) -> Result<String, PonyError> {
+    render_output(config, pony).map(|output| output.text())
+}
+
+#[cfg(feature = "fs")]
+pub fn render_output(config: &RenderConfig, pony: &PonyAsset) -> Result<RenderOutput, PonyError> {
+    render_output_with(config, pony, |key| {
+        renderer::resolve_style(key, &config.balloon_paths, config.pack.as_ref())
+    })
+}
+
+/// Columns between the two ponies of a [`render_dialogue`] exchange.
+#[cfg(feature = "fs")]
+const DIALOGUE_GAP: usize = 2;
+
+/// Renders a conversation: `messages` alternate between `left` and `right`, starting with
+/// `left`. Each exchange shows the two facing each other, `right` mirrored (`left` with
+/// [`RenderConfig::flip`]), with a blank line between exchanges. A blank message (or a
+/// missing last reply) leaves that pony listening without a balloon. `config.message`,
+/// `config.stack` and `config.pony` are ignored.
+#[cfg(feature = "fs")]
+pub fn render_dialogue(
+    config: &RenderConfig,
+    left: &PonyAsset,
+    right: &PonyAsset,
+    messages: &[String],
+) -> Result<String, PonyError> {
+    render_dialogue_with(config, left, right, messages, |key| {
+        renderer::resolve_style(key, &config.balloon_paths, config.pack.as_ref())
+    })
+}
+
+#[cfg(feature = "fs")]
+fn render_dialogue_with(
+    config: &RenderConfig,
+    left: &PonyAsset,
+    right: &PonyAsset,
+    messages: &[String],
+    resolve_style: impl Fn(&StyleKey) -> Result<(String, BalloonStyle), PonyError>,
+) -> Result<String, PonyError> {
+    if messages.iter().all(|message| message.trim().is_empty()) {
         return Err(PonyError::NoMessage);
     }
 
-    let requested_pony = if config.pony.trim().is_empty() {
-        None
+    // Size caps and the trailing reset apply to the conversation, not to each pony.
+    let side = RenderConfig {
+        stack: Vec::new(),
+        max_width: None,
+        max_height: None,
+        trailing_reset: false,
+        ..config.clone()
+    };
+    let speak = |pony: &PonyAsset, message: Option<&String>, flip| match message
+        .filter(|message| !message.trim().is_empty())
+    {
+        Some(message) => {
+            let config = RenderConfig {
+                message: message.clone(),
+                flip,
+                ..side.clone()
+            };
+            render_output_with(&config, pony, &resolve_style).map(|output| output.lines)
+        }
+        None => silent_lines(
+            &RenderConfig {
+                flip,
+                ..side.clone()
+            },
+            pony,
+            false,
+            &resolve_style,
+        ),
+    };
+
+    info!(
+        left = %left.path.display(),
+        right = %right.path.display(),
+        messages = messages.len(),
+        "rendering dialogue"
+    );
+    let mut exchanges = Vec::new();
+    for exchange in messages.chunks(2) {
+        let blocks = [
+            speak(left, exchange.first(), config.flip)?,
+            speak(right, exchange.get(1), !config.flip)?,
+        ];
+        exchanges.push(compose_horizontal(&blocks, DIALOGUE_GAP));
+    }
+    let lines = compose_vertical(&exchanges, 1);
+    Ok(finish_lines(&lines.join("\n"), config).join("\n"))
+}
+
+/// Columns between neighbouring ponies of a [`render_gallery`].
+#[cfg(feature = "fs")]
+const GALLERY_GAP: usize = 3;
+
+/// Renders `ponies` without balloons, each captioned with its display name, in rows that fit in
+/// `width` columns (a pony wider than that gets a row to itself). Only the drawing options
+/// of `config` are used; `config.message` and `config.pony` are ignored.
+#[cfg(feature = "fs")]
+pub fn render_gallery(
+    config: &RenderConfig,
+    ponies: &[PonyAsset],
+    width: usize,
+) -> Result<String, PonyError> {
+    render_gallery_with(config, ponies, width, |key| {
+        renderer::resolve_style(key, &config.balloon_paths, config.pack.as_ref())
+    })
+}
+
+#[cfg(feature = "fs")]
+fn render_gallery_with(
+    config: &RenderConfig,
+    ponies: &[PonyAsset],
+    width: usize,
+    resolve_style: impl Fn(&StyleKey) -> Result<(String, BalloonStyle), PonyError>,
+) -> Result<String, PonyError> {
+    let tile = RenderConfig {
+        max_width: None,
+        max_height: None,
+        trailing_reset: false,
+        show_name: true,
+        ..config.clone()
+    };
+    let mut rows: Vec<Vec<Vec<String>>> = Vec::new();
+    let mut row_width = 0;
+    for pony in ponies {
+        let mut lines = silent_lines(&tile, pony, true, &resolve_style)?;
+        // The blank `$balloon$` line and any padding above the art.
+        let blank = lines
+            .iter()
+            .take_while(|line| strip_ansi(line).trim().is_empty())
+            .count();
+        lines.drain(..blank);
+        let tile_width = lines
+            .iter()
+            .map(|line| visible_width(line))
+            .max()
+            .unwrap_or(0);
+        match rows.last_mut() {
+            Some(row) if row_width + GALLERY_GAP + tile_width <= width => {
+                row.push(lines);
+                row_width += GALLERY_GAP + tile_width;
+            }
+            _ => {
+                rows.push(vec![lines]);
+                row_width = tile_width;
+            }
+        }
+    }
+
+    info!(
+        ponies = ponies.len(),
+        rows = rows.len(),
+        width,
+        "rendering gallery"
+    );
+    let rows = rows
+        .iter()
+        .map(|row| compose_horizontal(row, GALLERY_GAP))
+        .collect::<Vec<_>>();
+    let lines = compose_vertical(&rows, 1);
+    Ok(finish_lines(&lines.join("\n"), config).join("\n"))
+}
+
+/// `pony` drawn without a balloon: the anchor is left blank, and with `blank_links` the
+/// links to it are blanked out too.
+#[cfg(feature = "fs")]
+fn silent_lines(
+    config: &RenderConfig,
+    pony: &PonyAsset,
+    blank_links: bool,
+    resolve_style: impl FnOnce(&StyleKey) -> Result<(String, BalloonStyle), PonyError>,
+) -> Result<Vec<String>, PonyError> {
+    let mode = balloon_mode(config.mode);
+    let (_, mut style) = balloon_style(config, pony, mode, resolve_style)?;
+    if blank_links {
+        let blank = |link: &str| " ".repeat(visible_width(link));
+        style = BalloonStyle {
+            link: blank(&style.link),
+            link_mirror: blank(&style.link_mirror),
+            link_cross: blank(&style.link_cross),
+            ..style
+        };
+    }
+    let mut text = render_asset(
+        Vec::new(),
+        pony,
+        &style,
+        mode,
+        config.balloon_placement,
+        config.leading_reset,
+        config.flip,
+    );
+    if config.show_name {
+        push_caption(&mut text, pony, &style, mode, config.flip);
+    }
+    Ok(finish_lines(&text, config))
+}
+
+/// Appends `pony`'s display name on a line of its own, centered under the art of `text`.
+#[cfg(feature = "fs")]
+fn push_caption(
+    text: &mut String,
+    pony: &PonyAsset,
+    style: &BalloonStyle,
+    mode: BalloonMode,
+    flipped: bool,
+) {
+    let name = pony.display_name();
+    let (art_width, _) = pony::art_size(&pony.body, style, mode);
+    // Mirrored art ends at the right edge of the widest line rather than starting at 0.
+    let art_start = if flipped {
+        let width = text.split('\n').map(visible_width).max().unwrap_or(0);
+        width.saturating_sub(art_width)
     } else {
-        Some(config.pony.as_str())
+        0
     };
-    let pony_name = select_pony(requested_pony, &config.pony_paths, None)?;
+    let indent = art_start + art_width.saturating_sub(visible_width(&name)) / 2;
+    text.push('\n');
+    text.push_str(&" ".repeat(indent));
+    text.push_str(&name);
+}
+
+/// [`render_output`] with the balloon style looked up by `resolve_style`, unless the
+/// config carries one outright.
+#[cfg(feature = "fs")]
+fn render_output_with(
+    config: &RenderConfig,
+    pony: &PonyAsset,
+    resolve_style: impl FnOnce(&StyleKey) -> Result<(String, BalloonStyle), PonyError>,
+) -> Result<RenderOutput, PonyError> {
+    if config.message.trim().is_empty() {
+        return Err(PonyError::NoMessage);
+    }
 
     info!(
-        pony = %pony_name,
+        pony = %pony.path.display(),
         balloon = config.balloon.as_deref().unwrap_or("<default>"),
         width = config.wrap_width,
         mode = ?config.mode,
         "rendering ponysay output"
     );
 
-    let pony = pony::load_pony(&pony_name, &config.pony_paths)?;
-    let mode = match config.mode {
-        Mode::Say => BalloonMode::Say,
-        Mode::Think => BalloonMode::Think,
+    let mode = balloon_mode(config.mode);
+    let (balloon_name, style) = balloon_style(config, pony, mode, resolve_style)?;
+
+    debug!(pony_path = %pony.path.display(), "loaded pony template");
+    let text_width = balloon::text_width(&style, config.wrap_width, config.compat);
+    // Transformed text, and whether it is already laid out as a FIGlet banner.
+    let prepared = config
+        .stack
+        .iter()
+        .chain([&config.message])
+        .map(|message| {
+            let message = config
+                .transforms
+                .iter()
+                .fold(message.clone(), |message, transform| {
+                    ansi::map_text(&message, |text| transform.apply(text))
+                });
+            #[cfg(feature = "figlet")]
+            if let Some(font) = &config.figlet {
+                return (font.render(&message, text_width).join("\n"), true);
+            }
+            (message, false)
+        })
+        .collect::<Vec<_>>();
+    let requests = prepared
+        .iter()
+        .map(|(message, banner)| BalloonRequest {
+            message,
+            wrap: (!banner).then_some(text_width),
+            wrap_options: config.wrap_options.clone(),
+            #[cfg(feature = "bidi")]
+            bidi: config.bidi,
+            compat: config.compat,
+            ..BalloonRequest::default()
+        })
+        .collect();
+    let mut text = render_asset(
+        requests,
+        pony,
+        &style,
+        mode,
+        config.balloon_placement,
+        config.leading_reset,
+        config.flip,
+    );
+    if config.show_name {
+        push_caption(&mut text, pony, &style, mode, config.flip);
+    }
+    Ok(RenderOutput {
+        pony: pony.clone(),
+        balloon: balloon_name,
+        lines: finish_lines(&text, config),
+    })
+}
+
+/// The balloon style name and style for `pony`: the config's own style, reported as
+/// `"custom"`, or whatever `resolve_style` finds.
+#[cfg(feature = "fs")]
+fn balloon_style(
+    config: &RenderConfig,
+    pony: &PonyAsset,
+    mode: BalloonMode,
+    resolve_style: impl FnOnce(&StyleKey) -> Result<(String, BalloonStyle), PonyError>,
+) -> Result<(String, BalloonStyle), PonyError> {
+    match &config.balloon_style {
+        Some(style) => Ok(("custom".to_string(), style.clone())),
+        None => resolve_style(&StyleKey::new(config, pony, mode)),
+    }
+}
+
+/// Renders from in-memory pony and balloon file contents, without touching the filesystem.
+///
+/// `balloon_source` uses the upstream balloon file format; `None` selects the built-in
+/// style for `mode`.
+pub fn render_from_sources(
+    message: &str,
+    pony_source: &str,
+    balloon_source: Option<&str>,
+    mode: Mode,
+    wrap_width: usize,
+) -> Result<String, PonyError> {
+    let config = RenderConfig {
+        message: message.to_string(),
+        mode,
+        wrap_width,
+        ..RenderConfig::default()
     };
+    render_from_sources_with(&config, pony_source, balloon_source)
+}
 
-    let style = balloon::load_style(config.balloon.as_deref(), &config.balloon_paths, mode)
-        .ok_or_else(|| PonyError::BalloonNotFound {
-            name: config
-                .balloon
-                .clone()
-                .unwrap_or_else(|| "<default>".to_string()),
-        })?;
+/// [`render_from_sources`] with the message, mode, wrap width and options, balloon geometry,
+/// leading reset, [`RenderConfig::flip`] and trailing whitespace trimming taken from `config`.
+/// Nothing is looked up, so its pony, balloon and search path settings are ignored.
+pub fn render_from_sources_with(
+    config: &RenderConfig,
+    pony_source: &str,
+    balloon_source: Option<&str>,
+) -> Result<String, PonyError> {
+    if config.message.trim().is_empty() {
+        return Err(PonyError::NoMessage);
+    }
 
-    debug!(pony_path = %pony.path.display(), "loaded pony template");
+    let mode = balloon_mode(config.mode);
+    let pony = pony::asset_from_source(PathBuf::new(), pony_source);
+    let style = match balloon_source {
+        Some(raw) => balloon::parse_style(raw),
+        None => BalloonStyle::default_for_mode(mode),
+    };
 
-    let bubble = balloon::render_balloon(&config.message, config.wrap_width, &style);
-    let rendered = pony::insert_balloon(&pony.body, &bubble, &style);
-    Ok(format!("\u{1b}[0m{rendered}"))
+    info!(width = config.wrap_width, mode = ?config.mode, "rendering from in-memory sources");
+    let request = BalloonRequest {
+        wrap: Some(balloon::text_width(
+            &style,
+            config.wrap_width.max(1),
+            config.compat,
+        )),
+        wrap_options: config.wrap_options.clone(),
+        compat: config.compat,
+        ..BalloonRequest::new(&config.message)
+    };
+    let text = render_asset(
+        vec![request],
+        &pony,
+        &style,
+        mode,
+        BalloonPlacement::Anchor,
+        config.leading_reset,
+        config.flip,
+    );
+    if !config.trim_trailing_whitespace {
+        return Ok(text);
+    }
+    Ok(text
+        .split('\n')
+        .map(trim_trailing_whitespace)
+        .collect::<Vec<_>>()
+        .join("\n"))
 }
 
+/// Draws a balloon per request into `pony`, stacked top to bottom. The last one is the
+/// balloon the pony speaks, grown to the `$balloonW,H$` anchor size. A `mirrored` pony is
+/// flipped left to right around its balloon, which keeps reading normally. Without requests
+/// the anchor is left blank.
+fn render_asset(
+    mut requests: Vec<BalloonRequest<'_>>,
+    pony: &PonyAsset,
+    style: &BalloonStyle,
+    mode: BalloonMode,
+    placement: BalloonPlacement,
+    leading_reset: bool,
+    mirrored: bool,
+) -> String {
+    let (min_width, min_height) = pony::balloon_anchor_size(&pony.body);
+    if let Some(last) = requests.last_mut() {
+        last.min_width = last.min_width.max(min_width);
+        last.min_height = last.min_height.max(min_height);
+    }
+    let mut bubble = requests
+        .iter()
+        .flat_map(|request| render_balloon(request, style))
+        .collect::<Vec<_>>();
+    if bubble.is_empty() {
+        bubble.push(String::new());
+    }
+    let link_rows = pony.metadata.link_rows().unwrap_or(0);
+    let mut out = String::new();
+    if leading_reset {
+        out.push_str("\u{1b}[0m");
+    }
+    if mirrored {
+        let mut art = String::new();
+        let placeholder = layout::balloon_placeholder(&bubble);
+        pony::insert_balloon_into(
+            &mut art,
+            &pony.body,
+            &placeholder,
+            style,
+            mode,
+            link_rows,
+            placement,
+        );
+        out.push_str(&layout::mirror_around_balloon(&art, &bubble));
+    } else {
+        pony::insert_balloon_into(
+            &mut out, &pony.body, &bubble, style, mode, link_rows, placement,
+        );
+    }
+    out
+}
+
+/// Applies the output-wide knobs of `config`: color mode, size caps and trailing reset.
+#[cfg(feature = "fs")]
+fn finish_lines(text: &str, config: &RenderConfig) -> Vec<String> {
+    let mut lines = text
+        .split('\n')
+        .map(|line| {
+            let line = config.color.apply(line);
+            let line = match config.max_width {
+                Some(width) => truncate_width(&line, width),
+                None => line.into_owned(),
+            };
+            if config.trim_trailing_whitespace {
+                trim_trailing_whitespace(&line)
+            } else {
+                line
+            }
+        })
+        .collect::<Vec<_>>();
+    if let Some(max_height) = config.max_height
+        && lines.len() > max_height
+    {
+        debug!(lines = lines.len(), max_height, "capping output height");
+        lines.truncate(max_height);
+    }
+    if config.trailing_reset
+        && config.color != ColorMode::Off
+        && let Some(last) = lines.last_mut()
+    {
+        last.push_str("\u{1b}[0m");
+    }
+    lines
+}
+
+fn balloon_mode(mode: Mode) -> BalloonMode {
+    match mode {
+        Mode::Say => BalloonMode::Say,
+        Mode::Think => BalloonMode::Think,
+    }
+}
+
+#[cfg(feature = "fs")]
 fn find_best_pony(pony_paths: &[PathBuf]) -> Option<PathBuf> {
     for root in pony_paths {
         let candidate = root.join("best.pony");
@@ -194,25 +1020,23 @@ fn find_best_pony(pony_paths: &[PathBuf]) -> Option<PathBuf> {
     None
 }
 
-fn seeded_rng(seed: Option<u64>) -> StdRng {
+/// Picks an index in `0..len`. A seeded pick is the index upstream would pick after
+/// `random.seed(seed)`: CPython's `random.randrange(len)` over the sorted candidates.
+#[cfg(feature = "fs")]
+fn random_index(len: usize, seed: Option<u64>) -> usize {
     match seed {
-        Some(value) => StdRng::seed_from_u64(value),
-        None => {
-            let mut entropy = rand::rng();
-            StdRng::from_rng(&mut entropy)
-        }
+        Some(seed) => random::PythonRandom::new(seed).randbelow(len),
+        None => rand::rng().random_range(0..len),
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "fs"))]
 mod tests {
     use super::*;
     use std::fs;
 
     #[test]
     fn render_inserts_balloon() {
-        let mut config = RenderConfig::default();
-        config.message = "hello world".to_string();
         let tmp = tempfile::tempdir().expect("tempdir");
         let pony_dir = tmp.path().join("ponies");
         fs::create_dir_all(&pony_dir).expect("pony dir");
@@ -222,16 +1046,378 @@ mod tests {
         )
         .expect("write pony");
 
-        config.pony = "default".to_string();
-        config.balloon = None;
-        config.pony_paths = vec![pony_dir];
-        config.balloon_paths = vec![];
+        let config = RenderConfig {
+            message: "hello world".to_string(),
+            pony: "default".to_string(),
+            balloon: None,
+            pony_paths: vec![pony_dir],
+            balloon_paths: vec![],
+            ..RenderConfig::default()
+        };
 
-        let out = render(&config).expect("rendered");
+        let out = resolve_and_render(&config).expect("rendered");
         assert!(out.contains("hello world"));
         assert!(out.contains("\\"));
     }
 
+    #[test]
+    fn stacked_messages_get_their_own_balloons() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        fs::write(
+            tmp.path().join("stack.pony"),
+            "$balloon$\n  $\\$\n   pony\n",
+        )
+        .expect("write");
+        let config = RenderConfig {
+            message: "last".to_string(),
+            stack: vec!["first".to_string(), "second".to_string()],
+            pony: "stack".to_string(),
+            pony_paths: vec![tmp.path().to_path_buf()],
+            builtin_balloon: true,
+            leading_reset: false,
+            ..RenderConfig::default()
+        };
+
+        let out = strip_ansi(&resolve_and_render(&config).expect("rendered"));
+        let rows = out.lines().collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            [
+                " _______",
+                "< first >",
+                " -------",
+                " ________",
+                "< second >",
+                " --------",
+                " ______",
+                "< last >",
+                " ------",
+                "  \\",
+                "   pony",
+            ]
+        );
+    }
+
+    #[test]
+    fn flip_mirrors_the_art_but_not_the_balloon() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        fs::write(
+            tmp.path().join("bird.pony"),
+            "$balloon$\n $\\$\n  \u{1b}[33m(o>\u{1b}[0m\n",
+        )
+        .expect("write");
+        let config = RenderConfig {
+            message: "(hi)".to_string(),
+            pony: "bird".to_string(),
+            pony_paths: vec![tmp.path().to_path_buf()],
+            builtin_balloon: true,
+            leading_reset: false,
+            flip: true,
+            ..RenderConfig::default()
+        };
+
+        let out = resolve_and_render(&config).expect("rendered");
+        assert_eq!(
+            strip_ansi(&out).lines().collect::<Vec<_>>(),
+            [" ______", "< (hi) >", " ------", "      /", "   <o)"]
+        );
+        assert!(out.ends_with("\u{1b}[0m\u{1b}[33m<o)\u{1b}[0m"));
+
+        let source = fs::read_to_string(tmp.path().join("bird.pony")).expect("read");
+        let in_memory = render_from_sources_with(&config, &source, None).expect("rendered");
+        assert_eq!(in_memory, out);
+    }
+
+    #[test]
+    fn show_name_captions_under_the_art() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        fs::write(
+            tmp.path().join("cat.pony"),
+            "$$$\nNAME: Tom\n$$$\n$balloon$\n/\\_/\\  \n( o.o )\n",
+        )
+        .expect("write");
+        fs::write(tmp.path().join("ox.pony"), "$balloon$\nOX\n").expect("write");
+        let config = RenderConfig {
+            message: "hi".to_string(),
+            pony: "cat".to_string(),
+            pony_paths: vec![tmp.path().to_path_buf()],
+            builtin_balloon: true,
+            leading_reset: false,
+            show_name: true,
+            ..RenderConfig::default()
+        };
+
+        let out = strip_ansi(&resolve_and_render(&config).expect("rendered"));
+        assert_eq!(out.lines().last(), Some("  Tom"));
+        let ox = RenderConfig {
+            pony: "ox".to_string(),
+            ..config
+        };
+        let out = strip_ansi(&resolve_and_render(&ox).expect("rendered"));
+        assert_eq!(out.lines().last(), Some("ox"));
+    }
+
+    #[test]
+    fn gallery_captions_ponies_in_rows_that_fit() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        fs::write(tmp.path().join("a.pony"), "$balloon$\n $\\$\nAA\n").expect("write");
+        fs::write(
+            tmp.path().join("bb.pony"),
+            "$$$\nNAME: Bee\n$$$\n$balloon$\nBBB\n",
+        )
+        .expect("write");
+        let config = RenderConfig {
+            pony_paths: vec![tmp.path().to_path_buf()],
+            builtin_balloon: true,
+            leading_reset: false,
+            ..RenderConfig::default()
+        };
+        let index = PonyIndex::scan(&config.pony_paths);
+        let mut names = sample_ponies(&index, 5, Some(7));
+        assert_eq!(names.len(), 2);
+        names.sort();
+        let ponies = names
+            .iter()
+            .map(|name| load_pony(name, &config.pony_paths, false).expect("pony"))
+            .collect::<Vec<_>>();
+
+        let gallery = |width| render_gallery(&config, &ponies, width).expect("rendered");
+        assert_eq!(gallery(8), "AA   BBB\na    Bee");
+        assert_eq!(gallery(7), "AA\na\n\nBBB\nBee");
+    }
+
+    #[test]
+    fn dialogue_faces_ponies_each_other() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        fs::write(tmp.path().join("bird.pony"), "$balloon$\n $\\$\n  (o>\n").expect("write");
+        let config = RenderConfig {
+            pony_paths: vec![tmp.path().to_path_buf()],
+            builtin_balloon: true,
+            leading_reset: false,
+            ..RenderConfig::default()
+        };
+        let bird = load_pony("bird", &config.pony_paths, false).expect("bird");
+        let messages = ["hi", "yo", "bye"].map(String::from);
+
+        let out = render_dialogue(&config, &bird, &bird, &messages).expect("rendered");
+        assert_eq!(
+            strip_ansi(&out).lines().collect::<Vec<_>>(),
+            [
+                " ____    ____",
+                "< hi >  < yo >",
+                " ----    ----",
+                " \\          /",
+                "  (o>    <o)",
+                "",
+                " _____",
+                "< bye >",
+                " -----",
+                " \\          /",
+                "  (o>    <o)",
+            ]
+        );
+        assert!(matches!(
+            render_dialogue(&config, &bird, &bird, &[" ".to_string()]),
+            Err(PonyError::NoMessage)
+        ));
+    }
+
+    #[test]
+    fn render_honors_pony_balloon_preference() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let pony_dir = tmp.path().join("ponies");
+        let balloon_dir = tmp.path().join("balloons");
+        fs::create_dir_all(&pony_dir).expect("pony dir");
+        fs::create_dir_all(&balloon_dir).expect("balloon dir");
+        fs::write(
+            pony_dir.join("fancy.pony"),
+            "$$$\nBALLOON: stars.think stars.say\n$$$\n$balloon$\npony\n",
+        )
+        .expect("write pony");
+        fs::write(balloon_dir.join("stars.say"), "ww:* \nee: *\n").expect("write say");
+        fs::write(balloon_dir.join("stars.think"), "ww:~ \nee: ~\n").expect("write think");
+
+        let mut config = RenderConfig {
+            message: "hi".to_string(),
+            pony: "fancy".to_string(),
+            pony_paths: vec![pony_dir],
+            balloon_paths: vec![balloon_dir],
+            ..RenderConfig::default()
+        };
+
+        let say = resolve_and_render(&config).expect("say render");
+        assert!(say.contains("* hi"));
+
+        config.mode = Mode::Think;
+        let think = resolve_and_render(&config).expect("think render");
+        assert!(think.contains("~ hi"));
+    }
+
+    #[test]
+    fn render_output_reports_resolution() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let pony_dir = tmp.path().join("ponies");
+        fs::create_dir_all(&pony_dir).expect("pony dir");
+        fs::write(
+            pony_dir.join("named.pony"),
+            "$$$\nNAME: Named Pony\n$$$\n$balloon$\npony\n",
+        )
+        .expect("write pony");
+
+        let config = RenderConfig {
+            message: "hi".to_string(),
+            pony: "named".to_string(),
+            pony_paths: vec![pony_dir],
+            balloon_paths: vec![],
+            ..RenderConfig::default()
+        };
+        let pony = resolve_pony(&config, None).expect("resolved");
+        let output = render_output(&config, &pony).expect("rendered");
+        assert_eq!(output.pony.display_name(), "Named Pony");
+        assert_eq!(output.balloon, "default");
+        let text = resolve_and_render(&config).expect("text");
+        assert_eq!(output.text(), text);
+
+        let plain = RenderConfig {
+            leading_reset: false,
+            ..config.clone()
+        };
+        let plain = resolve_and_render(&plain).expect("plain");
+        assert_eq!(Some(plain.as_str()), text.strip_prefix("\u{1b}[0m"));
+
+        let custom = RenderConfig {
+            balloon: Some("missing".to_string()),
+            balloon_style: Some(
+                BalloonStyle::from_chars("+-+|+-+|", BalloonMode::Say).expect("chars"),
+            ),
+            ..config
+        };
+        let output = render_output(&custom, &pony).expect("custom balloon");
+        assert_eq!(output.balloon, "custom");
+        let text = strip_ansi(&output.text());
+        assert!(text.contains("+----+\n| hi |\n+----+"), "{text}");
+    }
+
+    #[test]
+    fn render_output_applies_color_height_and_trailing_reset() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        fs::write(
+            tmp.path().join("red.pony"),
+            "$balloon$\n\u{1b}[38;5;196mred\none\ntwo\nthree\n",
+        )
+        .expect("write pony");
+        let config = RenderConfig {
+            message: "hi".to_string(),
+            pony: "red".to_string(),
+            pony_paths: vec![tmp.path().to_path_buf()],
+            balloon_paths: vec![],
+            builtin_balloon: true,
+            color: ColorMode::Console,
+            max_height: Some(5),
+            trailing_reset: true,
+            ..RenderConfig::default()
+        };
+
+        let pony = resolve_pony(&config, None).expect("resolved");
+        let output = render_output(&config, &pony).expect("rendered");
+        assert_eq!(output.lines.len(), 5, "{:?}", output.lines);
+        assert_eq!(output.lines[3], "\u{1b}[31mred");
+        assert_eq!(output.lines[4], "one\u{1b}[0m");
+
+        let plain = RenderConfig {
+            color: ColorMode::Off,
+            max_height: None,
+            max_width: Some(2),
+            ..config
+        };
+        let output = render_output(&plain, &pony).expect("plain");
+        assert!(output.lines.iter().all(|line| !line.contains('\u{1b}')));
+        assert_eq!(output.lines[3..], ["re", "on", "tw", "th"]);
+    }
+
+    #[test]
+    fn pick_quote_is_seeded_and_reports_missing_quotes() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let quotes = tmp.path().join("quotes");
+        fs::create_dir_all(&quotes).expect("mkdir");
+        fs::write(quotes.join("rarity+rarity2.0"), "Darling!\n").expect("write");
+        fs::write(quotes.join("rarity.1"), "How dreadful.\n").expect("write");
+        let roots = vec![quotes];
+
+        let first = pick_quote("rarity", &roots, Some(3)).expect("quote");
+        assert_eq!(pick_quote("rarity", &roots, Some(3)).expect("quote"), first);
+        assert_eq!(
+            pick_quote("rarity2", &roots, None).expect("quote"),
+            "Darling!"
+        );
+        assert!(matches!(
+            pick_quote("twilight", &roots, Some(1)),
+            Err(PonyError::NoQuotes { .. })
+        ));
+    }
+
+    #[test]
+    fn quote_mode_only_selects_installed_ponies_with_quotes() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let quotes = tmp.path().join("quotes");
+        let ponies = tmp.path().join("ponies");
+        fs::create_dir_all(&quotes).expect("mkdir");
+        fs::create_dir_all(&ponies).expect("mkdir");
+        fs::write(quotes.join("rarity+spike.0"), "Gems!\n").expect("write");
+        fs::write(quotes.join("rarity.1"), "Darling.\n").expect("write");
+        fs::write(ponies.join("rarity.pony"), "$balloon$\n").expect("write");
+        fs::write(ponies.join("twilight.pony"), "$balloon$\n").expect("write");
+        let quote_paths = vec![quotes];
+        let index = PonyIndex::scan(&[ponies]);
+
+        let listed = list_quoted_ponies(&quote_paths, &index);
+        assert_eq!(
+            listed.into_iter().collect::<Vec<_>>(),
+            vec![("rarity".to_string(), 2)]
+        );
+        assert_eq!(
+            select_quoted_pony(&[], &quote_paths, &index, Some(9)).expect("pony"),
+            "rarity"
+        );
+        assert!(matches!(
+            select_quoted_pony(&["twilight".to_string()], &quote_paths, &index, None),
+            Err(PonyError::NoQuotes { .. })
+        ));
+    }
+
+    #[test]
+    fn render_from_sources_matches_file_render() {
+        let source = "$$$\nNAME: Mem\n$$$\n$balloon$\n  $\\$\n   pony\n";
+        let style = "ww:[ \nee: ]\n";
+        let out =
+            render_from_sources("in memory", source, Some(style), Mode::Say, 40).expect("rendered");
+        assert!(out.starts_with("\u{1b}[0m"));
+        assert!(out.contains("[ in memory"));
+        assert!(!out.contains("NAME: Mem"));
+        assert!(out.lines().all(|line| !strip_ansi(line).ends_with(' ')));
+        assert!(matches!(
+            render_from_sources(" ", source, None, Mode::Think, 40),
+            Err(PonyError::NoMessage)
+        ));
+
+        let lopsided = "nw:+\n:|\nn:-\nne:+\nww:[[ \nee: ]\nw:[[ \ne: ]\nsw:+\ns:-\nse:+\n";
+        let config = RenderConfig {
+            message: "hi".to_string(),
+            compat: CompatLevel::Corrected,
+            leading_reset: false,
+            ..RenderConfig::default()
+        };
+        let out = render_from_sources_with(&config, source, Some(lopsided)).expect("rendered");
+        assert!(out.contains("+-----+"));
+        assert!(out.contains("|     ]"));
+        let untrimmed = RenderConfig {
+            trim_trailing_whitespace: false,
+            ..config
+        };
+        let out = render_from_sources_with(&untrimmed, source, Some(lopsided)).expect("rendered");
+        assert!(out.lines().any(|line| strip_ansi(line).ends_with(' ')));
+    }
+
     #[test]
     fn select_pony_prefers_best_pony() {
         let tmp = tempfile::tempdir().expect("tempdir");
@@ -252,9 +1438,12 @@ mod tests {
         fs::write(pony_dir.join("alpha.pony"), "$$$\n$$$\na\n").expect("write alpha");
         fs::write(pony_dir.join("beta.pony"), "$$$\n$$$\nb\n").expect("write beta");
 
-        let first = select_pony(None, &[pony_dir.clone()], Some(42)).expect("first");
-        let second = select_pony(None, &[pony_dir], Some(42)).expect("second");
+        let first = select_pony(None, std::slice::from_ref(&pony_dir), Some(42)).expect("first");
+        let second = select_pony(None, std::slice::from_ref(&pony_dir), Some(42)).expect("second");
         assert_eq!(first, second);
-        assert!(first == "alpha" || first == "beta");
+        // random.seed(42); random.randrange(2) == 0, random.seed(7) gives 1.
+        assert_eq!(first, "alpha");
+        let third = select_pony(None, &[pony_dir], Some(7)).expect("third");
+        assert_eq!(third, "beta");
     }
 }
@@ -0,0 +1,358 @@
+use std::collections::HashSet;
+use std::fmt;
+#[cfg(feature = "fs")]
+use std::path::Path;
+use std::path::PathBuf;
+
+#[cfg(feature = "fs")]
+use tracing::debug;
+use tracing::trace;
+#[cfg(feature = "fs")]
+use walkdir::WalkDir;
+
+use crate::ansi::{self, Token, visible_width};
+#[cfg(feature = "fs")]
+use crate::encoding;
+use crate::pony::{DollarCommand, PonyParseError, dollar_commands, parse_problems};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LintSeverity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for LintSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Warning => f.write_str("warning"),
+            Self::Error => f.write_str("error"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintKind {
+    UnterminatedHeader,
+    BadTag,
+    StrayDollar,
+    UnterminatedEscape,
+    UnresetColor,
+    MissingBalloonAnchor,
+    UnknownVariable,
+    TrailingWhitespace,
+    WidthExceeded,
+    Tab,
+    /// The file could not be read or decoded at all.
+    Unreadable,
+}
+
+impl LintKind {
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::UnterminatedHeader => "unterminated-header",
+            Self::BadTag => "bad-tag",
+            Self::StrayDollar => "stray-dollar",
+            Self::UnterminatedEscape => "unterminated-escape",
+            Self::UnresetColor => "unreset-color",
+            Self::MissingBalloonAnchor => "missing-balloon-anchor",
+            Self::UnknownVariable => "unknown-variable",
+            Self::TrailingWhitespace => "trailing-whitespace",
+            Self::WidthExceeded => "width-exceeded",
+            Self::Tab => "tab",
+            Self::Unreadable => "unreadable",
+        }
+    }
+
+    pub fn severity(self) -> LintSeverity {
+        match self {
+            Self::UnterminatedHeader | Self::UnterminatedEscape | Self::Unreadable => {
+                LintSeverity::Error
+            }
+            _ => LintSeverity::Warning,
+        }
+    }
+}
+
+/// A single finding; `line` is 1-based within the whole file (0 for file-level findings).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintIssue {
+    pub line: usize,
+    pub kind: LintKind,
+    pub message: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct LintReport {
+    pub path: PathBuf,
+    pub issues: Vec<LintIssue>,
+}
+
+impl LintReport {
+    pub fn has_errors(&self) -> bool {
+        self.issues
+            .iter()
+            .any(|issue| issue.kind.severity() == LintSeverity::Error)
+    }
+}
+
+/// Lints every pony file below `root` (or `root` itself when it is a file). A file that
+/// cannot be read or decoded gets an `unreadable` issue; only an unreadable `root` is an
+/// error.
+#[cfg(feature = "fs")]
+pub fn lint_pony_dir(root: &Path) -> std::io::Result<Vec<LintReport>> {
+    std::fs::metadata(root)?;
+    let mut paths = if root.is_file() {
+        vec![root.to_path_buf()]
+    } else {
+        WalkDir::new(root)
+            .follow_links(false)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.into_path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "pony"))
+            .collect::<Vec<_>>()
+    };
+    paths.sort();
+
+    let mut reports = Vec::new();
+    for path in paths {
+        let issues = match encoding::read_text(&path) {
+            Ok(raw) => lint_pony(&raw),
+            Err(error) => vec![LintIssue {
+                line: 0,
+                kind: LintKind::Unreadable,
+                message: error.to_string(),
+            }],
+        };
+        debug!(path = %path.display(), issues = issues.len(), "linted pony file");
+        reports.push(LintReport { path, issues });
+    }
+    Ok(reports)
+}
+
+pub fn lint_pony(raw: &str) -> Vec<LintIssue> {
+    let text = raw.strip_prefix('\u{feff}').unwrap_or(raw);
+    let lines = text.lines().collect::<Vec<_>>();
+    let mut issues = parse_problems(text)
+        .into_iter()
+        .map(|problem| {
+            let line = problem.line();
+            let (kind, message) = match problem {
+                PonyParseError::UnterminatedHeader { .. } => (
+                    LintKind::UnterminatedHeader,
+                    "metadata header opened with $$$ is never closed".to_string(),
+                ),
+                PonyParseError::BadTag { reason, .. } => (LintKind::BadTag, reason),
+                PonyParseError::StrayDollar { column, .. } => (
+                    LintKind::StrayDollar,
+                    format!("`$` at column {column} has no closing `$` and is drawn as is"),
+                ),
+            };
+            LintIssue {
+                line,
+                kind,
+                message,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut body_start = 0;
+    let mut declared_width = None;
+    if lines.first().is_some_and(|line| line.trim_end() == "$$$")
+        && let Some(end) = lines
+            .iter()
+            .skip(1)
+            .position(|line| line.trim_end() == "$$$")
+    {
+        body_start = end + 2;
+        declared_width = lines[1..end + 1].iter().find_map(|line| {
+            let (tag, value) = line.split_once(':')?;
+            (tag.trim() == "WIDTH")
+                .then(|| value.trim().parse::<usize>().ok())
+                .flatten()
+        });
+    }
+
+    let mut anchor_seen = false;
+    let mut color_active = false;
+    let mut assigned = HashSet::new();
+    for (offset, line) in lines.iter().enumerate().skip(body_start) {
+        let number = offset + 1;
+        if line.contains("$balloon") {
+            anchor_seen = true;
+        }
+        if line.ends_with([' ', '\t']) {
+            issues.push(LintIssue {
+                line: number,
+                kind: LintKind::TrailingWhitespace,
+                message: "line ends with whitespace".to_string(),
+            });
+        }
+        if line.contains('\t') {
+            issues.push(LintIssue {
+                line: number,
+                kind: LintKind::Tab,
+                message: "tab characters render with terminal-dependent width".to_string(),
+            });
+        }
+
+        lint_escapes(line, number, &mut color_active, &mut issues);
+
+        for (_, command) in dollar_commands(line) {
+            match command {
+                DollarCommand::Assign { name, .. } => {
+                    assigned.insert(name);
+                }
+                DollarCommand::Variable(name) if !assigned.contains(name) => {
+                    issues.push(LintIssue {
+                        line: number,
+                        kind: LintKind::UnknownVariable,
+                        message: format!("unknown variable ${name}$"),
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(width) = declared_width {
+            let visible = visible_width(line);
+            if visible > width && !line.contains('$') {
+                issues.push(LintIssue {
+                    line: number,
+                    kind: LintKind::WidthExceeded,
+                    message: format!("visible width {visible} exceeds declared WIDTH {width}"),
+                });
+            }
+        }
+    }
+
+    if !anchor_seen {
+        issues.push(LintIssue {
+            line: 0,
+            kind: LintKind::MissingBalloonAnchor,
+            message: "no $balloon$ anchor; balloon will be stacked above the art".to_string(),
+        });
+    }
+    if color_active {
+        issues.push(LintIssue {
+            line: lines.len(),
+            kind: LintKind::UnresetColor,
+            message: "file ends with a non-default color still active".to_string(),
+        });
+    }
+
+    trace!(issues = issues.len(), "linted pony source");
+    issues
+}
+
+fn lint_escapes(line: &str, number: usize, color_active: &mut bool, issues: &mut Vec<LintIssue>) {
+    for token in ansi::tokens(line) {
+        let Token::Sequence(sequence) = token else {
+            continue;
+        };
+        if !sequence.terminated {
+            issues.push(LintIssue {
+                line: number,
+                kind: LintKind::UnterminatedEscape,
+                message: "escape sequence is missing its terminator".to_string(),
+            });
+            return;
+        }
+        if let Some(params) = sequence.sgr_params() {
+            *color_active = sgr_leaves_color(params, *color_active);
+        }
+    }
+}
+
+/// Whether a non-default color or attribute is in effect after the SGR `params`. Every
+/// parameter counts in order, so `ESC[0;31m` leaves red on and `ESC[31;0m` resets it; the
+/// arguments of extended colors (`38;5;0`) are not codes.
+fn sgr_leaves_color(params: &str, mut active: bool) -> bool {
+    let mut values = params.split(';');
+    while let Some(value) = values.next() {
+        active = !matches!(value, "" | "0");
+        if matches!(value, "38" | "48" | "58") {
+            match values.next() {
+                Some("5") => {
+                    values.next();
+                }
+                Some("2") => {
+                    values.nth(2);
+                }
+                _ => {}
+            }
+        }
+    }
+    active
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(raw: &str) -> Vec<LintKind> {
+        lint_pony(raw).into_iter().map(|issue| issue.kind).collect()
+    }
+
+    #[test]
+    fn clean_pony_has_no_issues() {
+        let raw =
+            "$$$\nNAME: Clean\nWIDTH: 10\n$$$\n$balloon5$\n  $\\$\n \u{1b}[31mpony\u{1b}[0m\n";
+        assert!(lint_pony(raw).is_empty());
+    }
+
+    #[test]
+    fn assigned_variables_are_known() {
+        let found = kinds("$balloon$\n$eye=o$ $eye$\n$mouth$\n");
+        assert_eq!(found, vec![LintKind::UnknownVariable]);
+    }
+
+    #[test]
+    fn reports_structural_problems() {
+        let found = kinds("$$$\nNAME: Broken\n$unknown$ \n\u{1b}[31\n");
+        assert!(found.contains(&LintKind::UnterminatedHeader));
+
+        let found = kinds("$$$\nName: Broken\nLINK 2\n$$$\n$balloon$ $5\n");
+        assert_eq!(
+            found,
+            vec![LintKind::BadTag, LintKind::BadTag, LintKind::StrayDollar]
+        );
+
+        let found = kinds("$$$\n$$$\n$mystery$ \n\u{1b}[31mred\n\u{1b}[1\n");
+        assert_eq!(
+            found,
+            vec![
+                LintKind::TrailingWhitespace,
+                LintKind::UnknownVariable,
+                LintKind::UnterminatedEscape,
+                LintKind::MissingBalloonAnchor,
+                LintKind::UnresetColor,
+            ]
+        );
+    }
+
+    #[test]
+    fn every_sgr_parameter_counts_toward_the_reset() {
+        let unreset = |art: &str| kinds(&format!("$balloon$\n{art}\n")) == [LintKind::UnresetColor];
+        assert!(unreset("\u{1b}[0;31mred"));
+        assert!(!unreset("\u{1b}[31mred\u{1b}[31;0m"));
+        assert!(unreset("\u{1b}[38;5;0mblack"));
+        assert!(!unreset("\u{1b}[38;2;0;0;0;0mblack"));
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn unreadable_files_are_reported_without_stopping_the_run() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        std::fs::write(tmp.path().join("binary.pony"), b"\xffpony\x00").expect("write");
+        std::fs::write(tmp.path().join("fine.pony"), "$balloon$\npony\n").expect("write");
+
+        let reports = lint_pony_dir(tmp.path()).expect("lint");
+        assert_eq!(reports.len(), 2);
+        assert!(reports[0].has_errors());
+        assert_eq!(reports[0].issues[0].kind, LintKind::Unreadable);
+        assert!(reports[1].issues.is_empty());
+        assert!(lint_pony_dir(&tmp.path().join("missing")).is_err());
+    }
+}
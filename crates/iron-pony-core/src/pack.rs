@@ -0,0 +1,349 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use tracing::{debug, trace};
+
+use crate::PonyError;
+use crate::balloon::{self, BalloonMode, BalloonParseError, BalloonStyle};
+use crate::encoding;
+use crate::pony::{self, PonyAsset};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PackKind {
+    Tar,
+    Zip,
+}
+
+#[derive(Debug, Clone)]
+enum MemberLocation {
+    /// Byte range of the member's data inside an uncompressed tar.
+    Tar { offset: u64, size: u64 },
+    /// Index into the zip central directory.
+    Zip { index: usize },
+}
+
+/// A `.tar` or `.zip` bundle of pony and balloon files.
+///
+/// Opening a pack only reads its member table; member contents are read on demand so
+/// large packs cost nothing for ponies that are never rendered.
+#[derive(Debug, Clone)]
+pub struct AssetPack {
+    path: PathBuf,
+    kind: PackKind,
+    members: BTreeMap<String, MemberLocation>,
+}
+
+impl AssetPack {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, PonyError> {
+        let path = path.as_ref().to_path_buf();
+        let kind = match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("zip") => PackKind::Zip,
+            Some(ext) if ext.eq_ignore_ascii_case("tar") => PackKind::Tar,
+            _ => return Err(PonyError::UnsupportedPack { path }),
+        };
+
+        let members = match kind {
+            PackKind::Tar => index_tar(&path),
+            PackKind::Zip => index_zip(&path),
+        }
+        .map_err(|source| PonyError::Io {
+            path: path.clone(),
+            source,
+        })?;
+
+        debug!(path = %path.display(), members = members.len(), "opened asset pack");
+        Ok(Self {
+            path,
+            kind,
+            members,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Sorted pony names (file stems of `*.pony` members).
+    pub fn pony_names(&self) -> Vec<String> {
+        let mut names = self
+            .members
+            .keys()
+            .filter_map(|member| member_stem(member, "pony"))
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    pub fn balloon_names(&self) -> Vec<String> {
+        let mut names = self
+            .members
+            .keys()
+            .filter_map(|member| {
+                member_stem(member, "say").or_else(|| member_stem(member, "think"))
+            })
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Loads the pony `name`; unless `exact`, a member whose name only matches when case is
+    /// ignored is used when none matches exactly. `None` means no member matches.
+    pub fn load_pony(&self, name: &str, exact: bool) -> Option<Result<PonyAsset, PonyError>> {
+        let member = self.find_member(name, &["pony"], exact)?;
+        trace!(member, "loading pony from asset pack");
+        let path = self.path.join(member);
+        Some(self.read_member(member).and_then(|raw| {
+            let source = encoding::decode(&raw).map_err(|source| PonyError::Io {
+                path: path.clone(),
+                source,
+            })?;
+            Ok(pony::asset_from_source(path.clone(), &source))
+        }))
+    }
+
+    /// Loads the balloon style `name`, falling back to the other mode's file like
+    /// [`balloon::load_style`] and matching names as [`AssetPack::load_pony`] does. `None`
+    /// means no member matches; a member that cannot be read, decoded or parsed is an error
+    /// rather than a miss.
+    pub fn load_style(
+        &self,
+        name: &str,
+        mode: BalloonMode,
+        exact: bool,
+    ) -> Option<Result<BalloonStyle, BalloonParseError>> {
+        let other = match mode {
+            BalloonMode::Say => BalloonMode::Think,
+            BalloonMode::Think => BalloonMode::Say,
+        };
+        let member = self.find_member(
+            name,
+            &[
+                balloon::mode_suffix(mode),
+                "balloon",
+                balloon::mode_suffix(other),
+            ],
+            exact,
+        )?;
+        let path = self.path.join(member);
+        let unreadable = |reason: String| BalloonParseError {
+            path: path.clone(),
+            line: None,
+            reason,
+        };
+        let style = self
+            .read_member(member)
+            .map_err(|error| match error {
+                PonyError::Io { source, .. } => unreadable(source.to_string()),
+                other => unreadable(other.to_string()),
+            })
+            .and_then(|raw| {
+                let source =
+                    encoding::decode(&raw).map_err(|error| unreadable(error.to_string()))?;
+                balloon::parse_style_source(&path, &source)
+            });
+        debug!(
+            member,
+            ok = style.is_ok(),
+            "loaded balloon style from asset pack"
+        );
+        Some(style)
+    }
+
+    pub fn read_member(&self, member: &str) -> Result<Vec<u8>, PonyError> {
+        let io_error = |source| PonyError::Io {
+            path: self.path.join(member),
+            source,
+        };
+        let location = self.members.get(member).ok_or_else(|| {
+            io_error(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "member not in asset pack",
+            ))
+        })?;
+
+        match (self.kind, location) {
+            (PackKind::Tar, MemberLocation::Tar { offset, size }) => {
+                let mut file = File::open(&self.path).map_err(io_error)?;
+                file.seek(SeekFrom::Start(*offset)).map_err(io_error)?;
+                let mut data = Vec::with_capacity(*size as usize);
+                file.take(*size).read_to_end(&mut data).map_err(io_error)?;
+                Ok(data)
+            }
+            (PackKind::Zip, MemberLocation::Zip { index }) => {
+                let file = File::open(&self.path).map_err(io_error)?;
+                let mut archive = zip::ZipArchive::new(BufReader::new(file))
+                    .map_err(|error| io_error(std::io::Error::other(error)))?;
+                let mut entry = archive
+                    .by_index(*index)
+                    .map_err(|error| io_error(std::io::Error::other(error)))?;
+                let mut data = Vec::new();
+                entry.read_to_end(&mut data).map_err(io_error)?;
+                Ok(data)
+            }
+            _ => unreachable!("member location always matches pack kind"),
+        }
+    }
+
+    /// Finds the member for `name`: an exact member path first, then `<name>.<ext>`
+    /// anywhere in the pack (shallowest, then lexicographic, wins), then unless `exact` the
+    /// same ignoring case.
+    fn find_member(&self, name: &str, extensions: &[&str], exact: bool) -> Option<&str> {
+        if let Some((member, _)) = self.members.get_key_value(name) {
+            return Some(member.as_str());
+        }
+
+        let find = |matches: &dyn Fn(&str) -> bool| {
+            extensions.iter().find_map(|ext| {
+                self.members
+                    .keys()
+                    .filter(|member| member_stem(member, ext).is_some_and(matches))
+                    .min_by_key(|member| (member.matches('/').count(), member.as_str()))
+                    .map(String::as_str)
+            })
+        };
+        find(&|stem| stem == name).or_else(|| {
+            if exact {
+                return None;
+            }
+            let lowered = name.to_lowercase();
+            let found = find(&|stem| stem.to_lowercase() == lowered);
+            if let Some(member) = found {
+                debug!(name, member, "matched pack member ignoring case");
+            }
+            found
+        })
+    }
+}
+
+fn member_stem<'a>(member: &'a str, extension: &str) -> Option<&'a str> {
+    let file = member.rsplit('/').next()?;
+    let (stem, ext) = file.rsplit_once('.')?;
+    (ext == extension && !stem.is_empty()).then_some(stem)
+}
+
+fn index_tar(path: &Path) -> std::io::Result<BTreeMap<String, MemberLocation>> {
+    let file = File::open(path)?;
+    let mut archive = tar::Archive::new(file);
+    let mut members = BTreeMap::new();
+
+    for entry in archive.entries_with_seek()? {
+        let entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry
+            .path()?
+            .to_string_lossy()
+            .trim_start_matches("./")
+            .to_string();
+        members.insert(
+            name,
+            MemberLocation::Tar {
+                offset: entry.raw_file_position(),
+                size: entry.size(),
+            },
+        );
+    }
+
+    Ok(members)
+}
+
+fn index_zip(path: &Path) -> std::io::Result<BTreeMap<String, MemberLocation>> {
+    let file = File::open(path)?;
+    let mut archive = zip::ZipArchive::new(BufReader::new(file)).map_err(std::io::Error::other)?;
+    let mut members = BTreeMap::new();
+
+    for index in 0..archive.len() {
+        let entry = archive.by_index_raw(index).map_err(std::io::Error::other)?;
+        if entry.is_file() {
+            members.insert(
+                entry.name().trim_start_matches("./").to_string(),
+                MemberLocation::Zip { index },
+            );
+        }
+    }
+
+    Ok(members)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn append(builder: &mut tar::Builder<File>, name: &str, data: &str) {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, name, data.as_bytes())
+            .expect("append member");
+    }
+
+    #[test]
+    fn reads_tar_members_lazily() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let pack_path = tmp.path().join("assets.tar");
+        let mut builder = tar::Builder::new(File::create(&pack_path).expect("create tar"));
+        append(
+            &mut builder,
+            "ponies/packed.pony",
+            "$$$\nNAME: Packed\n$$$\n$balloon$\npony\n",
+        );
+        append(&mut builder, "balloons/fancy.say", "ww:{ \nee: }\n");
+        append(
+            &mut builder,
+            "balloons/broken.say",
+            "ww:{ \nno colon here\n",
+        );
+        builder.finish().expect("finish tar");
+
+        let pack = AssetPack::open(&pack_path).expect("open pack");
+        assert_eq!(pack.pony_names(), vec!["packed"]);
+
+        let gzipped = tmp.path().join("assets.tar.gz");
+        std::fs::copy(&pack_path, &gzipped).expect("copy pack");
+        assert!(matches!(
+            AssetPack::open(&gzipped),
+            Err(PonyError::UnsupportedPack { path }) if path == gzipped
+        ));
+        assert_eq!(pack.balloon_names(), vec!["broken", "fancy"]);
+
+        let asset = pack
+            .load_pony("packed", true)
+            .expect("pony member")
+            .expect("pony read");
+        assert_eq!(asset.display_name(), "Packed");
+        assert!(asset.body.contains("$balloon$"));
+        assert!(pack.load_pony("Packed", false).is_some());
+        assert!(pack.load_pony("Packed", true).is_none());
+
+        let style = pack
+            .load_style("FANCY", BalloonMode::Say, false)
+            .expect("style member")
+            .expect("style parsed");
+        assert_eq!(style.ww, "{ ");
+        let style = pack
+            .load_style("fancy", BalloonMode::Think, true)
+            .expect("other-mode fallback")
+            .expect("style parsed");
+        assert_eq!(style.ww, "{ ");
+        assert!(
+            pack.load_style("plain", BalloonMode::Think, false)
+                .is_none()
+        );
+
+        let error = pack
+            .load_style("broken", BalloonMode::Say, true)
+            .expect("broken member")
+            .expect_err("malformed style");
+        assert_eq!(error.line, Some(2));
+        assert!(error.path.ends_with("balloons/broken.say"));
+    }
+}
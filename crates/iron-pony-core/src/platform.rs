@@ -0,0 +1,153 @@
+//! Platform conventions: path list separators and the home, cache and data directories.
+//!
+//! Lookups take a [`Platform`] and an environment reader, so Windows behavior can be
+//! tested on any host.
+
+use std::path::PathBuf;
+
+/// Separator for path lists in `--ponydir`-style flags and `*_PATH` variables on this host.
+pub const PATH_LIST_SEPARATOR: char = Platform::current().path_list_separator();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    Unix,
+    Windows,
+}
+
+impl Platform {
+    pub const fn current() -> Self {
+        if cfg!(windows) {
+            Self::Windows
+        } else {
+            Self::Unix
+        }
+    }
+
+    pub const fn path_list_separator(self) -> char {
+        match self {
+            Self::Unix => ':',
+            Self::Windows => ';',
+        }
+    }
+
+    /// Splits a `PATH`-style list, skipping empty entries.
+    pub fn split_path_list(self, value: &str) -> Vec<PathBuf> {
+        value
+            .split(self.path_list_separator())
+            .filter(|part| !part.is_empty())
+            .map(PathBuf::from)
+            .collect()
+    }
+
+    /// Roots that hold shared data such as `ponysay/` and `figlet/`, most preferred first.
+    pub fn share_dirs(self, var: impl Fn(&str) -> Option<String>) -> Vec<PathBuf> {
+        match self {
+            Self::Unix => vec![
+                PathBuf::from("/usr/share"),
+                PathBuf::from("/usr/local/share"),
+            ],
+            Self::Windows => ["ProgramData", "APPDATA"]
+                .into_iter()
+                .filter_map(|name| var(name).map(PathBuf::from))
+                .collect(),
+        }
+    }
+
+    /// Per-user data root: `$XDG_DATA_HOME` or `~/.local/share`, or `%APPDATA%` on Windows.
+    pub fn user_data_dir(self, var: impl Fn(&str) -> Option<String>) -> Option<PathBuf> {
+        match self {
+            Self::Unix => var("XDG_DATA_HOME").map(PathBuf::from).or_else(|| {
+                self.home_dir(var)
+                    .map(|home| home.join(".local").join("share"))
+            }),
+            Self::Windows => var("APPDATA").map(PathBuf::from),
+        }
+    }
+
+    pub fn home_dir(self, var: impl Fn(&str) -> Option<String>) -> Option<PathBuf> {
+        match self {
+            Self::Unix => var("HOME"),
+            Self::Windows => var("USERPROFILE").or_else(|| var("HOME")),
+        }
+        .map(PathBuf::from)
+    }
+
+    /// Per-user cache root: `$XDG_CACHE_HOME` or `~/.cache`, or `%LOCALAPPDATA%` on Windows.
+    pub fn cache_dir(self, var: impl Fn(&str) -> Option<String>) -> Option<PathBuf> {
+        match self {
+            Self::Unix => var("XDG_CACHE_HOME")
+                .map(PathBuf::from)
+                .or_else(|| self.home_dir(var).map(|home| home.join(".cache"))),
+            Self::Windows => var("LOCALAPPDATA").map(PathBuf::from),
+        }
+    }
+}
+
+/// A non-empty environment variable of this process.
+pub(crate) fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+/// `<share>/<dir>/<kind>` for each of this host's share roots, grouped by root; an empty
+/// `kind` stands for `<share>/<dir>` itself.
+pub(crate) fn share_subdirs(dir: &str, kinds: &[&str]) -> Vec<PathBuf> {
+    Platform::current()
+        .share_dirs(env_var)
+        .into_iter()
+        .flat_map(|share| {
+            let root = share.join(dir);
+            kinds.iter().map(move |kind| match *kind {
+                "" => root.clone(),
+                kind => root.join(kind),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn resolves_unix_and_windows_conventions() {
+        assert_eq!(
+            Platform::Unix.split_path_list("/a::/b"),
+            [PathBuf::from("/a"), PathBuf::from("/b")]
+        );
+        assert_eq!(
+            Platform::Windows.split_path_list(r"C:\ponies;;D:\more"),
+            [PathBuf::from(r"C:\ponies"), PathBuf::from(r"D:\more")]
+        );
+
+        let vars = HashMap::from([
+            ("HOME", "/home/ada"),
+            ("USERPROFILE", r"C:\Users\ada"),
+            ("LOCALAPPDATA", r"C:\Users\ada\AppData\Local"),
+            ("ProgramData", r"C:\ProgramData"),
+        ]);
+        let var = |name: &str| vars.get(name).map(|value| value.to_string());
+        assert_eq!(
+            Platform::Windows.share_dirs(var),
+            [PathBuf::from(r"C:\ProgramData")]
+        );
+        assert_eq!(
+            Platform::Windows.home_dir(var),
+            Some(PathBuf::from(r"C:\Users\ada"))
+        );
+        assert_eq!(
+            Platform::Windows.cache_dir(var),
+            Some(PathBuf::from(r"C:\Users\ada\AppData\Local"))
+        );
+        assert_eq!(
+            Platform::Unix.cache_dir(var),
+            Some(PathBuf::from("/home/ada/.cache"))
+        );
+        assert_eq!(Platform::Unix.share_dirs(var).len(), 2);
+        assert_eq!(
+            Platform::Unix.user_data_dir(var),
+            Some(PathBuf::from("/home/ada/.local/share"))
+        );
+    }
+}
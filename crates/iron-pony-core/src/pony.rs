@@ -1,10 +1,18 @@
-use std::collections::BTreeMap;
-use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, HashMap};
+#[cfg(feature = "fs")]
+use std::path::Path;
+use std::path::PathBuf;
 
 use tracing::{debug, trace};
-use walkdir::WalkDir;
 
-use crate::{PonyError, balloon::BalloonStyle};
+#[cfg(feature = "fs")]
+use crate::PonyError;
+use crate::ansi::{self, Token, visible_width};
+use crate::balloon::{BalloonMode, BalloonStyle};
+#[cfg(feature = "fs")]
+use crate::platform::{Platform, env_var};
+#[cfg(feature = "fs")]
+use crate::{alias, encoding, scan};
 
 #[derive(Debug, Clone, Default)]
 pub struct PonyMetadata {
@@ -12,6 +20,84 @@ pub struct PonyMetadata {
     pub comments: Vec<String>,
 }
 
+impl PonyMetadata {
+    /// First value recorded for `tag`, if any.
+    pub fn first(&self, tag: &str) -> Option<&str> {
+        self.tags
+            .get(tag)
+            .and_then(|values| values.first())
+            .map(String::as_str)
+            .filter(|value| !value.is_empty())
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.first("NAME")
+    }
+
+    pub fn other_names(&self) -> Vec<&str> {
+        self.list("OTHER NAMES")
+    }
+
+    pub fn groups(&self) -> Vec<&str> {
+        self.list("GROUP")
+    }
+
+    /// Balloon style names the pony declares support for via `BALLOON`.
+    pub fn balloon_preference(&self) -> Vec<&str> {
+        self.tags
+            .get("BALLOON")
+            .into_iter()
+            .flatten()
+            .flat_map(|value| value.split([',', ' ', '\t']))
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .collect()
+    }
+
+    /// Link row count declared via `LINK`; `None` when absent or not a number.
+    pub fn link_rows(&self) -> Option<usize> {
+        self.first("LINK")
+            .and_then(|value| value.parse::<usize>().ok())
+    }
+
+    /// The `LINK` count; it counts rows below the balloon, not columns.
+    #[deprecated(note = "renamed to `link_rows`")]
+    pub fn link_columns(&self) -> Option<usize> {
+        self.link_rows()
+    }
+
+    pub fn source(&self) -> Option<&str> {
+        self.first("SOURCE")
+    }
+
+    pub fn license(&self) -> Option<&str> {
+        self.first("LICENSE")
+    }
+
+    fn list(&self, tag: &str) -> Vec<&str> {
+        self.tags
+            .get(tag)
+            .into_iter()
+            .flatten()
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .collect()
+    }
+}
+
+/// Horizontal position of the balloon relative to the pony art.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BalloonPlacement {
+    /// Wherever the template's `$balloon$` anchor sits, as upstream does.
+    #[default]
+    Anchor,
+    /// Centered over the widest line of the art.
+    Center,
+    /// Starting at a fixed column.
+    Column(usize),
+}
+
 #[derive(Debug, Clone)]
 pub struct PonyAsset {
     pub path: PathBuf,
@@ -19,124 +105,592 @@ pub struct PonyAsset {
     pub body: String,
 }
 
-pub fn load_pony(name: &str, roots: &[PathBuf]) -> Result<PonyAsset, PonyError> {
+impl PonyAsset {
+    /// Visible `(width, height)` of the art, leaving out the balloon anchor line and escape
+    /// sequences. Links count as the built-in style draws them and variables as their
+    /// values. Measured from `body` on each call.
+    pub fn dimensions(&self) -> (usize, usize) {
+        let style = BalloonStyle::default_for_mode(BalloonMode::Say);
+        art_size(&self.body, &style, BalloonMode::Say)
+    }
+
+    /// `NAME` metadata when present, otherwise the file stem.
+    pub fn display_name(&self) -> String {
+        if let Some(name) = self.metadata.name() {
+            return name.to_string();
+        }
+
+        self.path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or_default()
+            .to_string()
+    }
+}
+
+/// Loads a pony by name from `roots`, or directly from a file when `name` looks like a
+/// path (contains `/`, starts with `~`, or is an existing `*.pony` file).
+///
+/// A file whose name matches exactly wins in any root; unless `exact`, one matching
+/// only when case is ignored (`TwilightSparkle` for `twilightsparkle.pony`) is used next.
+/// Failing both, `name` is looked up in the roots' `aliases` files.
+#[cfg(feature = "fs")]
+pub fn load_pony(name: &str, roots: &[PathBuf], exact: bool) -> Result<PonyAsset, PonyError> {
+    if is_path_like(name) {
+        let path = expand_tilde(name);
+        if !path.is_file() {
+            return Err(PonyError::PonyFileNotFound { path });
+        }
+        trace!(path = %path.display(), "loading pony from explicit path");
+        return read_pony_file(path);
+    }
+
+    if let Some(path) = find_pony(name, roots, exact) {
+        return read_pony_file(path);
+    }
+    // Aliases are not followed any further, so a cycle between them cannot loop.
+    if let Some(pony) = alias::load_aliases(roots).remove(name) {
+        debug!(alias = name, %pony, "resolved pony alias");
+        if let Some(path) = find_pony(&pony, roots, exact) {
+            return read_pony_file(path);
+        }
+    }
+
+    Err(PonyError::PonyNotFound {
+        name: name.to_string(),
+    })
+}
+
+#[cfg(feature = "fs")]
+fn find_pony(name: &str, roots: &[PathBuf], exact: bool) -> Option<PathBuf> {
+    let file_names = pony_file_names(name);
     for root in roots {
-        for candidate in pony_candidates(root, name) {
+        for file_name in &file_names {
+            let candidate = root.join(file_name);
             if candidate.is_file() {
-                let raw = std::fs::read_to_string(&candidate).map_err(|source| PonyError::Io {
-                    path: candidate.clone(),
-                    source,
-                })?;
-
-                let (metadata, body) = parse_metadata_header(&raw);
-                debug!(path = %candidate.display(), tags = metadata.tags.len(), "loaded pony asset");
-
-                return Ok(PonyAsset {
-                    path: candidate,
-                    metadata,
-                    body,
-                });
+                return Some(candidate);
+            }
+        }
+    }
+    if exact {
+        return None;
+    }
+    for root in roots {
+        for candidate in scan::case_insensitive_matches(root, &file_names) {
+            if candidate.is_file() {
+                debug!(pony = name, path = %candidate.display(), "matched pony ignoring case");
+                return Some(candidate);
             }
         }
     }
+    None
+}
 
-    Err(PonyError::PonyNotFound {
-        name: name.to_string(),
+#[cfg(feature = "fs")]
+fn read_pony_file(path: PathBuf) -> Result<PonyAsset, PonyError> {
+    let raw = encoding::read_text(&path).map_err(|source| PonyError::Io {
+        path: path.clone(),
+        source,
+    })?;
+
+    let asset = asset_from_source(path, &raw);
+    debug!(path = %asset.path.display(), tags = asset.metadata.tags.len(), "loaded pony asset");
+    Ok(asset)
+}
+
+#[cfg(feature = "fs")]
+fn is_path_like(name: &str) -> bool {
+    name.contains(std::path::is_separator)
+        || name == "~"
+        || name.starts_with("~/")
+        || name.starts_with(r"~\")
+        || (name.ends_with(".pony") && Path::new(name).is_file())
+}
+
+/// Expands a leading `~` or `~/` to the home directory; other names are returned unchanged.
+#[cfg(feature = "fs")]
+fn expand_tilde(name: &str) -> PathBuf {
+    let rest = match name.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(std::path::is_separator) => rest,
+        _ => return PathBuf::from(name),
+    };
+    match Platform::current().home_dir(env_var) {
+        Some(home) => home.join(rest.trim_start_matches(std::path::is_separator)),
+        None => PathBuf::from(name),
+    }
+}
+
+/// Just the metadata header of the pony file at `path`; empty when it cannot be read.
+#[cfg(feature = "fs")]
+pub(crate) fn read_metadata(path: &Path) -> PonyMetadata {
+    match encoding::read_text(path) {
+        Ok(raw) => parse_metadata_header(&raw).0,
+        Err(error) => {
+            debug!(path = %path.display(), %error, "failed reading pony metadata");
+            PonyMetadata::default()
+        }
+    }
+}
+
+pub(crate) fn asset_from_source(path: PathBuf, raw: &str) -> PonyAsset {
+    let (metadata, body) = parse_metadata_header(raw);
+    PonyAsset {
+        path,
+        metadata,
+        body,
+    }
+}
+
+/// Reads the pony file at `path` like [`load_pony`] does, but fails on the first problem
+/// [`parse_pony_strict`] finds.
+#[cfg(feature = "fs")]
+pub fn read_pony_strict(path: &Path) -> Result<PonyAsset, PonyError> {
+    let raw = encoding::read_text(path).map_err(|source| PonyError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    parse_pony_strict(path.to_path_buf(), &raw).map_err(|source| PonyError::PonyParse {
+        path: path.to_path_buf(),
+        source,
     })
 }
 
-pub fn list_pony_names(root: &Path) -> Vec<String> {
-    let mut names = Vec::new();
-    if !root.exists() {
-        return names;
+/// Every problem [`parse_pony_strict`] would reject in the pony file at `path`, in file
+/// order, for reporting them without refusing the file.
+#[cfg(feature = "fs")]
+pub fn pony_file_problems(path: &Path) -> Result<Vec<PonyParseError>, PonyError> {
+    let raw = encoding::read_text(path).map_err(|source| PonyError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    Ok(parse_problems(&raw))
+}
+
+/// Something in a pony file that rendering silently works around. Lines and columns are
+/// 1-based and count from the top of the file, header included.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum PonyParseError {
+    #[error("line {line}: metadata header opened with $$$ is never closed")]
+    UnterminatedHeader { line: usize },
+    #[error("line {line}: {reason}")]
+    BadTag { line: usize, reason: String },
+    #[error("line {line}, column {column}: `$` has no closing `$`")]
+    StrayDollar { line: usize, column: usize },
+}
+
+impl PonyParseError {
+    pub fn line(&self) -> usize {
+        match self {
+            Self::UnterminatedHeader { line }
+            | Self::BadTag { line, .. }
+            | Self::StrayDollar { line, .. } => *line,
+        }
     }
+}
 
-    for entry in WalkDir::new(root)
-        .follow_links(false)
-        .min_depth(1)
-        .max_depth(3)
-        .into_iter()
-        .filter_map(Result::ok)
-    {
-        if !entry.file_type().is_file() {
-            continue;
+/// Parses a pony file as rendering does, but rejects an unterminated metadata header, a
+/// header line that looks like a misspelt tag, and a `$` in the art that nothing closes;
+/// the error is the first of them. Rendering keeps the lenient reading, which takes such
+/// lines as comments or literal text.
+pub fn parse_pony_strict(path: PathBuf, raw: &str) -> Result<PonyAsset, PonyParseError> {
+    match parse_problems(raw).into_iter().next() {
+        Some(problem) => Err(problem),
+        None => Ok(asset_from_source(path, raw)),
+    }
+}
+
+/// Tags [`PonyMetadata`] or the linter read, for telling a misspelt tag from a comment.
+const KNOWN_TAGS: [&str; 8] = [
+    "NAME",
+    "OTHER NAMES",
+    "GROUP",
+    "BALLOON",
+    "LINK",
+    "LICENSE",
+    "SOURCE",
+    "WIDTH",
+];
+
+/// Every problem [`parse_pony_strict`] rejects, in file order.
+pub(crate) fn parse_problems(raw: &str) -> Vec<PonyParseError> {
+    let text = raw.strip_prefix('\u{feff}').unwrap_or(raw);
+    let lines = text.lines().collect::<Vec<_>>();
+    let mut problems = Vec::new();
+    let mut body_start = 0;
+    if lines.first().is_some_and(|line| line.trim_end() == "$$$") {
+        let Some(end) = lines
+            .iter()
+            .skip(1)
+            .position(|line| line.trim_end() == "$$$")
+        else {
+            // Everything after the opening line would be header; nothing more to check.
+            return vec![PonyParseError::UnterminatedHeader { line: 1 }];
+        };
+        for (offset, line) in lines[1..=end].iter().enumerate() {
+            if let Some(reason) = bad_tag(line) {
+                problems.push(PonyParseError::BadTag {
+                    line: offset + 2,
+                    reason,
+                });
+            }
         }
+        body_start = end + 2;
+    }
 
-        if let Some(stem) = entry.path().file_stem().and_then(|s| s.to_str()) {
-            names.push(stem.to_string());
+    for (offset, line) in lines.iter().enumerate().skip(body_start) {
+        let mut consumed = 0;
+        for (raw, command) in dollar_commands(line) {
+            if matches!(command, DollarCommand::Text(text) if text.starts_with('$')) {
+                problems.push(PonyParseError::StrayDollar {
+                    line: offset + 1,
+                    column: line[..consumed].chars().count() + 1,
+                });
+            }
+            consumed += raw.len();
+        }
+    }
+    problems
+}
+
+/// Why a header line is a broken tag rather than a tag or a comment.
+fn bad_tag(line: &str) -> Option<String> {
+    if let Some((tag, _)) = line.split_once(':') {
+        let tag = tag.trim();
+        if tag.is_empty() {
+            return Some("metadata tag name before `:` is empty".to_string());
+        }
+        let upper = tag.to_ascii_uppercase();
+        return (tag != upper && KNOWN_TAGS.contains(&upper.as_str())).then(|| {
+            format!("metadata tag `{tag}` must be upper case (`{upper}`); ponysay reads the line as a comment")
+        });
+    }
+    let tag = KNOWN_TAGS.iter().find(|tag| {
+        line.trim_start()
+            .strip_prefix(**tag)
+            .is_some_and(|rest| rest.starts_with([' ', '\t', '=']))
+    })?;
+    Some(format!(
+        "metadata tag `{tag}` is missing its `:`; ponysay reads the line as a comment"
+    ))
+}
+
+/// One piece of a pony body line, as split by [`dollar_commands`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DollarCommand<'a> {
+    /// Literal text, including a trailing `$` that has no closing partner.
+    Text(&'a str),
+    /// `$$`: a literal dollar sign.
+    Dollar,
+    /// `$\$`: the balloon link character.
+    Link,
+    /// `$/$`: the mirrored link character.
+    LinkMirror,
+    /// `$X$`: the crossing link character.
+    LinkCross,
+    /// `$balloon$`, `$balloonW$`, or `$balloonW,H$`: the balloon anchor with its minimum size.
+    Balloon { width: usize, height: usize },
+    /// `$name=value$`: defines a variable for the rest of the file; renders as nothing.
+    Assign { name: &'a str, value: &'a str },
+    /// `$name$`: a previously assigned variable, or an unknown command.
+    Variable(&'a str),
+}
+
+/// Splits `line` into dollar commands, pairing `$` signs left to right. Each item carries
+/// the exact source text it was parsed from. A `$` never pairs across a newline.
+pub fn dollar_commands(line: &str) -> DollarCommands<'_> {
+    DollarCommands { rest: line }
+}
+
+#[derive(Debug, Clone)]
+pub struct DollarCommands<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Iterator for DollarCommands<'a> {
+    type Item = (&'a str, DollarCommand<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rest.is_empty() {
+            return None;
         }
+
+        let rest = self.rest;
+        let Some(body) = rest.strip_prefix('$') else {
+            let end = rest.find('$').unwrap_or(rest.len());
+            self.rest = &rest[end..];
+            return Some((&rest[..end], DollarCommand::Text(&rest[..end])));
+        };
+
+        let close = body.find(['$', '\n']);
+        let Some(close) = close.filter(|&index| body.as_bytes()[index] == b'$') else {
+            // Unpaired: the `$` is literal text up to the next line break.
+            let end = 1 + close.unwrap_or(body.len());
+            self.rest = &rest[end..];
+            return Some((&rest[..end], DollarCommand::Text(&rest[..end])));
+        };
+
+        let raw = &rest[..close + 2];
+        self.rest = &rest[close + 2..];
+        Some((raw, classify(&body[..close])))
     }
+}
 
-    names.sort();
-    names.dedup();
-    names
+fn classify(inner: &str) -> DollarCommand<'_> {
+    match inner {
+        "" => DollarCommand::Dollar,
+        "\\" => DollarCommand::Link,
+        "/" => DollarCommand::LinkMirror,
+        "X" => DollarCommand::LinkCross,
+        _ => {
+            if let Some((width, height)) = inner.strip_prefix("balloon").and_then(parse_anchor_size)
+            {
+                DollarCommand::Balloon { width, height }
+            } else if let Some((name, value)) = inner.split_once('=') {
+                DollarCommand::Assign { name, value }
+            } else {
+                DollarCommand::Variable(inner)
+            }
+        }
+    }
 }
 
-pub fn insert_balloon(template: &str, balloon_lines: &[String], style: &BalloonStyle) -> String {
+/// Places `balloon_lines` at the template's `$balloon$` anchor.
+///
+/// `link_rows` rows of the style's link character are drawn diagonally below the
+/// balloon, leading from it towards the art. Dollar commands on the anchor line are
+/// expanded around the balloon, never inside it, so message text is emitted verbatim.
+///
+/// Output is appended to `out`, which is grown once up front.
+pub(crate) fn insert_balloon_into(
+    out: &mut String,
+    template: &str,
+    balloon_lines: &[String],
+    style: &BalloonStyle,
+    mode: BalloonMode,
+    link_rows: usize,
+    placement: BalloonPlacement,
+) {
+    let mut expander = Expander::new(style, mode);
     if balloon_lines.is_empty() {
-        return expand_predefined_vars(template, style);
+        out.reserve(template.len());
+        expander.expand_into(out, template);
+        return;
     }
 
-    let mut out = Vec::new();
-    let mut inserted = false;
+    let balloon_len = balloon_lines
+        .iter()
+        .map(|line| line.len() + 1)
+        .sum::<usize>();
+    out.reserve(template.len() + balloon_len + link_rows * (expander.link.len() + 16));
 
-    for line in template.lines() {
-        if let Some((prefix, suffix)) = line.split_once("$balloon$") {
-            trace!("expanding $balloon$ anchor");
-            let indent = " ".repeat(prefix.chars().count());
-            let mut inserted_block = Vec::new();
-
-            for (index, balloon_line) in balloon_lines.iter().enumerate() {
-                if index == 0 {
-                    inserted_block.push(format!("{prefix}{balloon_line}"));
-                } else {
-                    inserted_block.push(format!("{indent}{balloon_line}"));
+    let fixed_indent = match placement {
+        BalloonPlacement::Anchor => None,
+        BalloonPlacement::Column(column) => Some(column),
+        BalloonPlacement::Center => {
+            let balloon_width = balloon_lines
+                .iter()
+                .map(|line| visible_width(line))
+                .max()
+                .unwrap_or(0);
+            Some(
+                art_size(template, style, mode)
+                    .0
+                    .saturating_sub(balloon_width)
+                    / 2,
+            )
+        }
+    };
+
+    let anchor = template
+        .lines()
+        .position(|line| split_balloon_anchor(line).is_some());
+    if anchor.is_none() {
+        // No anchor: stack the balloon above the art.
+        for line in balloon_lines {
+            push_spaces(out, fixed_indent.unwrap_or(0));
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+
+    for (number, line) in template.lines().enumerate() {
+        if number > 0 {
+            out.push('\n');
+        }
+        let split = if Some(number) == anchor {
+            split_balloon_anchor(line)
+        } else {
+            None
+        };
+        let Some((prefix, _, suffix)) = split else {
+            expander.expand_into(out, line);
+            continue;
+        };
+
+        trace!(link_rows, "expanding $balloon$ anchor");
+        let prefix_start = out.len();
+        expander.expand_into(out, prefix);
+        let indent = match fixed_indent {
+            Some(indent) => {
+                // Keep the prefix's colors but replace its columns with the fixed indent.
+                let expanded = out.split_off(prefix_start);
+                for token in ansi::tokens(&expanded) {
+                    if let Token::Sequence(sequence) = token {
+                        out.push_str(sequence.raw);
+                    }
                 }
+                push_spaces(out, indent);
+                indent
             }
+            None => visible_width(&out[prefix_start..]),
+        };
 
-            if let Some(last) = inserted_block.last_mut() {
-                last.push_str(suffix);
+        for (index, balloon_line) in balloon_lines.iter().enumerate() {
+            if index > 0 {
+                out.push('\n');
+                push_spaces(out, indent);
             }
+            out.push_str(balloon_line);
+        }
+        expander.expand_into(out, suffix);
 
-            for line in inserted_block {
-                out.push(expand_predefined_vars(&line, style));
+        for row in 0..link_rows {
+            out.push('\n');
+            push_spaces(out, indent + row + 1);
+            out.push_str(&expander.link);
+        }
+    }
+
+    if anchor.is_none() && template.lines().next().is_none() {
+        out.pop();
+    }
+}
+
+/// `(width, height)` of the expanded art: the widest line, ignoring the balloon anchor
+/// itself, and the number of lines other than the anchor line.
+pub(crate) fn art_size(template: &str, style: &BalloonStyle, mode: BalloonMode) -> (usize, usize) {
+    let mut expander = Expander::new(style, mode);
+    let mut line_out = String::new();
+    let mut size = (0, 0);
+    for line in template.lines() {
+        line_out.clear();
+        match split_balloon_anchor(line) {
+            Some((prefix, _, suffix)) => {
+                expander.expand_into(&mut line_out, prefix);
+                expander.expand_into(&mut line_out, suffix);
+            }
+            None => {
+                expander.expand_into(&mut line_out, line);
+                size.1 += 1;
             }
-            inserted = true;
-        } else {
-            out.push(expand_predefined_vars(line, style));
         }
+        size.0 = size.0.max(visible_width(&line_out));
     }
+    size
+}
 
-    if !inserted {
-        let mut merged = balloon_lines.to_vec();
-        merged = merged
-            .into_iter()
-            .map(|line| expand_predefined_vars(&line, style))
-            .collect();
-        merged.extend(
-            out.into_iter()
-                .map(|line| expand_predefined_vars(&line, style)),
-        );
-        return merged.join("\n");
+fn push_spaces(out: &mut String, count: usize) {
+    out.extend(std::iter::repeat_n(' ', count));
+}
+
+/// Minimum balloon `(width, height)` requested by the template's first `$balloon...$`
+/// anchor; `$balloon$` requests nothing.
+pub(crate) fn balloon_anchor_size(template: &str) -> (usize, usize) {
+    template
+        .lines()
+        .find_map(split_balloon_anchor)
+        .map_or((0, 0), |(_, size, _)| size)
+}
+
+/// Splits a line around its first balloon anchor command.
+fn split_balloon_anchor(line: &str) -> Option<(&str, (usize, usize), &str)> {
+    let mut offset = 0;
+    for (raw, command) in dollar_commands(line) {
+        if let DollarCommand::Balloon { width, height } = command {
+            return Some((
+                &line[..offset],
+                (width, height),
+                &line[offset + raw.len()..],
+            ));
+        }
+        offset += raw.len();
     }
+    None
+}
 
-    out.join("\n")
+fn parse_anchor_size(params: &str) -> Option<(usize, usize)> {
+    if params.is_empty() {
+        return Some((0, 0));
+    }
+    let (width, height) = params.split_once(',').unwrap_or((params, ""));
+    let parse = |value: &str| {
+        if value.is_empty() {
+            Some(0)
+        } else if value.chars().all(|ch| ch.is_ascii_digit()) {
+            value.parse::<usize>().ok()
+        } else {
+            None
+        }
+    };
+    Some((parse(width)?, parse(height)?))
 }
 
-fn expand_predefined_vars(input: &str, style: &BalloonStyle) -> String {
-    let link = format!("\u{1b}[0m{}\u{1b}[0m", style.link);
-    let link_mirror = format!("\u{1b}[0m{}\u{1b}[0m", style.link_mirror);
-    let link_cross = format!("\u{1b}[0m{}\u{1b}[0m", style.link_cross);
+/// Expands dollar commands for one template, with link pieces rendered once and
+/// variable assignments carried from line to line.
+///
+/// In think mode every link command draws the style's think chain (`\\`), as upstream
+/// ponythink does. Like upstream, a link piece wider than one column pushes the art to its
+/// right along rather than eating the spaces after it.
+struct Expander<'a> {
+    link: String,
+    link_mirror: String,
+    link_cross: String,
+    variables: HashMap<&'a str, &'a str>,
+}
+
+impl<'a> Expander<'a> {
+    fn new(style: &BalloonStyle, mode: BalloonMode) -> Self {
+        let (mirror, cross) = match mode {
+            BalloonMode::Say => (&style.link_mirror, &style.link_cross),
+            BalloonMode::Think => (&style.link, &style.link),
+        };
+        let piece = |value: &str| format!("\u{1b}[0m{value}\u{1b}[0m");
+        Self {
+            link: piece(&style.link),
+            link_mirror: piece(mirror),
+            link_cross: piece(cross),
+            variables: HashMap::new(),
+        }
+    }
 
-    input
-        .replace("$\\$", &link)
-        .replace("$/$", &link_mirror)
-        .replace("$X$", &link_cross)
-        .replace("$$", "$")
+    fn expand_into(&mut self, out: &mut String, input: &'a str) {
+        for (raw, command) in dollar_commands(input) {
+            match command {
+                DollarCommand::Text(text) => out.push_str(text),
+                DollarCommand::Dollar => out.push('$'),
+                DollarCommand::Link => out.push_str(&self.link),
+                DollarCommand::LinkMirror => out.push_str(&self.link_mirror),
+                DollarCommand::LinkCross => out.push_str(&self.link_cross),
+                DollarCommand::Assign { name, value } => {
+                    self.variables.insert(name, value);
+                }
+                DollarCommand::Variable(name) => match self.variables.get(name) {
+                    Some(value) => out.push_str(value),
+                    None => {
+                        trace!(name, "leaving unknown dollar command verbatim");
+                        out.push_str(raw);
+                    }
+                },
+                // Only the anchor line's first balloon is replaced; extras stay literal.
+                DollarCommand::Balloon { .. } => out.push_str(raw),
+            }
+        }
+    }
 }
 
-fn pony_candidates(root: &Path, name: &str) -> [PathBuf; 2] {
-    [root.join(name), root.join(format!("{name}.pony"))]
+#[cfg(feature = "fs")]
+fn pony_file_names(name: &str) -> [String; 2] {
+    [name.to_string(), format!("{name}.pony")]
 }
 
 fn parse_metadata_header(raw: &str) -> (PonyMetadata, String) {
@@ -163,7 +717,7 @@ fn parse_metadata_header(raw: &str) -> (PonyMetadata, String) {
 
         if let Some((tag, value)) = line.split_once(':') {
             let tag = tag.trim();
-            if !tag.is_empty() && tag.chars().all(|c| c.is_ascii_uppercase() || c == '_') {
+            if is_metadata_tag(tag) {
                 metadata
                     .tags
                     .entry(tag.to_string())
@@ -190,37 +744,221 @@ fn parse_metadata_header(raw: &str) -> (PonyMetadata, String) {
     (metadata, body)
 }
 
+fn is_metadata_tag(tag: &str) -> bool {
+    tag.starts_with(|c: char| c.is_ascii_uppercase())
+        && tag
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_' || c == ' ')
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::balloon::{BalloonMode, load_style};
+    use crate::ansi::strip_ansi;
+    use crate::balloon::BalloonMode;
 
     use super::*;
 
+    fn insert_balloon(
+        template: &str,
+        balloon_lines: &[String],
+        style: &BalloonStyle,
+        link_rows: usize,
+    ) -> String {
+        let mut out = String::new();
+        insert_balloon_into(
+            &mut out,
+            template,
+            balloon_lines,
+            style,
+            BalloonMode::Say,
+            link_rows,
+            BalloonPlacement::Anchor,
+        );
+        out
+    }
+
     #[test]
     fn inserts_balloon_anchor() {
         let template = "  $balloon$\n   \\\n    (oo)";
-        let style = load_style(None, &[], BalloonMode::Say).expect("default style");
+        let style = BalloonStyle::default_for_mode(BalloonMode::Say);
         let out = insert_balloon(
             template,
             &["< hi >".to_string(), "\\----/".to_string()],
             &style,
+            0,
         );
         assert!(out.contains("< hi >"));
         assert!(out.contains("\\----/"));
     }
 
+    #[test]
+    fn parses_sized_balloon_anchors() {
+        assert_eq!(balloon_anchor_size("art\n  $balloon$\n"), (0, 0));
+        assert_eq!(balloon_anchor_size("  $balloon30$x"), (30, 0));
+        assert_eq!(balloon_anchor_size("$balloon12,6$"), (12, 6));
+        assert_eq!(balloon_anchor_size("$balloonish$ $balloon,4$"), (0, 4));
+        assert_eq!(
+            split_balloon_anchor("ab$balloon5,2$cd"),
+            Some(("ab", (5, 2), "cd"))
+        );
+
+        let style = BalloonStyle::default_for_mode(BalloonMode::Say);
+        let out = insert_balloon("  $balloon8,4$\n pony", &["<>".to_string()], &style, 0);
+        assert_eq!(out, "  <>\n pony");
+    }
+
+    #[test]
+    fn places_balloon_centered_or_at_a_column() {
+        let style = BalloonStyle::default_for_mode(BalloonMode::Say);
+        let balloon = vec!["<ab>".to_string(), "<cd>".to_string()];
+        let template = "$balloon$\n  $\\$\n0123456789";
+        let place = |placement| {
+            let mut out = String::new();
+            insert_balloon_into(
+                &mut out,
+                template,
+                &balloon,
+                &style,
+                BalloonMode::Say,
+                0,
+                placement,
+            );
+            out.lines().take(2).map(str::to_string).collect::<Vec<_>>()
+        };
+
+        assert_eq!(place(BalloonPlacement::Anchor), vec!["<ab>", "<cd>"]);
+        assert_eq!(place(BalloonPlacement::Center), vec!["   <ab>", "   <cd>"]);
+        assert_eq!(place(BalloonPlacement::Column(1)), vec![" <ab>", " <cd>"]);
+
+        let mut out = String::new();
+        let colored = "\u{1b}[31m  $balloon$\n0123";
+        insert_balloon_into(
+            &mut out,
+            colored,
+            &balloon,
+            &style,
+            BalloonMode::Say,
+            0,
+            BalloonPlacement::Center,
+        );
+        assert!(out.starts_with("\u{1b}[31m<ab>\n<cd>"));
+    }
+
+    #[test]
+    fn think_mode_uses_the_chain_for_every_link_and_keeps_spaces() {
+        let mut style = BalloonStyle::default_for_mode(BalloonMode::Think);
+        style.link = "oO".to_string();
+        style.link_mirror = "/".to_string();
+        style.link_cross = "\u{1b}]8;;https://example.org\u{1b}\\xX\u{1b}]8;;\u{1b}\\".to_string();
+        let expand = |template, mode| {
+            let mut out = String::new();
+            insert_balloon_into(
+                &mut out,
+                template,
+                &[],
+                &style,
+                mode,
+                0,
+                BalloonPlacement::Anchor,
+            );
+            out.replace("\u{1b}[0m", "")
+        };
+
+        assert_eq!(
+            expand("$\\$  | $/$ |\n $X$x", BalloonMode::Think),
+            "oO  | oO |\n oOx"
+        );
+        assert_eq!(expand("$\\$  | $/$ |", BalloonMode::Say), "oO  | / |");
+        assert_eq!(strip_ansi(&expand("$X$  |", BalloonMode::Say)), "xX  |");
+    }
+
     #[test]
     fn expands_link_vars_from_style() {
-        let say = load_style(None, &[], BalloonMode::Say).expect("say style");
-        let think = load_style(None, &[], BalloonMode::Think).expect("think style");
+        let say = BalloonStyle::default_for_mode(BalloonMode::Say);
+        let think = BalloonStyle::default_for_mode(BalloonMode::Think);
 
-        let say_out = insert_balloon("x $\\$ y", &[], &say);
-        let think_out = insert_balloon("x $\\$ y", &[], &think);
+        let say_out = insert_balloon("x $\\$ y", &[], &say, 0);
+        let think_out = insert_balloon("x $\\$ y", &[], &think, 0);
 
         assert_eq!(say_out, "x \u{1b}[0m\\\u{1b}[0m y");
         assert_eq!(think_out, "x \u{1b}[0mo\u{1b}[0m y");
     }
 
+    #[test]
+    fn draws_link_rows_and_keeps_balloon_text_verbatim() {
+        let style = BalloonStyle::default_for_mode(BalloonMode::Say);
+        let balloon = ["< $$5 $\\$ >".to_string(), " ---- ".to_string()];
+        let out = insert_balloon("$/$$balloon$$\\$\nart", &balloon, &style, 2);
+        let link = |ch: &str| format!("\u{1b}[0m{ch}\u{1b}[0m");
+
+        assert_eq!(
+            out.lines().collect::<Vec<_>>(),
+            vec![
+                format!("{}< $$5 $\\$ >", link("/")),
+                format!("  ---- {}", link("\\")),
+                format!("  {}", link("\\")),
+                format!("   {}", link("\\")),
+                "art".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn expands_dollar_commands_pairwise() {
+        let style = BalloonStyle::default_for_mode(BalloonMode::Say);
+        let out = insert_balloon("$$$\\$ $foo$ $X$$$", &[], &style, 0);
+        assert_eq!(out, "$\u{1b}[0m\\\u{1b}[0m $foo$ \u{1b}[0mX\u{1b}[0m$");
+    }
+
+    #[test]
+    fn tokenizes_dollar_commands() {
+        let commands = dollar_commands("a$$b$\\$$balloon3,2$$eye=o$$eye$$nope$$tail\n$x")
+            .map(|(_, command)| command)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            commands,
+            vec![
+                DollarCommand::Text("a"),
+                DollarCommand::Dollar,
+                DollarCommand::Text("b"),
+                DollarCommand::Link,
+                DollarCommand::Balloon {
+                    width: 3,
+                    height: 2
+                },
+                DollarCommand::Assign {
+                    name: "eye",
+                    value: "o"
+                },
+                DollarCommand::Variable("eye"),
+                DollarCommand::Variable("nope"),
+                DollarCommand::Text("$tail"),
+                DollarCommand::Text("\n"),
+                DollarCommand::Text("$x"),
+            ]
+        );
+
+        let raw = dollar_commands("x$/$y$")
+            .map(|(raw, _)| raw)
+            .collect::<String>();
+        assert_eq!(raw, "x$/$y$");
+    }
+
+    #[test]
+    fn assigned_variables_expand_on_later_lines() {
+        let style = BalloonStyle::default_for_mode(BalloonMode::Say);
+        let out = insert_balloon("$eye=@$\n($eye$_$eye$) $mouth$", &[], &style, 0);
+        assert_eq!(out, "\n(@_@) $mouth$");
+    }
+
+    #[test]
+    fn stacks_balloon_above_art_without_anchor() {
+        let style = BalloonStyle::default_for_mode(BalloonMode::Say);
+        let balloon = ["<>".to_string()];
+        assert_eq!(insert_balloon("a\n\n", &balloon, &style, 0), "<>\na\n");
+        assert_eq!(insert_balloon("", &balloon, &style, 0), "<>");
+    }
+
     #[test]
     fn parses_metadata_header() {
         let raw = "$$$\nNAME: Twilight\ncomment\n$$$\npony";
@@ -229,4 +967,136 @@ mod tests {
         assert_eq!(meta.comments, vec!["comment"]);
         assert_eq!(body.trim(), "pony");
     }
+
+    #[test]
+    fn typed_metadata_accessors() {
+        let raw = "$$$\nNAME: Twilight Sparkle\nOTHER NAMES: Twi, Twilight\nGROUP: mane six, unicorn\nBALLOON: round, ascii\nLINK: 2\nLICENSE: CC-BY\n$$$\npony";
+        let (metadata, body) = parse_metadata_header(raw);
+        let asset = PonyAsset {
+            path: PathBuf::from("/ponies/twilight.pony"),
+            metadata,
+            body,
+        };
+
+        assert_eq!(asset.display_name(), "Twilight Sparkle");
+        assert_eq!(asset.metadata.other_names(), vec!["Twi", "Twilight"]);
+        assert_eq!(asset.metadata.groups(), vec!["mane six", "unicorn"]);
+        assert_eq!(asset.metadata.balloon_preference(), vec!["round", "ascii"]);
+        assert_eq!(asset.metadata.link_rows(), Some(2));
+        #[allow(deprecated)]
+        let link_columns = asset.metadata.link_columns();
+        assert_eq!(link_columns, Some(2));
+        assert_eq!(asset.metadata.license(), Some("CC-BY"));
+        assert_eq!(asset.metadata.source(), None);
+    }
+
+    #[test]
+    fn strict_parsing_locates_what_lenient_parsing_tolerates() {
+        let path = PathBuf::from("p.pony");
+        let strict = |raw: &str| parse_pony_strict(path.clone(), raw);
+        let asset =
+            strict("$$$\nNAME: Ok\nsome comment\n$$$\n$balloon$\n$$ $\\$\n").expect("clean");
+        assert_eq!(asset.metadata.name(), Some("Ok"));
+
+        assert_eq!(
+            strict("$$$\nNAME: Open\n$balloon$\n").expect_err("unterminated"),
+            PonyParseError::UnterminatedHeader { line: 1 }
+        );
+        let error = strict("$$$\nNAME: Ok\nGroup: mane six\n$$$\n").expect_err("bad tag");
+        assert_eq!(error.line(), 3);
+        assert!(
+            error.to_string().contains("`Group` must be upper case"),
+            "{error}"
+        );
+        assert!(matches!(
+            strict("$$$\nBALLOON=round\n$$$\n").expect_err("no colon"),
+            PonyParseError::BadTag { line: 2, .. }
+        ));
+        assert_eq!(
+            strict("$balloon$\n é $eye=o$ costs $5\n").expect_err("stray"),
+            PonyParseError::StrayDollar {
+                line: 2,
+                column: 18
+            }
+        );
+
+        let raw = "$$$\nGroup: x\n$$$\n$balloon$\n";
+        assert_eq!(
+            asset_from_source(path.clone(), raw).metadata.comments,
+            ["Group: x"]
+        );
+    }
+
+    #[test]
+    fn dimensions_measure_visible_art_below_the_anchor() {
+        let body = "$eye=o$\n$balloon8$\n  $\\$\n \u{1b}[35m($eye$$$o)\u{1b}[0m\n";
+        let asset = PonyAsset {
+            path: PathBuf::from("p.pony"),
+            metadata: PonyMetadata::default(),
+            body: body.into(),
+        };
+        assert_eq!(asset.dimensions(), (6, 3));
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn loads_latin1_pony_files() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        std::fs::write(
+            tmp.path().join("legacy.pony"),
+            b"$$$\nNAME: Caf\xe9\n$$$\n$balloon$\n \xab\xbb\n",
+        )
+        .expect("write");
+
+        let asset = load_pony("legacy", &[tmp.path().to_path_buf()], true).expect("load");
+        assert_eq!(asset.display_name(), "Caf\u{e9}");
+        assert_eq!(asset.body.lines().last(), Some(" \u{ab}\u{bb}"));
+
+        std::fs::write(tmp.path().join("binary.pony"), b"\xff\x00").expect("write");
+        assert!(matches!(
+            load_pony("binary", &[tmp.path().to_path_buf()], true),
+            Err(PonyError::Io { .. })
+        ));
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn loads_explicit_paths_without_searching_roots() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let file = tmp.path().join("mine.pony");
+        std::fs::write(&file, "$balloon$\nmine\n").expect("write");
+        let roots = vec![PathBuf::from("/nonexistent")];
+
+        let asset = load_pony(file.to_str().expect("utf-8"), &roots, true).expect("load");
+        assert_eq!(asset.path, file);
+
+        let missing = tmp.path().join("gone.pony");
+        match load_pony(missing.to_str().expect("utf-8"), &roots, true) {
+            Err(PonyError::PonyFileNotFound { path }) => assert_eq!(path, missing),
+            other => panic!("unexpected result: {other:?}"),
+        }
+        assert!(matches!(
+            load_pony("mine", &roots, true),
+            Err(PonyError::PonyNotFound { .. })
+        ));
+
+        let first = tmp.path().join("first");
+        std::fs::create_dir_all(&first).expect("mkdir");
+        std::fs::write(first.join("Mine.pony"), "$balloon$\nfirst\n").expect("write");
+        let roots = vec![first.clone(), tmp.path().to_path_buf()];
+        let exact = load_pony("mine", &roots, false).expect("exact match in a later root");
+        assert_eq!(exact.path, file);
+        let folded = load_pony("MINE", &roots, false).expect("case-insensitive match");
+        assert_eq!(folded.path, first.join("Mine.pony"));
+        assert!(load_pony("MINE", &roots, true).is_err());
+
+        std::fs::write(first.join(crate::ALIASES_FILE), "me -> mine\n").expect("write");
+        let aliased = load_pony("me", &roots, true).expect("alias");
+        assert_eq!(aliased.path, file);
+
+        if let Some(home) = std::env::var_os("HOME") {
+            assert_eq!(expand_tilde("~/p.pony"), PathBuf::from(home).join("p.pony"));
+        }
+        assert_eq!(expand_tilde("~other/p"), PathBuf::from("~other/p"));
+    }
 }
@@ -0,0 +1,168 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use tracing::{debug, trace, warn};
+use walkdir::WalkDir;
+
+/// One upstream quote file, e.g. `applejack+applejack2.0`.
+///
+/// The stem lists every pony the quote belongs to, joined with `+`; the numeric
+/// extension only keeps file names unique.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct QuoteFile {
+    pub(crate) ponies: Vec<String>,
+    pub(crate) number: u32,
+    pub(crate) path: PathBuf,
+}
+
+impl QuoteFile {
+    fn parse(path: PathBuf) -> Option<Self> {
+        let file_name = path.file_name()?.to_str()?;
+        let (stem, number) = file_name.rsplit_once('.')?;
+        let number = number.parse::<u32>().ok()?;
+        let ponies = stem
+            .split('+')
+            .filter(|pony| !pony.is_empty())
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        if ponies.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            ponies,
+            number,
+            path,
+        })
+    }
+
+    pub(crate) fn belongs_to(&self, pony: &str) -> bool {
+        self.ponies.iter().any(|name| name == pony)
+    }
+}
+
+/// Quote files from every root, in root order; earlier roots shadow identical file names.
+pub(crate) fn quote_files(roots: &[PathBuf]) -> Vec<QuoteFile> {
+    let mut files = BTreeMap::new();
+    for found in crate::scan::scan_roots(roots, scan_root) {
+        for file in found {
+            let key = file.path.file_name().map(|name| name.to_os_string());
+            files.entry(key).or_insert(file);
+        }
+    }
+
+    let mut files = files.into_values().collect::<Vec<_>>();
+    files.sort_by(|a, b| (&a.ponies, a.number).cmp(&(&b.ponies, b.number)));
+    debug!(files = files.len(), "indexed quote files");
+    files
+}
+
+fn scan_root(root: &Path) -> Vec<QuoteFile> {
+    if !root.is_dir() {
+        return Vec::new();
+    }
+
+    WalkDir::new(root)
+        .follow_links(true)
+        .min_depth(1)
+        .max_depth(1)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            let path = entry.into_path();
+            let parsed = QuoteFile::parse(path.clone());
+            if parsed.is_none() {
+                trace!(path = %path.display(), "skipping non-quote file");
+            }
+            parsed
+        })
+        .collect()
+}
+
+/// Number of quotes linked to each pony name, counting each file name once and leaving
+/// out files with nothing to say, as [`list_quotes`] does.
+pub(crate) fn quote_counts(roots: &[PathBuf]) -> BTreeMap<String, usize> {
+    let mut counts = BTreeMap::new();
+    for file in quote_files(roots) {
+        if read_quote(&file).is_none() {
+            continue;
+        }
+        for pony in file.ponies {
+            *counts.entry(pony).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+/// Every quote for `pony`, ordered by linked pony names and then quote number.
+pub(crate) fn list_quotes(pony: &str, roots: &[PathBuf]) -> Vec<String> {
+    quote_files(roots)
+        .iter()
+        .filter(|file| file.belongs_to(pony))
+        .filter_map(read_quote)
+        .collect()
+}
+
+/// The text of a quote file without its final newline; `None` when it is blank or
+/// unreadable.
+fn read_quote(file: &QuoteFile) -> Option<String> {
+    match std::fs::read(&file.path) {
+        Ok(raw) => Some(
+            String::from_utf8_lossy(&raw)
+                .trim_end_matches(['\n', '\r'])
+                .to_string(),
+        )
+        .filter(|quote| !quote.trim().is_empty()),
+        Err(error) => {
+            warn!(path = %file.path.display(), %error, "failed reading quote file");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_quotes_to_every_named_pony() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let dir = tmp.path().join("quotes");
+        std::fs::create_dir_all(&dir).expect("mkdir");
+        std::fs::write(dir.join("applejack+applejack2.0"), "Yeehaw!\n").expect("write");
+        std::fs::write(dir.join("applejack.1"), "Apples.\n").expect("write");
+        std::fs::write(dir.join("rarity.0"), "Darling.\n").expect("write");
+        std::fs::write(dir.join("README"), "not a quote\n").expect("write");
+
+        let roots = vec![dir];
+        assert_eq!(list_quotes("applejack", &roots), vec!["Apples.", "Yeehaw!"]);
+        assert_eq!(list_quotes("applejack2", &roots), vec!["Yeehaw!"]);
+        assert!(list_quotes("twilight", &roots).is_empty());
+        assert_eq!(quote_files(&roots).len(), 3);
+
+        let counts = quote_counts(&roots);
+        assert_eq!(counts.get("applejack"), Some(&2));
+        assert_eq!(counts.get("applejack2"), Some(&1));
+        assert_eq!(counts.get("rarity"), Some(&1));
+    }
+
+    #[test]
+    fn counts_skip_blank_files_and_shadowed_names() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let (first, second) = (tmp.path().join("first"), tmp.path().join("second"));
+        std::fs::create_dir_all(&first).expect("mkdir");
+        std::fs::create_dir_all(&second).expect("mkdir");
+        std::fs::write(first.join("rarity.0"), "Darling.\n").expect("write");
+        std::fs::write(first.join("rarity.1"), "\n  \n").expect("write");
+        std::fs::write(second.join("rarity.0"), "Shadowed.\n").expect("write");
+        std::fs::write(second.join("rarity.2"), "Marvelous.\n").expect("write");
+
+        let roots = vec![first, second];
+        assert_eq!(quote_counts(&roots).get("rarity"), Some(&2));
+        assert_eq!(
+            list_quotes("rarity", &roots),
+            vec!["Darling.", "Marvelous."]
+        );
+    }
+}
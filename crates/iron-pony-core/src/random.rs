@@ -0,0 +1,144 @@
+//! Seeded draws reproducing CPython's `random` module, which upstream ponysay uses.
+//!
+//! After `random.seed(seed)`, Python's `random.randrange(len)` returns the same index as
+//! `PythonRandom::new(seed).randbelow(len)`, on every platform and release.
+
+const N: usize = 624;
+const M: usize = 397;
+const MATRIX_A: u32 = 0x9908_b0df;
+const UPPER_MASK: u32 = 0x8000_0000;
+const LOWER_MASK: u32 = 0x7fff_ffff;
+
+/// MT19937, seeded the way CPython seeds it from an integer.
+pub(crate) struct PythonRandom {
+    state: [u32; N],
+    index: usize,
+}
+
+impl PythonRandom {
+    pub(crate) fn new(seed: u64) -> Self {
+        // CPython splits the seed into 32-bit words, least significant first.
+        let key = match (seed as u32, (seed >> 32) as u32) {
+            (low, 0) => vec![low],
+            (low, high) => vec![low, high],
+        };
+        let mut rng = Self::init_genrand(19_650_218);
+        rng.init_by_array(&key);
+        rng
+    }
+
+    /// Uniform integer in `0..n`; `0` when `n` is `0`.
+    pub(crate) fn randbelow(&mut self, n: usize) -> usize {
+        if n == 0 {
+            return 0;
+        }
+        let n = n as u64;
+        let bits = u64::BITS - n.leading_zeros();
+        loop {
+            let candidate = self.getrandbits(bits);
+            if candidate < n {
+                return candidate as usize;
+            }
+        }
+    }
+
+    fn getrandbits(&mut self, bits: u32) -> u64 {
+        if bits <= 32 {
+            return u64::from(self.next_u32() >> (32 - bits));
+        }
+        let low = u64::from(self.next_u32());
+        let high = u64::from(self.next_u32() >> (64 - bits));
+        low | (high << 32)
+    }
+
+    fn init_genrand(seed: u32) -> Self {
+        let mut state = [0u32; N];
+        state[0] = seed;
+        for i in 1..N {
+            let prev = state[i - 1];
+            state[i] = 1_812_433_253u32
+                .wrapping_mul(prev ^ (prev >> 30))
+                .wrapping_add(i as u32);
+        }
+        Self { state, index: N }
+    }
+
+    fn init_by_array(&mut self, key: &[u32]) {
+        let mt = &mut self.state;
+        let (mut i, mut j) = (1, 0);
+        for _ in 0..N.max(key.len()) {
+            let prev = mt[i - 1];
+            mt[i] = (mt[i] ^ (prev ^ (prev >> 30)).wrapping_mul(1_664_525))
+                .wrapping_add(key[j])
+                .wrapping_add(j as u32);
+            i += 1;
+            j += 1;
+            if i >= N {
+                mt[0] = mt[N - 1];
+                i = 1;
+            }
+            if j >= key.len() {
+                j = 0;
+            }
+        }
+        for _ in 0..N - 1 {
+            let prev = mt[i - 1];
+            mt[i] =
+                (mt[i] ^ (prev ^ (prev >> 30)).wrapping_mul(1_566_083_941)).wrapping_sub(i as u32);
+            i += 1;
+            if i >= N {
+                mt[0] = mt[N - 1];
+                i = 1;
+            }
+        }
+        mt[0] = UPPER_MASK;
+    }
+
+    fn next_u32(&mut self) -> u32 {
+        if self.index >= N {
+            self.twist();
+        }
+        let mut y = self.state[self.index];
+        self.index += 1;
+        y ^= y >> 11;
+        y ^= (y << 7) & 0x9d2c_5680;
+        y ^= (y << 15) & 0xefc6_0000;
+        y ^ (y >> 18)
+    }
+
+    fn twist(&mut self) {
+        let mt = &mut self.state;
+        for i in 0..N {
+            let y = (mt[i] & UPPER_MASK) | (mt[(i + 1) % N] & LOWER_MASK);
+            let mag = if y & 1 == 0 { 0 } else { MATRIX_A };
+            mt[i] = mt[(i + M) % N] ^ (y >> 1) ^ mag;
+        }
+        self.index = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_cpython_random() {
+        // python3 -c "import random; random.seed(s); print(random.getrandbits(32),
+        //     random.randrange(0, 10), random.randrange(0, 1000))"
+        for (seed, bits, tenth, thousandth) in [
+            (0, 3_626_764_237, 6, 776),
+            (1, 577_090_037, 9, 867),
+            (42, 2_746_317_213, 1, 25),
+            ((1 << 40) + 7, 2_635_837_658, 9, 722),
+        ] {
+            let mut rng = PythonRandom::new(seed);
+            assert_eq!(rng.getrandbits(32), bits, "seed {seed}");
+            assert_eq!(rng.randbelow(10), tenth, "seed {seed}");
+            assert_eq!(rng.randbelow(1000), thousandth, "seed {seed}");
+        }
+
+        let mut rng = PythonRandom::new(7);
+        let draws = (0..8).map(|_| rng.randbelow(5)).collect::<Vec<_>>();
+        assert_eq!(draws, [2, 1, 3, 0, 0, 4, 0, 2]);
+    }
+}
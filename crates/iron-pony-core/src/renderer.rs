@@ -0,0 +1,284 @@
+//! A long-lived renderer that keeps what it reads from disk between renders.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use tracing::debug;
+
+use crate::balloon::{self, BalloonMode, BalloonStyle};
+use crate::pony::{self, PonyAsset};
+use crate::{AssetPack, PonyError, PonyIndex, RenderConfig, RenderOutput};
+
+/// Everything that decides which balloon style a render uses, short of a
+/// [`RenderConfig::balloon_style`] given outright.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct StyleKey {
+    name: Option<String>,
+    builtin: bool,
+    /// The pony's `BALLOON` preference; only consulted when no style is named.
+    preference: Vec<String>,
+    mode: BalloonMode,
+    exact: bool,
+}
+
+impl StyleKey {
+    pub(crate) fn new(config: &RenderConfig, pony: &PonyAsset, mode: BalloonMode) -> Self {
+        let preference = if config.balloon.is_none() && !config.builtin_balloon {
+            pony.metadata
+                .balloon_preference()
+                .into_iter()
+                .map(str::to_string)
+                .collect()
+        } else {
+            Vec::new()
+        };
+        Self {
+            name: config.balloon.clone(),
+            builtin: config.builtin_balloon,
+            preference,
+            mode,
+            exact: config.exact_names,
+        }
+    }
+}
+
+/// Finds the style for `key`: the pony's preferred balloon, then one from `pack`, then
+/// the built-in, installed default or named style.
+pub(crate) fn resolve_style(
+    key: &StyleKey,
+    balloon_paths: &[PathBuf],
+    pack: Option<&AssetPack>,
+) -> Result<(String, BalloonStyle), PonyError> {
+    let (mode, exact) = (key.mode, key.exact);
+    if !key.preference.is_empty() {
+        let preference = key
+            .preference
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        if let Some(preferred) =
+            balloon::load_preferred_style(&preference, balloon_paths, mode, exact)
+        {
+            return Ok(preferred);
+        }
+    }
+
+    let Some(name) = key.name.as_deref() else {
+        if key.builtin {
+            return Ok(("default".to_string(), BalloonStyle::default_for_mode(mode)));
+        }
+        return Ok(balloon::load_default_style(balloon_paths, mode, exact));
+    };
+    let loaded = match pack.and_then(|pack| pack.load_style(name, mode, exact)) {
+        Some(packed) => packed.map(Some),
+        None => balloon::load_style(Some(name), balloon_paths, mode, exact),
+    };
+    let style = loaded
+        .map_err(|source| PonyError::BalloonParse {
+            name: name.to_string(),
+            source,
+        })?
+        .ok_or_else(|| PonyError::BalloonNotFound {
+            name: name.to_string(),
+        })?;
+    Ok((name.to_string(), style))
+}
+
+/// Renders many messages against the same asset directories, caching the pony index,
+/// loaded ponies and parsed balloon styles instead of re-reading files on every render.
+///
+/// Built for the server, `--filter` and `--loop`. The caches never expire on their own;
+/// call [`Renderer::invalidate`] after the directories change. The renderer is `Sync`, so
+/// one instance can serve many threads.
+#[derive(Debug)]
+pub struct Renderer {
+    pony_paths: Vec<PathBuf>,
+    balloon_paths: Vec<PathBuf>,
+    pack: Option<AssetPack>,
+    exact_names: bool,
+    index: Mutex<Option<Arc<PonyIndex>>>,
+    ponies: Mutex<HashMap<String, PonyAsset>>,
+    styles: Mutex<HashMap<StyleKey, (String, BalloonStyle)>>,
+}
+
+impl Renderer {
+    /// Takes the asset locations (`pony_paths`, `balloon_paths` and `pack`) and
+    /// `exact_names` from `config`. The same fields of configs later passed to the renderer
+    /// are ignored.
+    pub fn new(config: &RenderConfig) -> Self {
+        Self {
+            pony_paths: config.pony_paths.clone(),
+            balloon_paths: config.balloon_paths.clone(),
+            pack: config.pack.clone(),
+            exact_names: config.exact_names,
+            index: Mutex::new(None),
+            ponies: Mutex::new(HashMap::new()),
+            styles: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Starts from `index`, such as one from [`PonyIndex::load`], instead of scanning the
+    /// pony directories on first use. It should already include the pack's ponies.
+    pub fn with_index(self, index: PonyIndex) -> Self {
+        *lock(&self.index) = Some(Arc::new(index));
+        self
+    }
+
+    /// The pony index, scanned on first use and after [`Renderer::invalidate`].
+    pub fn index(&self) -> Arc<PonyIndex> {
+        let mut index = lock(&self.index);
+        let index = index.get_or_insert_with(|| {
+            let mut scanned = PonyIndex::scan(&self.pony_paths);
+            if let Some(pack) = &self.pack {
+                scanned.add_pack(pack);
+            }
+            Arc::new(scanned)
+        });
+        Arc::clone(index)
+    }
+
+    /// Like [`crate::resolve_pony`], but random picks come from the cached index and the
+    /// loaded pony is kept for next time.
+    pub fn resolve_pony(
+        &self,
+        config: &RenderConfig,
+        seed: Option<u64>,
+    ) -> Result<PonyAsset, PonyError> {
+        let requested = config.requested_pony();
+        let name = crate::select_pony_indexed(requested, &self.pony_paths, &self.index(), seed)?;
+        if let Some(pony) = lock(&self.ponies).get(&name) {
+            return Ok(pony.clone());
+        }
+        let pony = match self
+            .pack
+            .as_ref()
+            .and_then(|pack| pack.load_pony(&name, self.exact_names))
+        {
+            Some(packed) => packed?,
+            None => pony::load_pony(&name, &self.pony_paths, self.exact_names)?,
+        };
+        debug!(pony = %name, "cached pony");
+        lock(&self.ponies).insert(name, pony.clone());
+        Ok(pony)
+    }
+
+    /// Like [`crate::render_output`], with the balloon style taken from the cache.
+    pub fn render_output(
+        &self,
+        config: &RenderConfig,
+        pony: &PonyAsset,
+    ) -> Result<RenderOutput, PonyError> {
+        crate::render_output_with(config, pony, |key| self.style(key))
+    }
+
+    /// Like [`crate::render_dialogue`], with balloon styles taken from the cache.
+    pub fn render_dialogue(
+        &self,
+        config: &RenderConfig,
+        left: &PonyAsset,
+        right: &PonyAsset,
+        messages: &[String],
+    ) -> Result<String, PonyError> {
+        crate::render_dialogue_with(config, left, right, messages, |key| self.style(key))
+    }
+
+    /// Like [`crate::render_gallery`], with balloon styles taken from the cache.
+    pub fn render_gallery(
+        &self,
+        config: &RenderConfig,
+        ponies: &[PonyAsset],
+        width: usize,
+    ) -> Result<String, PonyError> {
+        crate::render_gallery_with(config, ponies, width, |key| self.style(key))
+    }
+
+    fn style(&self, key: &StyleKey) -> Result<(String, BalloonStyle), PonyError> {
+        let key = &StyleKey {
+            exact: self.exact_names,
+            ..key.clone()
+        };
+        if let Some(cached) = lock(&self.styles).get(key) {
+            return Ok(cached.clone());
+        }
+        let resolved = resolve_style(key, &self.balloon_paths, self.pack.as_ref())?;
+        debug!(balloon = %resolved.0, "cached balloon style");
+        lock(&self.styles).insert(key.clone(), resolved.clone());
+        Ok(resolved)
+    }
+
+    /// [`Renderer::resolve_pony`] followed by [`Renderer::render_output`], as text.
+    pub fn render(&self, config: &RenderConfig) -> Result<String, PonyError> {
+        let pony = self.resolve_pony(config, None)?;
+        self.render_output(config, &pony)
+            .map(|output| output.text())
+    }
+
+    /// Drops every cached index, pony and balloon style, so the next render re-reads them.
+    pub fn invalidate(&self) {
+        lock(&self.index).take();
+        lock(&self.ponies).clear();
+        lock(&self.styles).clear();
+        debug!("renderer caches invalidated");
+    }
+}
+
+/// The caches stay consistent even if a render panicked while holding a lock.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn caches_ponies_and_balloons_until_invalidated() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let ponies = tmp.path().join("ponies");
+        let balloons = tmp.path().join("balloons");
+        fs::create_dir_all(&ponies).expect("mkdir");
+        fs::create_dir_all(&balloons).expect("mkdir");
+        fs::write(ponies.join("alpha.pony"), "$balloon$\nalpha\n").expect("write");
+        fs::write(balloons.join("stars.say"), "ww:* \nee: *\n").expect("write");
+
+        let config = RenderConfig {
+            message: "hi".to_string(),
+            pony: "alpha".to_string(),
+            balloon: Some("stars".to_string()),
+            pony_paths: vec![ponies.clone()],
+            balloon_paths: vec![balloons.clone()],
+            leading_reset: false,
+            ..RenderConfig::default()
+        };
+        let renderer = Renderer::new(&config);
+        let pony = renderer.resolve_pony(&config, None).expect("pony");
+        let first = renderer
+            .render_output(&config, &pony)
+            .expect("first render");
+        assert_eq!(first.balloon, "stars");
+        assert!(
+            crate::strip_ansi(&first.text()).contains("* hi *"),
+            "{}",
+            first.text()
+        );
+        assert_eq!(renderer.index().names(), ["alpha"]);
+
+        fs::remove_file(ponies.join("alpha.pony")).expect("remove");
+        fs::remove_file(balloons.join("stars.say")).expect("remove");
+        let again = RenderConfig {
+            message: "again".to_string(),
+            ..config.clone()
+        };
+        assert!(renderer.render(&again).expect("cached").contains("again"));
+
+        renderer.invalidate();
+        assert!(renderer.index().names().is_empty());
+        assert!(matches!(
+            renderer.render(&again),
+            Err(PonyError::PonyFileNotFound { .. } | PonyError::PonyNotFound { .. })
+        ));
+    }
+}
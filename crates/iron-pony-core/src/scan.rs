@@ -0,0 +1,75 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use tracing::trace;
+
+/// Runs `scan` over every root concurrently and returns results in root order.
+///
+/// Directory walks dominate startup on network filesystems, so each root gets its own
+/// scoped thread; merging by index keeps output identical to a sequential scan.
+pub(crate) fn scan_roots<T, F>(roots: &[PathBuf], scan: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(&Path) -> T + Sync,
+{
+    if roots.len() <= 1 {
+        return roots.iter().map(|root| scan(root)).collect();
+    }
+
+    trace!(roots = roots.len(), "scanning search roots concurrently");
+    thread::scope(|scope| {
+        let handles = roots
+            .iter()
+            .map(|root| {
+                let scan = &scan;
+                scope.spawn(move || scan(root))
+            })
+            .collect::<Vec<_>>();
+
+        handles
+            .into_iter()
+            .map(|handle| match handle.join() {
+                Ok(result) => result,
+                Err(panic) => std::panic::resume_unwind(panic),
+            })
+            .collect()
+    })
+}
+
+/// Files directly in `root` whose names equal one of `file_names` ignoring case, in the
+/// order of `file_names`. Exact matches are left out: callers look those up first.
+pub(crate) fn case_insensitive_matches(root: &Path, file_names: &[String]) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(root) else {
+        return Vec::new();
+    };
+    let mut entries = entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .collect::<Vec<_>>();
+    entries.sort();
+
+    let mut out = Vec::new();
+    for wanted in file_names {
+        let lowered = wanted.to_lowercase();
+        for entry in &entries {
+            if entry != wanted && entry.to_lowercase() == lowered {
+                out.push(root.join(entry));
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preserves_root_order() {
+        let roots = (0..8)
+            .map(|i| PathBuf::from(format!("/root-{i}")))
+            .collect::<Vec<_>>();
+        let scanned = scan_roots(&roots, |root| root.to_path_buf());
+        assert_eq!(scanned, roots);
+    }
+}
@@ -0,0 +1,140 @@
+//! `--search`: finds ponies by name, metadata and quote text.
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use crate::{PonyIndex, quote};
+
+/// Where a [`SearchMatch`] was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SearchField {
+    /// The file name the pony is selected by.
+    Name,
+    /// The `NAME` metadata tag.
+    DisplayName,
+    OtherNames,
+    Group,
+    Quote,
+}
+
+impl SearchField {
+    /// Label for output: metadata tags are spelled as in the pony header.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::DisplayName => "NAME",
+            Self::OtherNames => "OTHER NAMES",
+            Self::Group => "GROUP",
+            Self::Quote => "quote",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchMatch {
+    pub pony: String,
+    pub field: SearchField,
+    /// The whole value that contained the query.
+    pub text: String,
+}
+
+/// Ponies in `index` whose name, `NAME`/`OTHER NAMES`/`GROUP` tags or quotes contain
+/// `query`, ignoring case. A pony appears once per matching value, in index order; a blank
+/// query matches nothing.
+pub fn search_ponies(index: &PonyIndex, quote_paths: &[PathBuf], query: &str) -> Vec<SearchMatch> {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let contains = |text: &str| text.to_lowercase().contains(&query);
+    let quoted = quote::quote_counts(quote_paths);
+
+    let mut seen = HashSet::new();
+    let mut matches = Vec::new();
+    for pony in index.summaries() {
+        // Later roots only shadow the name; the first pony found is the one selected.
+        if !seen.insert(pony.name.clone()) {
+            continue;
+        }
+        let mut found = |field, text: &str| {
+            if contains(text) {
+                matches.push(SearchMatch {
+                    pony: pony.name.clone(),
+                    field,
+                    text: text.to_string(),
+                });
+            }
+        };
+        found(SearchField::Name, &pony.name);
+        let metadata = pony.metadata();
+        if let Some(name) = metadata.name() {
+            found(SearchField::DisplayName, name);
+        }
+        for name in metadata.other_names() {
+            found(SearchField::OtherNames, name);
+        }
+        for group in metadata.groups() {
+            found(SearchField::Group, group);
+        }
+        if quoted.contains_key(&pony.name) {
+            for text in quote::list_quotes(&pony.name, quote_paths) {
+                found(SearchField::Quote, text.trim());
+            }
+        }
+    }
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn matches_names_metadata_and_quotes() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let ponies = tmp.path().join("ponies");
+        let quotes = tmp.path().join("quotes");
+        fs::create_dir_all(&ponies).expect("mkdir");
+        fs::create_dir_all(&quotes).expect("mkdir");
+        fs::write(
+            ponies.join("twilight.pony"),
+            "$$$\nNAME: Twilight Sparkle\nGROUP: mane six, alicorn\n$$$\n$balloon$\n",
+        )
+        .expect("write");
+        fs::write(
+            ponies.join("sparkler.pony"),
+            "$$$\nNAME: Sparkler\nOTHER NAMES: Sparkles\n$$$\n$balloon$\n",
+        )
+        .expect("write");
+        fs::write(
+            quotes.join("twilight.1"),
+            "All the ponies in this town are CRAZY!\n",
+        )
+        .expect("write");
+
+        let index = PonyIndex::scan(std::slice::from_ref(&ponies));
+        let found = |query| {
+            search_ponies(&index, std::slice::from_ref(&quotes), query)
+                .into_iter()
+                .map(|found| format!("{} {} {}", found.pony, found.field.as_str(), found.text))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            found("SPARK"),
+            [
+                "sparkler name sparkler",
+                "sparkler NAME Sparkler",
+                "sparkler OTHER NAMES Sparkles",
+                "twilight NAME Twilight Sparkle",
+            ]
+        );
+        assert_eq!(found("alicorn"), ["twilight GROUP alicorn"]);
+        assert_eq!(
+            found("crazy"),
+            ["twilight quote All the ponies in this town are CRAZY!"]
+        );
+        assert!(found("  ").is_empty());
+    }
+}
@@ -0,0 +1,292 @@
+//! Where the message comes from: arguments, stdin, a file, pony quotes, fortunes, or a
+//! quote with fortunes to fall back on.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use tracing::{debug, warn};
+
+use crate::{
+    FortuneConfig, PonyError, PonyIndex, pick_fortune, pick_quote, random_index, select_quoted_pony,
+};
+
+/// Where a message comes from.
+#[derive(Debug, Clone)]
+pub enum MessageSource {
+    /// The words joined with spaces; with no words, stdin (trimmed) unless it is a terminal.
+    Args(Vec<String>),
+    /// A file read verbatim, so leading blank lines, indentation and escape sequences reach
+    /// the balloon unchanged; bytes that are not UTF-8 become U+FFFD. `-` reads stdin.
+    File(PathBuf),
+    /// A quote from one of these ponies, or from any installed pony with quotes when empty.
+    Quote(Vec<String>),
+    Fortune(FortuneConfig),
+    /// A quote as with [`MessageSource::Quote`] when one of the ponies has any, otherwise a
+    /// fortune.
+    QuoteOrFortune(Vec<String>, FortuneConfig),
+}
+
+/// What quotes and random choices are drawn from.
+#[derive(Debug, Clone, Copy)]
+pub struct SourceOptions<'a> {
+    /// Quote directories, searched in order.
+    pub quote_paths: &'a [PathBuf],
+    /// Installed ponies, which limit a bare quote to ponies that exist.
+    pub index: &'a PonyIndex,
+    /// Seed for every random choice; `None` draws fresh entropy.
+    pub seed: Option<u64>,
+}
+
+/// A message from [`MessageSource::resolve`] and the pony meant to say it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourcedMessage {
+    pub message: String,
+    /// The quoted pony, or for a fortune standing in for a quote, one of the requested
+    /// ponies. `None` leaves the pony to the caller.
+    pub pony: Option<String>,
+}
+
+impl MessageSource {
+    /// Produces the message, reading `stdin` only when the source calls for it; `is_tty`
+    /// says whether it is a terminal. Every random choice uses the options' seed. For
+    /// [`MessageSource::QuoteOrFortune`], only a missing quote falls through to fortunes,
+    /// and when both come up empty the error carries both reasons.
+    pub fn resolve(
+        &self,
+        options: &SourceOptions<'_>,
+        stdin: impl Read,
+        is_tty: bool,
+    ) -> Result<SourcedMessage, PonyError> {
+        let SourceOptions {
+            quote_paths,
+            index,
+            seed,
+        } = *options;
+        match self {
+            Self::Args(words) if !words.is_empty() => Ok(SourcedMessage {
+                message: words.join(" "),
+                pony: None,
+            }),
+            Self::Args(_) => {
+                let message = if is_tty {
+                    String::new()
+                } else {
+                    read_stdin(stdin)?.trim().to_string()
+                };
+                if message.is_empty() {
+                    warn!("no message source resolved");
+                    return Err(PonyError::NoMessage);
+                }
+                Ok(SourcedMessage {
+                    message,
+                    pony: None,
+                })
+            }
+            Self::File(path) => Ok(SourcedMessage {
+                message: read_message_file(path, stdin)?,
+                pony: None,
+            }),
+            Self::Quote(ponies) => quote(ponies, quote_paths, index, seed),
+            Self::Fortune(config) => Ok(SourcedMessage {
+                message: pick_fortune(config)?,
+                pony: None,
+            }),
+            Self::QuoteOrFortune(ponies, config) => {
+                let quote_error = match quote(ponies, quote_paths, index, seed) {
+                    Err(error @ PonyError::NoQuotes { .. }) => error,
+                    other => return other,
+                };
+                debug!(%quote_error, "no quote to use; falling back to a fortune");
+                let message =
+                    pick_fortune(config).map_err(|fortune| PonyError::NoQuoteOrFortune {
+                        quote: Box::new(quote_error),
+                        fortune: Box::new(fortune),
+                    })?;
+                let pony =
+                    (!ponies.is_empty()).then(|| ponies[random_index(ponies.len(), seed)].clone());
+                Ok(SourcedMessage { message, pony })
+            }
+        }
+    }
+}
+
+fn read_stdin(mut stdin: impl Read) -> Result<String, PonyError> {
+    let mut data = String::new();
+    stdin
+        .read_to_string(&mut data)
+        .map_err(|source| PonyError::Io {
+            path: PathBuf::from("<stdin>"),
+            source,
+        })?;
+    Ok(data)
+}
+
+fn read_message_file(path: &Path, mut stdin: impl Read) -> Result<String, PonyError> {
+    let io_error = |source| PonyError::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+    let raw = if path == Path::new("-") {
+        let mut raw = Vec::new();
+        stdin.read_to_end(&mut raw).map_err(io_error)?;
+        raw
+    } else {
+        std::fs::read(path).map_err(io_error)?
+    };
+    debug!(path = %path.display(), bytes = raw.len(), "read message file");
+    Ok(String::from_utf8(raw)
+        .unwrap_or_else(|error| String::from_utf8_lossy(error.as_bytes()).into_owned()))
+}
+
+fn quote(
+    ponies: &[String],
+    quote_paths: &[PathBuf],
+    index: &PonyIndex,
+    seed: Option<u64>,
+) -> Result<SourcedMessage, PonyError> {
+    let pony = select_quoted_pony(ponies, quote_paths, index, seed)?;
+    let message = pick_quote(&pony, quote_paths, seed)?;
+    Ok(SourcedMessage {
+        message,
+        pony: Some(pony),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io};
+
+    use super::*;
+
+    #[test]
+    fn quotes_win_and_fortunes_fill_in() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let quotes = tmp.path().join("quotes");
+        let ponies = tmp.path().join("ponies");
+        let fortunes = tmp.path().join("fortunes");
+        for dir in [&quotes, &ponies, &fortunes] {
+            fs::create_dir_all(dir).expect("mkdir");
+        }
+        fs::write(quotes.join("rarity.0"), "Darling.\n").expect("write");
+        fs::write(ponies.join("rarity.pony"), "$balloon$\n").expect("write");
+        fs::write(ponies.join("spike.pony"), "$balloon$\n").expect("write");
+        fs::write(fortunes.join("sample"), "Gems!\n").expect("write");
+        let quote_paths = vec![quotes];
+        let index = PonyIndex::scan(&[ponies]);
+        let fortune = FortuneConfig {
+            search_paths: vec![fortunes],
+            ..FortuneConfig::default()
+        };
+        let options = SourceOptions {
+            quote_paths: &quote_paths,
+            index: &index,
+            seed: Some(2),
+        };
+        let resolve = |source: MessageSource| source.resolve(&options, io::empty(), true);
+
+        let both = |ponies: &[&str]| {
+            MessageSource::QuoteOrFortune(
+                ponies.iter().map(ToString::to_string).collect(),
+                fortune.clone(),
+            )
+        };
+        assert_eq!(
+            resolve(both(&["rarity"])).expect("quote"),
+            SourcedMessage {
+                message: "Darling.".to_string(),
+                pony: Some("rarity".to_string()),
+            }
+        );
+        assert_eq!(
+            resolve(both(&["spike"])).expect("fortune"),
+            SourcedMessage {
+                message: "Gems!".to_string(),
+                pony: Some("spike".to_string()),
+            }
+        );
+        assert!(matches!(
+            resolve(MessageSource::Quote(vec!["spike".to_string()])),
+            Err(PonyError::NoQuotes { .. })
+        ));
+
+        let empty = tmp.path().join("empty");
+        let error = resolve(MessageSource::QuoteOrFortune(
+            vec!["spike".to_string()],
+            FortuneConfig {
+                search_paths: vec![empty],
+                ..FortuneConfig::default()
+            },
+        ))
+        .expect_err("neither");
+        assert_eq!(
+            error.to_string(),
+            "pony 'spike' has no quotes, and fortune selection failed: no fortune sources found"
+        );
+    }
+
+    #[test]
+    fn arguments_files_and_stdin_resolve_with_injected_input() {
+        let index = PonyIndex::scan(&[]);
+        let options = SourceOptions {
+            quote_paths: &[],
+            index: &index,
+            seed: None,
+        };
+        let resolve = |source: MessageSource, stdin: &[u8], is_tty: bool| {
+            source
+                .resolve(&options, stdin, is_tty)
+                .map(|sourced| sourced.message)
+        };
+        let args =
+            |words: &[&str]| MessageSource::Args(words.iter().map(ToString::to_string).collect());
+
+        assert_eq!(
+            resolve(args(&["hi", "there"]), b"ignored", false).expect("args"),
+            "hi there"
+        );
+        assert_eq!(
+            resolve(args(&[]), b"  piped\n\n", false).expect("stdin"),
+            "piped"
+        );
+        assert!(matches!(
+            resolve(args(&[]), b"piped", true),
+            Err(PonyError::NoMessage)
+        ));
+        assert!(matches!(
+            resolve(args(&[]), b" \n", false),
+            Err(PonyError::NoMessage)
+        ));
+
+        let stdin_file = MessageSource::File(PathBuf::from("-"));
+        assert_eq!(
+            resolve(stdin_file.clone(), b"\n  indented\n", true).expect("file"),
+            "\n  indented\n"
+        );
+        assert_eq!(
+            resolve(stdin_file, b"ok\xff", false).expect("lossy"),
+            "ok\u{fffd}"
+        );
+    }
+
+    #[test]
+    fn message_files_are_read_verbatim() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let path = tmp.path().join("message.txt");
+        let message = "\n  indented\n\u{1b}[31mred\u{1b}[0m\ttab\n\n";
+        fs::write(&path, message).expect("write");
+        assert_eq!(
+            read_message_file(&path, io::empty()).expect("message"),
+            message
+        );
+
+        fs::write(&path, b"caf\xe9\x00\n").expect("write binary");
+        assert_eq!(
+            read_message_file(&path, io::empty()).expect("lossy message"),
+            "caf\u{fffd}\0\n"
+        );
+        assert!(matches!(
+            read_message_file(&tmp.path().join("missing"), io::empty()),
+            Err(PonyError::Io { .. })
+        ));
+    }
+}
@@ -0,0 +1,51 @@
+//! Terminal text utilities used by the renderer, for tools that lay out text next to
+//! ponies or compare rendered output.
+//!
+//! Everything here treats escape sequences (ECMA-48 CSI, OSC, DCS and friends, in both
+//! 7-bit and 8-bit form) as zero-width, never splits one, and measures text in terminal
+//! columns as given by Unicode East Asian Width: most characters take one column, wide
+//! CJK characters two, combining marks none. An unterminated sequence runs to the end of
+//! its input.
+//!
+//! The same functions are re-exported at the crate root.
+
+pub use crate::ansi::{
+    ansi_chunks, strip_ansi, trim_trailing_whitespace, truncate_width, visible_width,
+};
+pub use crate::balloon::{WrapOptions, wrap_message, wrap_message_with};
+#[cfg(feature = "bidi")]
+pub use crate::bidi::reorder_bidi;
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::ansi::tests::styled_text;
+
+    fn words(text: &str) -> String {
+        strip_ansi(text).split_whitespace().collect()
+    }
+
+    proptest! {
+        #[test]
+        fn wrapping_keeps_every_word(message in styled_text(), width in 1usize..40) {
+            let lines = wrap_message(&message, width);
+            prop_assert_eq!(words(&lines.concat()), words(&message));
+        }
+
+        #[test]
+        fn trimming_only_drops_trailing_blanks(input in styled_text()) {
+            let trimmed = trim_trailing_whitespace(&input);
+            let text = strip_ansi(&input);
+            prop_assert_eq!(strip_ansi(&trimmed), text.trim_end());
+            prop_assert_eq!(trim_trailing_whitespace(&trimmed), trimmed.clone());
+        }
+
+        #[test]
+        fn truncation_is_idempotent(input in styled_text(), width in 0usize..20) {
+            let truncated = truncate_width(&input, width);
+            prop_assert_eq!(truncate_width(&truncated, width), truncated.clone());
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! Message transforms applied before the text is wrapped into the balloon.
+
+use std::fmt;
+
+use crate::ansi;
+
+/// Rewrites the message before it is wrapped. Implement it to plug custom text effects
+/// into [`crate::RenderConfig::transforms`]; they run in order, and each call sees one run
+/// of text between escape sequences, so colors and links in the message survive.
+pub trait MessageTransform: fmt::Debug + Send + Sync {
+    fn apply(&self, message: &str) -> String;
+}
+
+/// Combining marks kept on one base character by [`BuiltinTransform::StripZalgo`]; enough
+/// for real diacritics (Vietnamese stacks two), too few for "zalgo" text.
+pub const COMBINING_MARK_LIMIT: usize = 2;
+
+/// The transforms behind `--transform`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuiltinTransform {
+    Upper,
+    Lower,
+    /// Lowercase letters become their small-capital look-alikes (`ᴀʙᴄ`).
+    SmallCaps,
+    /// Drops combining marks past [`COMBINING_MARK_LIMIT`] per base character.
+    StripZalgo,
+}
+
+impl MessageTransform for BuiltinTransform {
+    /// Escape sequences in `message` are passed through untouched.
+    fn apply(&self, message: &str) -> String {
+        ansi::map_text(message, |text| match self {
+            Self::Upper => text.to_uppercase(),
+            Self::Lower => text.to_lowercase(),
+            Self::SmallCaps => text.chars().map(small_cap).collect(),
+            Self::StripZalgo => limit_combining_marks(text, COMBINING_MARK_LIMIT),
+        })
+    }
+}
+
+fn small_cap(c: char) -> char {
+    match c {
+        'a' => 'ᴀ',
+        'b' => 'ʙ',
+        'c' => 'ᴄ',
+        'd' => 'ᴅ',
+        'e' => 'ᴇ',
+        'f' => 'ꜰ',
+        'g' => 'ɢ',
+        'h' => 'ʜ',
+        'i' => 'ɪ',
+        'j' => 'ᴊ',
+        'k' => 'ᴋ',
+        'l' => 'ʟ',
+        'm' => 'ᴍ',
+        'n' => 'ɴ',
+        'o' => 'ᴏ',
+        'p' => 'ᴘ',
+        'q' => 'ǫ',
+        'r' => 'ʀ',
+        's' => 'ꜱ',
+        't' => 'ᴛ',
+        'u' => 'ᴜ',
+        'v' => 'ᴠ',
+        'w' => 'ᴡ',
+        'y' => 'ʏ',
+        'z' => 'ᴢ',
+        // `x` has no small capital; it already looks like one.
+        other => other,
+    }
+}
+
+fn limit_combining_marks(message: &str, limit: usize) -> String {
+    let mut out = String::with_capacity(message.len());
+    let mut stacked = 0;
+    for c in message.chars() {
+        if is_combining_mark(c) {
+            stacked += 1;
+            if stacked > limit {
+                continue;
+            }
+        } else {
+            stacked = 0;
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// The combining diacritical mark blocks, which is where zalgo generators draw from.
+fn is_combining_mark(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0300}'..='\u{036f}'
+            | '\u{0483}'..='\u{0489}'
+            | '\u{1ab0}'..='\u{1aff}'
+            | '\u{1dc0}'..='\u{1dff}'
+            | '\u{20d0}'..='\u{20ff}'
+            | '\u{fe20}'..='\u{fe2f}'
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_transforms_rewrite_text() {
+        assert_eq!(BuiltinTransform::Upper.apply("Hi ß"), "HI SS");
+        assert_eq!(BuiltinTransform::Lower.apply("Hi THERE"), "hi there");
+        assert_eq!(BuiltinTransform::SmallCaps.apply("Box fox!"), "Bᴏx ꜰᴏx!");
+        assert_eq!(
+            BuiltinTransform::StripZalgo.apply("e\u{301}\u{302}\u{303}\u{304}x Vie\u{323}\u{302}t"),
+            "e\u{301}\u{302}x Vie\u{323}\u{302}t"
+        );
+    }
+
+    #[test]
+    fn escape_sequences_pass_through_untouched() {
+        let colored = "\u{1b}[31mred\u{1b}[0m \u{1b}]8;;https://a.test/x\u{7}link\u{1b}]8;;\u{7}";
+        assert_eq!(
+            BuiltinTransform::Upper.apply(colored),
+            "\u{1b}[31mRED\u{1b}[0m \u{1b}]8;;https://a.test/x\u{7}LINK\u{1b}]8;;\u{7}"
+        );
+        assert_eq!(
+            BuiltinTransform::SmallCaps.apply("\u{1b}[1mhi\u{1b}[m"),
+            "\u{1b}[1mʜɪ\u{1b}[m"
+        );
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::BTreeMap;
+#[cfg(feature = "fs")]
+use std::path::Path;
+use std::path::PathBuf;
+
+use tracing::trace;
+#[cfg(feature = "fs")]
+use tracing::{debug, warn};
+#[cfg(feature = "fs")]
+use walkdir::WalkDir;
+
+/// Bidirectional pony name mapping loaded from upstream `*.ucsmap` files.
+///
+/// Each non-comment line has the form `UCS NAME → ascii-name`, mapping the
+/// Unicode display name to the ASCII file stem on disk.
+#[derive(Debug, Clone, Default)]
+pub struct UcsMap {
+    to_ascii: BTreeMap<String, String>,
+    to_ucs: BTreeMap<String, String>,
+}
+
+impl UcsMap {
+    #[cfg(feature = "fs")]
+    pub fn load(roots: &[PathBuf]) -> Self {
+        let mut map = Self::default();
+        for root in roots {
+            for path in ucsmap_files(root) {
+                match std::fs::read_to_string(&path) {
+                    Ok(raw) => {
+                        let before = map.len();
+                        map.extend_from_str(&raw);
+                        debug!(
+                            path = %path.display(),
+                            entries = map.len() - before,
+                            "loaded ucsmap file"
+                        );
+                    }
+                    Err(error) => {
+                        warn!(path = %path.display(), %error, "failed reading ucsmap file");
+                    }
+                }
+            }
+        }
+        map
+    }
+
+    pub fn parse(raw: &str) -> Self {
+        let mut map = Self::default();
+        map.extend_from_str(raw);
+        map
+    }
+
+    fn extend_from_str(&mut self, raw: &str) {
+        for line in raw.lines() {
+            let line = line.trim_end_matches('\r');
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let Some((ucs, ascii)) = line.split_once('→') else {
+                trace!(line, "skipping malformed ucsmap line");
+                continue;
+            };
+
+            let ucs = ucs.trim_matches(' ');
+            let ascii = ascii.trim_matches(' ');
+            if ucs.is_empty() || ascii.is_empty() {
+                continue;
+            }
+
+            self.to_ascii.insert(ucs.to_string(), ascii.to_string());
+            self.to_ucs.insert(ascii.to_string(), ucs.to_string());
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.to_ascii.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.to_ascii.len()
+    }
+
+    /// Maps a Unicode display name to its ASCII file name, or returns the input unchanged.
+    pub fn to_ascii<'a>(&'a self, name: &'a str) -> &'a str {
+        self.to_ascii.get(name).map_or(name, String::as_str)
+    }
+
+    /// Maps an ASCII file name to its Unicode display name, or returns the input unchanged.
+    pub fn to_ucs<'a>(&'a self, name: &'a str) -> &'a str {
+        self.to_ucs.get(name).map_or(name, String::as_str)
+    }
+}
+
+pub fn default_ucsmap_paths() -> Vec<PathBuf> {
+    crate::platform::share_subdirs("ponysay", &["ucsmap"])
+}
+
+#[cfg(feature = "fs")]
+fn ucsmap_files(root: &Path) -> Vec<PathBuf> {
+    if root.is_file() {
+        return vec![root.to_path_buf()];
+    }
+    if !root.is_dir() {
+        return Vec::new();
+    }
+
+    let mut files = WalkDir::new(root)
+        .follow_links(false)
+        .min_depth(1)
+        .max_depth(1)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| entry.into_path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "ucsmap"))
+        .collect::<Vec<_>>();
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_names_both_ways() {
+        let map = UcsMap::parse("# comment\nFleur‐de‐Lis → fleurdelis\nbroken line\n");
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.to_ascii("Fleur‐de‐Lis"), "fleurdelis");
+        assert_eq!(map.to_ucs("fleurdelis"), "Fleur‐de‐Lis");
+        assert_eq!(map.to_ucs("twilight"), "twilight");
+    }
+}
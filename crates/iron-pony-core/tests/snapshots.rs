@@ -0,0 +1,155 @@
+//! Golden snapshots of `render` over the ponies and balloons in `tests/fixtures`.
+//!
+//! Each case's output is compared with `tests/snapshots/<case>.txt`, where escape
+//! characters are written as `\x1b` so diffs stay readable. A missing snapshot fails like a
+//! changed one; run with `UPDATE_SNAPSHOTS=1` to write new snapshots or accept intended
+//! rendering changes.
+
+#![cfg(feature = "fs")]
+
+use std::path::{Path, PathBuf};
+
+use iron_pony_core::{BalloonPlacement, Mode, RenderConfig, resolve_and_render};
+
+struct Case {
+    name: &'static str,
+    pony: &'static str,
+    balloon: Option<&'static str>,
+    mode: Mode,
+    message: &'static str,
+    wrap_width: usize,
+    placement: BalloonPlacement,
+}
+
+const CASES: &[Case] = &[
+    Case {
+        name: "plain_say",
+        pony: "plain",
+        balloon: None,
+        mode: Mode::Say,
+        message: "Hello, world!",
+        wrap_width: 40,
+        placement: BalloonPlacement::Anchor,
+    },
+    Case {
+        name: "plain_think",
+        pony: "plain",
+        balloon: None,
+        mode: Mode::Think,
+        message: "Hmm.",
+        wrap_width: 40,
+        placement: BalloonPlacement::Anchor,
+    },
+    Case {
+        name: "plain_wrapped_multiline",
+        pony: "plain",
+        balloon: None,
+        mode: Mode::Say,
+        message: "The quick brown fox jumps over the lazy dog.\n\nSecond paragraph here.",
+        wrap_width: 16,
+        placement: BalloonPlacement::Anchor,
+    },
+    Case {
+        name: "colored_round_say",
+        pony: "colored",
+        balloon: Some("round"),
+        mode: Mode::Say,
+        message: "\u{1b}[1mBold\u{1b}[0m words in a round balloon",
+        wrap_width: 20,
+        placement: BalloonPlacement::Anchor,
+    },
+    Case {
+        name: "colored_round_think",
+        pony: "colored",
+        balloon: Some("round"),
+        mode: Mode::Think,
+        message: "thinking...",
+        wrap_width: 40,
+        placement: BalloonPlacement::Anchor,
+    },
+    Case {
+        name: "wide_characters",
+        pony: "plain",
+        balloon: Some("round"),
+        mode: Mode::Say,
+        message: "日本語のテキスト and caf\u{e9}",
+        wrap_width: 10,
+        placement: BalloonPlacement::Anchor,
+    },
+    Case {
+        name: "centered",
+        pony: "colored",
+        balloon: None,
+        mode: Mode::Say,
+        message: "centered",
+        wrap_width: 40,
+        placement: BalloonPlacement::Center,
+    },
+];
+
+fn fixtures() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+}
+
+fn render_case(case: &Case) -> String {
+    let config = RenderConfig {
+        message: case.message.to_string(),
+        pony: case.pony.to_string(),
+        pony_paths: vec![fixtures().join("ponies")],
+        balloon: case.balloon.map(str::to_string),
+        balloon_paths: vec![fixtures().join("balloons")],
+        mode: case.mode,
+        wrap_width: case.wrap_width,
+        balloon_placement: case.placement,
+        builtin_balloon: case.balloon.is_none(),
+        ..RenderConfig::default()
+    };
+    resolve_and_render(&config)
+        .unwrap_or_else(|error| panic!("{}: render failed: {error}", case.name))
+}
+
+fn check(name: &str, actual: &str) -> Option<String> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(format!("{name}.txt"));
+    let actual = actual.replace('\u{1b}', "\\x1b");
+    let update = std::env::var_os("UPDATE_SNAPSHOTS").is_some_and(|value| value != "0");
+
+    match std::fs::read_to_string(&path) {
+        Ok(expected) if expected == actual => None,
+        Ok(_) | Err(_) if update => {
+            std::fs::write(&path, &actual).expect("write snapshot");
+            None
+        }
+        Ok(expected) => {
+            let line = expected
+                .lines()
+                .zip(actual.lines())
+                .position(|(left, right)| left != right)
+                .unwrap_or_else(|| expected.lines().count().min(actual.lines().count()));
+            Some(format!(
+                "{name}: output differs from {} at line {}\n--- expected\n{expected}\n--- actual\n{actual}",
+                path.display(),
+                line + 1
+            ))
+        }
+        Err(error) => Some(format!(
+            "{name}: failed reading {}: {error}",
+            path.display()
+        )),
+    }
+}
+
+#[test]
+fn render_matches_snapshots() {
+    let failures = CASES
+        .iter()
+        .filter_map(|case| check(case.name, &render_case(case)))
+        .collect::<Vec<_>>();
+    assert!(
+        failures.is_empty(),
+        "{} snapshot(s) changed; rerun with UPDATE_SNAPSHOTS=1 if intended:\n\n{}",
+        failures.len(),
+        failures.join("\n\n")
+    );
+}
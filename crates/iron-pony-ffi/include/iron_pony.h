@@ -0,0 +1,51 @@
+/*
+ * C interface to iron-pony rendering.
+ *
+ * Every `char *` returned by this library is owned by the caller and must be
+ * released with `ip_string_free`. On failure functions return NULL and the
+ * reason is available from `ip_last_error` on the same thread.
+ */
+#ifndef IRON_PONY_H
+#define IRON_PONY_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/*
+ * Render a pony from a JSON configuration object:
+ *
+ *   {
+ *     "message": "hello",            required
+ *     "pony": "twilight",             name or path; omitted = best.pony/random
+ *     "pony_paths": ["/dir", ...],    omitted = upstream defaults
+ *     "balloon": "ascii",             omitted = pony preference/unicode/built-in
+ *     "balloon_paths": ["/dir", ...],
+ *     "mode": "say" | "think",        default "say"
+ *     "wrap_width": 40,
+ *     "seed": 7,                      random pony selection seed
+ *     "leading_reset": true,          prefix the output with ESC[0m (default true)
+ *     "pony_source": "...",           in-memory pony file; skips the filesystem
+ *     "balloon_source": "..."         in-memory balloon file (with pony_source)
+ *   }
+ */
+char *ip_render(const char *config_json);
+
+/* Newline-separated pony names from roots separated like PATH: ':', or ';' on
+ * Windows (NULL = defaults). */
+char *ip_list_ponies(const char *pony_paths);
+
+/* Last error message for the calling thread, or NULL. Owned by the library. */
+const char *ip_last_error(void);
+
+/* Library version string. Owned by the library. */
+const char *ip_version(void);
+
+/* Release a string returned by ip_render or ip_list_ponies. NULL is ignored. */
+void ip_string_free(char *value);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* IRON_PONY_H */
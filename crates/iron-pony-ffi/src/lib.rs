@@ -0,0 +1,303 @@
+//! C ABI for iron-pony. See `include/iron_pony.h` for the contract.
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::path::PathBuf;
+use std::ptr;
+
+use iron_pony_core::{
+    Mode, Platform, RenderConfig, default_balloon_paths, default_pony_paths, list_ponies, render,
+    render_from_sources_with, resolve_pony,
+};
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+static VERSION: &CStr =
+    match CStr::from_bytes_with_nul(concat!(env!("CARGO_PKG_VERSION"), "\0").as_bytes()) {
+        Ok(version) => version,
+        Err(_) => panic!("version contains an interior nul"),
+    };
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FfiRenderConfig {
+    message: String,
+    #[serde(default)]
+    pony: Option<String>,
+    #[serde(default)]
+    pony_paths: Option<Vec<PathBuf>>,
+    #[serde(default)]
+    balloon: Option<String>,
+    #[serde(default)]
+    balloon_paths: Option<Vec<PathBuf>>,
+    #[serde(default)]
+    mode: FfiMode,
+    #[serde(default = "default_wrap_width")]
+    wrap_width: usize,
+    #[serde(default)]
+    seed: Option<u64>,
+    #[serde(default = "default_leading_reset")]
+    leading_reset: bool,
+    #[serde(default)]
+    pony_source: Option<String>,
+    #[serde(default)]
+    balloon_source: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum FfiMode {
+    #[default]
+    Say,
+    Think,
+}
+
+fn default_wrap_width() -> usize {
+    40
+}
+
+fn default_leading_reset() -> bool {
+    true
+}
+
+/// Renders a pony described by a JSON configuration object.
+///
+/// Returns a newly allocated string (free with [`ip_string_free`]) or NULL on error.
+///
+/// # Safety
+///
+/// `config_json` must be NULL or point to a valid nul-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ip_render(config_json: *const c_char) -> *mut c_char {
+    // SAFETY: forwarded caller contract.
+    let input = unsafe { read_str(config_json) };
+    guard(|| {
+        let raw = input?;
+        let config = serde_json::from_str::<FfiRenderConfig>(raw)
+            .map_err(|error| format!("invalid config json: {error}"))?;
+        render_config(config)
+    })
+}
+
+/// Lists pony names from roots separated like `PATH` (`:`, or `;` on Windows; NULL selects
+/// the upstream defaults).
+///
+/// # Safety
+///
+/// `pony_paths` must be NULL or point to a valid nul-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ip_list_ponies(pony_paths: *const c_char) -> *mut c_char {
+    let paths = if pony_paths.is_null() {
+        Ok(None)
+    } else {
+        // SAFETY: forwarded caller contract.
+        unsafe { read_str(pony_paths) }.map(Some)
+    };
+
+    guard(|| {
+        let roots = match paths? {
+            Some(value) => Platform::current().split_path_list(value),
+            None => default_pony_paths(),
+        };
+        Ok(list_ponies(&roots).join("\n"))
+    })
+}
+
+/// Returns the calling thread's last error message, or NULL. The pointer stays valid
+/// until the next failing call on the same thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn ip_last_error() -> *const c_char {
+    LAST_ERROR.with(|slot| {
+        slot.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn ip_version() -> *const c_char {
+    VERSION.as_ptr()
+}
+
+/// Releases a string returned by this library.
+///
+/// # Safety
+///
+/// `value` must be NULL or a pointer previously returned by `ip_render` or
+/// `ip_list_ponies` that has not been freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ip_string_free(value: *mut c_char) {
+    if value.is_null() {
+        return;
+    }
+    // SAFETY: the pointer came from `CString::into_raw` per the caller contract.
+    drop(unsafe { CString::from_raw(value) });
+}
+
+fn render_config(config: FfiRenderConfig) -> Result<String, String> {
+    let mode = match config.mode {
+        FfiMode::Say => Mode::Say,
+        FfiMode::Think => Mode::Think,
+    };
+
+    if let Some(source) = &config.pony_source {
+        let render_config = RenderConfig {
+            message: config.message,
+            mode,
+            wrap_width: config.wrap_width,
+            leading_reset: config.leading_reset,
+            ..RenderConfig::default()
+        };
+        return render_from_sources_with(&render_config, source, config.balloon_source.as_deref())
+            .map_err(|error| error.to_string());
+    }
+
+    let render_config = RenderConfig {
+        message: config.message,
+        pony: config.pony.unwrap_or_default(),
+        pony_paths: config.pony_paths.unwrap_or_else(default_pony_paths),
+        balloon: config.balloon,
+        balloon_paths: config.balloon_paths.unwrap_or_else(default_balloon_paths),
+        mode,
+        wrap_width: config.wrap_width.max(1),
+        leading_reset: config.leading_reset,
+        ..RenderConfig::default()
+    };
+    resolve_pony(&render_config, config.seed)
+        .and_then(|pony| render(&render_config, &pony))
+        .map_err(|error| error.to_string())
+}
+
+/// Runs `body` with panics contained, converting the result into an owned C string.
+fn guard(body: impl FnOnce() -> Result<String, String>) -> *mut c_char {
+    let result = catch_unwind(AssertUnwindSafe(body)).unwrap_or_else(|panic| {
+        let reason = panic
+            .downcast_ref::<&str>()
+            .map(|value| value.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        Err(format!("internal panic: {reason}"))
+    });
+
+    match result.and_then(|value| {
+        CString::new(value).map_err(|_| "output contains an interior nul byte".to_string())
+    }) {
+        Ok(value) => {
+            debug!("ffi call succeeded");
+            value.into_raw()
+        }
+        Err(message) => {
+            warn!(%message, "ffi call failed");
+            set_last_error(message);
+            ptr::null_mut()
+        }
+    }
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|slot| *slot.borrow_mut() = Some(message));
+}
+
+/// # Safety
+///
+/// `value` must be NULL or point to a valid nul-terminated string.
+unsafe fn read_str<'a>(value: *const c_char) -> Result<&'a str, String> {
+    if value.is_null() {
+        return Err("null pointer argument".to_string());
+    }
+    // SAFETY: non-null and nul-terminated per the caller contract.
+    unsafe { CStr::from_ptr(value) }
+        .to_str()
+        .map_err(|error| format!("argument is not valid utf-8: {error}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn take(value: *mut c_char) -> String {
+        assert!(!value.is_null(), "unexpected error: {:?}", last_error());
+        // SAFETY: non-null pointer returned by the library.
+        let out = unsafe { CStr::from_ptr(value) }
+            .to_string_lossy()
+            .to_string();
+        // SAFETY: pointer returned by the library and not yet freed.
+        unsafe { ip_string_free(value) };
+        out
+    }
+
+    fn last_error() -> Option<String> {
+        let value = ip_last_error();
+        // SAFETY: pointer owned by the thread-local slot.
+        (!value.is_null()).then(|| {
+            unsafe { CStr::from_ptr(value) }
+                .to_string_lossy()
+                .to_string()
+        })
+    }
+
+    #[test]
+    fn renders_in_memory_sources() {
+        let config = CString::new(
+            r#"{"message":"hello ffi","pony_source":"$$$\n$$$\n$balloon$\npony\n","mode":"think"}"#,
+        )
+        .expect("config");
+        // SAFETY: valid nul-terminated string.
+        let out = take(unsafe { ip_render(config.as_ptr()) });
+        assert!(out.contains("( hello ffi"));
+        assert!(out.starts_with("\u{1b}[0m"));
+
+        let render = |leading_reset: bool| {
+            let config = CString::new(format!(
+                r#"{{"message":"hi","pony_source":"$balloon$\n","leading_reset":{leading_reset}}}"#
+            ))
+            .expect("config");
+            // SAFETY: valid nul-terminated string.
+            take(unsafe { ip_render(config.as_ptr()) })
+        };
+        let plain = render(false);
+        assert_eq!(
+            Some(plain.as_str()),
+            render(true).strip_prefix("\u{1b}[0m"),
+            "{plain:?}"
+        );
+    }
+
+    #[test]
+    fn reports_errors_through_last_error() {
+        let config = CString::new(r#"{"message":"x","bogus":1}"#).expect("config");
+        // SAFETY: valid nul-terminated string.
+        let out = unsafe { ip_render(config.as_ptr()) };
+        assert!(out.is_null());
+        assert!(last_error().expect("error").contains("bogus"));
+
+        // SAFETY: NULL is explicitly allowed.
+        assert!(unsafe { ip_render(ptr::null()) }.is_null());
+        assert_eq!(last_error().as_deref(), Some("null pointer argument"));
+    }
+
+    #[test]
+    fn lists_ponies_from_a_platform_path_list() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let roots = ["ponies", "extraponies"].map(|dir| tmp.path().join(dir));
+        for (root, name) in roots.iter().zip(["alpha", "beta"]) {
+            std::fs::create_dir_all(root).expect("pony dir");
+            std::fs::write(root.join(format!("{name}.pony")), "$$$\n$$$\n").expect("pony");
+        }
+        let joined = roots
+            .iter()
+            .map(|root| root.display().to_string())
+            .collect::<Vec<_>>()
+            .join(&iron_pony_core::PATH_LIST_SEPARATOR.to_string());
+        let paths = CString::new(joined).expect("paths");
+        // SAFETY: valid nul-terminated string.
+        let out = take(unsafe { ip_list_ponies(paths.as_ptr()) });
+        assert_eq!(out, "alpha\nbeta");
+    }
+}
@@ -1,11 +1,15 @@
 use std::collections::{BTreeMap, BTreeSet};
 use std::ffi::OsStr;
-use std::io::Write;
-use std::path::{Path, PathBuf};
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
 use std::process::{Command, Stdio};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
+use flate2::Compression;
+use flate2::write::GzEncoder;
 use iron_pony_spec::RequirementSpec;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, info, warn};
@@ -17,9 +21,36 @@ pub struct ParityConfig {
     pub spec_path: PathBuf,
     pub output_dir: PathBuf,
     pub reference_program: String,
+    /// Upstream ponysay checkout run with [`REFERENCE_PYTHON`] as the reference instead of
+    /// `reference_program`, so results do not depend on the installed ponysay. Cases with
+    /// their own `reference_program` still use it.
+    pub reference_checkout: Option<PathBuf>,
     pub candidate_program: Option<PathBuf>,
+    /// Variables set for every candidate run on top of the case `env`, e.g.
+    /// `LLVM_PROFILE_FILE` for an instrumented build.
+    pub candidate_env: BTreeMap<String, String>,
+    /// Bytes kept per stdout/stderr stream before the process is killed.
+    pub output_limit: usize,
+    /// Unknown case fields and features the spec does not map fail [`validate_cases`]
+    /// instead of only being logged.
+    pub strict: bool,
+    /// Scores a run must reach to pass, as recorded in `summary.json`.
+    pub thresholds: ParityThresholds,
 }
 
+/// Minimum scores, as fractions from 0 to 1, a run must reach; unset ones always pass.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct ParityThresholds {
+    pub case_parity: Option<f64>,
+    pub weighted_requirement_parity: Option<f64>,
+}
+
+/// Default for [`ParityConfig::output_limit`].
+pub const DEFAULT_OUTPUT_LIMIT: usize = 16 * 1024 * 1024;
+
+/// Interpreter that runs a [`ParityConfig::reference_checkout`].
+pub const REFERENCE_PYTHON: &str = "python3";
+
 impl ParityConfig {
     pub fn default_for_workspace(workspace_root: impl AsRef<Path>) -> Self {
         let workspace_root = workspace_root.as_ref().to_path_buf();
@@ -29,7 +60,12 @@ impl ParityConfig {
             output_dir: workspace_root.join("target/parity"),
             reference_program: std::env::var("PONYSAY_REF")
                 .unwrap_or_else(|_| "ponysay".to_string()),
+            reference_checkout: std::env::var_os("PONYSAY_REF_CHECKOUT").map(PathBuf::from),
             candidate_program: std::env::var("IRON_PONY_BIN").ok().map(PathBuf::from),
+            candidate_env: BTreeMap::new(),
+            output_limit: DEFAULT_OUTPUT_LIMIT,
+            strict: false,
+            thresholds: ParityThresholds::default(),
             workspace_root,
         }
     }
@@ -53,6 +89,36 @@ pub struct ParityCase {
     pub stdin: Option<String>,
     #[serde(default)]
     pub env: BTreeMap<String, String>,
+    /// Case-defined placeholders, usable as `{name}` next to the built-in ones.
+    #[serde(default)]
+    pub vars: BTreeMap<String, String>,
+    /// Fixture files written below `{temp}` before the case runs, keyed by relative path.
+    #[serde(default)]
+    pub files: BTreeMap<String, String>,
+    /// Overrides [`ParityConfig::output_limit`] for this case.
+    #[serde(default)]
+    pub output_limit: Option<usize>,
+    /// Seconds either program may run before it is killed and the case fails with
+    /// [`FailureKind::Timeout`]; unlimited when unset.
+    #[serde(default)]
+    pub timeout_secs: Option<f64>,
+    /// Runs the case once per locale (as `LANG` and `LC_ALL`); each run is reported as
+    /// the sub-case `<id>@<locale>`.
+    #[serde(default)]
+    pub locales: Vec<String>,
+    /// Runs the case once per seed, available as the `{seed}` placeholder; each run is
+    /// reported as the sub-case `<id>#<seed>`.
+    #[serde(default)]
+    pub seeds: Vec<u64>,
+    /// Seed of this sub-case when `seeds` was expanded.
+    #[serde(skip)]
+    pub seed: Option<u64>,
+    /// File the case was loaded from.
+    #[serde(skip)]
+    pub source: PathBuf,
+    /// Fields of the case file that are not part of the case format, usually typos.
+    #[serde(flatten)]
+    pub unknown_fields: BTreeMap<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -63,40 +129,428 @@ pub struct CaseResult {
     pub exit_match: bool,
     pub stdout_match: bool,
     pub stderr_match: bool,
+    /// The candidate was killed by a signal the reference did not die from.
+    pub crashed: bool,
+    /// Signal that terminated the candidate (Unix only).
+    pub candidate_signal: Option<i32>,
+    /// A program exceeded the output limit and was killed; its output is truncated.
+    pub output_limit_exceeded: bool,
+    /// Why the case failed; `None` when it passed.
+    pub failure_kind: Option<FailureKind>,
     pub detail: String,
 }
 
+/// Why a case failed. When several apply, the first in declaration order is reported: a
+/// program that did not run or was killed explains any mismatch that follows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    ReferenceSpawnFailed,
+    CandidateSpawnFailed,
+    /// A program ran past the case's `timeout_secs` and was killed.
+    Timeout,
+    /// A program exceeded the output limit and was killed.
+    OutputLimit,
+    /// The candidate was killed by a signal the reference did not die from.
+    Crash,
+    ExitMismatch,
+    StdoutMismatch,
+    StderrMismatch,
+}
+
+impl FailureKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::ReferenceSpawnFailed => "reference_spawn_failed",
+            Self::CandidateSpawnFailed => "candidate_spawn_failed",
+            Self::Timeout => "timeout",
+            Self::OutputLimit => "output_limit",
+            Self::Crash => "crash",
+            Self::ExitMismatch => "exit_mismatch",
+            Self::StdoutMismatch => "stdout_mismatch",
+            Self::StderrMismatch => "stderr_mismatch",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct RequirementResult {
     pub id: String,
+    pub description: String,
+    pub category: Option<String>,
+    pub milestone: Option<String>,
     pub weight: f64,
     pub covered_cases: usize,
     pub passing_cases: usize,
     pub score: f64,
     pub status: String,
+    /// Ids of the covering cases that fail.
+    pub failing_cases: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct ReportSummary {
     pub total_cases: usize,
     pub passed_cases: usize,
+    pub crashed_cases: usize,
+    /// Failing cases counted by [`CaseResult::failure_kind`].
+    pub failures_by_kind: BTreeMap<FailureKind, usize>,
+    /// Case features that map to no requirement in the spec, with the ids of the cases
+    /// naming them; those cases count toward no requirement.
+    pub unmapped_features: BTreeMap<String, Vec<String>>,
     pub case_parity: f64,
     pub weighted_requirement_parity: f64,
     pub requirement_completion: f64,
     pub untested_requirements: usize,
 }
 
+/// What produced a [`ParityReport`], so reports from different machines can be compared
+/// and version skew spotted. Anything that could not be found out is left empty.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RunEnvironment {
+    /// The reference command line, e.g. `ponysay` or `python3 /src/ponysay/src`.
+    pub reference: String,
+    /// First line the reference printed for `--version`.
+    pub reference_version: Option<String>,
+    /// First line the candidate printed for `--version`.
+    pub candidate_version: Option<String>,
+    /// Workspace `HEAD`, suffixed with `-dirty` when the work tree has changes.
+    pub candidate_commit: Option<String>,
+    pub os: String,
+    pub arch: String,
+    /// The harness's [`TERMINAL_VARS`] that are set; case `env` entries override them.
+    pub terminal: BTreeMap<String, String>,
+}
+
+/// Environment variables that change how either program draws, recorded in
+/// [`RunEnvironment::terminal`].
+pub const TERMINAL_VARS: [&str; 7] = [
+    "TERM",
+    "COLORTERM",
+    "COLUMNS",
+    "LINES",
+    "LANG",
+    "LC_ALL",
+    "NO_COLOR",
+];
+
+impl RunEnvironment {
+    fn probe(config: &ParityConfig, cancel: &CancelToken) -> Self {
+        let policy = OutputPolicy {
+            stream: false,
+            limit: VERSION_OUTPUT_LIMIT,
+            timeout: Some(VERSION_TIMEOUT),
+        };
+        let no_env = BTreeMap::new();
+        let cwd = &config.workspace_root;
+        let version = |output: Result<ProcessOutput>| {
+            output
+                .ok()
+                .filter(|output| output.status_code == 0)
+                .and_then(|output| first_line(&output))
+        };
+
+        let checkout = config
+            .reference_checkout
+            .as_deref()
+            .map(ReferenceCheckout::locate);
+        let (reference, reference_version) = match checkout {
+            Some(Ok(checkout)) => {
+                let entry = checkout.entry.to_string_lossy().to_string();
+                let argv = [entry.clone(), "--version".to_string()];
+                let output = run_process(
+                    REFERENCE_PYTHON,
+                    &argv,
+                    &checkout.env,
+                    None,
+                    cwd,
+                    cancel,
+                    policy,
+                );
+                (format!("{REFERENCE_PYTHON} {entry}"), version(output))
+            }
+            Some(Err(error)) => {
+                warn!(%error, "cannot stamp the reference checkout version");
+                (config.reference_program.clone(), None)
+            }
+            None => {
+                let argv = ["--version".to_string()];
+                let output = run_process(
+                    &config.reference_program,
+                    &argv,
+                    &no_env,
+                    None,
+                    cwd,
+                    cancel,
+                    policy,
+                );
+                (config.reference_program.clone(), version(output))
+            }
+        };
+        let candidate_version = version(run_candidate(
+            config,
+            None,
+            &["--version".to_string()],
+            &no_env,
+            None,
+            cancel,
+            policy,
+        ));
+
+        let git = |args: &[&str]| {
+            let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+            run_process("git", &args, &no_env, None, cwd, cancel, policy)
+                .ok()
+                .filter(|output| output.status_code == 0)
+        };
+        let candidate_commit = git(&["rev-parse", "HEAD"])
+            .and_then(|output| first_line(&output))
+            .map(|commit| match git(&["status", "--porcelain"]) {
+                Some(status) if !status.stdout.is_empty() => format!("{commit}-dirty"),
+                _ => commit,
+            });
+
+        let terminal = TERMINAL_VARS
+            .iter()
+            .filter_map(|name| Some((name.to_string(), std::env::var(name).ok()?)))
+            .collect();
+        Self {
+            reference,
+            reference_version,
+            candidate_version,
+            candidate_commit,
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            terminal,
+        }
+    }
+}
+
+/// Bytes of `--version` output kept by [`RunEnvironment::probe`].
+const VERSION_OUTPUT_LIMIT: usize = 64 * 1024;
+
+/// How long [`RunEnvironment::probe`] waits for each `--version`. Generous, since the
+/// candidate may be built by `cargo run` first.
+const VERSION_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// First non-blank line of stdout, or of stderr for programs that print versions there.
+fn first_line(output: &ProcessOutput) -> Option<String> {
+    [&output.stdout, &output.stderr]
+        .into_iter()
+        .find_map(|stream| {
+            String::from_utf8_lossy(stream)
+                .lines()
+                .map(str::trim)
+                .find(|line| !line.is_empty())
+                .map(str::to_string)
+        })
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct ParityReport {
     pub generated_epoch_secs: u64,
+    pub environment: RunEnvironment,
     pub summary: ReportSummary,
     pub requirements: Vec<RequirementResult>,
     pub cases: Vec<CaseResult>,
 }
 
+impl ParityReport {
+    /// The thresholds this report misses, described for people; empty when it passes.
+    pub fn missed_thresholds(&self, thresholds: &ParityThresholds) -> Vec<String> {
+        [
+            (
+                "case parity",
+                self.summary.case_parity,
+                thresholds.case_parity,
+            ),
+            (
+                "weighted requirement parity",
+                self.summary.weighted_requirement_parity,
+                thresholds.weighted_requirement_parity,
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(name, score, minimum)| {
+            minimum.filter(|&minimum| score < minimum).map(|minimum| {
+                format!(
+                    "{name} {:.2}% is below {:.2}%",
+                    score * 100.0,
+                    minimum * 100.0
+                )
+            })
+        })
+        .collect()
+    }
+}
+
+/// The `summary.json` written next to the full report: scores and a verdict for CI
+/// scripts that do not want to parse the report.
+#[derive(Debug, Clone, Serialize)]
+pub struct CiSummary {
+    /// Every threshold was reached.
+    pub passed: bool,
+    pub missed_thresholds: Vec<String>,
+    pub thresholds: ParityThresholds,
+    pub total_cases: usize,
+    pub passed_cases: usize,
+    pub crashed_cases: usize,
+    pub case_parity: f64,
+    pub weighted_requirement_parity: f64,
+    pub requirement_completion: f64,
+    pub untested_requirements: usize,
+}
+
+impl CiSummary {
+    pub fn new(report: &ParityReport, thresholds: ParityThresholds) -> Self {
+        let missed_thresholds = report.missed_thresholds(&thresholds);
+        let summary = &report.summary;
+        Self {
+            passed: missed_thresholds.is_empty(),
+            missed_thresholds,
+            thresholds,
+            total_cases: summary.total_cases,
+            passed_cases: summary.passed_cases,
+            crashed_cases: summary.crashed_cases,
+            case_parity: summary.case_parity,
+            weighted_requirement_parity: summary.weighted_requirement_parity,
+            requirement_completion: summary.requirement_completion,
+            untested_requirements: summary.untested_requirements,
+        }
+    }
+
+    /// `summary.env`: one `PARITY_*=value` line per score, ready for `source` or
+    /// `$GITHUB_ENV`.
+    pub fn to_env(&self) -> String {
+        [
+            ("PARITY_PASSED", self.passed.to_string()),
+            ("PARITY_TOTAL_CASES", self.total_cases.to_string()),
+            ("PARITY_PASSED_CASES", self.passed_cases.to_string()),
+            ("PARITY_CRASHED_CASES", self.crashed_cases.to_string()),
+            ("PARITY_CASE_PARITY", format!("{:.4}", self.case_parity)),
+            (
+                "PARITY_REQUIREMENT_PARITY",
+                format!("{:.4}", self.weighted_requirement_parity),
+            ),
+            (
+                "PARITY_REQUIREMENT_COMPLETION",
+                format!("{:.4}", self.requirement_completion),
+            ),
+            (
+                "PARITY_UNTESTED_REQUIREMENTS",
+                self.untested_requirements.to_string(),
+            ),
+        ]
+        .into_iter()
+        .map(|(key, value)| format!("{key}={value}\n"))
+        .collect()
+    }
+}
+
+/// Progress events reported by [`run_parity_with`], in run order.
+#[derive(Debug, Clone, Copy)]
+pub enum ParityProgress<'a> {
+    /// Cases were loaded; `total` of them will run.
+    Started { total: usize },
+    /// Case number `index` (0-based) is about to run.
+    CaseStarted {
+        index: usize,
+        total: usize,
+        id: &'a str,
+    },
+    /// Case number `index` finished.
+    CaseFinished {
+        index: usize,
+        total: usize,
+        result: &'a CaseResult,
+    },
+    /// All cases ran and the report artifacts were written.
+    Finished { report: &'a ParityReport },
+}
+
+/// Cooperative cancellation for [`run_parity_with`]. Clones share one flag, so a
+/// token can be handed to another thread (or async task) and cancelled from there.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Error returned by [`run_parity_with`] when its token is cancelled. Running child
+/// processes are killed and no report artifacts are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("parity run was cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Knobs for [`run_case_with`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CaseRunOptions {
+    /// Echo both programs' stdout/stderr to this process as they run.
+    pub stream_output: bool,
+    /// Keep the case's `{temp}` directory instead of deleting it.
+    pub keep_temp: bool,
+}
+
+/// Outcome of [`run_case_with`].
+#[derive(Debug, Clone)]
+pub struct CaseRun {
+    pub result: CaseResult,
+    /// The `{temp}` directory; it only still exists when `keep_temp` was set.
+    pub temp_dir: PathBuf,
+    /// Exact child environment: this process's variables overlaid with the case `env`.
+    pub env: BTreeMap<String, String>,
+    /// Raw output keyed by file name (`reference.stdout`, `candidate.stderr`, ...), for
+    /// every program that ran.
+    pub captures: BTreeMap<String, Vec<u8>>,
+}
+
+/// How a case process's output is collected.
+#[derive(Debug, Clone, Copy)]
+struct OutputPolicy {
+    /// Echo output to this process as it arrives.
+    stream: bool,
+    /// Bytes kept per stream.
+    limit: usize,
+    /// Kill the process once it has run this long.
+    timeout: Option<Duration>,
+}
+
+/// How often a running case process is checked for exit or cancellation.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How long output may keep arriving after a case process exits. Anything it left running
+/// (the binary under `cargo run`, or a command under `sh -c`) can hold its pipes open
+/// indefinitely, so the capture stops there instead of waiting for them.
+const DRAIN_GRACE: Duration = Duration::from_millis(500);
+
 #[derive(Debug, Clone)]
 struct ProcessOutput {
+    /// Exit code, or -1 when the process was killed by a signal.
     status_code: i32,
+    signal: Option<i32>,
+    core_dumped: bool,
+    /// Output passed the limit; the streams end in a truncation marker.
+    limit_exceeded: bool,
+    /// Killed for running past [`OutputPolicy::timeout`].
+    timed_out: bool,
     stdout: Vec<u8>,
     stderr: Vec<u8>,
 }
@@ -106,52 +560,191 @@ struct RequirementAgg {
     weight: f64,
     covered: usize,
     passed: usize,
+    failing: Vec<String>,
 }
 
 pub fn run_parity(config: &ParityConfig) -> Result<ParityReport> {
+    run_parity_with(config, |_| {}, &CancelToken::new())
+}
+
+/// Like [`run_parity`], reporting progress to `progress` and stopping early with a
+/// [`Cancelled`] error once `cancel` is cancelled.
+pub fn run_parity_with(
+    config: &ParityConfig,
+    mut progress: impl FnMut(ParityProgress<'_>),
+    cancel: &CancelToken,
+) -> Result<ParityReport> {
     info!(cases_dir = %config.cases_dir.display(), reference = %config.reference_program, "starting parity run");
     let spec = RequirementSpec::load(&config.spec_path)?;
     let cases = load_cases(&config.cases_dir)?;
     if cases.is_empty() {
         warn!("no parity cases found");
     }
+    let violations = validate_cases(&cases, &spec, config.strict);
+    if !violations.is_empty() {
+        let listed = violations
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        bail!(
+            "{} problem(s) in parity cases:\n{}",
+            violations.len(),
+            listed.join("\n")
+        );
+    }
 
-    std::fs::create_dir_all(config.output_dir.join("failures"))
-        .context("failed creating parity output directories")?;
+    let failures_dir = config.output_dir.join("failures");
+    let pages_dir = config.output_dir.join("requirements");
+    for dir in [&failures_dir, &pages_dir] {
+        if dir.exists() {
+            // Artifacts from a previous run would describe failures that may be fixed now.
+            std::fs::remove_dir_all(dir)
+                .with_context(|| format!("failed removing stale artifacts in {}", dir.display()))?;
+            debug!(path = %dir.display(), "removed stale parity failure artifacts");
+        }
+    }
+    std::fs::create_dir_all(&failures_dir).context("failed creating parity output directories")?;
+
+    let environment = RunEnvironment::probe(config, cancel);
+    info!(
+        reference = ?environment.reference_version,
+        candidate = ?environment.candidate_version,
+        commit = ?environment.candidate_commit,
+        "stamped parity run"
+    );
 
+    let total = cases.len();
+    progress(ParityProgress::Started { total });
     let mut case_results = Vec::new();
+    let mut excerpts = BTreeMap::new();
 
-    for case in cases {
-        let result = run_case(config, &case)?;
-        if !result.passed {
-            let diff_path = config
-                .output_dir
-                .join("failures")
-                .join(format!("{}.diff", case.id));
-            std::fs::write(&diff_path, &result.detail)
-                .with_context(|| format!("failed writing diff for case {}", case.id))?;
-            debug!(case = %case.id, path = %diff_path.display(), "wrote parity failure diff");
+    for (index, case) in cases.iter().enumerate() {
+        if cancel.is_cancelled() {
+            info!(completed = index, total, "parity run cancelled");
+            return Err(Cancelled.into());
+        }
+        progress(ParityProgress::CaseStarted {
+            index,
+            total,
+            id: &case.id,
+        });
+
+        let run = run_case_with(config, case, CaseRunOptions::default(), cancel)?;
+        if !run.result.passed {
+            write_failure_artifacts(&failures_dir, case, &run)?;
+            excerpts.insert(case.id.clone(), diff_excerpt(&run));
         }
+        let result = run.result;
+        progress(ParityProgress::CaseFinished {
+            index,
+            total,
+            result: &result,
+        });
         case_results.push(result);
     }
 
     let requirements = compute_requirement_scores(&spec, &case_results);
-    let summary = compute_summary(&requirements, &case_results);
+    let unmapped = unmapped_features(&spec, &case_results);
+    let summary = compute_summary(&requirements, &case_results, unmapped);
     let report = ParityReport {
         generated_epoch_secs: SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap_or_default()
             .as_secs(),
+        environment,
         summary,
         requirements,
         cases: case_results,
     };
 
     write_report_artifacts(config, &report)?;
+    write_requirement_pages(&pages_dir, &spec, &report, &cases, &excerpts)?;
+    progress(ParityProgress::Finished { report: &report });
     Ok(report)
 }
 
-fn load_cases(path: &Path) -> Result<Vec<ParityCase>> {
+/// Runs one case on its own, e.g. to re-check it after an edit.
+pub fn run_single_case(
+    config: &ParityConfig,
+    case: &ParityCase,
+    cancel: &CancelToken,
+) -> Result<CaseResult> {
+    run_case(config, case, cancel)
+}
+
+/// A problem in a case file found by [`validate_cases`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaseViolation {
+    pub source: PathBuf,
+    pub message: String,
+}
+
+impl std::fmt::Display for CaseViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.source.display(), self.message)
+    }
+}
+
+/// Checks loaded `cases` before anything runs: ids (including sub-case ids) must be
+/// unique, since failure artifacts are named after them, and argv lists must not be empty.
+/// Unknown fields and features `spec` does not map are violations when `strict` and
+/// warnings otherwise. Every problem is returned, not just the first.
+pub fn validate_cases(
+    cases: &[ParityCase],
+    spec: &RequirementSpec,
+    strict: bool,
+) -> Vec<CaseViolation> {
+    let mut violations = Vec::new();
+    let mut ids = BTreeMap::<&str, &Path>::new();
+    let mut checked = BTreeSet::new();
+    for case in cases {
+        let mut violation = |message: String| {
+            violations.push(CaseViolation {
+                source: case.source.clone(),
+                message,
+            });
+        };
+        if let Some(first) = ids.insert(&case.id, &case.source) {
+            violation(format!(
+                "case id `{}` is already used by {}",
+                case.id,
+                first.display()
+            ));
+        }
+        // Sub-cases share their file's fields; check those once per file.
+        if !checked.insert(&case.source) {
+            continue;
+        }
+        for (name, argv) in [
+            ("argv", Some(&case.argv)),
+            ("reference_argv", case.reference_argv.as_ref()),
+            ("candidate_argv", case.candidate_argv.as_ref()),
+        ] {
+            if argv.is_some_and(Vec::is_empty) {
+                violation(format!("`{name}` is empty"));
+            }
+        }
+        let mut lenient = Vec::new();
+        for field in case.unknown_fields.keys() {
+            lenient.push(format!("unknown field `{field}`"));
+        }
+        for feature in spec.unmapped_features(&case.features) {
+            lenient.push(format!("feature `{feature}` maps to no spec requirement"));
+        }
+        for message in lenient {
+            if strict {
+                violation(message);
+            } else {
+                warn!(source = %case.source.display(), "{message}");
+            }
+        }
+    }
+    violations
+}
+
+/// Every `*.json` case in `path`, sorted by file name (the order runs use), with locale
+/// and seed matrices expanded into sub-cases.
+pub fn load_cases(path: &Path) -> Result<Vec<ParityCase>> {
     let mut files = Vec::new();
     if !path.exists() {
         return Ok(files);
@@ -172,37 +765,140 @@ fn load_cases(path: &Path) -> Result<Vec<ParityCase>> {
 
         let raw = std::fs::read_to_string(&candidate)
             .with_context(|| format!("failed reading case file {}", candidate.display()))?;
-        let parsed = serde_json::from_str::<ParityCase>(&raw)
+        let mut parsed = serde_json::from_str::<ParityCase>(&raw)
             .with_context(|| format!("failed parsing case file {}", candidate.display()))?;
-        files.push(parsed);
+        parsed.source = candidate;
+        files.extend(expand_matrix(parsed));
     }
 
     Ok(files)
 }
 
-fn run_case(config: &ParityConfig, case: &ParityCase) -> Result<CaseResult> {
+/// Expands the `locales` and `seeds` axes into one sub-case per combination.
+fn expand_matrix(case: ParityCase) -> Vec<ParityCase> {
+    let by_locale = if case.locales.is_empty() {
+        vec![case]
+    } else {
+        case.locales
+            .iter()
+            .map(|locale| {
+                let mut sub_case = case.clone();
+                sub_case.id = format!("{}@{locale}", case.id);
+                sub_case.locales = vec![locale.clone()];
+                for key in ["LANG", "LC_ALL"] {
+                    sub_case.env.insert(key.to_string(), locale.clone());
+                }
+                sub_case
+            })
+            .collect()
+    };
+
+    by_locale
+        .into_iter()
+        .flat_map(|case| {
+            if case.seeds.is_empty() {
+                return vec![case];
+            }
+            case.seeds
+                .iter()
+                .map(|&seed| {
+                    let mut sub_case = case.clone();
+                    sub_case.id = format!("{}#{seed}", case.id);
+                    sub_case.seeds = vec![seed];
+                    sub_case.seed = Some(seed);
+                    sub_case
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn run_case(config: &ParityConfig, case: &ParityCase, cancel: &CancelToken) -> Result<CaseResult> {
+    run_case_with(config, case, CaseRunOptions::default(), cancel).map(|run| run.result)
+}
+
+/// Runs one case for debugging: optionally streams both programs' output live and keeps
+/// the `{temp}` directory, and reports the exact environment the children saw.
+pub fn run_case_with(
+    config: &ParityConfig,
+    case: &ParityCase,
+    options: CaseRunOptions,
+    cancel: &CancelToken,
+) -> Result<CaseRun> {
     debug!(case = %case.id, "running parity case");
 
+    let checkout = match (&case.reference_program, &config.reference_checkout) {
+        (None, Some(root)) => Some(ReferenceCheckout::locate(root)?),
+        _ => None,
+    };
     let temp = tempfile::tempdir().context("failed creating parity temp dir")?;
-    let temp_path = temp.path();
+    let vars = CaseVars::new(config, case, temp.path())?;
+    stage_files(case, temp.path())?;
+    let mut env = checkout
+        .as_ref()
+        .map(|checkout| checkout.env.clone())
+        .unwrap_or_default();
+    env.extend(
+        case.env
+            .iter()
+            .map(|(key, value)| (key.clone(), vars.apply(value))),
+    );
 
-    let env = case
-        .env
-        .iter()
+    let mut captures = BTreeMap::new();
+    let result = execute_case(
+        config,
+        case,
+        checkout.as_ref(),
+        &vars,
+        &env,
+        options,
+        cancel,
+        &mut captures,
+    )?;
+
+    let mut child_env = std::env::vars_os()
         .map(|(key, value)| {
             (
-                key.clone(),
-                substitute_vars(value, temp_path, &config.workspace_root),
+                key.to_string_lossy().to_string(),
+                value.to_string_lossy().to_string(),
             )
         })
         .collect::<BTreeMap<_, _>>();
+    child_env.extend(env);
 
-    let reference_argv = normalize_argv(
+    let temp_dir = if options.keep_temp {
+        let kept = temp.keep();
+        info!(case = %case.id, path = %kept.display(), "kept parity temp dir");
+        kept
+    } else {
+        temp.path().to_path_buf()
+    };
+
+    Ok(CaseRun {
+        result,
+        temp_dir,
+        env: child_env,
+        captures,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn execute_case(
+    config: &ParityConfig,
+    case: &ParityCase,
+    checkout: Option<&ReferenceCheckout>,
+    vars: &CaseVars,
+    env: &BTreeMap<String, String>,
+    options: CaseRunOptions,
+    cancel: &CancelToken,
+    captures: &mut BTreeMap<String, Vec<u8>>,
+) -> Result<CaseResult> {
+    let mut reference_argv = normalize_argv(
         case.reference_argv
             .as_ref()
             .unwrap_or(&case.argv)
             .iter()
-            .map(|arg| substitute_vars(arg, temp_path, &config.workspace_root))
+            .map(|arg| vars.apply(arg))
             .collect::<Vec<_>>(),
     );
 
@@ -211,28 +907,47 @@ fn run_case(config: &ParityConfig, case: &ParityCase) -> Result<CaseResult> {
             .as_ref()
             .unwrap_or(&case.argv)
             .iter()
-            .map(|arg| substitute_vars(arg, temp_path, &config.workspace_root))
+            .map(|arg| vars.apply(arg))
             .collect::<Vec<_>>(),
     );
 
-    let stdin = case
-        .stdin
-        .as_ref()
-        .map(|value| substitute_vars(value, temp_path, &config.workspace_root));
+    let stdin = case.stdin.as_ref().map(|value| vars.apply(value));
 
-    let reference_program = case
-        .reference_program
-        .as_deref()
-        .unwrap_or(&config.reference_program);
+    let reference_program = match checkout {
+        Some(checkout) => {
+            reference_argv.insert(0, checkout.entry.to_string_lossy().to_string());
+            REFERENCE_PYTHON
+        }
+        None => case
+            .reference_program
+            .as_deref()
+            .unwrap_or(&config.reference_program),
+    };
+    let policy = OutputPolicy {
+        stream: options.stream_output,
+        limit: case.output_limit.unwrap_or(config.output_limit),
+        timeout: case
+            .timeout_secs
+            .and_then(|secs| Duration::try_from_secs_f64(secs).ok()),
+    };
 
+    if options.stream_output {
+        warn!(
+            "=== reference: {reference_program} {} ===",
+            reference_argv.join(" ")
+        );
+    }
     let reference = match run_process(
         reference_program,
         &reference_argv,
-        &env,
+        env,
         stdin.as_deref(),
         &config.workspace_root,
+        cancel,
+        policy,
     ) {
         Ok(output) => output,
+        Err(error) if error.is::<Cancelled>() => return Err(error),
         Err(error) => {
             return Ok(CaseResult {
                 id: case.id.clone(),
@@ -241,19 +956,30 @@ fn run_case(config: &ParityConfig, case: &ParityCase) -> Result<CaseResult> {
                 exit_match: false,
                 stdout_match: false,
                 stderr_match: false,
+                crashed: false,
+                candidate_signal: None,
+                output_limit_exceeded: false,
+                failure_kind: Some(FailureKind::ReferenceSpawnFailed),
                 detail: format!("reference command failed: {error:#}"),
             });
         }
     };
+    capture_output(captures, "reference", &reference);
 
+    if options.stream_output {
+        warn!("=== candidate: {} ===", candidate_argv.join(" "));
+    }
     let candidate = match run_candidate(
         config,
         case.candidate_program.as_deref(),
         &candidate_argv,
-        &env,
+        env,
         stdin.as_deref(),
+        cancel,
+        policy,
     ) {
         Ok(output) => output,
+        Err(error) if error.is::<Cancelled>() => return Err(error),
         Err(error) => {
             return Ok(CaseResult {
                 id: case.id.clone(),
@@ -262,15 +988,41 @@ fn run_case(config: &ParityConfig, case: &ParityCase) -> Result<CaseResult> {
                 exit_match: false,
                 stdout_match: false,
                 stderr_match: false,
+                crashed: false,
+                candidate_signal: None,
+                output_limit_exceeded: false,
+                failure_kind: Some(FailureKind::CandidateSpawnFailed),
                 detail: format!("candidate command failed: {error:#}"),
             });
         }
     };
-
-    let exit_match = reference.status_code == candidate.status_code;
+    capture_output(captures, "candidate", &candidate);
+
+    let exit_match =
+        reference.status_code == candidate.status_code && reference.signal == candidate.signal;
+    let output_limit_exceeded = reference.limit_exceeded || candidate.limit_exceeded;
+    let timed_out = reference.timed_out || candidate.timed_out;
+    // Processes killed for runaway output or time die from SIGKILL; that is not a crash.
+    let crashed = !output_limit_exceeded
+        && !timed_out
+        && candidate.signal.is_some()
+        && candidate.signal != reference.signal;
     let stdout_match = reference.stdout == candidate.stdout;
     let stderr_match = reference.stderr == candidate.stderr;
-    let passed = exit_match && stdout_match && stderr_match;
+    let failure_kind = [
+        (timed_out, FailureKind::Timeout),
+        (output_limit_exceeded, FailureKind::OutputLimit),
+        (crashed, FailureKind::Crash),
+        (!exit_match, FailureKind::ExitMismatch),
+        (!stdout_match, FailureKind::StdoutMismatch),
+        (!stderr_match, FailureKind::StderrMismatch),
+    ]
+    .into_iter()
+    .find_map(|(failed, kind)| failed.then_some(kind));
+    let passed = failure_kind.is_none();
+    if crashed {
+        warn!(case = %case.id, signal = ?candidate.signal, "candidate crashed");
+    }
 
     let detail = build_case_detail(
         case,
@@ -288,23 +1040,104 @@ fn run_case(config: &ParityConfig, case: &ParityCase) -> Result<CaseResult> {
         exit_match,
         stdout_match,
         stderr_match,
+        crashed,
+        candidate_signal: candidate.signal,
+        output_limit_exceeded,
+        failure_kind,
         detail,
     })
 }
 
+fn capture_output(captures: &mut BTreeMap<String, Vec<u8>>, name: &str, output: &ProcessOutput) {
+    captures.insert(format!("{name}.stdout"), output.stdout.clone());
+    captures.insert(format!("{name}.stderr"), output.stderr.clone());
+}
+
+/// Writes `<id>.diff` plus a `<id>/` directory holding the raw captures and a copy of the
+/// case file, so a failure can be reproduced elsewhere.
+fn write_failure_artifacts(failures_dir: &Path, case: &ParityCase, run: &CaseRun) -> Result<()> {
+    let diff_path = failures_dir.join(format!("{}.diff", case.id));
+    std::fs::write(&diff_path, &run.result.detail)
+        .with_context(|| format!("failed writing diff for case {}", case.id))?;
+
+    let case_dir = failures_dir.join(&case.id);
+    std::fs::create_dir_all(&case_dir)
+        .with_context(|| format!("failed creating {}", case_dir.display()))?;
+    for (name, bytes) in &run.captures {
+        std::fs::write(case_dir.join(name), bytes)
+            .with_context(|| format!("failed writing {name} for case {}", case.id))?;
+    }
+    if case.source.is_file() {
+        std::fs::copy(&case.source, case_dir.join("case.json"))
+            .with_context(|| format!("failed copying case file {}", case.source.display()))?;
+    }
+
+    debug!(case = %case.id, path = %diff_path.display(), "wrote parity failure artifacts");
+    Ok(())
+}
+
+/// Bundles the failure artifacts and report of the last run in `config.output_dir` into a
+/// gzipped tarball at `archive_path`.
+pub fn archive_failures(config: &ParityConfig, archive_path: &Path) -> Result<()> {
+    let file = std::fs::File::create(archive_path)
+        .with_context(|| format!("failed creating archive {}", archive_path.display()))?;
+    let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+
+    let failures_dir = config.output_dir.join("failures");
+    if failures_dir.is_dir() {
+        builder
+            .append_dir_all("failures", &failures_dir)
+            .with_context(|| format!("failed archiving {}", failures_dir.display()))?;
+    }
+    let pages_dir = config.output_dir.join("requirements");
+    if pages_dir.is_dir() {
+        builder
+            .append_dir_all("requirements", &pages_dir)
+            .with_context(|| format!("failed archiving {}", pages_dir.display()))?;
+    }
+    for name in [
+        "parity-report.json",
+        "parity-report.md",
+        "summary.json",
+        "summary.env",
+    ] {
+        let path = config.output_dir.join(name);
+        if path.is_file() {
+            builder
+                .append_path_with_name(&path, name)
+                .with_context(|| format!("failed archiving {}", path.display()))?;
+        }
+    }
+
+    builder
+        .into_inner()
+        .and_then(GzEncoder::finish)
+        .with_context(|| format!("failed finishing archive {}", archive_path.display()))?;
+    info!(path = %archive_path.display(), "wrote parity failure archive");
+    Ok(())
+}
+
 fn run_candidate(
     config: &ParityConfig,
     case_program: Option<&str>,
     argv: &[String],
     env: &BTreeMap<String, String>,
     stdin: Option<&str>,
+    cancel: &CancelToken,
+    policy: OutputPolicy,
 ) -> Result<ProcessOutput> {
+    let cwd = &config.workspace_root;
+    let env = &env
+        .iter()
+        .chain(&config.candidate_env)
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect::<BTreeMap<_, _>>();
     if let Some(program) = case_program {
-        return run_process(program, argv, env, stdin, &config.workspace_root);
+        return run_process(program, argv, env, stdin, cwd, cancel, policy);
     }
 
     if let Some(program) = &config.candidate_program {
-        return run_process(program, argv, env, stdin, &config.workspace_root);
+        return run_process(program, argv, env, stdin, cwd, cancel, policy);
     }
 
     let mut cargo_args = vec![
@@ -318,7 +1151,7 @@ fn run_candidate(
     ];
     cargo_args.extend(argv.iter().cloned());
 
-    run_process("cargo", &cargo_args, env, stdin, &config.workspace_root)
+    run_process("cargo", &cargo_args, env, stdin, cwd, cancel, policy)
 }
 
 fn run_process(
@@ -327,6 +1160,8 @@ fn run_process(
     env: &BTreeMap<String, String>,
     stdin: Option<&str>,
     cwd: &Path,
+    cancel: &CancelToken,
+    policy: OutputPolicy,
 ) -> Result<ProcessOutput> {
     let mut command = Command::new(program.as_ref());
     command
@@ -344,25 +1179,190 @@ fn run_process(
         )
     })?;
 
-    if let Some(stdin) = stdin {
-        if let Some(mut input) = child.stdin.take() {
-            input
-                .write_all(stdin.as_bytes())
-                .with_context(|| "failed to write process stdin")?;
-        }
+    if let Some(stdin) = stdin
+        && let Some(mut input) = child.stdin.take()
+    {
+        input
+            .write_all(stdin.as_bytes())
+            .with_context(|| "failed to write process stdin")?;
     }
+    drop(child.stdin.take());
+
+    let exceeded = Arc::new(AtomicBool::new(false));
+    let stdout = drain(
+        child.stdout.take(),
+        policy.stream.then(|| Box::new(std::io::stdout()) as _),
+        policy.limit,
+        Arc::clone(&exceeded),
+    );
+    let stderr = drain(
+        child.stderr.take(),
+        policy.stream.then(|| Box::new(std::io::stderr()) as _),
+        policy.limit,
+        Arc::clone(&exceeded),
+    );
+    let started = std::time::Instant::now();
+    let mut timed_out = false;
+    let status = loop {
+        if let Some(status) = child
+            .try_wait()
+            .with_context(|| "failed waiting for process output")?
+        {
+            break status;
+        }
+        if cancel.is_cancelled() {
+            debug!("killing case process after cancellation");
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(Cancelled.into());
+        }
+        if exceeded.load(Ordering::SeqCst) {
+            debug!(
+                limit = policy.limit,
+                "killing case process after output limit"
+            );
+            let _ = child.kill();
+            break child
+                .wait()
+                .with_context(|| "failed waiting for killed process")?;
+        }
+        if policy
+            .timeout
+            .is_some_and(|timeout| started.elapsed() >= timeout)
+        {
+            debug!(timeout = ?policy.timeout, "killing case process after timeout");
+            timed_out = true;
+            let _ = child.kill();
+            break child
+                .wait()
+                .with_context(|| "failed waiting for killed process")?;
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    };
 
-    let output = child
-        .wait_with_output()
-        .with_context(|| "failed waiting for process output")?;
-
+    let (signal, core_dumped) = termination_signal(&status);
     Ok(ProcessOutput {
-        status_code: output.status.code().unwrap_or(-1),
-        stdout: output.stdout,
-        stderr: output.stderr,
+        status_code: status.code().unwrap_or(-1),
+        signal,
+        core_dumped,
+        limit_exceeded: exceeded.load(Ordering::SeqCst),
+        timed_out,
+        stdout: stdout.finish(),
+        stderr: stderr.finish(),
     })
 }
 
+/// A pipe being read by [`drain`].
+struct Drain {
+    buffer: Arc<Mutex<Vec<u8>>>,
+    thread: std::thread::JoinHandle<()>,
+}
+
+impl Drain {
+    /// The output read so far, once the pipe closes or [`DRAIN_GRACE`] runs out.
+    fn finish(self) -> Vec<u8> {
+        let started = std::time::Instant::now();
+        while !self.thread.is_finished() && started.elapsed() < DRAIN_GRACE {
+            std::thread::sleep(POLL_INTERVAL);
+        }
+        std::mem::take(&mut self.buffer.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+/// Reads `pipe` to the end on a helper thread so a full pipe never blocks the child,
+/// copying each kept chunk to `echo` as it arrives. At most `limit` bytes are kept; past
+/// that the output is cut with a marker, `exceeded` is raised so the process gets killed,
+/// and the pipe is closed so anything still writing to it gets `SIGPIPE`.
+fn drain(
+    pipe: Option<impl Read + Send + 'static>,
+    mut echo: Option<Box<dyn Write + Send>>,
+    limit: usize,
+    exceeded: Arc<AtomicBool>,
+) -> Drain {
+    let buffer = Arc::new(Mutex::new(Vec::new()));
+    let shared = Arc::clone(&buffer);
+    let thread = std::thread::spawn(move || {
+        let Some(mut pipe) = pipe else {
+            return;
+        };
+        let mut chunk = [0; 8192];
+        loop {
+            let read = match pipe.read(&mut chunk) {
+                Ok(0) | Err(_) => break,
+                Ok(read) => read,
+            };
+            let mut buffer = shared.lock().unwrap_or_else(PoisonError::into_inner);
+            let kept = read.min(limit - buffer.len());
+            buffer.extend_from_slice(&chunk[..kept]);
+            if let Some(echo) = echo.as_mut() {
+                let _ = echo.write_all(&chunk[..kept]);
+                let _ = echo.flush();
+            }
+            if kept < read {
+                buffer.extend_from_slice(
+                    format!("\n[output truncated at {limit} bytes]\n").as_bytes(),
+                );
+                exceeded.store(true, Ordering::SeqCst);
+                break;
+            }
+        }
+    });
+    Drain { buffer, thread }
+}
+
+/// A ponysay checkout run as the reference, see [`ParityConfig::reference_checkout`].
+#[derive(Debug)]
+struct ReferenceCheckout {
+    /// Script or package directory handed to [`REFERENCE_PYTHON`].
+    entry: PathBuf,
+    /// What the checkout needs to run from where it is; case `env` entries win.
+    env: BTreeMap<String, String>,
+}
+
+impl ReferenceCheckout {
+    /// Finds the entry point under `root`: upstream's `src/` package, a `__main__.py` at the
+    /// root, or a `ponysay.py`/`ponysay` script. `PYTHONPATH` points at the entry's
+    /// directory, bytecode writes are off so the checkout stays clean, and
+    /// `PONYSAY_PONY_PATH`/`PONYSAY_BALLOON_PATH` point both programs at the checkout's
+    /// `ponies/` and `balloons/`, when present.
+    fn locate(root: &Path) -> Result<Self> {
+        let src = root.join("src");
+        let (entry, module_dir) = if src.join("__main__.py").is_file() {
+            (src.clone(), src)
+        } else if root.join("__main__.py").is_file() {
+            (root.to_path_buf(), root.to_path_buf())
+        } else if let Some(script) = ["ponysay.py", "ponysay"]
+            .iter()
+            .map(|name| root.join(name))
+            .find(|script| script.is_file())
+        {
+            (script, root.to_path_buf())
+        } else {
+            bail!(
+                "{} is not a ponysay checkout: no src/__main__.py, __main__.py, ponysay.py or ponysay",
+                root.display()
+            );
+        };
+
+        let path = |path: &Path| path.to_string_lossy().to_string();
+        let mut env = BTreeMap::from([
+            ("PYTHONPATH".to_string(), path(&module_dir)),
+            ("PYTHONDONTWRITEBYTECODE".to_string(), "1".to_string()),
+        ]);
+        for (name, dir) in [
+            ("PONYSAY_PONY_PATH", "ponies"),
+            ("PONYSAY_BALLOON_PATH", "balloons"),
+        ] {
+            let dir = root.join(dir);
+            if dir.is_dir() {
+                env.insert(name.to_string(), path(&dir));
+            }
+        }
+        debug!(entry = %entry.display(), "using ponysay checkout as reference");
+        Ok(Self { entry, env })
+    }
+}
+
 fn normalize_argv(mut argv: Vec<String>) -> Vec<String> {
     if argv
         .first()
@@ -374,6 +1374,42 @@ fn normalize_argv(mut argv: Vec<String>) -> Vec<String> {
     argv
 }
 
+#[cfg(unix)]
+fn termination_signal(status: &std::process::ExitStatus) -> (Option<i32>, bool) {
+    use std::os::unix::process::ExitStatusExt;
+    (status.signal(), status.core_dumped())
+}
+
+#[cfg(not(unix))]
+fn termination_signal(_status: &std::process::ExitStatus) -> (Option<i32>, bool) {
+    (None, false)
+}
+
+/// Names for the signals whose numbers POSIX platforms agree on.
+fn signal_name(signal: i32) -> &'static str {
+    match signal {
+        1 => "SIGHUP",
+        2 => "SIGINT",
+        3 => "SIGQUIT",
+        4 => "SIGILL",
+        6 => "SIGABRT",
+        7 => "SIGBUS",
+        8 => "SIGFPE",
+        9 => "SIGKILL",
+        11 => "SIGSEGV",
+        13 => "SIGPIPE",
+        14 => "SIGALRM",
+        15 => "SIGTERM",
+        _ => "unknown signal",
+    }
+}
+
+/// Whether `signal` dumps core by default, so a missing dump means the core size limit
+/// stopped it.
+fn dumps_core(signal: i32) -> bool {
+    matches!(signal, 3 | 4 | 5 | 6 | 7 | 8 | 11 | 24 | 25 | 31)
+}
+
 fn build_case_detail(
     case: &ParityCase,
     reference: &ProcessOutput,
@@ -384,18 +1420,39 @@ fn build_case_detail(
 ) -> String {
     let mut detail = String::new();
     detail.push_str(&format!("case: {}\n", case.id));
+    for (name, output) in [("reference", reference), ("candidate", candidate)] {
+        if output.limit_exceeded {
+            detail.push_str(&format!(
+                "{name} output limit exceeded; the process was killed\n"
+            ));
+            continue;
+        }
+        if let Some(signal) = output.signal {
+            detail.push_str(&format!(
+                "{name} terminated by signal {signal} ({})",
+                signal_name(signal)
+            ));
+            if output.core_dumped {
+                detail.push_str(", core dumped\n");
+            } else if dumps_core(signal) {
+                detail.push_str("; run `ulimit -c unlimited` first to get a core dump\n");
+            } else {
+                detail.push('\n');
+            }
+        }
+    }
     detail.push_str(&format!("exit_match: {exit_match}\n"));
     detail.push_str(&format!("stdout_match: {stdout_match}\n"));
     detail.push_str(&format!("stderr_match: {stderr_match}\n\n"));
 
     detail.push_str("=== reference (stdout) ===\n");
-    detail.push_str(&String::from_utf8_lossy(&reference.stdout));
+    detail.push_str(&cap_section(escape_output(&reference.stdout), &case.id));
     detail.push_str("\n\n=== candidate (stdout) ===\n");
-    detail.push_str(&String::from_utf8_lossy(&candidate.stdout));
+    detail.push_str(&cap_section(escape_output(&candidate.stdout), &case.id));
     detail.push_str("\n\n=== reference (stderr) ===\n");
-    detail.push_str(&String::from_utf8_lossy(&reference.stderr));
+    detail.push_str(&cap_section(escape_output(&reference.stderr), &case.id));
     detail.push_str("\n\n=== candidate (stderr) ===\n");
-    detail.push_str(&String::from_utf8_lossy(&candidate.stderr));
+    detail.push_str(&cap_section(escape_output(&candidate.stderr), &case.id));
 
     if !stdout_match {
         detail.push_str("\n\n=== first stdout mismatch ===\n");
@@ -419,6 +1476,48 @@ fn build_case_detail(
     detail
 }
 
+/// Upper bound, in bytes, on each of the four output sections of a case's `detail`, so
+/// one flood of output cannot push the mismatch summary after them out of the report.
+/// The raw output lives in the failure artifacts.
+const SECTION_LIMIT: usize = 16 * 1024;
+
+/// Renders program output as printable text: control characters other than newline and
+/// tab become `\x1b`-style literals, C1 controls `\u{9b}`-style ones, and bytes that are
+/// not UTF-8 `\xff`-style ones, so reports stay valid text that is safe to print.
+fn escape_output(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        for ch in chunk.valid().chars() {
+            match ch {
+                '\n' | '\t' => out.push(ch),
+                '\u{0}'..='\u{1f}' | '\u{7f}' => out.push_str(&format!("\\x{:02x}", ch as u32)),
+                '\u{80}'..='\u{9f}' => out.push_str(&format!("\\u{{{:x}}}", ch as u32)),
+                _ => out.push(ch),
+            }
+        }
+        for byte in chunk.invalid() {
+            out.push_str(&format!("\\x{byte:02x}"));
+        }
+    }
+    out
+}
+
+fn cap_section(mut section: String, id: &str) -> String {
+    if section.len() <= SECTION_LIMIT {
+        return section;
+    }
+    let mut end = SECTION_LIMIT;
+    while !section.is_char_boundary(end) {
+        end -= 1;
+    }
+    let omitted = section.len() - end;
+    section.truncate(end);
+    section.push_str(&format!(
+        "\n[truncated: {omitted} more bytes; raw output in failures/{id}/]"
+    ));
+    section
+}
+
 fn first_mismatch(left: &[u8], right: &[u8], left_name: &str, right_name: &str) -> String {
     let min = left.len().min(right.len());
     for index in 0..min {
@@ -459,13 +1558,14 @@ fn compute_requirement_scores(
     for case in cases {
         let mapped = map_requirements(spec, &case.features);
         for requirement in mapped {
-            let entry = agg.entry(requirement).or_insert(RequirementAgg {
-                weight: 1.0,
-                ..RequirementAgg::default()
-            });
+            let Some(entry) = agg.get_mut(&requirement) else {
+                continue;
+            };
             entry.covered += 1;
             if case.passed {
                 entry.passed += 1;
+            } else {
+                entry.failing.push(case.id.clone());
             }
         }
     }
@@ -486,13 +1586,20 @@ fn compute_requirement_scores(
                 "failing"
             };
 
+            let requirement = spec.requirement(&id);
             RequirementResult {
+                description: requirement
+                    .map(|item| item.description.clone())
+                    .unwrap_or_default(),
+                category: requirement.and_then(|item| item.category.clone()),
+                milestone: requirement.and_then(|item| item.milestone.clone()),
                 id,
                 weight: agg.weight,
                 covered_cases: agg.covered,
                 passing_cases: agg.passed,
                 score,
                 status: status.to_string(),
+                failing_cases: agg.failing,
             }
         })
         .collect::<Vec<_>>();
@@ -509,9 +1616,39 @@ fn map_requirements(spec: &RequirementSpec, features: &[String]) -> BTreeSet<Str
     mapped
 }
 
-fn compute_summary(requirements: &[RequirementResult], cases: &[CaseResult]) -> ReportSummary {
+/// Features named by `cases` that the spec does not map to a requirement, with the cases
+/// naming each; every one is logged, since those cases silently count for nothing.
+fn unmapped_features(
+    spec: &RequirementSpec,
+    cases: &[CaseResult],
+) -> BTreeMap<String, Vec<String>> {
+    let mut unmapped = BTreeMap::<String, Vec<String>>::new();
+    for case in cases {
+        for feature in spec.unmapped_features(&case.features) {
+            unmapped
+                .entry(feature.to_string())
+                .or_default()
+                .push(case.id.clone());
+        }
+    }
+    for (feature, cases) in &unmapped {
+        warn!(%feature, cases = %cases.join(", "), "case feature maps to no requirement");
+    }
+    unmapped
+}
+
+fn compute_summary(
+    requirements: &[RequirementResult],
+    cases: &[CaseResult],
+    unmapped_features: BTreeMap<String, Vec<String>>,
+) -> ReportSummary {
     let total_cases = cases.len();
     let passed_cases = cases.iter().filter(|case| case.passed).count();
+    let crashed_cases = cases.iter().filter(|case| case.crashed).count();
+    let mut failures_by_kind = BTreeMap::new();
+    for kind in cases.iter().filter_map(|case| case.failure_kind) {
+        *failures_by_kind.entry(kind).or_insert(0) += 1;
+    }
     let case_parity = if total_cases == 0 {
         0.0
     } else {
@@ -547,6 +1684,9 @@ fn compute_summary(requirements: &[RequirementResult], cases: &[CaseResult]) ->
     ReportSummary {
         total_cases,
         passed_cases,
+        crashed_cases,
+        failures_by_kind,
+        unmapped_features,
         case_parity,
         weighted_requirement_parity,
         requirement_completion,
@@ -569,10 +1709,191 @@ fn write_report_artifacts(config: &ParityConfig, report: &ParityReport) -> Resul
     std::fs::write(&md_path, markdown)
         .with_context(|| format!("failed writing {}", md_path.display()))?;
 
+    let summary = CiSummary::new(report, config.thresholds);
+    let summary_path = config.output_dir.join("summary.json");
+    let json = serde_json::to_string_pretty(&summary).context("failed serializing summary")?;
+    std::fs::write(&summary_path, json)
+        .with_context(|| format!("failed writing {}", summary_path.display()))?;
+    let env_path = config.output_dir.join("summary.env");
+    std::fs::write(&env_path, summary.to_env())
+        .with_context(|| format!("failed writing {}", env_path.display()))?;
+
     info!(json = %json_path.display(), markdown = %md_path.display(), "wrote parity report artifacts");
     Ok(())
 }
 
+/// Lines of each mismatching stream shown in a requirement page's diff excerpt.
+const EXCERPT_LINES: usize = 8;
+
+/// Where a failing case first goes wrong, for the requirement pages: the detail header
+/// (match flags, signals, spawn errors) and, for each stream that differs, the reference
+/// and candidate lines from the first differing one on.
+fn diff_excerpt(run: &CaseRun) -> String {
+    let detail = &run.result.detail;
+    let header = detail
+        .split_once("\n\n=== ")
+        .map_or(detail.as_str(), |(header, _)| header);
+    let mut out = format!("{}\n", header.trim_end());
+    for stream in ["stdout", "stderr"] {
+        let (Some(reference), Some(candidate)) = (
+            run.captures.get(&format!("reference.{stream}")),
+            run.captures.get(&format!("candidate.{stream}")),
+        ) else {
+            continue;
+        };
+        if reference == candidate {
+            continue;
+        }
+        let (reference, candidate) = (escape_output(reference), escape_output(candidate));
+        let (reference, candidate) = (
+            reference.lines().collect::<Vec<_>>(),
+            candidate.lines().collect::<Vec<_>>(),
+        );
+        let first = reference
+            .iter()
+            .zip(&candidate)
+            .take_while(|(left, right)| left == right)
+            .count();
+        out.push_str(&format!(
+            "\n--- reference {stream}\n+++ candidate {stream}\n@@ line {} @@\n",
+            first + 1
+        ));
+        for (sign, lines) in [('-', &reference), ('+', &candidate)] {
+            for line in lines.iter().skip(first).take(EXCERPT_LINES) {
+                out.push_str(&format!("{sign}{line}\n"));
+            }
+        }
+    }
+    out
+}
+
+/// Writes `<id>.md` into `dir` for every failing requirement, so one requirement can be
+/// picked up with everything needed to work on it in one file.
+fn write_requirement_pages(
+    dir: &Path,
+    spec: &RequirementSpec,
+    report: &ParityReport,
+    cases: &[ParityCase],
+    excerpts: &BTreeMap<String, String>,
+) -> Result<()> {
+    let failing = report
+        .requirements
+        .iter()
+        .filter(|req| req.status == "failing")
+        .collect::<Vec<_>>();
+    if failing.is_empty() {
+        return Ok(());
+    }
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("failed creating requirement pages dir {}", dir.display()))?;
+    let cases = cases
+        .iter()
+        .map(|case| (case.id.as_str(), case))
+        .collect::<BTreeMap<_, _>>();
+    for req in &failing {
+        let covering = report
+            .cases
+            .iter()
+            .filter(|result| map_requirements(spec, &result.features).contains(&req.id))
+            .map(|result| (result, cases.get(result.id.as_str()).copied()))
+            .collect::<Vec<_>>();
+        let path = dir.join(format!("{}.md", req.id));
+        std::fs::write(&path, render_requirement_page(req, &covering, excerpts))
+            .with_context(|| format!("failed writing {}", path.display()))?;
+    }
+    info!(dir = %dir.display(), pages = failing.len(), "wrote requirement pages");
+    Ok(())
+}
+
+fn render_requirement_page(
+    req: &RequirementResult,
+    covering: &[(&CaseResult, Option<&ParityCase>)],
+    excerpts: &BTreeMap<String, String>,
+) -> String {
+    let argv = |argv: &[String]| serde_json::to_string(argv).unwrap_or_default();
+    let mut out = format!("# Requirement `{}`\n\n", req.id);
+    out.push_str(&format!("{}\n\n", req.description));
+    out.push_str(&format!("- Status: `{}`\n", req.status));
+    for (name, value) in [("Category", &req.category), ("Milestone", &req.milestone)] {
+        if let Some(value) = value {
+            out.push_str(&format!("- {name}: `{value}`\n"));
+        }
+    }
+    out.push_str(&format!(
+        "- Score: `{:.2}%` ({} of {} cases passing)\n",
+        req.score * 100.0,
+        req.passing_cases,
+        req.covered_cases
+    ));
+    out.push_str(&format!("- Weight: `{:.2}`\n\n", req.weight));
+
+    out.push_str(&format!(
+        "## Failing Cases ({})\n\n",
+        req.failing_cases.len()
+    ));
+    for (result, case) in covering.iter().filter(|(result, _)| !result.passed) {
+        out.push_str(&format!("### `{}`\n\n", result.id));
+        if let Some(kind) = result.failure_kind {
+            out.push_str(&format!("- Failure: `{}`\n", kind.as_str()));
+        }
+        if let Some(case) = case {
+            out.push_str(&format!("- Argv: `{}`\n", argv(&case.argv)));
+            for (name, custom) in [
+                ("Reference argv", &case.reference_argv),
+                ("Candidate argv", &case.candidate_argv),
+            ] {
+                if let Some(custom) = custom {
+                    out.push_str(&format!("- {name}: `{}`\n", argv(custom)));
+                }
+            }
+            out.push_str(&format!("- Source: `{}`\n", case.source.display()));
+        }
+        out.push_str(&format!("- Artifacts: `failures/{}/`\n\n", result.id));
+        if let Some(excerpt) = excerpts.get(&result.id) {
+            out.push_str(&format!("```diff\n{excerpt}```\n\n"));
+        }
+    }
+
+    let passing = covering
+        .iter()
+        .filter(|(result, _)| result.passed)
+        .collect::<Vec<_>>();
+    out.push_str(&format!("## Passing Cases ({})\n\n", passing.len()));
+    if passing.is_empty() {
+        out.push_str("None.\n");
+    }
+    for (result, case) in passing {
+        match case {
+            Some(case) => out.push_str(&format!("- `{}`: `{}`\n", result.id, argv(&case.argv))),
+            None => out.push_str(&format!("- `{}`\n", result.id)),
+        }
+    }
+    out
+}
+
+/// `text` fit for a markdown table cell, `-` when missing.
+fn table_cell(text: Option<&str>) -> String {
+    match text {
+        Some(text) if !text.trim().is_empty() => text.replace('|', "\\|").replace('\n', " "),
+        _ => "-".to_string(),
+    }
+}
+
+/// Entries in the report's quick-wins section.
+const QUICK_WINS: usize = 5;
+
+/// The `limit` failing requirements that gain the most weight per failing case fixed.
+fn quick_wins(requirements: &[RequirementResult], limit: usize) -> Vec<&RequirementResult> {
+    let mut failing = requirements
+        .iter()
+        .filter(|req| req.status == "failing" && !req.failing_cases.is_empty())
+        .collect::<Vec<_>>();
+    let value = |req: &RequirementResult| req.weight / req.failing_cases.len() as f64;
+    failing.sort_by(|a, b| value(b).total_cmp(&value(a)).then_with(|| a.id.cmp(&b.id)));
+    failing.truncate(limit);
+    failing
+}
+
 fn render_markdown(report: &ParityReport) -> String {
     let mut out = String::new();
     out.push_str("# Iron Pony Parity Report\n\n");
@@ -580,8 +1901,33 @@ fn render_markdown(report: &ParityReport) -> String {
         "- Generated epoch: `{}`\n",
         report.generated_epoch_secs
     ));
+    let environment = &report.environment;
+    let unknown = |value: &Option<String>| value.clone().unwrap_or_else(|| "unknown".to_string());
+    out.push_str(&format!(
+        "- Reference: `{}` (`{}`)\n",
+        unknown(&environment.reference_version),
+        environment.reference
+    ));
+    out.push_str(&format!(
+        "- Candidate: `{}` at `{}`\n",
+        unknown(&environment.candidate_version),
+        unknown(&environment.candidate_commit)
+    ));
+    out.push_str(&format!(
+        "- Platform: `{}/{}`\n",
+        environment.os, environment.arch
+    ));
+    if !environment.terminal.is_empty() {
+        let terminal = environment
+            .terminal
+            .iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>();
+        out.push_str(&format!("- Terminal: `{}`\n", terminal.join(" ")));
+    }
     out.push_str(&format!("- Cases: `{}`\n", report.summary.total_cases));
     out.push_str(&format!("- Passed: `{}`\n", report.summary.passed_cases));
+    out.push_str(&format!("- Crashed: `{}`\n", report.summary.crashed_cases));
     out.push_str(&format!(
         "- Case parity: `{:.2}%`\n",
         report.summary.case_parity * 100.0
@@ -599,22 +1945,87 @@ fn render_markdown(report: &ParityReport) -> String {
         report.summary.untested_requirements
     ));
 
-    out.push_str("## Requirements\n\n");
-    out.push_str("| Requirement | Status | Score | Covered | Passing | Weight |\n");
-    out.push_str("|---|---|---:|---:|---:|---:|\n");
-    for req in &report.requirements {
-        out.push_str(&format!(
-            "| {} | {} | {:.2}% | {} | {} | {:.2} |\n",
-            req.id,
-            req.status,
-            req.score * 100.0,
-            req.covered_cases,
-            req.passing_cases,
-            req.weight
-        ));
+    if !report.summary.failures_by_kind.is_empty() {
+        out.push_str("## Failures by Kind\n\n");
+        for (kind, count) in &report.summary.failures_by_kind {
+            out.push_str(&format!("- `{}`: {count}\n", kind.as_str()));
+        }
+        out.push('\n');
     }
 
-    out.push_str("\n## Cases\n\n");
+    if !report.summary.unmapped_features.is_empty() {
+        out.push_str("## Unmapped Features\n\n");
+        out.push_str(
+            "Case features the spec maps to no requirement; these cases are not scored.\n\n",
+        );
+        for (feature, cases) in &report.summary.unmapped_features {
+            out.push_str(&format!("- `{feature}`: {}\n", cases.join(", ")));
+        }
+        out.push('\n');
+    }
+
+    let quick_wins = quick_wins(&report.requirements, QUICK_WINS);
+    if !quick_wins.is_empty() {
+        out.push_str("## Quick Wins\n\n");
+        out.push_str("Failing requirements with the most weight per failing case.\n\n");
+        for (rank, req) in quick_wins.iter().enumerate() {
+            out.push_str(&format!(
+                "{}. `{}` (weight {:.2}): fix {}\n",
+                rank + 1,
+                req.id,
+                req.weight,
+                req.failing_cases.join(", ")
+            ));
+        }
+        out.push('\n');
+    }
+
+    for (title, status) in [
+        ("Failing Requirements", "failing"),
+        ("Untested Requirements", "untested"),
+        ("Done Requirements", "done"),
+    ] {
+        let group = report
+            .requirements
+            .iter()
+            .filter(|req| req.status == status)
+            .collect::<Vec<_>>();
+        out.push_str(&format!("## {title} ({})\n\n", group.len()));
+        if group.is_empty() {
+            out.push_str("None.\n\n");
+            continue;
+        }
+        out.push_str(
+            "| Requirement | Description | Category | Milestone | Score | Covered | Passing | Weight |\n",
+        );
+        out.push_str("|---|---|---|---|---:|---:|---:|---:|\n");
+        for req in &group {
+            out.push_str(&format!(
+                "| {} | {} | {} | {} | {:.2}% | {} | {} | {:.2} |\n",
+                req.id,
+                table_cell(Some(&req.description)),
+                table_cell(req.category.as_deref()),
+                table_cell(req.milestone.as_deref()),
+                req.score * 100.0,
+                req.covered_cases,
+                req.passing_cases,
+                req.weight
+            ));
+        }
+        if status == "failing" {
+            out.push('\n');
+            for req in &group {
+                out.push_str(&format!(
+                    "- `{}` failing cases: {}\n",
+                    req.id,
+                    req.failing_cases.join(", ")
+                ));
+            }
+        }
+        out.push('\n');
+    }
+
+    out.push_str("## Cases\n\n");
     out.push_str("| Case | Passed | Exit | Stdout | Stderr |\n");
     out.push_str("|---|---|---|---|---|\n");
     for case in &report.cases {
@@ -627,10 +2038,122 @@ fn render_markdown(report: &ParityReport) -> String {
     out
 }
 
-fn substitute_vars(input: &str, temp: &Path, workspace: &Path) -> String {
-    input
-        .replace("{temp}", &temp.to_string_lossy())
-        .replace("{workspace}", &workspace.to_string_lossy())
+/// Writes the case's `files` into `temp`, creating parent directories as needed.
+fn stage_files(case: &ParityCase, temp: &Path) -> Result<()> {
+    for (relative, content) in &case.files {
+        let relative_path = Path::new(relative);
+        if !relative_path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            bail!(
+                "case {} file `{relative}` must be a relative path inside {{temp}}",
+                case.id
+            );
+        }
+
+        let path = temp.join(relative_path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("failed creating {}", parent.display()))?;
+        }
+        std::fs::write(&path, content)
+            .with_context(|| format!("failed staging fixture {}", path.display()))?;
+        debug!(case = %case.id, path = %path.display(), "staged parity fixture");
+    }
+    Ok(())
+}
+
+/// Placeholder values for one case run.
+///
+/// Built-ins are `{temp}`, `{workspace}`, `{case_dir}` (directory of the case file),
+/// `{assets}` (`<workspace>/assets`), `{home}` (an empty per-case directory under `{temp}`),
+/// `{ref_bin}`, `{cand_bin}` and, in seed sub-cases, `{seed}`. A case's `vars` add more;
+/// their values may use built-ins.
+#[derive(Debug)]
+struct CaseVars(BTreeMap<String, String>);
+
+impl CaseVars {
+    fn new(config: &ParityConfig, case: &ParityCase, temp: &Path) -> Result<Self> {
+        let home = temp.join("home");
+        std::fs::create_dir_all(&home)
+            .with_context(|| format!("failed creating case home {}", home.display()))?;
+
+        let case_dir = case
+            .source
+            .parent()
+            .unwrap_or(&config.cases_dir)
+            .to_path_buf();
+        let reference = case
+            .reference_program
+            .as_deref()
+            .unwrap_or(&config.reference_program);
+        // Without an explicit candidate the harness goes through `cargo run`, which builds
+        // the binary at the default target location.
+        let candidate = match (&case.candidate_program, &config.candidate_program) {
+            (Some(program), _) => program.clone(),
+            (None, Some(program)) => program.to_string_lossy().to_string(),
+            (None, None) => config
+                .workspace_root
+                .join("target/debug/iron-pony")
+                .to_string_lossy()
+                .to_string(),
+        };
+
+        let path = |path: &Path| path.to_string_lossy().to_string();
+        let mut values = BTreeMap::from([
+            ("temp".to_string(), path(temp)),
+            ("workspace".to_string(), path(&config.workspace_root)),
+            ("case_dir".to_string(), path(&case_dir)),
+            (
+                "assets".to_string(),
+                path(&config.workspace_root.join("assets")),
+            ),
+            ("home".to_string(), path(&home)),
+            ("ref_bin".to_string(), reference.to_string()),
+            ("cand_bin".to_string(), candidate),
+        ]);
+        if let Some(seed) = case.seed {
+            values.insert("seed".to_string(), seed.to_string());
+        }
+
+        let builtins = Self(values.clone());
+        for (name, value) in &case.vars {
+            if builtins.0.contains_key(name) {
+                bail!(
+                    "case {} var `{name}` shadows a built-in placeholder",
+                    case.id
+                );
+            }
+            values.insert(name.clone(), builtins.apply(value));
+        }
+        Ok(Self(values))
+    }
+
+    /// Replaces every known `{name}`; unknown braces are left untouched.
+    fn apply(&self, input: &str) -> String {
+        let mut out = String::with_capacity(input.len());
+        let mut rest = input;
+        while let Some(open) = rest.find('{') {
+            out.push_str(&rest[..open]);
+            let after = &rest[open + 1..];
+            match after
+                .find('}')
+                .and_then(|close| Some((close, self.0.get(&after[..close])?)))
+            {
+                Some((close, value)) => {
+                    out.push_str(value);
+                    rest = &after[close + 1..];
+                }
+                None => {
+                    out.push('{');
+                    rest = after;
+                }
+            }
+        }
+        out.push_str(rest);
+        out
+    }
 }
 
 #[cfg(test)]
@@ -648,4 +2171,709 @@ mod tests {
         let detail = first_mismatch(b"abc", b"ab", "a", "b");
         assert!(detail.contains("length mismatch"));
     }
+
+    fn write_fixture(root: &Path, program: &str, argv: &[&str]) -> ParityConfig {
+        let cases = root.join("cases");
+        std::fs::create_dir_all(&cases).expect("cases dir");
+        std::fs::write(
+            root.join("requirements.yaml"),
+            "requirements:\n  - id: smoke\n    description: Smoke.\n    weight: 1.0\n",
+        )
+        .expect("spec");
+        let case = serde_json::json!({
+            "id": "smoke",
+            "features": ["smoke"],
+            "argv": argv,
+            "reference_program": program,
+            "candidate_program": program,
+        });
+        std::fs::write(cases.join("smoke.json"), case.to_string()).expect("case");
+
+        ParityConfig {
+            workspace_root: root.to_path_buf(),
+            cases_dir: cases,
+            spec_path: root.join("requirements.yaml"),
+            output_dir: root.join("out"),
+            reference_program: program.to_string(),
+            reference_checkout: None,
+            candidate_program: None,
+            candidate_env: BTreeMap::new(),
+            output_limit: DEFAULT_OUTPUT_LIMIT,
+            strict: false,
+            thresholds: ParityThresholds::default(),
+        }
+    }
+
+    #[test]
+    fn reports_progress_for_every_case() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config = write_fixture(tmp.path(), "true", &["true"]);
+
+        let mut events = Vec::new();
+        let report = run_parity_with(
+            &config,
+            |event| {
+                events.push(match event {
+                    ParityProgress::Started { total } => format!("started {total}"),
+                    ParityProgress::CaseStarted { id, .. } => format!("case {id}"),
+                    ParityProgress::CaseFinished { result, .. } => {
+                        format!("done {} {}", result.id, result.passed)
+                    }
+                    ParityProgress::Finished { report } => {
+                        format!("finished {}", report.summary.passed_cases)
+                    }
+                })
+            },
+            &CancelToken::new(),
+        )
+        .expect("report");
+
+        assert_eq!(report.summary.passed_cases, 1);
+        assert_eq!(
+            events,
+            vec!["started 1", "case smoke", "done smoke true", "finished 1"]
+        );
+    }
+
+    #[test]
+    fn cancellation_kills_running_cases() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config = write_fixture(tmp.path(), "sleep", &["sleep", "30"]);
+
+        let cancel = CancelToken::new();
+        let remote = cancel.clone();
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            remote.cancel();
+        });
+
+        let started = std::time::Instant::now();
+        let error = run_parity_with(&config, |_| {}, &cancel).expect_err("cancelled");
+        canceller.join().expect("canceller");
+        assert!(error.is::<Cancelled>());
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(!config.output_dir.join("parity-report.json").exists());
+    }
+
+    #[test]
+    fn debug_runs_keep_temp_and_report_child_env() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config = write_fixture(tmp.path(), "true", &["true"]);
+        let mut case = load_cases(&config.cases_dir).expect("cases").remove(0);
+        assert!(case.source.ends_with("smoke.json"));
+        case.env
+            .insert("CASE_TEMP".to_string(), "{temp}/home".to_string());
+
+        let options = CaseRunOptions {
+            stream_output: false,
+            keep_temp: true,
+        };
+        let run = run_case_with(&config, &case, options, &CancelToken::new()).expect("run");
+        assert!(run.result.passed);
+        assert!(run.temp_dir.is_dir());
+        assert_eq!(
+            run.env.get("CASE_TEMP").map(String::as_str),
+            Some(format!("{}/home", run.temp_dir.display()).as_str())
+        );
+        std::fs::remove_dir_all(&run.temp_dir).expect("cleanup");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reports_are_stamped_with_versions_and_platform() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let mut config = write_fixture(tmp.path(), "true", &["true"]);
+        let program = tmp.path().join("fake-ponysay");
+        std::fs::write(&program, "#!/bin/sh\necho\necho \"fake 1.0 $*\"\n").expect("script");
+        std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755)).expect("chmod");
+        config.reference_program = program.to_string_lossy().to_string();
+        config.candidate_program = Some(program);
+
+        let report = run_parity(&config).expect("report");
+        let environment = &report.environment;
+        assert_eq!(
+            environment.reference_version.as_deref(),
+            Some("fake 1.0 --version")
+        );
+        assert_eq!(environment.candidate_version, environment.reference_version);
+        assert_eq!(environment.os, std::env::consts::OS);
+        let markdown =
+            std::fs::read_to_string(config.output_dir.join("parity-report.md")).expect("markdown");
+        assert!(markdown.contains(&format!(
+            "- Reference: `fake 1.0 --version` (`{}`)\n",
+            config.reference_program
+        )));
+    }
+
+    #[test]
+    fn checkout_reference_runs_under_python_with_its_assets() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let mut config = write_fixture(tmp.path(), "echo", &["echo"]);
+        let checkout = tmp.path().join("ponysay");
+        std::fs::create_dir_all(checkout.join("ponies")).expect("ponies dir");
+        std::fs::write(
+            checkout.join("ponysay.py"),
+            "import os, sys\nprint(' '.join(sys.argv[1:]), \
+             os.path.basename(os.environ['PONYSAY_PONY_PATH']), \
+             os.environ['PYTHONDONTWRITEBYTECODE'])\n",
+        )
+        .expect("script");
+        config.reference_checkout = Some(checkout.clone());
+        let case = serde_json::json!({
+            "id": "smoke",
+            "argv": ["ponysay", "-f", "x"],
+            "candidate_program": "echo",
+            "candidate_argv": ["echo", "-f x ponies 1"],
+        });
+        std::fs::write(config.cases_dir.join("smoke.json"), case.to_string()).expect("case");
+
+        let case = load_cases(&config.cases_dir).expect("cases").remove(0);
+        let run = run_case_with(
+            &config,
+            &case,
+            CaseRunOptions::default(),
+            &CancelToken::new(),
+        )
+        .expect("run");
+        assert!(run.result.passed, "{}", run.result.detail);
+        assert_eq!(
+            run.env.get("PYTHONPATH").map(PathBuf::from),
+            Some(checkout.clone())
+        );
+
+        config.reference_checkout = Some(tmp.path().join("cases"));
+        assert!(
+            run_case_with(
+                &config,
+                &case,
+                CaseRunOptions::default(),
+                &CancelToken::new()
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn failures_are_captured_and_archived_after_clearing_stale_artifacts() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config = write_fixture(tmp.path(), "echo", &["echo", "ref"]);
+        let case = serde_json::json!({
+            "id": "smoke",
+            "argv": ["echo", "ref"],
+            "candidate_argv": ["echo", "cand"],
+            "reference_program": "echo",
+            "candidate_program": "echo",
+        });
+        std::fs::write(config.cases_dir.join("smoke.json"), case.to_string()).expect("case");
+        let failures = config.output_dir.join("failures");
+        std::fs::create_dir_all(&failures).expect("failures dir");
+        std::fs::write(failures.join("stale.diff"), "old").expect("stale");
+
+        let report = run_parity(&config).expect("report");
+        assert_eq!(report.summary.passed_cases, 0);
+        assert!(!failures.join("stale.diff").exists());
+        assert!(failures.join("smoke.diff").is_file());
+        assert_eq!(
+            std::fs::read(failures.join("smoke/reference.stdout")).expect("reference"),
+            b"ref\n"
+        );
+        assert_eq!(
+            std::fs::read(failures.join("smoke/candidate.stdout")).expect("candidate"),
+            b"cand\n"
+        );
+        assert!(failures.join("smoke/case.json").is_file());
+
+        let archive_path = tmp.path().join("failures.tar.gz");
+        archive_failures(&config, &archive_path).expect("archive");
+        let file = std::fs::File::open(&archive_path).expect("open archive");
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(file));
+        let names = archive
+            .entries()
+            .expect("entries")
+            .map(|entry| {
+                let entry = entry.expect("entry");
+                entry.path().expect("path").to_string_lossy().to_string()
+            })
+            .collect::<Vec<_>>();
+        assert!(names.contains(&"failures/smoke/candidate.stderr".to_string()));
+        assert!(names.contains(&"failures/smoke.diff".to_string()));
+        assert!(names.contains(&"parity-report.json".to_string()));
+    }
+
+    #[test]
+    fn failing_requirements_get_drill_down_pages() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config = write_fixture(tmp.path(), "sh", &["sh", "-c", "echo ok"]);
+        let case = serde_json::json!({
+            "id": "wrong",
+            "features": ["smoke"],
+            "argv": ["sh", "-c", "printf 'a\\nb\\n'"],
+            "candidate_argv": ["sh", "-c", "printf 'a\\nc\\n'"],
+            "reference_program": "sh",
+            "candidate_program": "sh",
+        });
+        std::fs::write(config.cases_dir.join("wrong.json"), case.to_string()).expect("case");
+        let pages = config.output_dir.join("requirements");
+        std::fs::create_dir_all(&pages).expect("pages dir");
+        std::fs::write(pages.join("stale.md"), "old").expect("stale");
+
+        run_parity(&config).expect("report");
+        assert!(!pages.join("stale.md").exists());
+        let page = std::fs::read_to_string(pages.join("smoke.md")).expect("page");
+        assert!(page.starts_with("# Requirement `smoke`\n\nSmoke.\n\n"));
+        assert!(page.contains("(1 of 2 cases passing)"));
+        assert!(page.contains("### `wrong`\n\n- Failure: `stdout_mismatch`\n"));
+        assert!(page.contains(r#"- Candidate argv: `["sh","-c","printf 'a\\nc\\n'"]`"#));
+        assert!(page.contains("@@ line 2 @@\n-b\n+c\n```"));
+        assert!(
+            page.contains("## Passing Cases (1)\n\n- `smoke`: `[\"sh\",\"-c\",\"echo ok\"]`\n")
+        );
+    }
+
+    #[test]
+    fn summary_files_record_scores_against_thresholds() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let mut config = write_fixture(tmp.path(), "sh", &["sh", "-c", "echo ok"]);
+        let case = serde_json::json!({
+            "id": "wrong",
+            "features": ["smoke"],
+            "argv": ["sh", "-c", "echo ok"],
+            "candidate_argv": ["sh", "-c", "echo no"],
+            "reference_program": "sh",
+            "candidate_program": "sh",
+        });
+        std::fs::write(config.cases_dir.join("wrong.json"), case.to_string()).expect("case");
+        config.thresholds = ParityThresholds {
+            case_parity: Some(0.75),
+            weighted_requirement_parity: Some(0.5),
+        };
+
+        run_parity(&config).expect("report");
+        let summary: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(config.output_dir.join("summary.json")).expect("json"),
+        )
+        .expect("summary");
+        assert_eq!(summary["passed"], false);
+        assert_eq!(
+            summary["missed_thresholds"],
+            serde_json::json!(["case parity 50.00% is below 75.00%"])
+        );
+        assert_eq!(summary["thresholds"]["case_parity"], 0.75);
+        assert_eq!(summary["passed_cases"], 1);
+        let env = std::fs::read_to_string(config.output_dir.join("summary.env")).expect("env");
+        assert!(env.starts_with("PARITY_PASSED=false\nPARITY_TOTAL_CASES=2\n"));
+        assert!(env.contains("PARITY_CASE_PARITY=0.5000\n"));
+    }
+
+    #[test]
+    fn case_files_are_validated_before_running() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let mut config = write_fixture(tmp.path(), "sh", &["sh", "-c", "echo ok"]);
+        let write = |name: &str, case: serde_json::Value| {
+            std::fs::write(config.cases_dir.join(name), case.to_string()).expect("case");
+        };
+        write(
+            "copy.json",
+            serde_json::json!({ "id": "smoke", "features": ["smoke"], "argv": ["sh"] }),
+        );
+        write(
+            "loose.json",
+            serde_json::json!({
+                "id": "loose",
+                "features": ["smok"],
+                "argv": [],
+                "timeout": 5,
+            }),
+        );
+
+        let cases = load_cases(&config.cases_dir).expect("cases");
+        assert_eq!(cases[1].unknown_fields["timeout"], 5);
+        let spec = RequirementSpec::load(&config.spec_path).expect("spec");
+        let messages = |strict| {
+            validate_cases(&cases, &spec, strict)
+                .into_iter()
+                .map(|violation| {
+                    let file = violation.source.file_name().expect("file").to_owned();
+                    format!("{}: {}", file.to_string_lossy(), violation.message)
+                })
+                .collect::<Vec<_>>()
+        };
+        let lenient = messages(false);
+        assert_eq!(lenient.len(), 2);
+        assert_eq!(lenient[0], "loose.json: `argv` is empty");
+        assert!(lenient[1].starts_with("smoke.json: case id `smoke` is already used by "));
+        assert!(lenient[1].ends_with("copy.json"));
+        assert_eq!(
+            messages(true)[1..3],
+            [
+                "loose.json: unknown field `timeout`",
+                "loose.json: feature `smok` maps to no spec requirement",
+            ]
+        );
+
+        config.strict = true;
+        let error = run_parity(&config).expect_err("invalid cases");
+        assert!(
+            error
+                .to_string()
+                .starts_with("4 problem(s) in parity cases:\n")
+        );
+        assert!(!config.output_dir.join("parity-report.json").exists());
+    }
+
+    #[test]
+    fn case_vars_expand_builtins_and_user_values() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config = write_fixture(tmp.path(), "true", &["true"]);
+        let mut case = load_cases(&config.cases_dir).expect("cases").remove(0);
+        case.vars
+            .insert("fixture".to_string(), "{case_dir}/fixture.pony".to_string());
+
+        let vars = CaseVars::new(&config, &case, &tmp.path().join("t")).expect("vars");
+        let root = tmp.path().display();
+        assert_eq!(
+            vars.apply("{fixture} {home} {ref_bin} {unknown} {"),
+            format!("{root}/cases/fixture.pony {root}/t/home true {{unknown}} {{")
+        );
+        assert!(tmp.path().join("t/home").is_dir());
+        assert_eq!(vars.apply("{assets}"), format!("{root}/assets"));
+
+        case.vars.insert("temp".to_string(), "x".to_string());
+        assert!(CaseVars::new(&config, &case, tmp.path()).is_err());
+    }
+
+    #[test]
+    fn stages_fixture_files_into_temp() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config = write_fixture(tmp.path(), "cat", &["cat", "{temp}/ponies/custom.pony"]);
+        let mut case = load_cases(&config.cases_dir).expect("cases").remove(0);
+        case.files.insert(
+            "ponies/custom.pony".to_string(),
+            "$balloon$\n{not a var}\n".to_string(),
+        );
+
+        let run = run_case_with(
+            &config,
+            &case,
+            CaseRunOptions::default(),
+            &CancelToken::new(),
+        )
+        .expect("run");
+        assert!(run.result.passed);
+        assert_eq!(
+            run.captures["reference.stdout"],
+            b"$balloon$\n{not a var}\n"
+        );
+
+        case.files
+            .insert("../escape.pony".to_string(), String::new());
+        assert!(
+            run_case_with(
+                &config,
+                &case,
+                CaseRunOptions::default(),
+                &CancelToken::new()
+            )
+            .is_err()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn candidate_signals_are_classified_as_crashes() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config = write_fixture(tmp.path(), "sh", &["sh", "-c", "exit 0"]);
+        let mut case = load_cases(&config.cases_dir).expect("cases").remove(0);
+        case.candidate_argv = Some(vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            "kill -SEGV $$".to_string(),
+        ]);
+
+        let run = run_case_with(
+            &config,
+            &case,
+            CaseRunOptions::default(),
+            &CancelToken::new(),
+        )
+        .expect("run");
+        assert!(run.result.crashed);
+        assert!(!run.result.exit_match);
+        assert_eq!(run.result.candidate_signal, Some(11));
+        assert!(
+            run.result
+                .detail
+                .contains("candidate terminated by signal 11 (SIGSEGV)")
+        );
+
+        case.candidate_argv = Some(vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            "kill -KILL $$".to_string(),
+        ]);
+        let run = run_case_with(
+            &config,
+            &case,
+            CaseRunOptions::default(),
+            &CancelToken::new(),
+        )
+        .expect("run");
+        assert!(
+            run.result
+                .detail
+                .contains("candidate terminated by signal 9 (SIGKILL)\n"),
+            "{}",
+            run.result.detail
+        );
+        assert!(!run.result.detail.contains("ulimit"));
+    }
+
+    #[test]
+    fn runaway_output_is_truncated_and_killed() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config = write_fixture(tmp.path(), "sh", &["sh", "-c", "echo ok"]);
+        let mut case = load_cases(&config.cases_dir).expect("cases").remove(0);
+        case.output_limit = Some(64);
+        case.candidate_argv = Some(vec!["sh".to_string(), "-c".to_string(), "yes".to_string()]);
+
+        let started = std::time::Instant::now();
+        let run = run_case_with(
+            &config,
+            &case,
+            CaseRunOptions::default(),
+            &CancelToken::new(),
+        )
+        .expect("run");
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(run.result.output_limit_exceeded);
+        assert!(!run.result.passed);
+        assert!(!run.result.crashed);
+        assert_eq!(run.result.failure_kind, Some(FailureKind::OutputLimit));
+        let stdout = &run.captures["candidate.stdout"];
+        assert!(stdout.starts_with(b"y\ny\n"));
+        assert!(stdout.ends_with(b"\n[output truncated at 64 bytes]\n"));
+        assert_eq!(
+            stdout.len(),
+            64 + "\n[output truncated at 64 bytes]\n".len()
+        );
+    }
+
+    #[test]
+    fn failures_are_classified_by_kind() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config = write_fixture(tmp.path(), "sh", &["sh", "-c", "echo ok"]);
+        let mut case = load_cases(&config.cases_dir).expect("cases").remove(0);
+        let kind = |case: &ParityCase| {
+            run_case_with(
+                &config,
+                case,
+                CaseRunOptions::default(),
+                &CancelToken::new(),
+            )
+            .expect("run")
+            .result
+            .failure_kind
+        };
+        assert_eq!(kind(&case), None);
+
+        case.candidate_argv = Some(vec!["sh".into(), "-c".into(), "echo ok >&2".into()]);
+        assert_eq!(kind(&case), Some(FailureKind::StdoutMismatch));
+        case.candidate_argv = Some(vec!["sh".into(), "-c".into(), "echo ok; exit 3".into()]);
+        assert_eq!(kind(&case), Some(FailureKind::ExitMismatch));
+        case.candidate_program = Some("/nonexistent/iron-pony".into());
+        assert_eq!(kind(&case), Some(FailureKind::CandidateSpawnFailed));
+
+        case.candidate_program = Some("sh".into());
+        case.candidate_argv = Some(vec!["sh".into(), "-c".into(), "sleep 30".into()]);
+        case.timeout_secs = Some(0.2);
+        let started = std::time::Instant::now();
+        assert_eq!(kind(&case), Some(FailureKind::Timeout));
+        assert!(started.elapsed() < Duration::from_secs(10));
+
+        let results = [
+            FailureKind::Timeout,
+            FailureKind::Timeout,
+            FailureKind::Crash,
+        ]
+        .map(|kind| CaseResult {
+            id: "case".to_string(),
+            features: Vec::new(),
+            passed: false,
+            exit_match: false,
+            stdout_match: false,
+            stderr_match: false,
+            crashed: kind == FailureKind::Crash,
+            candidate_signal: None,
+            output_limit_exceeded: false,
+            failure_kind: Some(kind),
+            detail: String::new(),
+        });
+        let summary = compute_summary(&[], &results, BTreeMap::new());
+        assert_eq!(
+            summary.failures_by_kind,
+            BTreeMap::from([(FailureKind::Timeout, 2), (FailureKind::Crash, 1)])
+        );
+    }
+
+    #[test]
+    fn locale_matrix_expands_into_sub_cases() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config = write_fixture(tmp.path(), "sh", &["sh", "-c", "echo $LANG $LC_ALL"]);
+        let case = serde_json::json!({
+            "id": "smoke",
+            "features": ["smoke"],
+            "argv": ["sh", "-c", "echo $LANG $LC_ALL"],
+            "reference_program": "sh",
+            "candidate_program": "sh",
+            "locales": ["C", "en_US.UTF-8"],
+        });
+        std::fs::write(config.cases_dir.join("smoke.json"), case.to_string()).expect("case");
+
+        let cases = load_cases(&config.cases_dir).expect("cases");
+        let ids = cases
+            .iter()
+            .map(|case| case.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["smoke@C", "smoke@en_US.UTF-8"]);
+
+        let run = run_case_with(
+            &config,
+            &cases[1],
+            CaseRunOptions::default(),
+            &CancelToken::new(),
+        )
+        .expect("run");
+        assert_eq!(
+            run.captures["reference.stdout"],
+            b"en_US.UTF-8 en_US.UTF-8\n"
+        );
+
+        let report = run_parity(&config).expect("report");
+        assert_eq!(report.summary.total_cases, 2);
+    }
+
+    #[test]
+    fn seed_matrix_multiplies_with_locales() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config = write_fixture(tmp.path(), "echo", &["echo"]);
+        let case = serde_json::json!({
+            "id": "random",
+            "argv": ["echo", "--seed", "{seed}"],
+            "reference_program": "echo",
+            "candidate_program": "echo",
+            "locales": ["C", "C.UTF-8"],
+            "seeds": [1, 42],
+        });
+        std::fs::write(config.cases_dir.join("smoke.json"), case.to_string()).expect("case");
+
+        let cases = load_cases(&config.cases_dir).expect("cases");
+        let ids = cases
+            .iter()
+            .map(|case| case.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ids,
+            vec![
+                "random@C#1",
+                "random@C#42",
+                "random@C.UTF-8#1",
+                "random@C.UTF-8#42"
+            ]
+        );
+
+        let run = run_case_with(
+            &config,
+            &cases[1],
+            CaseRunOptions::default(),
+            &CancelToken::new(),
+        )
+        .expect("run");
+        assert_eq!(run.captures["candidate.stdout"], b"--seed 42\n");
+    }
+
+    #[test]
+    fn markdown_groups_requirements_and_ranks_quick_wins() {
+        let requirement =
+            |id: &str, weight: f64, status: &str, failing: &[&str]| RequirementResult {
+                id: id.to_string(),
+                description: format!("{id} | behavior"),
+                category: Some("cli".to_string()),
+                milestone: None,
+                weight,
+                covered_cases: failing.len() + 1,
+                passing_cases: 1,
+                score: 0.5,
+                status: status.to_string(),
+                failing_cases: failing.iter().map(|id| id.to_string()).collect(),
+            };
+        let requirements = vec![
+            requirement("cheap", 1.0, "failing", &["c1"]),
+            requirement("heavy", 3.0, "failing", &["h1", "h2"]),
+            requirement("ok", 2.0, "done", &[]),
+        ];
+        let ranked = quick_wins(&requirements, 5)
+            .into_iter()
+            .map(|req| req.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ranked, vec!["heavy", "cheap"]);
+
+        let report = ParityReport {
+            generated_epoch_secs: 0,
+            environment: RunEnvironment::default(),
+            summary: compute_summary(
+                &requirements,
+                &[],
+                BTreeMap::from([("typo".to_string(), vec!["t1".to_string(), "t2".to_string()])]),
+            ),
+            requirements,
+            cases: Vec::new(),
+        };
+        let markdown = render_markdown(&report);
+        assert!(markdown.contains("1. `heavy` (weight 3.00): fix h1, h2\n"));
+        assert!(markdown.contains("## Failing Requirements (2)"));
+        assert!(markdown.contains("- `cheap` failing cases: c1\n"));
+        assert!(markdown.contains("## Untested Requirements (0)\n\nNone."));
+        assert!(markdown.contains("## Done Requirements (1)"));
+        assert!(markdown.contains("## Unmapped Features\n\n"));
+        assert!(markdown.contains("- `typo`: t1, t2\n"));
+        assert!(markdown.contains("| ok | ok \\| behavior | cli | - | 50.00% | 1 | 1 | 2.00 |\n"));
+    }
+
+    #[test]
+    fn details_escape_control_bytes_and_are_capped() {
+        assert_eq!(
+            escape_output(b"\x1b[31mred\x1b[0m\tok\x07\n\xc2\x9b\xff"),
+            "\\x1b[31mred\\x1b[0m\tok\\x07\n\\u{9b}\\xff"
+        );
+
+        let capped = cap_section("\u{e9}".repeat(SECTION_LIMIT), "wide");
+        assert!(capped.len() < SECTION_LIMIT + 100);
+        assert!(capped.ends_with("raw output in failures/wide/]"));
+
+        let case: ParityCase =
+            serde_json::from_str(r#"{"id": "flood", "argv": []}"#).expect("case");
+        let output = |stdout: Vec<u8>| ProcessOutput {
+            status_code: 0,
+            signal: None,
+            core_dumped: false,
+            limit_exceeded: false,
+            timed_out: false,
+            stdout,
+            stderr: b"warning\n".to_vec(),
+        };
+        let detail = build_case_detail(
+            &case,
+            &output(vec![b'a'; 4 * SECTION_LIMIT]),
+            &output(vec![b'b'; 4 * SECTION_LIMIT]),
+            true,
+            false,
+            true,
+        );
+        assert!(detail.len() < 3 * SECTION_LIMIT);
+        assert_eq!(detail.matches("failures/flood/]").count(), 2);
+        assert!(detail.contains("=== candidate (stderr) ===\nwarning\n"));
+        assert!(detail.contains("=== first stdout mismatch ===\nbyte 0: reference=0x61"));
+    }
 }
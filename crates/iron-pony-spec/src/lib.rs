@@ -1,7 +1,7 @@
 use std::collections::{BTreeMap, BTreeSet};
 use std::path::Path;
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use serde::Deserialize;
 use tracing::{debug, info};
 
@@ -17,6 +17,12 @@ pub struct Requirement {
     pub id: String,
     pub description: String,
     pub weight: f64,
+    /// Area the requirement belongs to, e.g. `cli` or `render`.
+    #[serde(default)]
+    pub category: Option<String>,
+    /// Release the requirement is planned for.
+    #[serde(default)]
+    pub milestone: Option<String>,
 }
 
 impl RequirementSpec {
@@ -27,6 +33,8 @@ impl RequirementSpec {
             .with_context(|| format!("failed to read spec file {}", path.display()))?;
         let spec = serde_yaml::from_str::<Self>(&raw)
             .with_context(|| format!("failed to parse spec file {}", path.display()))?;
+        spec.validate()
+            .with_context(|| format!("invalid spec file {}", path.display()))?;
         debug!(
             requirements = spec.requirements.len(),
             feature_map = spec.feature_map.len(),
@@ -35,6 +43,38 @@ impl RequirementSpec {
         Ok(spec)
     }
 
+    /// Rejects weights that are not positive and finite: a zero weight hides a requirement
+    /// from the weighted parity and a negative one rewards failing it.
+    pub fn validate(&self) -> Result<()> {
+        for requirement in &self.requirements {
+            if !(requirement.weight.is_finite() && requirement.weight > 0.0) {
+                bail!(
+                    "requirement {} has weight {}; weights must be positive",
+                    requirement.id,
+                    requirement.weight
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn requirement(&self, id: &str) -> Option<&Requirement> {
+        self.requirements.iter().find(|req| req.id == id)
+    }
+
+    /// The `features` that map to no requirement in the spec.
+    pub fn unmapped_features<'a>(&self, features: &'a [String]) -> Vec<&'a str> {
+        features
+            .iter()
+            .filter(|feature| {
+                self.mapped_requirements(std::slice::from_ref(feature))
+                    .into_iter()
+                    .all(|id| self.requirement(id).is_none())
+            })
+            .map(String::as_str)
+            .collect()
+    }
+
     pub fn requirement_weights(&self) -> BTreeMap<String, f64> {
         self.requirements
             .iter()
@@ -68,6 +108,8 @@ mod tests {
                 id: "cli.flag.a".to_string(),
                 description: "-a flag support".to_string(),
                 weight: 1.0,
+                category: Some("cli".to_string()),
+                milestone: None,
             }],
             feature_map: BTreeMap::from([(
                 "include_offensive".to_string(),
@@ -79,4 +121,26 @@ mod tests {
         let mapped = spec.mapped_requirements(&features);
         assert!(mapped.contains("cli.flag.a"));
     }
+
+    #[test]
+    fn rejects_bad_weights_and_lists_unmapped_features() {
+        let mut spec = RequirementSpec {
+            requirements: vec![Requirement {
+                id: "cli.flag.a".to_string(),
+                description: "-a flag support".to_string(),
+                weight: 1.0,
+                category: None,
+                milestone: None,
+            }],
+            feature_map: BTreeMap::from([("typo".to_string(), vec!["cli.flag.b".to_string()])]),
+        };
+        let features = ["cli.flag.a", "typo", "unknown"].map(str::to_string);
+        assert_eq!(spec.unmapped_features(&features), ["typo", "unknown"]);
+        spec.validate().expect("valid");
+
+        for weight in [0.0, -1.0, f64::NAN] {
+            spec.requirements[0].weight = weight;
+            assert!(spec.validate().is_err(), "{weight}");
+        }
+    }
 }
@@ -0,0 +1,193 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result, bail};
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{debug, info, warn};
+
+/// Pinned upstream asset source (`spec/assets.json`).
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AssetManifest {
+    pub url: String,
+    /// Expected archive digest, as 64 lowercase hex digits. Only `--update` may run without
+    /// one, to record it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(default)]
+    pub strip_prefix: String,
+    pub members: Vec<String>,
+}
+
+impl AssetManifest {
+    /// Loads the manifest; a missing `sha256` is an error unless `allow_unpinned` is set.
+    pub fn load(path: &Path, allow_unpinned: bool) -> Result<Self> {
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("failed reading asset manifest {}", path.display()))?;
+        Self::parse(&raw, allow_unpinned)
+            .with_context(|| format!("failed parsing asset manifest {}", path.display()))
+    }
+
+    fn parse(raw: &str, allow_unpinned: bool) -> Result<Self> {
+        let manifest: Self = serde_json::from_str(raw)?;
+        match &manifest.sha256 {
+            None if !allow_unpinned => {
+                bail!("asset manifest has no pinned sha256; rerun with --update to record it")
+            }
+            Some(digest)
+                if !(digest.len() == 64
+                    && digest
+                        .bytes()
+                        .all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f'))) =>
+            {
+                bail!("sha256 must be 64 lowercase hex digits, got {digest:?}")
+            }
+            _ => Ok(manifest),
+        }
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        let mut json = serde_json::to_string_pretty(self).context("failed serializing manifest")?;
+        json.push('\n');
+        std::fs::write(path, json)
+            .with_context(|| format!("failed writing asset manifest {}", path.display()))
+    }
+}
+
+pub struct AssetsTask {
+    pub manifest: PathBuf,
+    pub dest: PathBuf,
+    pub download_dir: PathBuf,
+    /// Accept a new archive digest and record it in the manifest.
+    pub update: bool,
+}
+
+pub fn run(task: &AssetsTask) -> Result<()> {
+    let mut manifest = AssetManifest::load(&task.manifest, task.update)?;
+    std::fs::create_dir_all(&task.download_dir)
+        .with_context(|| format!("failed creating {}", task.download_dir.display()))?;
+
+    let archive = task.download_dir.join("upstream-assets.tar.gz");
+    download(&manifest.url, &archive)?;
+
+    let digest = sha256_file(&archive)?;
+    if manifest.sha256.as_deref() != Some(digest.as_str()) {
+        if !task.update {
+            bail!(
+                "checksum mismatch for {}: expected {}, got {digest}",
+                manifest.url,
+                manifest.sha256.as_deref().unwrap_or_default()
+            );
+        }
+        warn!(previous = ?manifest.sha256, digest = %digest, "recording new asset checksum");
+        manifest.sha256 = Some(digest);
+        manifest.save(&task.manifest)?;
+    }
+
+    let staging = task.download_dir.join("staging");
+    if staging.exists() {
+        std::fs::remove_dir_all(&staging)
+            .with_context(|| format!("failed clearing {}", staging.display()))?;
+    }
+    extract(&archive, &staging)?;
+
+    let source_root = staging.join(&manifest.strip_prefix);
+    std::fs::create_dir_all(&task.dest)
+        .with_context(|| format!("failed creating {}", task.dest.display()))?;
+    for member in &manifest.members {
+        let source = source_root.join(member);
+        let target = task.dest.join(member);
+        if !source.exists() {
+            warn!(member, "asset member missing from archive");
+            continue;
+        }
+        if target.exists() {
+            std::fs::remove_dir_all(&target)
+                .with_context(|| format!("failed clearing {}", target.display()))?;
+        }
+        std::fs::rename(&source, &target).with_context(|| {
+            format!("failed moving {} to {}", source.display(), target.display())
+        })?;
+        debug!(member, path = %target.display(), "installed asset member");
+    }
+
+    std::fs::remove_dir_all(&staging).ok();
+    info!(dest = %task.dest.display(), "asset set updated");
+    println!("Assets written to {}", task.dest.display());
+    Ok(())
+}
+
+fn download(url: &str, target: &Path) -> Result<()> {
+    info!(url, "downloading upstream assets");
+    let status = Command::new("curl")
+        .args([
+            "--fail",
+            "--silent",
+            "--show-error",
+            "--location",
+            "--output",
+        ])
+        .arg(target)
+        .arg(url)
+        .status()
+        .context("failed to spawn curl")?;
+    if !status.success() {
+        bail!("curl failed downloading {url} ({status})");
+    }
+    Ok(())
+}
+
+fn sha256_file(path: &Path) -> Result<String> {
+    let mut file =
+        File::open(path).with_context(|| format!("failed opening {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)
+        .with_context(|| format!("failed hashing {}", path.display()))?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+fn extract(archive: &Path, dest: &Path) -> Result<()> {
+    let file =
+        File::open(archive).with_context(|| format!("failed opening {}", archive.display()))?;
+    tar::Archive::new(GzDecoder::new(file))
+        .unpack(dest)
+        .with_context(|| format!("failed extracting {}", archive.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifests_must_pin_a_real_digest() {
+        let checked_in = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../spec/assets.json");
+        let manifest = AssetManifest::load(&checked_in, true).expect("spec/assets.json");
+        assert!(!manifest.members.is_empty());
+
+        let with = |sha256: &str, allow_unpinned| {
+            AssetManifest::parse(
+                &format!(
+                    r#"{{"url": "https://example.test/a.tar.gz", "sha256": {sha256}, "members": []}}"#
+                ),
+                allow_unpinned,
+            )
+        };
+        let digest = "ab".repeat(32);
+        assert_eq!(
+            with(&format!("\"{digest}\""), false)
+                .expect("pinned")
+                .sha256,
+            Some(digest)
+        );
+        assert!(with("null", false).is_err());
+        assert_eq!(with("null", true).expect("updating").sha256, None);
+        assert!(with(r#""""#, true).is_err());
+        assert!(with(&format!("\"{}\"", "AB".repeat(32)), true).is_err());
+    }
+}
@@ -0,0 +1,44 @@
+//! `xtask convert-balloon`: writes upstream balloon files from TOML descriptions.
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+use iron_pony_core::BalloonStyle;
+use tracing::info;
+
+pub struct ConvertTask {
+    /// A `.toml` description, or an existing balloon file to normalize.
+    pub input: PathBuf,
+    /// Destination file; stdout when absent.
+    pub output: Option<PathBuf>,
+}
+
+pub fn run(task: &ConvertTask) -> Result<()> {
+    let raw = std::fs::read_to_string(&task.input)
+        .with_context(|| format!("failed reading {}", task.input.display()))?;
+    let style = if task.input.extension().is_some_and(|ext| ext == "toml") {
+        BalloonStyle::from_toml(&raw)
+            .with_context(|| format!("invalid balloon description {}", task.input.display()))?
+    } else {
+        BalloonStyle::parse(&raw)
+    };
+
+    let written = style.to_balloon_file();
+    if BalloonStyle::parse(&written) != style {
+        bail!(
+            "{} does not survive a round trip through the balloon format",
+            task.input.display()
+        );
+    }
+
+    match &task.output {
+        Some(path) => {
+            std::fs::write(path, &written)
+                .with_context(|| format!("failed writing {}", path.display()))?;
+            info!(input = %task.input.display(), output = %path.display(), "converted balloon");
+            println!("Wrote {}", path.display());
+        }
+        None => print!("{written}"),
+    }
+    Ok(())
+}
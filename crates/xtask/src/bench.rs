@@ -0,0 +1,188 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use tracing::{debug, info, warn};
+use walkdir::WalkDir;
+
+pub struct BenchTask {
+    /// Criterion output directory (`target/criterion`).
+    pub criterion_dir: PathBuf,
+    /// Name the fresh results are saved under.
+    pub save_as: String,
+    /// Baseline to compare against; `None` only records results.
+    pub baseline: Option<String>,
+    /// Largest tolerated slowdown of any benchmark's mean, in percent.
+    pub max_regression: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Estimates {
+    mean: Estimate,
+}
+
+#[derive(Debug, Deserialize)]
+struct Estimate {
+    point_estimate: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub bench: String,
+    pub baseline_ns: f64,
+    pub current_ns: f64,
+}
+
+impl Comparison {
+    /// Relative slowdown in percent (negative when faster).
+    pub fn change_percent(&self) -> f64 {
+        (self.current_ns / self.baseline_ns - 1.0) * 100.0
+    }
+}
+
+pub fn run(task: &BenchTask) -> Result<()> {
+    info!(save_as = %task.save_as, "running criterion benchmarks");
+    let status = Command::new("cargo")
+        .args([
+            "bench",
+            "-p",
+            "iron-pony-core",
+            "--bench",
+            "render",
+            "--",
+            "--save-baseline",
+            &task.save_as,
+        ])
+        .status()
+        .context("failed to run cargo bench")?;
+    if !status.success() {
+        bail!("cargo bench failed with {status}");
+    }
+
+    let Some(baseline) = &task.baseline else {
+        println!(
+            "saved benchmark results as baseline '{}' in {}",
+            task.save_as,
+            task.criterion_dir.display()
+        );
+        return Ok(());
+    };
+
+    let comparisons = compare(&task.criterion_dir, baseline, &task.save_as)?;
+    if comparisons.is_empty() {
+        bail!(
+            "no benchmarks found for baseline '{baseline}'; record one with --save-as {baseline}"
+        );
+    }
+
+    let mut regressions = 0;
+    for comparison in &comparisons {
+        let change = comparison.change_percent();
+        let verdict = if change > task.max_regression {
+            regressions += 1;
+            "REGRESSED"
+        } else {
+            "ok"
+        };
+        println!(
+            "{:<24} {:>12.1} ns -> {:>12.1} ns  {:>+7.2}%  {verdict}",
+            comparison.bench, comparison.baseline_ns, comparison.current_ns, change
+        );
+    }
+
+    if regressions > 0 {
+        bail!(
+            "{regressions} benchmark(s) regressed by more than {:.1}% against '{baseline}'",
+            task.max_regression
+        );
+    }
+    Ok(())
+}
+
+/// Pairs up every benchmark that has results under both `baseline` and `current`.
+pub fn compare(criterion_dir: &Path, baseline: &str, current: &str) -> Result<Vec<Comparison>> {
+    let baseline_means = read_means(criterion_dir, baseline)?;
+    let current_means = read_means(criterion_dir, current)?;
+
+    let mut out = Vec::new();
+    for (bench, baseline_ns) in baseline_means {
+        match current_means.get(&bench) {
+            Some(&current_ns) => out.push(Comparison {
+                bench,
+                baseline_ns,
+                current_ns,
+            }),
+            None => warn!(%bench, "benchmark missing from current run"),
+        }
+    }
+    Ok(out)
+}
+
+/// Mean estimates for every `<criterion_dir>/<bench>/<name>/estimates.json`.
+fn read_means(criterion_dir: &Path, name: &str) -> Result<BTreeMap<String, f64>> {
+    let mut means = BTreeMap::new();
+    if !criterion_dir.is_dir() {
+        return Ok(means);
+    }
+
+    for entry in WalkDir::new(criterion_dir)
+        .min_depth(3)
+        .into_iter()
+        .filter_map(Result::ok)
+    {
+        let path = entry.path();
+        let in_baseline = path.parent().and_then(Path::file_name) == Some(name.as_ref());
+        if !in_baseline || path.file_name() != Some("estimates.json".as_ref()) {
+            continue;
+        }
+
+        let Some(bench_dir) = path.parent().and_then(Path::parent) else {
+            continue;
+        };
+        let bench = bench_dir
+            .strip_prefix(criterion_dir)
+            .unwrap_or(bench_dir)
+            .to_string_lossy()
+            .to_string();
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("failed reading {}", path.display()))?;
+        let estimates = serde_json::from_str::<Estimates>(&raw)
+            .with_context(|| format!("failed parsing {}", path.display()))?;
+        debug!(%bench, name, mean = estimates.mean.point_estimate, "read benchmark estimate");
+        means.insert(bench, estimates.mean.point_estimate);
+    }
+    Ok(means)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_estimate(root: &Path, bench: &str, name: &str, mean: f64) {
+        let dir = root.join(bench).join(name);
+        std::fs::create_dir_all(&dir).expect("mkdir");
+        std::fs::write(
+            dir.join("estimates.json"),
+            format!(r#"{{"mean":{{"point_estimate":{mean}}},"median":{{"point_estimate":0}}}}"#),
+        )
+        .expect("write");
+    }
+
+    #[test]
+    fn compares_named_baselines() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        write_estimate(tmp.path(), "render", "main", 100.0);
+        write_estimate(tmp.path(), "render", "current", 125.0);
+        write_estimate(tmp.path(), "wrap_message", "main", 50.0);
+        write_estimate(tmp.path(), "wrap_message", "current", 40.0);
+        write_estimate(tmp.path(), "only_new", "current", 1.0);
+
+        let comparisons = compare(tmp.path(), "main", "current").expect("compare");
+        assert_eq!(comparisons.len(), 2);
+        assert_eq!(comparisons[0].bench, "render");
+        assert!((comparisons[0].change_percent() - 25.0).abs() < 1e-9);
+        assert!((comparisons[1].change_percent() + 20.0).abs() < 1e-9);
+    }
+}
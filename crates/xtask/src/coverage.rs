@@ -0,0 +1,274 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result, bail};
+use iron_pony_parity::{ParityConfig, run_parity};
+use tracing::{info, warn};
+
+/// Sources left out of the report: dependencies and the standard library.
+const IGNORE_FILENAMES: &str = r"/\.cargo/registry/|/rustc/|/\.rustup/";
+
+/// Only files below this prefix are listed in the printed summary.
+const CORE_SOURCES: &str = "crates/iron-pony-core/src/";
+
+pub struct CoverageTask {
+    pub config: ParityConfig,
+    /// Holds the instrumented build, raw profiles and the reports.
+    pub out: PathBuf,
+    pub html: bool,
+}
+
+/// Line coverage of one source file, from an lcov report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileCoverage {
+    pub path: String,
+    pub lines_hit: usize,
+    pub lines_found: usize,
+}
+
+impl FileCoverage {
+    pub fn percent(&self) -> f64 {
+        if self.lines_found == 0 {
+            0.0
+        } else {
+            self.lines_hit as f64 * 100.0 / self.lines_found as f64
+        }
+    }
+}
+
+pub fn run(task: CoverageTask) -> Result<()> {
+    let profdata_tool = llvm_tool("llvm-profdata")?;
+    let cov_tool = llvm_tool("llvm-cov")?;
+    let workspace_root = task.config.workspace_root.clone();
+    let out = workspace_root.join(&task.out);
+    let profiles = out.join("profraw");
+    if profiles.exists() {
+        std::fs::remove_dir_all(&profiles)
+            .with_context(|| format!("failed clearing {}", profiles.display()))?;
+    }
+    std::fs::create_dir_all(&profiles)
+        .with_context(|| format!("failed creating {}", profiles.display()))?;
+
+    let binary = build_instrumented(&out.join("build"))?;
+    let mut config = task.config;
+    config.candidate_program = Some(binary.clone());
+    config.candidate_env.insert(
+        "LLVM_PROFILE_FILE".to_string(),
+        profiles
+            .join("iron-pony-%p-%m.profraw")
+            .to_string_lossy()
+            .to_string(),
+    );
+    let report = run_parity(&config)?;
+    info!(
+        case_parity = report.summary.case_parity,
+        "parity run under coverage completed"
+    );
+
+    let mut raw = std::fs::read_dir(&profiles)
+        .with_context(|| format!("failed reading {}", profiles.display()))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "profraw"))
+        .collect::<Vec<_>>();
+    raw.sort();
+    if raw.is_empty() {
+        bail!("no coverage profiles were written; did any case run the candidate?");
+    }
+    let profdata = out.join("iron-pony.profdata");
+    let mut merge = Command::new(&profdata_tool);
+    merge
+        .args(["merge", "-sparse", "-o"])
+        .arg(&profdata)
+        .args(&raw);
+    run_tool(&mut merge)?;
+
+    let mut export = Command::new(&cov_tool);
+    export
+        .args(["export", "-format=lcov", "-instr-profile"])
+        .arg(&profdata)
+        .arg(format!("-ignore-filename-regex={IGNORE_FILENAMES}"))
+        .arg(&binary);
+    let lcov = run_tool(&mut export)?;
+    let lcov_path = out.join("lcov.info");
+    std::fs::write(&lcov_path, &lcov)
+        .with_context(|| format!("failed writing {}", lcov_path.display()))?;
+
+    if task.html {
+        let mut show = Command::new(&cov_tool);
+        show.args(["show", "-format=html", "-instr-profile"])
+            .arg(&profdata)
+            .arg(format!("-output-dir={}", out.join("html").display()))
+            .arg(format!("-ignore-filename-regex={IGNORE_FILENAMES}"))
+            .arg(&binary);
+        run_tool(&mut show)?;
+        println!(
+            "HTML report written to {}",
+            out.join("html/index.html").display()
+        );
+    }
+
+    let lcov = String::from_utf8_lossy(&lcov);
+    let files = parse_lcov(&lcov, &workspace_root);
+    for file in files
+        .iter()
+        .filter(|file| file.path.starts_with(CORE_SOURCES))
+    {
+        println!(
+            "{:>6.1}%  {:>5}/{:<5} {}",
+            file.percent(),
+            file.lines_hit,
+            file.lines_found,
+            file.path
+        );
+    }
+    let (hit, found) = files.iter().fold((0, 0), |(hit, found), file| {
+        (hit + file.lines_hit, found + file.lines_found)
+    });
+    let total = FileCoverage {
+        path: String::new(),
+        lines_hit: hit,
+        lines_found: found,
+    };
+    println!(
+        "line coverage from {} parity case(s): {:.1}% ({hit}/{found}); lcov written to {}",
+        report.summary.total_cases,
+        total.percent(),
+        lcov_path.display()
+    );
+    Ok(())
+}
+
+/// Builds `iron-pony` with `-C instrument-coverage` into `target_dir`, kept apart from the
+/// regular build so neither invalidates the other.
+fn build_instrumented(target_dir: &Path) -> Result<PathBuf> {
+    let mut rustflags = std::env::var("RUSTFLAGS").unwrap_or_default();
+    rustflags.push_str(" -C instrument-coverage");
+    info!(target_dir = %target_dir.display(), "building instrumented iron-pony");
+    let status = Command::new("cargo")
+        .args(["build", "-p", "iron-pony-cli", "--bin", "iron-pony"])
+        .arg("--target-dir")
+        .arg(target_dir)
+        .env("RUSTFLAGS", rustflags.trim())
+        .status()
+        .context("failed to run cargo build")?;
+    if !status.success() {
+        bail!("instrumented cargo build failed with {status}");
+    }
+    let binary = target_dir
+        .join("debug")
+        .join(format!("iron-pony{}", std::env::consts::EXE_SUFFIX));
+    Ok(binary)
+}
+
+/// `name` from the `llvm-tools` rustup component, whose LLVM matches rustc's, or else
+/// from `PATH`.
+fn llvm_tool(name: &str) -> Result<PathBuf> {
+    let exe = format!("{name}{}", std::env::consts::EXE_SUFFIX);
+    let sysroot = Command::new("rustc")
+        .args(["--print", "sysroot"])
+        .output()
+        .ok()
+        .map(|output| PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()));
+    if let Some(sysroot) = sysroot
+        && let Ok(targets) = std::fs::read_dir(sysroot.join("lib/rustlib"))
+    {
+        for target in targets.flatten() {
+            let tool = target.path().join("bin").join(&exe);
+            if tool.is_file() {
+                return Ok(tool);
+            }
+        }
+    }
+    let on_path = Command::new(&exe).arg("--version").output();
+    if on_path.is_ok_and(|output| output.status.success()) {
+        warn!(
+            tool = name,
+            "using llvm tool from PATH; its LLVM may not match rustc's"
+        );
+        return Ok(PathBuf::from(exe));
+    }
+    bail!("{name} not found; install it with `rustup component add llvm-tools`")
+}
+
+fn run_tool(command: &mut Command) -> Result<Vec<u8>> {
+    let output = command
+        .output()
+        .with_context(|| format!("failed to run {:?}", command.get_program()))?;
+    if !output.status.success() {
+        bail!(
+            "{:?} failed with {}: {}",
+            command.get_program(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output.stdout)
+}
+
+/// Per-file line totals from an lcov report, with paths made relative to `root`.
+pub fn parse_lcov(lcov: &str, root: &Path) -> Vec<FileCoverage> {
+    let mut files = Vec::new();
+    let mut current: Option<FileCoverage> = None;
+    for line in lcov.lines() {
+        if let Some(path) = line.strip_prefix("SF:") {
+            let path = Path::new(path);
+            let path = path.strip_prefix(root).unwrap_or(path);
+            current = Some(FileCoverage {
+                path: path.to_string_lossy().replace('\\', "/"),
+                lines_hit: 0,
+                lines_found: 0,
+            });
+        } else if let Some(file) = current.as_mut() {
+            if let Some(count) = line.strip_prefix("LF:") {
+                file.lines_found = count.parse().unwrap_or(0);
+            } else if let Some(count) = line.strip_prefix("LH:") {
+                file.lines_hit = count.parse().unwrap_or(0);
+            } else if line == "end_of_record" {
+                files.extend(current.take());
+            }
+        }
+    }
+    files.sort_by(|left, right| left.path.cmp(&right.path));
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lcov_records_become_relative_file_totals() {
+        let lcov = "\
+SF:/work/crates/iron-pony-core/src/text.rs
+DA:1,3
+LF:40
+LH:30
+end_of_record
+SF:/work/crates/iron-pony-cli/src/main.rs
+LF:0
+LH:0
+end_of_record
+SF:/elsewhere/lib.rs
+LF:10
+LH:1
+";
+        let files = parse_lcov(lcov, Path::new("/work"));
+        assert_eq!(
+            files,
+            [
+                FileCoverage {
+                    path: "crates/iron-pony-cli/src/main.rs".to_string(),
+                    lines_hit: 0,
+                    lines_found: 0,
+                },
+                FileCoverage {
+                    path: "crates/iron-pony-core/src/text.rs".to_string(),
+                    lines_hit: 30,
+                    lines_found: 40,
+                },
+            ]
+        );
+        assert_eq!(files[1].percent(), 75.0);
+        assert_eq!(files[0].percent(), 0.0);
+    }
+}
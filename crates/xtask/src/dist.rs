@@ -0,0 +1,428 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result, bail};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use sha2::{Digest, Sha256};
+use tracing::{debug, info};
+use walkdir::WalkDir;
+
+/// Entry timestamp when `SOURCE_DATE_EPOCH` is unset: 1980-01-01, the earliest a zip can hold.
+const DEFAULT_EPOCH: u64 = 315_532_800;
+
+/// Files from the upstream asset set that carry its license terms.
+const ASSET_LICENSE_FILES: [&str; 4] = ["COPYING", "LICENSE", "CREDITS", "AUTHORS"];
+
+pub struct DistTask {
+    /// Target triples to package; the host's when empty.
+    pub targets: Vec<String>,
+    /// Asset set to pack, as installed by `xtask assets`.
+    pub assets: PathBuf,
+    pub out: PathBuf,
+}
+
+/// One file inside a release archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DistFile {
+    /// Path inside the archive, below its top-level directory.
+    pub path: String,
+    pub data: Vec<u8>,
+    pub executable: bool,
+}
+
+impl DistFile {
+    fn new(path: impl Into<String>, data: impl Into<Vec<u8>>) -> Self {
+        Self {
+            path: path.into(),
+            data: data.into(),
+            executable: false,
+        }
+    }
+}
+
+pub fn run(task: &DistTask) -> Result<()> {
+    if !task.assets.is_dir() {
+        bail!(
+            "no asset set at {}; run `cargo run -p xtask -- assets` first",
+            task.assets.display()
+        );
+    }
+    let version = cli_version()?;
+    let epoch = source_date_epoch();
+    let host = host_target()?;
+    let targets = if task.targets.is_empty() {
+        vec![host.clone()]
+    } else {
+        task.targets.clone()
+    };
+
+    // Man pages and completions do not depend on the target, so the host build writes them.
+    cargo_build(None)?;
+    let target_dir = target_directory()?;
+    let host_binary = target_dir.join("release").join(binary_name(&host));
+    let mut shared = man_pages(&host_binary, &target_dir.join("dist-man"))?;
+    shared.extend([
+        DistFile::new(
+            "completions/iron-pony.bash",
+            generate(&host_binary, &["--print-completions", "bash"])?,
+        ),
+        DistFile::new(
+            "completions/_iron-pony",
+            generate(&host_binary, &["--print-completions", "zsh"])?,
+        ),
+        DistFile::new(
+            "completions/iron-pony.fish",
+            generate(&host_binary, &["--print-completions", "fish"])?,
+        ),
+        DistFile::new(
+            "share/iron-pony/assets.tar",
+            asset_pack(&task.assets, epoch)?,
+        ),
+        DistFile::new("LICENSE", read("LICENSE")?),
+        DistFile::new("README.md", read("README.md")?),
+    ]);
+    for name in ASSET_LICENSE_FILES {
+        let path = task.assets.join(name);
+        if path.is_file() {
+            shared.push(DistFile::new(
+                format!("licenses/assets/{name}"),
+                read(&path)?,
+            ));
+        }
+    }
+
+    std::fs::create_dir_all(&task.out)
+        .with_context(|| format!("failed creating {}", task.out.display()))?;
+    let mut sums = String::new();
+    for target in &targets {
+        cargo_build(Some(target))?;
+        let binary_path = target_dir
+            .join(target)
+            .join("release")
+            .join(binary_name(target));
+        let mut files = shared.clone();
+        files.push(DistFile {
+            path: binary_name(target),
+            data: read(&binary_path)?,
+            executable: true,
+        });
+
+        let stem = format!("iron-pony-{version}-{target}");
+        let (name, bytes) = if target.contains("windows") {
+            (format!("{stem}.zip"), write_zip(&stem, &files, epoch)?)
+        } else {
+            (
+                format!("{stem}.tar.gz"),
+                write_tar_gz(&stem, &files, epoch)?,
+            )
+        };
+        let path = task.out.join(&name);
+        std::fs::write(&path, &bytes)
+            .with_context(|| format!("failed writing {}", path.display()))?;
+        sums.push_str(&format!("{:x}  {name}\n", Sha256::digest(&bytes)));
+        info!(archive = %path.display(), "packaged release");
+        println!("{}", path.display());
+    }
+    let sums_path = task.out.join("SHA256SUMS");
+    std::fs::write(&sums_path, sums)
+        .with_context(|| format!("failed writing {}", sums_path.display()))?;
+    Ok(())
+}
+
+fn read(path: impl AsRef<Path>) -> Result<Vec<u8>> {
+    let path = path.as_ref();
+    std::fs::read(path).with_context(|| format!("failed reading {}", path.display()))
+}
+
+fn binary_name(target: &str) -> String {
+    if target.contains("windows") {
+        "iron-pony.exe".to_string()
+    } else {
+        "iron-pony".to_string()
+    }
+}
+
+fn cli_version() -> Result<String> {
+    let manifest = std::fs::read_to_string("crates/iron-pony-cli/Cargo.toml")
+        .context("failed reading the iron-pony-cli manifest")?;
+    let manifest = manifest
+        .parse::<toml::Table>()
+        .context("failed parsing the iron-pony-cli manifest")?;
+    manifest
+        .get("package")
+        .and_then(|package| package.get("version"))
+        .and_then(toml::Value::as_str)
+        .map(str::to_string)
+        .context("iron-pony-cli manifest has no package.version")
+}
+
+/// Where cargo puts build output: `target/` unless `CARGO_TARGET_DIR` or a config file
+/// moves it.
+fn target_directory() -> Result<PathBuf> {
+    let output = Command::new("cargo")
+        .args(["metadata", "--format-version", "1", "--no-deps"])
+        .output()
+        .context("failed to run cargo metadata")?;
+    if !output.status.success() {
+        bail!("cargo metadata failed with {}", output.status);
+    }
+    let metadata: serde_json::Value =
+        serde_json::from_slice(&output.stdout).context("failed parsing cargo metadata")?;
+    metadata["target_directory"]
+        .as_str()
+        .map(PathBuf::from)
+        .context("cargo metadata did not report a target_directory")
+}
+
+fn host_target() -> Result<String> {
+    let output = Command::new("rustc")
+        .arg("-vV")
+        .output()
+        .context("failed to run rustc -vV")?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.strip_prefix("host: "))
+        .map(str::to_string)
+        .context("rustc -vV did not report a host target")
+}
+
+/// `SOURCE_DATE_EPOCH` when set, as reproducible-builds tooling expects.
+fn source_date_epoch() -> u64 {
+    std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_EPOCH)
+}
+
+fn cargo_build(target: Option<&str>) -> Result<()> {
+    let mut command = Command::new("cargo");
+    command.args(["build", "--release", "-p", "iron-pony-cli"]);
+    if let Some(target) = target {
+        command.args(["--target", target]);
+    }
+    info!(target = target.unwrap_or("host"), "building iron-pony");
+    let status = command.status().context("failed to run cargo build")?;
+    if !status.success() {
+        bail!("cargo build failed with {status}");
+    }
+    Ok(())
+}
+
+/// The man page and a page per subcommand, as `binary --write-man-pages` writes them into
+/// `staging`.
+fn man_pages(binary: &Path, staging: &Path) -> Result<Vec<DistFile>> {
+    if staging.exists() {
+        std::fs::remove_dir_all(staging)
+            .with_context(|| format!("failed clearing {}", staging.display()))?;
+    }
+    std::fs::create_dir_all(staging)
+        .with_context(|| format!("failed creating {}", staging.display()))?;
+    let dir = staging.to_string_lossy();
+    generate(binary, &["--write-man-pages", &dir])?;
+    let mut pages = Vec::new();
+    for entry in WalkDir::new(staging).min_depth(1).sort_by_file_name() {
+        let entry = entry.with_context(|| format!("failed walking {}", staging.display()))?;
+        let name = entry.file_name().to_string_lossy();
+        debug!(page = %name, "packaging man page");
+        pages.push(DistFile::new(format!("man/{name}"), read(entry.path())?));
+    }
+    Ok(pages)
+}
+
+fn generate(binary: &Path, args: &[&str]) -> Result<Vec<u8>> {
+    let output = Command::new(binary)
+        .args(args)
+        .output()
+        .with_context(|| format!("failed to run {}", binary.display()))?;
+    if !output.status.success() {
+        bail!(
+            "{} {} failed with {}",
+            binary.display(),
+            args.join(" "),
+            output.status
+        );
+    }
+    Ok(output.stdout)
+}
+
+/// Every file below `dir` as an uncompressed tar, the format `--ponypack` reads.
+fn asset_pack(dir: &Path, epoch: u64) -> Result<Vec<u8>> {
+    let mut files = Vec::new();
+    for entry in WalkDir::new(dir).sort_by_file_name() {
+        let entry = entry.with_context(|| format!("failed walking {}", dir.display()))?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let path = entry
+            .path()
+            .strip_prefix(dir)
+            .context("asset outside the asset dir")?;
+        let path = path
+            .components()
+            .map(|part| part.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        files.push(DistFile::new(path, read(entry.path())?));
+    }
+    debug!(files = files.len(), "packed assets");
+    write_tar(Vec::new(), None, &files, epoch)
+}
+
+/// Files in path order with fixed timestamps, owners and modes, so the same inputs
+/// always give the same bytes.
+fn sorted(files: &[DistFile]) -> Vec<&DistFile> {
+    let mut files = files.iter().collect::<Vec<_>>();
+    files.sort_by(|left, right| left.path.cmp(&right.path));
+    files
+}
+
+fn write_tar<W: Write>(out: W, prefix: Option<&str>, files: &[DistFile], epoch: u64) -> Result<W> {
+    let mut builder = tar::Builder::new(out);
+    for file in sorted(files) {
+        let path = match prefix {
+            Some(prefix) => format!("{prefix}/{}", file.path),
+            None => file.path.clone(),
+        };
+        let mut header = tar::Header::new_gnu();
+        header.set_size(file.data.len() as u64);
+        header.set_mode(if file.executable { 0o755 } else { 0o644 });
+        header.set_mtime(epoch);
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_entry_type(tar::EntryType::Regular);
+        builder
+            .append_data(&mut header, &path, file.data.as_slice())
+            .with_context(|| format!("failed archiving {path}"))?;
+    }
+    builder.into_inner().context("failed finishing tar")
+}
+
+/// A gzipped tar with every file below `prefix/`.
+pub fn write_tar_gz(prefix: &str, files: &[DistFile], epoch: u64) -> Result<Vec<u8>> {
+    let encoder = GzEncoder::new(Vec::new(), Compression::best());
+    write_tar(encoder, Some(prefix), files, epoch)?
+        .finish()
+        .context("failed finishing gzip stream")
+}
+
+/// A zip with every file below `prefix/`, for Windows targets.
+pub fn write_zip(prefix: &str, files: &[DistFile], epoch: u64) -> Result<Vec<u8>> {
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for file in sorted(files) {
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated)
+            .last_modified_time(zip_time(epoch))
+            .unix_permissions(if file.executable { 0o755 } else { 0o644 });
+        let path = format!("{prefix}/{}", file.path);
+        zip.start_file(path.as_str(), options)
+            .with_context(|| format!("failed archiving {path}"))?;
+        zip.write_all(&file.data)
+            .with_context(|| format!("failed archiving {path}"))?;
+    }
+    Ok(zip.finish().context("failed finishing zip")?.into_inner())
+}
+
+/// `epoch` as a zip timestamp (UTC), clamped to the years a zip can hold.
+fn zip_time(epoch: u64) -> zip::DateTime {
+    let days = (epoch / 86_400) as i64;
+    let seconds = epoch % 86_400;
+    // Howard Hinnant's days-to-civil conversion.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u8;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    zip::DateTime::from_date_and_time(
+        year.clamp(1980, 2107) as u16,
+        month,
+        day,
+        (seconds / 3_600) as u8,
+        (seconds / 60 % 60) as u8,
+        (seconds % 60) as u8,
+    )
+    .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    #[test]
+    fn archives_are_sorted_and_reproducible() {
+        let files = vec![
+            DistFile::new("man/iron-pony.1", ".TH IRON\\-PONY 1\n"),
+            DistFile {
+                path: "iron-pony".to_string(),
+                data: b"\x7fELF".to_vec(),
+                executable: true,
+            },
+            DistFile::new("LICENSE", "MIT\n"),
+        ];
+        let mut reversed = files.clone();
+        reversed.reverse();
+
+        let tarball = write_tar_gz("iron-pony-1.0.0-x", &files, DEFAULT_EPOCH).expect("tar");
+        assert_eq!(
+            tarball,
+            write_tar_gz("iron-pony-1.0.0-x", &reversed, DEFAULT_EPOCH).expect("tar")
+        );
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(tarball.as_slice()));
+        let entries = archive
+            .entries()
+            .expect("entries")
+            .map(|entry| {
+                let entry = entry.expect("entry");
+                let header = entry.header();
+                (
+                    entry.path().expect("path").display().to_string(),
+                    header.mode().expect("mode"),
+                    header.mtime().expect("mtime"),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            [
+                (
+                    "iron-pony-1.0.0-x/LICENSE".to_string(),
+                    0o644,
+                    DEFAULT_EPOCH
+                ),
+                (
+                    "iron-pony-1.0.0-x/iron-pony".to_string(),
+                    0o755,
+                    DEFAULT_EPOCH
+                ),
+                (
+                    "iron-pony-1.0.0-x/man/iron-pony.1".to_string(),
+                    0o644,
+                    DEFAULT_EPOCH
+                ),
+            ]
+        );
+
+        let zipped = write_zip("iron-pony-1.0.0-x", &files, DEFAULT_EPOCH).expect("zip");
+        assert_eq!(
+            zipped,
+            write_zip("iron-pony-1.0.0-x", &reversed, DEFAULT_EPOCH).expect("zip")
+        );
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(zipped)).expect("zip");
+        assert_eq!(archive.file_names().collect::<Vec<_>>().len(), files.len());
+        let mut license = String::new();
+        archive
+            .by_name("iron-pony-1.0.0-x/LICENSE")
+            .expect("license")
+            .read_to_string(&mut license)
+            .expect("read");
+        assert_eq!(license, "MIT\n");
+        assert_eq!(zip_time(1_700_000_000).to_string(), "2023-11-14 22:13:20");
+    }
+}
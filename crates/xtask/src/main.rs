@@ -1,8 +1,24 @@
+mod assets;
+mod balloon;
+mod bench;
+mod coverage;
+mod dist;
+#[cfg(feature = "tui")]
+mod tui;
+mod watch;
+
+use std::collections::BTreeMap;
 use std::path::PathBuf;
+use std::time::Duration;
 
-use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
-use iron_pony_parity::{ParityConfig, run_parity};
+use anyhow::{Context, Result, bail};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use iron_pony_core::{LintReport, lint_pony_dir, quantize_to_console};
+use iron_pony_parity::{
+    CancelToken, CaseRunOptions, DEFAULT_OUTPUT_LIMIT, ParityConfig, ParityThresholds,
+    archive_failures, load_cases, run_case_with, run_parity,
+};
+use serde_json::json;
 use tracing::info;
 use tracing_subscriber::EnvFilter;
 
@@ -13,53 +29,305 @@ struct Cli {
     command: Command,
 }
 
+/// Options shared by the parity subcommands.
+#[derive(Debug, Args)]
+struct ParityArgs {
+    #[arg(long, default_value = "tests/parity_cases")]
+    cases: PathBuf,
+    #[arg(long, default_value = "spec/requirements.yaml")]
+    spec: PathBuf,
+    #[arg(long, default_value = "target/parity")]
+    out: PathBuf,
+    #[arg(long, default_value = "ponysay")]
+    reference: String,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Run this upstream ponysay checkout with python3 as the reference instead [env: PONYSAY_REF_CHECKOUT]"
+    )]
+    reference_checkout: Option<PathBuf>,
+    #[arg(long)]
+    candidate: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "BYTES",
+        default_value_t = DEFAULT_OUTPUT_LIMIT,
+        help = "Per-stream output kept before a case process is killed"
+    )]
+    output_limit: usize,
+    #[arg(
+        long,
+        help = "Reject case files with unknown fields or features the spec does not map"
+    )]
+    strict: bool,
+    #[arg(
+        long,
+        value_name = "FRACTION",
+        help = "Fail the run when case parity is below this (0 to 1)"
+    )]
+    min_case_parity: Option<f64>,
+    #[arg(
+        long,
+        value_name = "FRACTION",
+        help = "Fail the run when weighted requirement parity is below this (0 to 1)"
+    )]
+    min_requirement_parity: Option<f64>,
+}
+
+impl ParityArgs {
+    fn into_config(self) -> Result<ParityConfig> {
+        let workspace_root = std::env::current_dir().context("failed to resolve current dir")?;
+        for minimum in [self.min_case_parity, self.min_requirement_parity]
+            .into_iter()
+            .flatten()
+        {
+            if !(0.0..=1.0).contains(&minimum) {
+                bail!("parity thresholds are fractions from 0 to 1, got {minimum}");
+            }
+        }
+        Ok(ParityConfig {
+            cases_dir: workspace_root.join(self.cases),
+            spec_path: workspace_root.join(self.spec),
+            output_dir: workspace_root.join(self.out),
+            reference_program: self.reference,
+            reference_checkout: self
+                .reference_checkout
+                .or_else(|| std::env::var_os("PONYSAY_REF_CHECKOUT").map(PathBuf::from)),
+            candidate_program: self.candidate,
+            candidate_env: BTreeMap::new(),
+            output_limit: self.output_limit,
+            strict: self.strict,
+            thresholds: ParityThresholds {
+                case_parity: self.min_case_parity,
+                weighted_requirement_parity: self.min_requirement_parity,
+            },
+            workspace_root,
+        })
+    }
+}
+
 #[derive(Debug, Subcommand)]
 enum Command {
     Parity {
-        #[arg(long, default_value = "tests/parity_cases")]
-        cases: PathBuf,
-        #[arg(long, default_value = "spec/requirements.yaml")]
-        spec: PathBuf,
-        #[arg(long, default_value = "target/parity")]
+        #[command(flatten)]
+        parity: ParityArgs,
+        #[arg(long, help = "Interactive dashboard (requires the `tui` feature)")]
+        tui: bool,
+        #[arg(
+            long,
+            value_name = "PATH",
+            conflicts_with = "tui",
+            help = "Bundle failure artifacts and the report into a .tar.gz"
+        )]
+        archive: Option<PathBuf>,
+        #[arg(
+            long,
+            conflicts_with_all = ["tui", "archive"],
+            help = "Re-run affected cases whenever sources or case files change"
+        )]
+        watch: bool,
+        #[arg(
+            long,
+            requires = "watch",
+            help = "In watch mode, only re-run failing cases"
+        )]
+        failing_only: bool,
+        #[arg(
+            long,
+            value_name = "MS",
+            default_value_t = 300,
+            help = "Watch-mode debounce window"
+        )]
+        debounce_ms: u64,
+    },
+    /// Run one parity case with both programs' output streamed to the console.
+    ParityCase {
+        id: String,
+        #[command(flatten)]
+        parity: ParityArgs,
+        #[arg(long, help = "Keep the case's {temp} directory for inspection")]
+        keep_temp: bool,
+        #[arg(long, help = "Print the exact child environment")]
+        env_dump: bool,
+    },
+    /// Validate pony files (metadata, escapes, anchors, variables, whitespace).
+    LintPonies {
+        #[arg(default_value = "/usr/share/ponysay/ponies")]
+        dir: PathBuf,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+        #[arg(long, help = "Treat warnings as failures")]
+        deny_warnings: bool,
+    },
+    /// Generate tty-safe ponies by quantizing colors to the Linux console palette.
+    Ttyponies {
+        #[arg(long, default_value = "/usr/share/ponysay/ponies")]
+        input: PathBuf,
+        #[arg(long, default_value = "target/ttyponies")]
+        output: PathBuf,
+    },
+    /// Write an upstream balloon file from a TOML description (or normalize an existing one).
+    ConvertBalloon {
+        input: PathBuf,
+        #[arg(long, short, help = "Output file (defaults to stdout)")]
+        output: Option<PathBuf>,
+    },
+    /// Download the pinned upstream asset set into `assets/` with checksum verification.
+    Assets {
+        #[arg(long, default_value = "spec/assets.json")]
+        manifest: PathBuf,
+        #[arg(long, default_value = "assets")]
+        dest: PathBuf,
+        #[arg(long, help = "Record a new archive checksum in the manifest")]
+        update: bool,
+    },
+    /// Run the criterion benchmarks and optionally gate on regressions against a baseline.
+    Bench {
+        #[arg(
+            long,
+            default_value = "current",
+            help = "Baseline name to save results under"
+        )]
+        save_as: String,
+        #[arg(long, help = "Baseline to compare against (e.g. one saved from main)")]
+        baseline: Option<String>,
+        #[arg(
+            long,
+            default_value_t = 10.0,
+            help = "Fail when any benchmark mean slows down by more than this percentage"
+        )]
+        max_regression: f64,
+    },
+    /// Run the parity suite against a coverage-instrumented candidate and write lcov/HTML.
+    Coverage {
+        #[command(flatten)]
+        parity: ParityArgs,
+        #[arg(long = "coverage-out", default_value = "target/coverage")]
+        coverage_out: PathBuf,
+        #[arg(long, help = "Also write an HTML report")]
+        html: bool,
+    },
+    /// Build release archives with the binary, man page, completions, asset pack and licenses.
+    Dist {
+        #[arg(
+            long = "target",
+            value_name = "TRIPLE",
+            help = "Target to package (repeatable; defaults to the host)"
+        )]
+        targets: Vec<String>,
+        #[arg(
+            long,
+            default_value = "assets",
+            help = "Asset set to pack, from `xtask assets`"
+        )]
+        assets: PathBuf,
+        #[arg(long, default_value = "target/dist")]
         out: PathBuf,
-        #[arg(long, default_value = "ponysay")]
-        reference: String,
-        #[arg(long)]
-        candidate: Option<PathBuf>,
     },
 }
 
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
 fn main() -> Result<()> {
-    init_tracing();
     let cli = Cli::parse();
+    // Log lines would tear through the dashboard, so the TUI runs without them.
+    init_tracing(!matches!(cli.command, Command::Parity { tui: true, .. }));
 
     match cli.command {
         Command::Parity {
-            cases,
-            spec,
+            parity,
+            watch: true,
+            failing_only,
+            debounce_ms,
+            ..
+        } => watch::run(&watch::WatchTask {
+            config: parity.into_config()?,
+            failing_only,
+            debounce: Duration::from_millis(debounce_ms),
+        }),
+        Command::Parity {
+            parity,
+            tui,
+            archive,
+            ..
+        } => run_parity_task(parity.into_config()?, tui, archive),
+        Command::ParityCase {
+            id,
+            parity,
+            keep_temp,
+            env_dump,
+        } => run_parity_case_task(
+            &id,
+            &parity.into_config()?,
+            CaseRunOptions {
+                stream_output: true,
+                keep_temp,
+            },
+            env_dump,
+        ),
+        Command::LintPonies {
+            dir,
+            format,
+            deny_warnings,
+        } => run_lint_task(dir, format, deny_warnings),
+        Command::Ttyponies { input, output } => run_ttyponies_task(input, output),
+        Command::ConvertBalloon { input, output } => {
+            balloon::run(&balloon::ConvertTask { input, output })
+        }
+        Command::Assets {
+            manifest,
+            dest,
+            update,
+        } => assets::run(&assets::AssetsTask {
+            manifest,
+            dest,
+            download_dir: PathBuf::from("target/assets"),
+            update,
+        }),
+        Command::Bench {
+            save_as,
+            baseline,
+            max_regression,
+        } => bench::run(&bench::BenchTask {
+            criterion_dir: PathBuf::from("target/criterion"),
+            save_as,
+            baseline,
+            max_regression,
+        }),
+        Command::Coverage {
+            parity,
+            coverage_out,
+            html,
+        } => coverage::run(coverage::CoverageTask {
+            config: parity.into_config()?,
+            out: coverage_out,
+            html,
+        }),
+        Command::Dist {
+            targets,
+            assets,
+            out,
+        } => dist::run(&dist::DistTask {
+            targets,
+            assets,
             out,
-            reference,
-            candidate,
-        } => run_parity_task(cases, spec, out, reference, candidate),
+        }),
     }
 }
 
-fn run_parity_task(
-    cases: PathBuf,
-    spec: PathBuf,
-    out: PathBuf,
-    reference: String,
-    candidate: Option<PathBuf>,
-) -> Result<()> {
-    let workspace_root = std::env::current_dir().context("failed to resolve current dir")?;
-    let config = ParityConfig {
-        workspace_root: workspace_root.clone(),
-        cases_dir: workspace_root.join(cases),
-        spec_path: workspace_root.join(spec),
-        output_dir: workspace_root.join(out),
-        reference_program: reference,
-        candidate_program: candidate,
-    };
+fn run_parity_task(config: ParityConfig, tui: bool, archive: Option<PathBuf>) -> Result<()> {
+    if tui {
+        #[cfg(feature = "tui")]
+        return tui::run(config);
+        #[cfg(not(feature = "tui"))]
+        bail!(
+            "xtask was built without the `tui` feature; use `cargo run -p xtask --features tui -- parity --tui`"
+        );
+    }
 
     let report = run_parity(&config)?;
     info!(
@@ -74,17 +342,196 @@ fn run_parity_task(
         report.summary.case_parity * 100.0,
         report.summary.weighted_requirement_parity * 100.0
     );
+    if report.summary.crashed_cases > 0 {
+        println!(
+            "candidate crashed (killed by a signal) in {} case(s)",
+            report.summary.crashed_cases
+        );
+    }
 
+    if let Some(archive) = archive {
+        let archive = config.workspace_root.join(archive);
+        archive_failures(&config, &archive)?;
+        println!("Failure bundle written to {}", archive.display());
+    }
+
+    let missed = report.missed_thresholds(&config.thresholds);
+    if !missed.is_empty() {
+        bail!("parity thresholds missed: {}", missed.join("; "));
+    }
     Ok(())
 }
 
-fn init_tracing() {
-    let filter = EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| EnvFilter::new("info,iron_pony_parity=debug,xtask=debug"));
+fn run_parity_case_task(
+    id: &str,
+    config: &ParityConfig,
+    options: CaseRunOptions,
+    env_dump: bool,
+) -> Result<()> {
+    let case = load_cases(&config.cases_dir)?
+        .into_iter()
+        .find(|case| case.id == id)
+        .with_context(|| {
+            format!(
+                "no parity case with id '{id}' in {}",
+                config.cases_dir.display()
+            )
+        })?;
+    info!(case = %case.id, path = %case.source.display(), "running single parity case");
+
+    let run = run_case_with(config, &case, options, &CancelToken::new())?;
+    if env_dump {
+        println!("=== child environment ===");
+        for (key, value) in &run.env {
+            println!("{key}={value}");
+        }
+    }
+    if options.keep_temp {
+        println!("temp dir kept at {}", run.temp_dir.display());
+    }
+
+    let result = &run.result;
+    let outcome = match result.failure_kind {
+        None => "passed".to_string(),
+        Some(kind) => format!("FAILED [{}]", kind.as_str()),
+    };
+    println!(
+        "case {}: {outcome} (exit_match={}, stdout_match={}, stderr_match={})",
+        result.id, result.exit_match, result.stdout_match, result.stderr_match
+    );
+    if !result.passed {
+        // The full streams were already printed; repeat only the mismatch summary.
+        if let Some((_, mismatch)) = result.detail.split_once("\n\n=== first ") {
+            println!("=== first {mismatch}");
+        } else {
+            println!("{}", result.detail);
+        }
+        bail!("parity case {id} failed");
+    }
+    Ok(())
+}
+
+fn run_lint_task(dir: PathBuf, format: OutputFormat, deny_warnings: bool) -> Result<()> {
+    let reports = lint_pony_dir(&dir)
+        .with_context(|| format!("failed linting ponies in {}", dir.display()))?;
+
+    match format {
+        OutputFormat::Text => print_lint_text(&reports),
+        OutputFormat::Json => {
+            let value = lint_reports_json(&reports);
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&value).context("failed serializing lint report")?
+            );
+        }
+    }
+
+    let files_with_errors = reports.iter().filter(|report| report.has_errors()).count();
+    let files_with_issues = reports
+        .iter()
+        .filter(|report| !report.issues.is_empty())
+        .count();
+    info!(
+        files = reports.len(),
+        files_with_errors, files_with_issues, "pony lint completed"
+    );
+
+    if files_with_errors > 0 || (deny_warnings && files_with_issues > 0) {
+        bail!(
+            "pony lint failed: {files_with_errors} file(s) with errors, {files_with_issues} file(s) with issues"
+        );
+    }
+    Ok(())
+}
+
+fn print_lint_text(reports: &[LintReport]) {
+    for report in reports {
+        for issue in &report.issues {
+            println!(
+                "{}:{}: {} [{}] {}",
+                report.path.display(),
+                issue.line,
+                issue.kind.severity(),
+                issue.kind.code(),
+                issue.message
+            );
+        }
+    }
+}
+
+fn lint_reports_json(reports: &[LintReport]) -> serde_json::Value {
+    let files = reports
+        .iter()
+        .map(|report| {
+            let issues = report
+                .issues
+                .iter()
+                .map(|issue| {
+                    json!({
+                        "line": issue.line,
+                        "code": issue.kind.code(),
+                        "severity": issue.kind.severity().to_string(),
+                        "message": issue.message,
+                    })
+                })
+                .collect::<Vec<_>>();
+            json!({
+                "path": report.path.display().to_string(),
+                "has_errors": report.has_errors(),
+                "issues": issues,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({ "version": 1, "files": files })
+}
+
+fn run_ttyponies_task(input: PathBuf, output: PathBuf) -> Result<()> {
+    let mut converted = 0;
+    for entry in walkdir::WalkDir::new(&input)
+        .follow_links(true)
+        .into_iter()
+        .filter_map(Result::ok)
+    {
+        let path = entry.path();
+        if !entry.file_type().is_file() || path.extension().is_none_or(|ext| ext != "pony") {
+            continue;
+        }
+
+        let relative = path
+            .strip_prefix(&input)
+            .with_context(|| format!("failed relativizing {}", path.display()))?;
+        let target = output.join(relative);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("failed creating {}", parent.display()))?;
+        }
+
+        let raw =
+            std::fs::read(path).with_context(|| format!("failed reading {}", path.display()))?;
+        let tty = quantize_to_console(&String::from_utf8_lossy(&raw));
+        std::fs::write(&target, tty)
+            .with_context(|| format!("failed writing {}", target.display()))?;
+        converted += 1;
+    }
+
+    info!(converted, output = %output.display(), "generated ttyponies");
+    println!("Wrote {converted} ttyponies to {}", output.display());
+    Ok(())
+}
+
+fn init_tracing(enabled: bool) {
+    let filter = if enabled {
+        EnvFilter::try_from_default_env()
+            .unwrap_or_else(|_| EnvFilter::new("info,iron_pony_parity=debug,xtask=debug"))
+    } else {
+        EnvFilter::new("off")
+    };
 
     tracing_subscriber::fmt()
         .with_env_filter(filter)
         .with_target(true)
         .with_ansi(true)
+        .with_writer(std::io::stderr)
         .init();
 }
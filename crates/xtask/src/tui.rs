@@ -0,0 +1,349 @@
+//! Interactive parity dashboard behind `xtask parity --tui`.
+
+use std::process::Command;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use iron_pony_parity::{
+    CancelToken, CaseResult, ParityCase, ParityConfig, ParityProgress, load_cases, run_parity_with,
+    run_single_case,
+};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use tracing::debug;
+
+const HELP: &str = "j/k select  J/K scroll  f next failure  r re-run  e edit  q quit";
+
+#[derive(Debug, Clone)]
+enum CaseStatus {
+    Pending,
+    Running,
+    Done(Box<CaseResult>),
+}
+
+/// Messages from background run threads to the UI loop.
+#[derive(Debug)]
+enum Update {
+    Started(usize),
+    Finished(usize, Box<CaseResult>),
+    RunComplete { passed: usize, total: usize },
+    Failed(String),
+}
+
+struct App {
+    config: ParityConfig,
+    cases: Vec<ParityCase>,
+    statuses: Vec<CaseStatus>,
+    list: ListState,
+    scroll: u16,
+    status_line: String,
+    cancel: CancelToken,
+    tx: Sender<Update>,
+    rx: Receiver<Update>,
+}
+
+pub fn run(config: ParityConfig) -> Result<()> {
+    let cases = load_cases(&config.cases_dir)?;
+    let mut app = App::new(config, cases);
+    app.start_full_run();
+
+    let mut terminal = ratatui::init();
+    let result = app.event_loop(&mut terminal);
+    app.cancel.cancel();
+    ratatui::restore();
+    result
+}
+
+impl App {
+    fn new(config: ParityConfig, cases: Vec<ParityCase>) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let mut list = ListState::default();
+        if !cases.is_empty() {
+            list.select(Some(0));
+        }
+        Self {
+            statuses: vec![CaseStatus::Pending; cases.len()],
+            config,
+            cases,
+            list,
+            scroll: 0,
+            status_line: "running parity cases".to_string(),
+            cancel: CancelToken::new(),
+            tx,
+            rx,
+        }
+    }
+
+    fn start_full_run(&self) {
+        let config = self.config.clone();
+        let cancel = self.cancel.clone();
+        let tx = self.tx.clone();
+        std::thread::spawn(move || {
+            let result = run_parity_with(
+                &config,
+                |event| {
+                    let update = match event {
+                        ParityProgress::CaseStarted { index, .. } => Update::Started(index),
+                        ParityProgress::CaseFinished { index, result, .. } => {
+                            Update::Finished(index, Box::new(result.clone()))
+                        }
+                        _ => return,
+                    };
+                    let _ = tx.send(update);
+                },
+                &cancel,
+            );
+            let _ = tx.send(match result {
+                Ok(report) => Update::RunComplete {
+                    passed: report.summary.passed_cases,
+                    total: report.summary.total_cases,
+                },
+                Err(error) => Update::Failed(format!("{error:#}")),
+            });
+        });
+    }
+
+    fn rerun_selected(&mut self) {
+        let Some(index) = self.list.selected() else {
+            return;
+        };
+        let config = self.config.clone();
+        let case = self.cases[index].clone();
+        let cancel = self.cancel.clone();
+        let tx = self.tx.clone();
+        self.statuses[index] = CaseStatus::Running;
+        self.status_line = format!("re-running {}", case.id);
+        std::thread::spawn(move || {
+            let update = match run_single_case(&config, &case, &cancel) {
+                Ok(result) => Update::Finished(index, Box::new(result)),
+                Err(error) => Update::Failed(format!("{}: {error:#}", case.id)),
+            };
+            let _ = tx.send(update);
+        });
+    }
+
+    fn apply(&mut self, update: Update) {
+        match update {
+            Update::Started(index) => {
+                if let Some(status) = self.statuses.get_mut(index) {
+                    *status = CaseStatus::Running;
+                }
+            }
+            Update::Finished(index, result) => {
+                debug!(case = %result.id, passed = result.passed, "parity case finished");
+                if let Some(status) = self.statuses.get_mut(index) {
+                    *status = CaseStatus::Done(result);
+                }
+            }
+            Update::RunComplete { passed, total } => {
+                self.status_line = format!("run complete: {passed}/{total} passed");
+            }
+            Update::Failed(message) => self.status_line = format!("error: {message}"),
+        }
+    }
+
+    fn select(&mut self, delta: isize) {
+        if self.cases.is_empty() {
+            return;
+        }
+        let current = self.list.selected().unwrap_or(0);
+        let next = current
+            .saturating_add_signed(delta)
+            .min(self.cases.len() - 1);
+        self.list.select(Some(next));
+        self.scroll = 0;
+    }
+
+    /// Selects the next failing case after the current one, wrapping around.
+    fn next_failure(&mut self) {
+        let start = self.list.selected().map_or(0, |index| index + 1);
+        let total = self.statuses.len();
+        let found = (0..total).map(|offset| (start + offset) % total).find(
+            |&index| matches!(&self.statuses[index], CaseStatus::Done(result) if !result.passed),
+        );
+        match found {
+            Some(index) => {
+                self.list.select(Some(index));
+                self.scroll = 0;
+            }
+            None => self.status_line = "no failing cases".to_string(),
+        }
+    }
+
+    /// Opens the selected case file in `$EDITOR` and reloads the case set afterwards.
+    fn edit_selected(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        let Some(index) = self.list.selected() else {
+            return Ok(());
+        };
+        let path = self.cases[index].source.clone();
+        let editor = std::env::var("VISUAL")
+            .or_else(|_| std::env::var("EDITOR"))
+            .unwrap_or_else(|_| "vi".to_string());
+
+        ratatui::restore();
+        let status = Command::new(&editor).arg(&path).status();
+        *terminal = ratatui::init();
+        status.with_context(|| format!("failed to launch editor {editor}"))?;
+
+        let cases = load_cases(&self.config.cases_dir)?;
+        if cases.len() == self.cases.len() {
+            self.cases = cases;
+            self.status_line = format!("reloaded {}; press r to re-run", path.display());
+        } else {
+            self.status_line = "case set changed on disk; restart to pick it up".to_string();
+        }
+        Ok(())
+    }
+
+    fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        loop {
+            while let Ok(update) = self.rx.try_recv() {
+                self.apply(update);
+            }
+            terminal
+                .draw(|frame| self.draw(frame))
+                .context("failed drawing parity dashboard")?;
+
+            if !event::poll(Duration::from_millis(100)).context("failed polling terminal")? {
+                continue;
+            }
+            let Event::Key(key) = event::read().context("failed reading terminal event")? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Down | KeyCode::Char('j') => self.select(1),
+                KeyCode::Up | KeyCode::Char('k') => self.select(-1),
+                KeyCode::PageDown | KeyCode::Char('J') => {
+                    self.scroll = self.scroll.saturating_add(10);
+                }
+                KeyCode::PageUp | KeyCode::Char('K') => {
+                    self.scroll = self.scroll.saturating_sub(10);
+                }
+                KeyCode::Char('f') => self.next_failure(),
+                KeyCode::Char('r') => self.rerun_selected(),
+                KeyCode::Char('e') => self.edit_selected(terminal)?,
+                _ => {}
+            }
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, footer] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let [left, right] =
+            Layout::horizontal([Constraint::Percentage(35), Constraint::Min(0)]).areas(main);
+
+        let passed = self
+            .statuses
+            .iter()
+            .filter(|status| matches!(status, CaseStatus::Done(result) if result.passed))
+            .count();
+        let items = self
+            .cases
+            .iter()
+            .zip(&self.statuses)
+            .map(|(case, status)| {
+                let (mark, color) = match status {
+                    CaseStatus::Pending => (' ', Color::DarkGray),
+                    CaseStatus::Running => ('~', Color::Yellow),
+                    CaseStatus::Done(result) if result.passed => ('+', Color::Green),
+                    CaseStatus::Done(result) if result.crashed => ('!', Color::Magenta),
+                    CaseStatus::Done(_) => ('x', Color::Red),
+                };
+                ListItem::new(format!("{mark} {}", case.id)).style(Style::default().fg(color))
+            })
+            .collect::<Vec<_>>();
+        let list = List::new(items)
+            .block(Block::bordered().title(format!(" cases {passed}/{} ", self.cases.len())))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, left, &mut self.list);
+
+        let (title, detail) = match self.list.selected() {
+            Some(index) => {
+                let detail = match &self.statuses[index] {
+                    CaseStatus::Pending => "pending".to_string(),
+                    CaseStatus::Running => "running...".to_string(),
+                    CaseStatus::Done(result) if result.passed => "passed".to_string(),
+                    CaseStatus::Done(result) => result.detail.clone(),
+                };
+                (format!(" {} ", self.cases[index].source.display()), detail)
+            }
+            None => (" no cases ".to_string(), String::new()),
+        };
+        frame.render_widget(
+            Paragraph::new(detail)
+                .block(Block::bordered().title(title))
+                .scroll((self.scroll, 0)),
+            right,
+        );
+        frame.render_widget(
+            Line::from(format!("{}  |  {HELP}", self.status_line)),
+            footer,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use iron_pony_parity::FailureKind;
+
+    use super::*;
+
+    fn case(id: &str) -> ParityCase {
+        serde_json::from_value(serde_json::json!({ "id": id, "argv": [] })).expect("case")
+    }
+
+    fn result(id: &str, passed: bool) -> Box<CaseResult> {
+        Box::new(CaseResult {
+            id: id.to_string(),
+            features: Vec::new(),
+            passed,
+            exit_match: passed,
+            stdout_match: passed,
+            stderr_match: true,
+            crashed: false,
+            candidate_signal: None,
+            output_limit_exceeded: false,
+            failure_kind: (!passed).then_some(FailureKind::StdoutMismatch),
+            detail: String::new(),
+        })
+    }
+
+    #[test]
+    fn tracks_statuses_and_jumps_between_failures() {
+        let config = ParityConfig::default_for_workspace(PathBuf::from("."));
+        let mut app = App::new(config, vec![case("a"), case("b"), case("c")]);
+
+        app.apply(Update::Started(0));
+        assert!(matches!(app.statuses[0], CaseStatus::Running));
+        app.apply(Update::Finished(0, result("a", false)));
+        app.apply(Update::Finished(1, result("b", true)));
+        app.apply(Update::Finished(2, result("c", false)));
+        app.apply(Update::RunComplete {
+            passed: 1,
+            total: 3,
+        });
+        assert_eq!(app.status_line, "run complete: 1/3 passed");
+
+        app.next_failure();
+        assert_eq!(app.list.selected(), Some(2));
+        app.next_failure();
+        assert_eq!(app.list.selected(), Some(0));
+
+        app.select(5);
+        assert_eq!(app.list.selected(), Some(2));
+        app.select(-9);
+        assert_eq!(app.list.selected(), Some(0));
+    }
+}
@@ -0,0 +1,211 @@
+//! `xtask parity --watch`: re-runs affected parity cases as files change.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use iron_pony_parity::{CancelToken, ParityCase, ParityConfig, load_cases, run_single_case};
+use notify::{Event, RecursiveMode, Watcher};
+use tracing::{debug, info, warn};
+
+pub struct WatchTask {
+    pub config: ParityConfig,
+    /// Only re-run cases that failed on their last run.
+    pub failing_only: bool,
+    pub debounce: Duration,
+}
+
+pub fn run(task: &WatchTask) -> Result<()> {
+    let config = &task.config;
+    let sources = config.workspace_root.join("crates");
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).context("failed creating watcher")?;
+    for path in [&sources, &config.cases_dir] {
+        watcher
+            .watch(path, RecursiveMode::Recursive)
+            .with_context(|| format!("failed watching {}", path.display()))?;
+    }
+
+    let mut cases = load_cases(&config.cases_dir)?;
+    let mut passed = BTreeMap::new();
+    let all = (0..cases.len()).collect::<Vec<_>>();
+    run_batch(config, &cases, &all, &mut passed);
+    println!(
+        "watching {} and {}",
+        sources.display(),
+        config.cases_dir.display()
+    );
+
+    while let Ok(first) = rx.recv() {
+        // Debounce: an editor save or a checkout arrives as a burst of events.
+        let mut events = vec![first];
+        while let Ok(event) = rx.recv_timeout(task.debounce) {
+            events.push(event);
+        }
+        let changed = modified_paths(events);
+        let changes = classify(&changed, &sources, &config.cases_dir);
+        if changes.is_empty() {
+            continue;
+        }
+        debug!(?changes, "parity inputs changed");
+        // A half-saved case file is common mid-edit; keep the last good set until it parses.
+        if changes.cases_changed {
+            match load_cases(&config.cases_dir) {
+                Ok(loaded) => cases = loaded,
+                Err(error) => {
+                    warn!(error = %format!("{error:#}"), "failed reloading parity cases");
+                    continue;
+                }
+            }
+        }
+
+        let selected = select_cases(&cases, &changes, &passed, task.failing_only);
+        if selected.is_empty() {
+            println!("no affected cases to re-run");
+            continue;
+        }
+        run_batch(config, &cases, &selected, &mut passed);
+    }
+    Ok(())
+}
+
+/// Paths touched by anything but reads; the harness itself reads case files, so access
+/// events would re-trigger runs forever.
+fn modified_paths(events: Vec<notify::Result<Event>>) -> Vec<PathBuf> {
+    events
+        .into_iter()
+        .filter_map(|event| {
+            event
+                .inspect_err(|error| warn!(%error, "file watcher error"))
+                .ok()
+        })
+        .filter(|event| !event.kind.is_access())
+        .flat_map(|event| event.paths)
+        .collect()
+}
+
+/// What a debounced batch of file events touched.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Changes {
+    sources_changed: bool,
+    cases_changed: bool,
+    case_files: Vec<PathBuf>,
+}
+
+impl Changes {
+    fn is_empty(&self) -> bool {
+        !self.sources_changed && !self.cases_changed
+    }
+}
+
+fn classify(paths: &[PathBuf], sources: &Path, cases_dir: &Path) -> Changes {
+    let mut changes = Changes::default();
+    for path in paths {
+        let extension = path.extension().and_then(|ext| ext.to_str());
+        if path.starts_with(cases_dir) && extension == Some("json") {
+            changes.cases_changed = true;
+            if !changes.case_files.contains(path) {
+                changes.case_files.push(path.clone());
+            }
+        } else if path.starts_with(sources) && matches!(extension, Some("rs" | "toml")) {
+            changes.sources_changed = true;
+        }
+    }
+    changes
+}
+
+/// Indices of the cases to re-run: everything after a source change, otherwise the cases
+/// whose files changed. `failing_only` keeps just the cases that are not known to pass.
+fn select_cases(
+    cases: &[ParityCase],
+    changes: &Changes,
+    passed: &BTreeMap<String, bool>,
+    failing_only: bool,
+) -> Vec<usize> {
+    cases
+        .iter()
+        .enumerate()
+        .filter(|(_, case)| changes.sources_changed || changes.case_files.contains(&case.source))
+        .filter(|(_, case)| !failing_only || passed.get(&case.id) != Some(&true))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// Runs the selected cases. A case that cannot run is reported and counted as failing, so
+/// watching carries on.
+fn run_batch(
+    config: &ParityConfig,
+    cases: &[ParityCase],
+    selected: &[usize],
+    passed: &mut BTreeMap<String, bool>,
+) {
+    let cancel = CancelToken::new();
+    let mut failures = 0;
+    for &index in selected {
+        let case = &cases[index];
+        let ok = match run_single_case(config, case, &cancel) {
+            Ok(result) => result.passed,
+            Err(error) => {
+                warn!(case = %case.id, error = %format!("{error:#}"), "parity case did not run");
+                false
+            }
+        };
+        if !ok {
+            failures += 1;
+            println!("FAIL {}", case.id);
+        }
+        passed.insert(case.id.clone(), ok);
+    }
+
+    let total_passed = passed.values().filter(|&&ok| ok).count();
+    info!(ran = selected.len(), failures, "watch batch finished");
+    println!(
+        "ran {} case(s), {failures} failed | {total_passed}/{} passing overall",
+        selected.len(),
+        passed.len()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn case(id: &str, source: &str) -> ParityCase {
+        let mut case: ParityCase =
+            serde_json::from_value(serde_json::json!({ "id": id, "argv": [] })).expect("case");
+        case.source = PathBuf::from(source);
+        case
+    }
+
+    #[test]
+    fn selects_affected_and_failing_cases() {
+        let cases = vec![case("a", "/w/cases/a.json"), case("b", "/w/cases/b.json")];
+        let passed = BTreeMap::from([("a".to_string(), true), ("b".to_string(), false)]);
+        let sources = Path::new("/w/crates");
+        let cases_dir = Path::new("/w/cases");
+
+        let changes = classify(
+            &[
+                PathBuf::from("/w/cases/a.json"),
+                PathBuf::from("/w/crates/x/notes.md"),
+            ],
+            sources,
+            cases_dir,
+        );
+        assert!(!changes.sources_changed);
+        assert_eq!(select_cases(&cases, &changes, &passed, false), vec![0]);
+        assert!(select_cases(&cases, &changes, &passed, true).is_empty());
+
+        let changes = classify(
+            &[PathBuf::from("/w/crates/core/src/lib.rs")],
+            sources,
+            cases_dir,
+        );
+        assert_eq!(select_cases(&cases, &changes, &passed, false), vec![0, 1]);
+        assert_eq!(select_cases(&cases, &changes, &passed, true), vec![1]);
+
+        assert!(classify(&[PathBuf::from("/w/target/x.rs")], sources, cases_dir).is_empty());
+    }
+}